@@ -8,6 +8,17 @@ pub struct Config {
     pub storage: StorageConfig,
     pub resources: ResourceConfig,
     pub embed: EmbedConfig,
+    #[serde(default)]
+    pub ranking: RankingConfig,
+    #[serde(default)]
+    pub ann: AnnConfig,
+    #[serde(default)]
+    pub vectors: VectorConfig,
+    #[serde(default)]
+    pub hybrid: HybridConfig,
+    /// Extra query expansions, both ways: `config = ["cfg", "conf"]`
+    #[serde(default)]
+    pub synonyms: BTreeMap<String, Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,7 +35,8 @@ pub struct ResourceConfig {
     pub max_threads: usize,
     /// Max memory budget hint in bytes for batching (0 = 256MB default)
     pub max_memory_bytes: u64,
-    /// Max files to process per indexing batch before committing
+    /// Ignored: indexing commits each file in its own short transaction.
+    /// Still accepted so existing config files keep loading.
     pub batch_size: usize,
 }
 
@@ -35,17 +47,218 @@ pub struct EmbedConfig {
     pub max_concurrent: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum EmbedBackend {
     #[serde(rename = "ollama")]
     Ollama { model: String, url: String },
+    /// An OpenAI-compatible `/embeddings` endpoint; `url` includes the API
+    /// version and the key is read from the `api_key_env` variable.
     #[serde(rename = "openai")]
-    OpenAi { model: String },
+    OpenAi {
+        model: String,
+        #[serde(default = "default_openai_url")]
+        url: String,
+        #[serde(default = "default_api_key_env")]
+        api_key_env: String,
+    },
+    /// No backend configured: embedding still uses the local Ollama
+    /// defaults, `nomic-embed-text` on `http://localhost:11434`.
     #[serde(rename = "none")]
     None,
 }
 
+fn default_openai_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_api_key_env() -> String {
+    "OPENAI_API_KEY".to_string()
+}
+
+/// Approximate nearest-neighbour (HNSW) index for semantic search; every
+/// field is optional in config.toml.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnConfig {
+    /// Below this many embeddings, semantic search scans them all instead
+    pub min_vectors: usize,
+    /// Neighbors per node (twice that on the bottom layer)
+    pub m: usize,
+    /// Candidate list size while inserting; higher builds a better graph, slower
+    pub ef_construction: usize,
+    /// Candidate list size while searching; higher raises recall, slower
+    pub ef_search: usize,
+}
+
+impl Default for AnnConfig {
+    fn default() -> Self {
+        Self {
+            min_vectors: 10_000,
+            m: 16,
+            ef_construction: 100,
+            ef_search: 100,
+        }
+    }
+}
+
+/// How semantic search scans embeddings. Quantization takes precedence
+/// over `[ann]`; the stored embeddings stay full precision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VectorConfig {
+    /// Scan a memory-mapped copy quantized to "int8" or "binary" (1 bit per
+    /// dimension), then rescore the best against the stored embeddings;
+    /// "none" scans f32
+    pub quantization: Quantization,
+    /// Candidates rescored, as a multiple of the results wanted
+    pub rescore_factor: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    None,
+    Int8,
+    Binary,
+}
+
+impl Default for VectorConfig {
+    fn default() -> Self {
+        Self {
+            quantization: Quantization::None,
+            rescore_factor: 4,
+        }
+    }
+}
+
+/// How hybrid search fuses keyword and semantic results; every field is
+/// optional in config.toml.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HybridConfig {
+    /// "rrf" (reciprocal rank fusion), "zscore" or "linear" (min-max blend)
+    pub fusion: Fusion,
+    /// Weight of the keyword results (0.0 = pure semantic, 1.0 = pure FTS)
+    pub alpha: f64,
+    /// RRF constant: higher flattens the difference between top ranks
+    pub rrf_k: f64,
+    /// Fused-score change per unit of `[ranking]` boost (fused scores are 0..1)
+    pub boost_scale: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fusion {
+    #[default]
+    Rrf,
+    Zscore,
+    Linear,
+}
+
+impl Fusion {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "rrf" => Ok(Self::Rrf),
+            "zscore" => Ok(Self::Zscore),
+            "linear" => Ok(Self::Linear),
+            other => anyhow::bail!("unknown fusion '{other}' (expected one of: rrf, zscore, linear)"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rrf => "rrf",
+            Self::Zscore => "zscore",
+            Self::Linear => "linear",
+        }
+    }
+}
+
+impl Default for HybridConfig {
+    fn default() -> Self {
+        Self {
+            fusion: Fusion::Rrf,
+            alpha: 0.7,
+            rrf_k: 60.0,
+            boost_scale: 0.05,
+        }
+    }
+}
+
+/// Search re-ranking: which stages run, in order, and their weights.
+/// Weights are in BM25 rank units; every field is optional in config.toml.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingConfig {
+    /// Stage names, applied in order (see `search::ranking::STAGES`)
+    pub stages: Vec<String>,
+    /// Boost for structural chunks (functions, structs, ...) over raw/module chunks
+    pub code_boost: f64,
+    /// Chunks longer than this multiple of the average result are penalized
+    pub oversize_threshold: f64,
+    /// Penalty per multiple of the average length
+    pub oversize_factor: f64,
+    /// Cap on the oversize penalty
+    pub oversize_max: f64,
+    /// Boost for results under a focused path
+    pub focus_boost: f64,
+    /// Penalty for results under a visited path
+    pub visited_penalty: f64,
+    /// Boost for annotated files and symbols
+    pub annotation_boost: f64,
+    /// Boost for results in files changed on the current branch
+    pub diff_file_boost: f64,
+    /// Extra boost for results overlapping a symbol changed on the branch
+    pub diff_symbol_boost: f64,
+    /// Ref to diff against; the default branch (origin/HEAD, main or master) if unset
+    pub diff_base: Option<String>,
+    /// Seconds a computed branch diff is reused before git runs again
+    pub diff_ttl_secs: u64,
+    /// Rank multiplier for results matching only through a synonym (BM25 ranks
+    /// are negative, so below 1.0 ranks them lower)
+    pub synonym_weight: f64,
+    /// Boost approached by results repeatedly marked useful for similar queries
+    pub feedback_boost: f64,
+    /// Boost for results covering all of a session intent's key terms, or
+    /// scaled by the share covered or their similarity to its embedding
+    pub intent_boost: f64,
+    /// Boost for the most-committed file in recent git history; others scale
+    /// logarithmically (opt-in `churn` stage)
+    pub churn_boost: f64,
+    /// Boost for a file changed in the newest commit (opt-in `recency` stage)
+    pub recency_boost: f64,
+    /// Days after which the recency boost halves, counted back from the newest commit
+    pub recency_half_life_days: f64,
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            stages: ["code", "oversize", "focus", "visited", "annotation", "diff", "feedback", "intent"]
+                .map(String::from)
+                .to_vec(),
+            code_boost: 3.0,
+            oversize_threshold: 2.0,
+            oversize_factor: 0.5,
+            oversize_max: 4.0,
+            focus_boost: 5.0,
+            visited_penalty: 3.0,
+            annotation_boost: 2.0,
+            diff_file_boost: 1.0,
+            diff_symbol_boost: 2.0,
+            diff_base: None,
+            diff_ttl_secs: 30,
+            synonym_weight: 0.5,
+            feedback_boost: 3.0,
+            intent_boost: 2.0,
+            churn_boost: 1.0,
+            recency_boost: 2.0,
+            recency_half_life_days: 14.0,
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         let num_cpus = std::thread::available_parallelism()
@@ -66,6 +279,11 @@ impl Default for Config {
                 backend: EmbedBackend::None,
                 max_concurrent: 4,
             },
+            ranking: RankingConfig::default(),
+            ann: AnnConfig::default(),
+            vectors: VectorConfig::default(),
+            hybrid: HybridConfig::default(),
+            synonyms: BTreeMap::new(),
         }
     }
 }
@@ -125,9 +343,29 @@ pub struct ProjectRegistry {
     pub projects: BTreeMap<String, ProjectEntry>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectEntry {
     pub path: PathBuf,
+    /// Multiplies the project's normalized scores in `workspace-search`.
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    pub weight: f64,
+    /// Named groups `workspace-search` can be limited to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+impl ProjectEntry {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, weight: default_weight(), groups: Vec::new() }
+    }
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn is_default_weight(weight: &f64) -> bool {
+    *weight == default_weight()
 }
 
 impl ProjectRegistry {
@@ -158,14 +396,43 @@ impl ProjectRegistry {
         Ok(())
     }
 
-    pub fn add(&mut self, name: String, path: PathBuf) {
-        self.projects.insert(name, ProjectEntry { path });
+    /// Register `name`, returning its entry so weight and groups can be
+    /// set. Re-registering only updates the path: the existing weight and
+    /// groups are kept.
+    pub fn add(&mut self, name: String, path: PathBuf) -> &mut ProjectEntry {
+        let entry = self.projects.entry(name).or_insert_with(|| ProjectEntry::new(path.clone()));
+        entry.path = path;
+        entry
     }
 
     pub fn remove(&mut self, name: &str) -> bool {
         self.projects.remove(name).is_some()
     }
 
+    /// Registered projects by name, plus `default_root` as "(default)"
+    /// when it is not one of them. With `group`, only that group's
+    /// projects (never the default root).
+    pub fn workspace(&self, default_root: &Path, group: Option<&str>) -> Vec<(String, ProjectEntry)> {
+        if let Some(group) = group {
+            return self
+                .projects
+                .iter()
+                .filter(|(_, entry)| entry.groups.iter().any(|g| g == group))
+                .map(|(name, entry)| (name.clone(), entry.clone()))
+                .collect();
+        }
+        let mut projects: Vec<(String, ProjectEntry)> = self
+            .projects
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.clone()))
+            .collect();
+        let default_canon = default_root.canonicalize().unwrap_or_else(|_| default_root.to_path_buf());
+        if !projects.iter().any(|(_, e)| e.path.canonicalize().unwrap_or_else(|_| e.path.clone()) == default_canon) {
+            projects.push(("(default)".into(), ProjectEntry::new(default_root.to_path_buf())));
+        }
+        projects
+    }
+
     /// Resolve a project name or path to an actual directory.
     /// Tries: registered project name first, then treats it as a literal path.
     pub fn resolve(&self, name_or_path: &str) -> Option<PathBuf> {
@@ -223,6 +490,54 @@ mod tests {
         assert_eq!(config.resources.batch_size, 500);
     }
 
+    #[test]
+    fn ranking_section_is_optional_and_partial() {
+        let dir = TempDir::new().unwrap();
+        let booger = dir.path().join(".booger");
+        std::fs::create_dir_all(&booger).unwrap();
+        let base = "[storage]\nmax_size_bytes = 0\n\n\
+                    [resources]\nmax_threads = 1\nmax_memory_bytes = 0\nbatch_size = 10\n\n\
+                    [embed]\nmax_concurrent = 1\n\n[embed.backend]\ntype = \"none\"\n";
+        std::fs::write(booger.join("config.toml"), base).unwrap();
+        let config = Config::load(dir.path()).unwrap();
+        assert_eq!(config.ranking.code_boost, 3.0);
+        assert_eq!(config.ranking.stages.len(), 8);
+
+        assert_eq!(config.vectors.quantization, Quantization::None);
+
+        let tuned = format!(
+            "{base}\n[ranking]\nfocus_boost = 8.0\nstages = [\"focus\"]\n\n[vectors]\nquantization = \"int8\"\n"
+        );
+        std::fs::write(booger.join("config.toml"), tuned).unwrap();
+        let config = Config::load(dir.path()).unwrap();
+        assert_eq!(config.ranking.focus_boost, 8.0);
+        assert_eq!(config.ranking.stages, ["focus"]);
+        assert_eq!(config.ranking.visited_penalty, 3.0);
+        assert_eq!(config.vectors.quantization, Quantization::Int8);
+        assert_eq!(config.vectors.rescore_factor, 4);
+    }
+
+    #[test]
+    fn openai_backend_defaults_url_and_key_variable() {
+        let dir = TempDir::new().unwrap();
+        let booger = dir.path().join(".booger");
+        std::fs::create_dir_all(&booger).unwrap();
+        let toml = "[storage]\nmax_size_bytes = 0\n\n\
+                    [resources]\nmax_threads = 1\nmax_memory_bytes = 0\nbatch_size = 10\n\n\
+                    [embed]\nmax_concurrent = 1\n\n\
+                    [embed.backend]\ntype = \"openai\"\nmodel = \"text-embedding-3-small\"\n";
+        std::fs::write(booger.join("config.toml"), toml).unwrap();
+        let config = Config::load(dir.path()).unwrap();
+        match config.embed.backend {
+            EmbedBackend::OpenAi { model, url, api_key_env } => {
+                assert_eq!(model, "text-embedding-3-small");
+                assert_eq!(url, "https://api.openai.com/v1");
+                assert_eq!(api_key_env, "OPENAI_API_KEY");
+            }
+            other => panic!("unexpected backend {other:?}"),
+        }
+    }
+
     #[test]
     fn storage_dir_default() {
         let config = Config::default();
@@ -264,6 +579,31 @@ mod tests {
         assert_eq!(resolved, Some(PathBuf::from("/tmp/myproject")));
     }
 
+    #[test]
+    fn registry_workspace_weights_and_groups() {
+        let mut reg: ProjectRegistry = serde_json::from_str(
+            r#"{"projects": {"api": {"path": "/tmp/api", "weight": 2.0, "groups": ["backend"]}, "web": {"path": "/tmp/web"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(reg.projects["web"].weight, 1.0);
+        let all = reg.workspace(Path::new("/tmp/other"), None);
+        let names: Vec<&str> = all.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["api", "web", "(default)"]);
+        let backend = reg.workspace(Path::new("/tmp/other"), Some("backend"));
+        assert_eq!(backend.len(), 1);
+        assert_eq!(backend[0].1.weight, 2.0);
+
+        reg.add("web".into(), PathBuf::from("/tmp/web")).groups.push("frontend".into());
+        let json = serde_json::to_string(&reg).unwrap();
+        assert!(json.contains(r#""web":{"path":"/tmp/web","groups":["frontend"]}"#), "{json}");
+
+        // Re-registering moves the project but keeps its weight and groups.
+        reg.add("api".into(), PathBuf::from("/srv/api"));
+        assert_eq!(reg.projects["api"].path, PathBuf::from("/srv/api"));
+        assert_eq!(reg.projects["api"].weight, 2.0);
+        assert_eq!(reg.projects["api"].groups, ["backend"]);
+    }
+
     #[test]
     fn registry_resolve_unknown() {
         let reg = ProjectRegistry::default();
@@ -2,7 +2,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 
 use crate::config::Config;
-use crate::store::sqlite::{Annotation, Store};
+use crate::store::sqlite::{Annotation, SearchFilter, SearchResult, Store};
 
 pub fn add(
     root: &Path,
@@ -30,6 +30,105 @@ pub fn list(
     store.get_annotations(target, session_id)
 }
 
+/// Annotations whose note or target match `query`, best match first.
+pub fn search(
+    root: &Path,
+    config: &Config,
+    query: &str,
+    session_id: Option<&str>,
+    max_results: usize,
+) -> Result<Vec<Annotation>> {
+    match open_store_ro(root, config)? {
+        Some(store) => store.search_annotations(query, session_id, max_results),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Notes merged into search output by `search --notes` (MCP: `notes: true`).
+pub const MAX_NOTE_RESULTS: usize = 5;
+
+/// Annotations matching `query` as search results of kind `note`, placed
+/// at what they annotate: the line of a `file:line` target, an indexed
+/// file, or the first chunk named like a symbol target. The note is the
+/// content and the target the name; unresolved targets keep line 0.
+/// Notes are kept only where `filter` would show code, the way search
+/// filters code results; an unresolved target has no code to check, so it
+/// is dropped whenever `filter` restricts anything.
+pub fn note_results(
+    root: &Path,
+    config: &Config,
+    query: &str,
+    filter: &SearchFilter,
+    session_id: Option<&str>,
+    max_results: usize,
+) -> Result<Vec<SearchResult>> {
+    let Some(store) = open_store_ro(root, config)? else {
+        return Ok(Vec::new());
+    };
+    let unfiltered = *filter == SearchFilter::default();
+    // Filtering drops some; look a little further for the rest.
+    let fetch = if unfiltered { max_results } else { max_results * 4 };
+    let mut results = Vec::new();
+    for ann in store.search_annotations(query, session_id, fetch)? {
+        let target = locate(&store, &ann.target)?;
+        let shown = match target.lines {
+            _ if unfiltered => true,
+            Some((start, end)) => store.lines_pass(&target.path, start, end, filter)?,
+            None => false,
+        };
+        if !shown {
+            continue;
+        }
+        let (start_line, end_line) = target.display.unwrap_or((0, 0));
+        results.push(SearchResult {
+            file_path: target.path,
+            language: target.language,
+            chunk_kind: "note".to_string(),
+            chunk_name: Some(ann.target),
+            signature: None,
+            start_line,
+            end_line,
+            content: ann.note,
+            rank: 0.0,
+        });
+        if results.len() >= max_results {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// Where an annotation target points.
+struct Located {
+    path: String,
+    language: Option<String>,
+    /// Lines shown for the note; None if the target did not resolve.
+    display: Option<(i64, i64)>,
+    /// Lines whose code decides whether filters keep the note: the whole
+    /// file for a file target.
+    lines: Option<(i64, i64)>,
+}
+
+fn locate(store: &Store, target: &str) -> Result<Located> {
+    if let Some((path, line)) = target.rsplit_once(':')
+        && let Ok(line) = line.parse::<i64>()
+    {
+        let language = store.get_file(path)?.and_then(|f| f.language);
+        let lines = Some((line, line));
+        return Ok(Located { path: path.to_string(), language, display: lines, lines });
+    }
+    if let Some(file) = store.get_file(target)? {
+        return Ok(Located { path: file.path, language: file.language, display: Some((1, 1)), lines: Some((1, i64::MAX)) });
+    }
+    for id in store.chunks_named(target)? {
+        if let Some(chunk) = store.chunk_by_id(id, &SearchFilter::default())? {
+            let lines = Some((chunk.start_line, chunk.end_line));
+            return Ok(Located { path: chunk.file_path, language: chunk.language, display: lines, lines });
+        }
+    }
+    Ok(Located { path: target.to_string(), language: None, display: None, lines: None })
+}
+
 pub fn remove(root: &Path, config: &Config, id: i64) -> Result<()> {
     match open_store_ro(root, config)? {
         Some(store) => store.delete_annotation(id),
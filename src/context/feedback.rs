@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use crate::config::Config;
+use crate::store::sqlite::{Feedback, SearchResult, Store};
+
+/// What a piece of feedback is about: a file, or one chunk in it.
+pub struct Target<'a> {
+    pub path: &'a str,
+    pub symbol: Option<&'a str>,
+    pub line: Option<i64>,
+}
+
+pub fn record(
+    root: &Path,
+    config: &Config,
+    query: &str,
+    target: &Target,
+    useful: bool,
+    session_id: Option<&str>,
+) -> Result<i64> {
+    let store = open_store_rw(root, config)?;
+    store.add_feedback(query, target.path, target.symbol, target.line, useful, session_id)
+}
+
+pub fn list(root: &Path, config: &Config, limit: usize) -> Result<Vec<Feedback>> {
+    match open_store_ro(root, config)? {
+        Some(store) => store.get_feedback(limit),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The latest search of a project, for implicit feedback.
+struct LastSearch {
+    query: String,
+    session_id: Option<String>,
+    /// (path, chunk name) of results not yet credited.
+    results: Vec<(String, Option<String>)>,
+}
+
+static LAST_SEARCH: LazyLock<Mutex<HashMap<PathBuf, LastSearch>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Remember the results of a search, so a follow-up call that digs into
+/// one of them (`symbols` on its file, `references` to its symbol) counts
+/// as that result being useful.
+pub fn remember(root: &Path, session_id: Option<&str>, query: &str, results: &[SearchResult]) {
+    let results = results.iter().map(|r| (r.file_path.clone(), r.chunk_name.clone())).collect();
+    let search = LastSearch {
+        query: query.to_string(),
+        session_id: session_id.map(String::from),
+        results,
+    };
+    let mut last = LAST_SEARCH.lock().unwrap_or_else(|e| e.into_inner());
+    last.insert(root.to_path_buf(), search);
+}
+
+/// Record implicit feedback when `path` or `symbol` points at a result of
+/// the project's latest search. Each result is credited at most once.
+/// Returns how many results were credited.
+pub fn follow_up(root: &Path, config: &Config, path: Option<&str>, symbol: Option<&str>) -> Result<usize> {
+    let (query, session_id, credited) = {
+        let mut last = LAST_SEARCH.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(search) = last.get_mut(root) else {
+            return Ok(0);
+        };
+        let (credited, rest): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut search.results).into_iter().partition(|(p, name)| {
+                path == Some(p.as_str()) || (symbol.is_some() && name.as_deref() == symbol)
+            });
+        search.results = rest;
+        (search.query.clone(), search.session_id.clone(), credited)
+    };
+    if credited.is_empty() {
+        return Ok(0);
+    }
+
+    let store = open_store_rw(root, config)?;
+    for (p, name) in &credited {
+        // A follow-up on a file credits the file; on a symbol, that chunk.
+        let symbol = if path == Some(p.as_str()) { None } else { name.as_deref() };
+        store.add_feedback(&query, p, symbol, None, true, session_id.as_deref())?;
+    }
+    Ok(credited.len())
+}
+
+fn open_store_rw(root: &Path, config: &Config) -> Result<Store> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let storage_dir = config.storage_dir(&root);
+    Store::open(&storage_dir)
+}
+
+fn open_store_ro(root: &Path, config: &Config) -> Result<Option<Store>> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let storage_dir = config.storage_dir(&root);
+    Store::open_if_exists(&storage_dir)
+}
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::embed::Embedder;
+use crate::store::sqlite::{Intent, SearchResult, Store};
+use crate::store::tokens::{query_terms, split_identifier};
+
+/// Terms match when equal or sharing this many leading characters, so
+/// `migrate` finds `migration` and `embeddings` finds `embedding`.
+const SHARED_PREFIX: usize = 5;
+
+/// Record `goal` as the session's intent, replacing any earlier one. Its
+/// key terms are stored; with an embedder, its embedding too.
+pub fn set(
+    root: &Path,
+    config: &Config,
+    goal: &str,
+    session_id: Option<&str>,
+    embedder: Option<&dyn Embedder>,
+) -> Result<Intent> {
+    let terms = key_terms(goal);
+    if terms.is_empty() {
+        anyhow::bail!("intent '{goal}' has no key terms");
+    }
+    let embedding = embedder.map(|e| e.embed(goal)).transpose()?;
+    let store = open_store_rw(root, config)?;
+    let id = store.set_intent(goal, &terms, embedding.as_deref(), session_id)?;
+    Ok(Intent {
+        id,
+        goal: goal.to_string(),
+        terms,
+        embedding,
+        session_id: session_id.map(String::from),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        neighbours_generation: None,
+    })
+}
+
+pub fn list(root: &Path, config: &Config, session_id: Option<&str>) -> Result<Vec<Intent>> {
+    match open_store_ro(root, config)? {
+        Some(store) => store.get_intents(session_id),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn clear(root: &Path, config: &Config, session_id: Option<&str>) -> Result<usize> {
+    match open_store_ro(root, config)? {
+        Some(store) => store.clear_intents(session_id),
+        None => Ok(0),
+    }
+}
+
+/// Key terms of a goal: its query terms (see `tokens::query_terms`), so
+/// intents weigh the same words a search for the goal would.
+pub fn key_terms(goal: &str) -> Vec<String> {
+    query_terms(goal)
+}
+
+/// Share of `terms` found in the result's path, name or content, as words
+/// or identifier parts.
+pub fn term_share(terms: &[String], result: &SearchResult) -> f64 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let mut words: Vec<String> = Vec::new();
+    let text = [result.file_path.as_str(), result.chunk_name.as_deref().unwrap_or(""), result.content.as_str()];
+    for word in text.iter().flat_map(|t| t.split(|c: char| !c.is_alphanumeric() && c != '_')) {
+        if !word.is_empty() {
+            words.push(word.to_lowercase());
+            words.extend(split_identifier(word));
+        }
+    }
+    words.sort();
+    words.dedup();
+    let found = terms.iter().filter(|t| words.iter().any(|w| matches(t, w))).count();
+    found as f64 / terms.len() as f64
+}
+
+fn matches(term: &str, word: &str) -> bool {
+    if term == word {
+        return true;
+    }
+    let shared = term.chars().zip(word.chars()).take_while(|(a, b)| a == b).count();
+    shared >= SHARED_PREFIX
+}
+
+fn open_store_rw(root: &Path, config: &Config) -> Result<Store> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let storage_dir = config.storage_dir(&root);
+    Store::open(&storage_dir)
+}
+
+fn open_store_ro(root: &Path, config: &Config) -> Result<Option<Store>> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let storage_dir = config.storage_dir(&root);
+    Store::open_if_exists(&storage_dir)
+}
@@ -1,3 +1,4 @@
 pub mod annotations;
+pub mod feedback;
 pub mod intent;
 pub mod workset;
@@ -1,7 +1,10 @@
 pub mod ollama;
+pub mod openai;
 
 use anyhow::Result;
 
+use crate::config::{EmbedBackend, EmbedConfig};
+
 pub type Embedding = Vec<f32>;
 
 pub trait Embedder: Send + Sync {
@@ -13,6 +16,18 @@ pub trait Embedder: Send + Sync {
     fn model_name(&self) -> &str;
 }
 
+/// The embedder `[embed.backend]` configures. `none`, the default, keeps
+/// the local Ollama defaults (`nomic-embed-text` on port 11434).
+pub fn from_config(config: &EmbedConfig) -> Result<Box<dyn Embedder>> {
+    Ok(match &config.backend {
+        EmbedBackend::Ollama { model, url } => Box::new(ollama::OllamaEmbedder::new(url, model)?),
+        EmbedBackend::OpenAi { model, url, api_key_env } => {
+            Box::new(openai::OpenAiEmbedder::from_env(url, model, api_key_env)?)
+        }
+        EmbedBackend::None => Box::new(ollama::OllamaEmbedder::default()?),
+    })
+}
+
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
     let mut dot = 0.0f32;
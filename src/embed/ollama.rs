@@ -28,6 +28,7 @@ impl OllamaEmbedder {
         Ok(embedder)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Result<Self> {
         Self::new("http://localhost:11434", "nomic-embed-text")
     }
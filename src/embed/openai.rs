@@ -0,0 +1,229 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use super::{Embedder, Embedding};
+
+/// Inputs sent per request; larger batches are split.
+const MAX_BATCH: usize = 64;
+
+/// Inputs are cut to this many bytes, as for Ollama.
+const MAX_INPUT_BYTES: usize = 8192;
+
+/// Client for the OpenAI embeddings API (`POST {base_url}/embeddings`), as
+/// served by OpenAI itself and by local servers such as vLLM, llama.cpp
+/// and LM Studio. `base_url` includes the version, e.g.
+/// `http://localhost:8000/v1`.
+pub struct OpenAiEmbedder {
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    dimensions: usize,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+impl OpenAiEmbedder {
+    /// Connect and probe the model's dimensions. Without an API key no
+    /// `Authorization` header is sent, which local servers accept.
+    pub fn new(base_url: &str, model: &str, api_key: Option<String>) -> Result<Self> {
+        let mut embedder = Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key,
+            dimensions: 0,
+        };
+
+        // Probe dimensions with a short test string
+        let test = embedder.embed("test")?;
+        embedder.dimensions = test.len();
+        Ok(embedder)
+    }
+
+    /// Like `new`, reading the API key from the environment variable
+    /// `api_key_env`; unset or empty means no key.
+    pub fn from_env(base_url: &str, model: &str, api_key_env: &str) -> Result<Self> {
+        let api_key = std::env::var(api_key_env).ok().filter(|k| !k.is_empty());
+        Self::new(base_url, model, api_key)
+    }
+
+    fn request(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        let input: Vec<&str> = texts.iter().map(|t| truncate(if t.is_empty() { " " } else { t })).collect();
+        let url = format!("{}/embeddings", self.base_url);
+        let body = serde_json::json!({
+            "model": self.model,
+            "input": input,
+        });
+
+        let mut request = ureq::post(&url);
+        if let Some(key) = &self.api_key {
+            request = request.header("Authorization", &format!("Bearer {key}"));
+        }
+        let mut response = match request.send_json(&body) {
+            Ok(r) => r,
+            Err(ureq::Error::StatusCode(code)) => {
+                bail!("embedding server returned HTTP {code}");
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!(e).context("embedding request failed"));
+            }
+        };
+
+        let mut resp: EmbeddingResponse = response
+            .body_mut()
+            .read_json()
+            .context("parsing embedding response")?;
+        if resp.data.len() != texts.len() {
+            bail!("embedding server returned {} embeddings for {} inputs", resp.data.len(), texts.len());
+        }
+        // Entries carry their input's index and need not arrive in order,
+        // but each input needs exactly one.
+        resp.data.sort_by_key(|d| d.index);
+        if let Some((i, d)) = resp.data.iter().enumerate().find(|(i, d)| d.index != *i) {
+            bail!("embedding server returned index {} where {i} was expected for {} inputs", d.index, texts.len());
+        }
+        Ok(resp.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+impl Embedder for OpenAiEmbedder {
+    fn embed(&self, text: &str) -> Result<Embedding> {
+        let mut embeddings = self.request(&[text])?;
+        Ok(embeddings.remove(0))
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH) {
+            embeddings.extend(self.request(batch)?);
+        }
+        Ok(embeddings)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+}
+
+fn truncate(text: &str) -> &str {
+    if text.len() <= MAX_INPUT_BYTES {
+        return text;
+    }
+    let mut end = MAX_INPUT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Serve `responses` in order on a local port, sending each request's
+    /// head and body back over the channel. Returns the base URL.
+    fn stub_server(responses: Vec<(u16, String)>) -> (String, mpsc::Receiver<(String, serde_json::Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let length = head
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                    .unwrap_or(0);
+                let mut request = vec![0; length];
+                reader.read_exact(&mut request).unwrap();
+                tx.send((head, serde_json::from_slice(&request).unwrap())).unwrap();
+                let reply = format!(
+                    "HTTP/1.1 {status} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                reader.get_mut().write_all(reply.as_bytes()).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn response(embeddings: &[(usize, [f32; 3])]) -> (u16, String) {
+        let data: Vec<_> = embeddings
+            .iter()
+            .map(|(i, e)| serde_json::json!({"object": "embedding", "index": i, "embedding": e}))
+            .collect();
+        (200, serde_json::json!({"object": "list", "data": data, "model": "m"}).to_string())
+    }
+
+    #[test]
+    fn embeds_batches_in_input_order_with_the_api_key() {
+        let (url, requests) = stub_server(vec![
+            response(&[(0, [1.0, 0.0, 0.0])]),
+            response(&[(1, [0.0, 2.0, 0.0]), (0, [0.0, 0.0, 3.0])]),
+            (500, "{}".to_string()),
+        ]);
+        let embedder = OpenAiEmbedder::new(&url, "nomic-embed", Some("sk-test".to_string())).unwrap();
+        assert_eq!(embedder.dimensions(), 3);
+        assert_eq!(embedder.model_name(), "nomic-embed");
+        let (head, body) = requests.recv().unwrap();
+        assert!(head.starts_with("POST /v1/embeddings "), "{head}");
+        assert!(head.to_ascii_lowercase().contains("authorization: bearer sk-test"), "{head}");
+        assert_eq!(body["input"], serde_json::json!(["test"]));
+
+        let embeddings = embedder.embed_batch(&["first", "second"]).unwrap();
+        assert_eq!(embeddings, [vec![0.0, 0.0, 3.0], vec![0.0, 2.0, 0.0]]);
+        let (_, body) = requests.recv().unwrap();
+        assert_eq!(body, serde_json::json!({"model": "nomic-embed", "input": ["first", "second"]}));
+
+        let err = embedder.embed("third").unwrap_err();
+        assert!(err.to_string().contains("HTTP 500"), "{err}");
+    }
+
+    #[test]
+    fn rejects_duplicate_and_out_of_range_indices() {
+        let (url, _requests) = stub_server(vec![
+            response(&[(0, [1.0, 0.0, 0.0])]),
+            response(&[(0, [0.0, 2.0, 0.0]), (0, [0.0, 0.0, 3.0])]),
+            response(&[(0, [0.0, 2.0, 0.0]), (2, [0.0, 0.0, 3.0])]),
+        ]);
+        let embedder = OpenAiEmbedder::new(&url, "m", None).unwrap();
+        let err = embedder.embed_batch(&["first", "second"]).unwrap_err();
+        assert!(err.to_string().contains("index 0 where 1 was expected"), "{err}");
+        let err = embedder.embed_batch(&["first", "second"]).unwrap_err();
+        assert!(err.to_string().contains("index 2 where 1 was expected"), "{err}");
+    }
+
+    #[test]
+    fn sends_no_key_when_the_variable_is_unset() {
+        let (url, requests) = stub_server(vec![response(&[(0, [1.0, 0.0, 0.0])])]);
+        OpenAiEmbedder::from_env(&url, "m", "BOOGER_TEST_UNSET_API_KEY").unwrap();
+        let (head, _) = requests.recv().unwrap();
+        assert!(!head.to_ascii_lowercase().contains("authorization"), "{head}");
+        // Cut inputs stay valid UTF-8.
+        assert_eq!(truncate(&format!("a{}", "é".repeat(MAX_INPUT_BYTES))).len(), MAX_INPUT_BYTES - 1);
+    }
+}
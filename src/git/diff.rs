@@ -1,11 +1,12 @@
 use anyhow::{bail, Context, Result};
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::process::Command;
 
 use crate::index::chunker;
 use crate::index::walker;
+use crate::store::sqlite::CommitSymbol;
 
 #[derive(Debug, Serialize)]
 pub struct BranchDiff {
@@ -221,6 +222,56 @@ pub fn staged_diff(root: &Path) -> Result<BranchDiff> {
     })
 }
 
+/// Files and symbols changed on the current branch, as seen by ranking.
+/// Serialized into the index, which caches it between processes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BranchChanges {
+    /// Added or modified files, relative to the repo root.
+    pub files: HashSet<String>,
+    /// Line ranges of added or modified symbols, per file.
+    pub symbols: HashMap<String, Vec<(u32, u32)>>,
+}
+
+impl BranchChanges {
+    pub fn from_diff(diff: &BranchDiff) -> Self {
+        let mut changes = BranchChanges::default();
+        for f in diff.files.iter().filter(|f| !matches!(f.status, FileStatus::Deleted)) {
+            changes.files.insert(f.path.clone());
+            let ranges = f.added.iter().chain(&f.modified).map(|s| (s.start_line, s.end_line));
+            changes.symbols.entry(f.path.clone()).or_default().extend(ranges);
+        }
+        changes
+    }
+
+    /// Whether lines `start..=end` of `path` overlap a changed symbol.
+    pub fn touches_lines(&self, path: &str, start: i64, end: i64) -> bool {
+        self.symbols.get(path).is_some_and(|ranges| {
+            ranges.iter().any(|&(s, e)| i64::from(s) <= end && i64::from(e) >= start)
+        })
+    }
+}
+
+/// `branch_diff` against `base_ref` (the default branch if None), reduced to
+/// `BranchChanges`. Outside a git repository there are no changes.
+pub fn branch_changes(root: &Path, base_ref: Option<&str>) -> BranchChanges {
+    let base = base_ref.map_or_else(|| default_branch(root), String::from);
+    branch_diff(root, &base)
+        .map(|diff| BranchChanges::from_diff(&diff))
+        .unwrap_or_default()
+}
+
+/// The commit HEAD points at, or None outside a git repository (or
+/// before the first commit).
+pub fn head_commit(root: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", "-q", "HEAD"])
+        .current_dir(root)
+        .output()
+        .ok()?;
+    let head = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !head.is_empty()).then_some(head)
+}
+
 /// Returns the list of changed files relative to the repo root.
 pub fn changed_files(root: &Path, base_ref: &str) -> Result<Vec<String>> {
     let root = root
@@ -231,6 +282,64 @@ pub fn changed_files(root: &Path, base_ref: &str) -> Result<Vec<String>> {
     Ok(changed.into_iter().map(|(_, path)| path).collect())
 }
 
+/// Symbols a commit added, modified or removed relative to its first
+/// parent (or to nothing, for a root commit), with paths relative to
+/// `root`. Imports are left out and merge commits report none. Commits changing more than
+/// `max_files` files (vendoring, mass reformatting) are not chunked.
+pub fn commit_symbols(root: &Path, hash: &str, max_files: usize) -> Result<Vec<CommitSymbol>> {
+    let output = Command::new("git")
+        .args(["diff-tree", "-r", "--root", "--no-commit-id", "--name-status", "-z", "--no-renames", "--relative"])
+        .arg(hash)
+        .current_dir(root)
+        .output()
+        .context("git diff-tree")?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        bail!("git diff-tree failed: {err}");
+    }
+    let changed = parse_name_status_nul(&output.stdout)?;
+    if changed.len() > max_files {
+        return Ok(Vec::new());
+    }
+
+    let parent = format!("{hash}^");
+    let mut symbols = Vec::new();
+    for (status_char, rel_path) in &changed {
+        let path = Path::new(rel_path);
+        let Some(lang) = walker::detect_language(path) else {
+            continue;
+        };
+        if walker::is_binary(path) {
+            continue;
+        }
+        // `./` makes the path relative to `root` rather than the repository.
+        let spec = format!("./{rel_path}");
+        let base_chunks = match status_char {
+            'A' => Vec::new(),
+            _ => git_show(root, &parent, &spec).map(|c| chunker::chunk_file(&c, Some(lang))).unwrap_or_default(),
+        };
+        let head_chunks = match status_char {
+            'D' => Vec::new(),
+            _ => git_show(root, hash, &spec).map(|c| chunker::chunk_file(&c, Some(lang))).unwrap_or_default(),
+        };
+
+        let (added, removed, modified) = diff_chunks(&base_chunks, &head_chunks);
+        let mut changes: Vec<(&str, SymbolChange)> = Vec::new();
+        changes.extend(added.into_iter().map(|c| ("added", c)));
+        changes.extend(modified.into_iter().map(|c| ("modified", c)));
+        changes.extend(removed.into_iter().map(|c| ("removed", c)));
+        changes.retain(|(_, c)| !c.name.is_empty() && c.kind != "import");
+        changes.sort_by_key(|(_, c)| c.start_line);
+        symbols.extend(changes.into_iter().map(|(change, c)| CommitSymbol {
+            path: rel_path.clone(),
+            kind: c.kind,
+            name: c.name,
+            change: change.to_string(),
+        }));
+    }
+    Ok(symbols)
+}
+
 type ChunkKey = (String, String, u32); // (kind, name, occurrence index)
 
 /// Build a map keyed by (kind, name, nth_occurrence) to handle duplicate names
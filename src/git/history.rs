@@ -0,0 +1,110 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use crate::store::sqlite::{Commit, FileHistory};
+
+/// How many of the latest commits `file_history` reads. Older history
+/// rarely says much about what is being worked on now.
+pub const MAX_COMMITS: usize = 1000;
+
+/// Churn and last-modified time of every file changed in the last
+/// `max_commits` commits, keyed by path relative to `root`. Files outside
+/// `root` (when it is a subdirectory of the repository) are left out.
+pub fn file_history(root: &Path, max_commits: usize) -> Result<HashMap<String, FileHistory>> {
+    let output = Command::new("git")
+        .args(["-c", "core.quotepath=off", "log", "--no-renames", "--relative", "--name-only"])
+        .arg(format!("-n{max_commits}"))
+        .arg("--format=%x01%ct")
+        .current_dir(root)
+        .output()
+        .context("git log")?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        bail!("git log failed: {err}");
+    }
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The latest `max_commits` commits touching `root`, newest first, without
+/// their symbols (see `diff::commit_symbols`).
+pub fn commit_log(root: &Path, max_commits: usize) -> Result<Vec<Commit>> {
+    let output = Command::new("git")
+        .args(["log"])
+        .arg(format!("-n{max_commits}"))
+        .arg("--format=%H%x1f%an%x1f%ct%x1f%B%x1e")
+        .args(["--", "."])
+        .current_dir(root)
+        .output()
+        .context("git log")?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        bail!("git log failed: {err}");
+    }
+    Ok(parse_commits(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse `git log --format=%H%x1f%an%x1f%ct%x1f%B%x1e`: unit-separated
+/// fields, one record-separated entry per commit.
+fn parse_commits(raw: &str) -> Vec<Commit> {
+    raw.split('\u{1e}')
+        .filter_map(|entry| {
+            let mut fields = entry.trim_start_matches('\n').splitn(4, '\u{1f}');
+            let hash = fields.next()?.to_string();
+            let author = fields.next()?.to_string();
+            let time = fields.next()?.parse().ok()?;
+            let message = fields.next()?.trim_end().to_string();
+            Some(Commit { hash, author, time, message, symbols: Vec::new() })
+        })
+        .collect()
+}
+
+/// Parse `git log --name-only --format=%x01%ct`: a `\x01<time>` line per
+/// commit, newest first, followed by the paths it touched.
+fn parse_log(raw: &str) -> HashMap<String, FileHistory> {
+    let mut history: HashMap<String, FileHistory> = HashMap::new();
+    let mut time = 0;
+    for line in raw.lines() {
+        if let Some(ts) = line.strip_prefix('\u{1}') {
+            time = ts.trim().parse().unwrap_or(0);
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        history
+            .entry(line.to_string())
+            .and_modify(|h| h.commits += 1)
+            .or_insert(FileHistory { commits: 1, last_modified: time });
+    }
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_commits_and_keeps_the_newest_time() {
+        let raw = "\u{1}300\n\nsrc/a.rs\nsrc/b.rs\n\u{1}200\n\nsrc/a.rs\n\u{1}100\n\nsrc/a.rs\nREADME.md\n";
+        let history = parse_log(raw);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history["src/a.rs"], FileHistory { commits: 3, last_modified: 300 });
+        assert_eq!(history["src/b.rs"], FileHistory { commits: 1, last_modified: 300 });
+        assert_eq!(history["README.md"], FileHistory { commits: 1, last_modified: 100 });
+    }
+
+    #[test]
+    fn parses_multi_line_messages() {
+        let raw = "abc\u{1f}Ada\u{1f}300\u{1f}Fix retry\n\nThe backoff overflowed.\n\u{1e}\n\
+                   def\u{1f}Bo\u{1f}200\u{1f}Add retry\n\u{1e}\n";
+        let commits = parse_commits(raw);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].hash, "abc");
+        assert_eq!(commits[0].author, "Ada");
+        assert_eq!(commits[0].time, 300);
+        assert_eq!(commits[0].message, "Fix retry\n\nThe backoff overflowed.");
+        assert_eq!(commits[1].message, "Add retry");
+    }
+}
@@ -0,0 +1,366 @@
+use tree_sitter::{Node, Parser};
+
+use crate::index::chunker::get_language;
+use crate::store::sqlite::{ChunkInsert, OccurrenceInsert};
+
+/// How an identifier is used at one occurrence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Definition,
+    Call,
+    Type,
+    Import,
+    Write,
+    Reference,
+}
+
+impl Role {
+    pub const ALL: [Role; 6] = [
+        Role::Definition,
+        Role::Call,
+        Role::Type,
+        Role::Import,
+        Role::Write,
+        Role::Reference,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Definition => "definition",
+            Role::Call => "call",
+            Role::Type => "type",
+            Role::Import => "import",
+            Role::Write => "write",
+            Role::Reference => "reference",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|r| r.as_str() == s)
+    }
+}
+
+/// One identifier occurrence found by tree-sitter.
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub name: String,
+    pub role: Role,
+    /// 1-based line.
+    pub line: u32,
+    /// 1-based column, in bytes.
+    pub column: u32,
+}
+
+const IDENTIFIER_KINDS: &[&str] = &[
+    "identifier",
+    "type_identifier",
+    "field_identifier",
+    "property_identifier",
+    "shorthand_property_identifier",
+    "package_identifier",
+];
+
+const IMPORT_KINDS: &[&str] = &[
+    "use_declaration",
+    "import_statement",
+    "import_from_statement",
+    "import_declaration",
+    "preproc_include",
+];
+
+/// Declaration nodes whose `name` field defines a symbol.
+const DEFINITION_KINDS: &[&str] = &[
+    // Rust
+    "function_item", "function_signature_item", "struct_item", "enum_item", "enum_variant",
+    "trait_item", "mod_item", "type_item", "const_item", "static_item", "macro_definition",
+    "field_declaration",
+    // Python
+    "function_definition", "class_definition",
+    // JavaScript / TypeScript
+    "function_declaration", "generator_function_declaration", "class_declaration",
+    "abstract_class_declaration", "method_definition", "interface_declaration",
+    "type_alias_declaration", "enum_declaration", "variable_declarator",
+    // Go
+    "method_declaration", "method_elem", "type_spec", "const_spec", "var_spec",
+];
+
+/// (call node, field holding the callee).
+const CALL_FIELDS: &[(&str, &str)] = &[
+    ("call_expression", "function"),
+    ("call", "function"),
+    ("macro_invocation", "macro"),
+    ("new_expression", "constructor"),
+];
+
+/// (path node, field holding the last segment): `a::b`, `a.b`.
+const PATH_FIELDS: &[(&str, &str)] = &[
+    ("scoped_identifier", "name"),
+    ("field_expression", "field"),
+    ("member_expression", "property"),
+    ("attribute", "attribute"),
+    ("selector_expression", "field"),
+    ("generic_function", "function"),
+];
+
+/// (assignment node, field holding the target).
+const WRITE_FIELDS: &[(&str, &str)] = &[
+    ("assignment_expression", "left"),
+    ("compound_assignment_expr", "left"),
+    ("augmented_assignment_expression", "left"),
+    ("assignment", "left"),
+    ("augmented_assignment", "left"),
+    ("let_declaration", "pattern"),
+    ("short_var_declaration", "left"),
+    ("assignment_statement", "left"),
+];
+
+/// Extract identifier occurrences from a file. Empty for languages without
+/// a tree-sitter grammar.
+pub fn extract_occurrences(content: &str, language: Option<&str>) -> Vec<Occurrence> {
+    let Some(ts_lang) = language.and_then(get_language) else {
+        return Vec::new();
+    };
+    let mut parser = Parser::new();
+    if parser.set_language(&ts_lang).is_err() {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(content, None) else {
+        return Vec::new();
+    };
+
+    let mut out = Vec::new();
+    collect(tree.root_node(), content, &mut out);
+    out
+}
+
+fn collect(node: Node, source: &str, out: &mut Vec<Occurrence>) {
+    if IDENTIFIER_KINDS.contains(&node.kind()) {
+        let pos = node.start_position();
+        out.push(Occurrence {
+            name: source[node.byte_range()].to_string(),
+            role: classify(node),
+            line: pos.row as u32 + 1,
+            column: pos.column as u32 + 1,
+        });
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, source, out);
+    }
+}
+
+/// True if `node` is (one of) the `field` children of `parent`.
+fn is_field(parent: Node, field: &str, node: Node) -> bool {
+    let mut cursor = parent.walk();
+    parent.children_by_field_name(field, &mut cursor).any(|c| c == node)
+}
+
+fn field_of(table: &[(&str, &str)], parent: Node, node: Node) -> bool {
+    table.iter().any(|(kind, field)| parent.kind() == *kind && is_field(parent, field, node))
+}
+
+fn classify(node: Node) -> Role {
+    let mut ancestor = node.parent();
+    while let Some(a) = ancestor {
+        if IMPORT_KINDS.contains(&a.kind()) {
+            return Role::Import;
+        }
+        ancestor = a.parent();
+    }
+
+    let Some(parent) = node.parent() else {
+        return Role::Reference;
+    };
+    if is_definition(parent, node) {
+        return Role::Definition;
+    }
+
+    // Climb from the last segment of `a.b.c` / `a::b::c` to the whole path,
+    // then look at what the path is used as.
+    let mut cur = node;
+    let mut parent = parent;
+    while field_of(PATH_FIELDS, parent, cur) {
+        cur = parent;
+        match parent.parent() {
+            Some(p) => parent = p,
+            None => break,
+        }
+    }
+    if field_of(CALL_FIELDS, parent, cur) || is_macro_arg_call(node) {
+        return Role::Call;
+    }
+    if field_of(WRITE_FIELDS, parent, cur)
+        || (parent.kind() == "expression_list"
+            && parent.parent().is_some_and(|gp| field_of(WRITE_FIELDS, gp, parent)))
+    {
+        return Role::Write;
+    }
+
+    if node.kind() == "type_identifier" || has_type_ancestor(node) {
+        return Role::Type;
+    }
+    Role::Reference
+}
+
+/// Rust macro arguments are unparsed token trees: treat `name(` and
+/// `name!(` inside one as calls, so `assert_eq!(helper(), 42)` counts.
+fn is_macro_arg_call(node: Node) -> bool {
+    if node.parent().is_none_or(|p| p.kind() != "token_tree") {
+        return false;
+    }
+    let mut next = node.next_sibling();
+    if next.is_some_and(|n| n.kind() == "!") {
+        next = next.and_then(|n| n.next_sibling());
+    }
+    next.is_some_and(|n| n.kind() == "token_tree" && n.child(0).is_some_and(|c| c.kind() == "("))
+}
+
+fn is_definition(parent: Node, node: Node) -> bool {
+    match parent.kind() {
+        // C: the name sits inside the declarator of a function definition.
+        "function_declarator" => {
+            is_field(parent, "declarator", node)
+                && parent.parent().is_some_and(|p| p.kind() == "function_definition")
+        }
+        "type_definition" => is_field(parent, "declarator", node),
+        // A C struct/enum/union is only defined where it has a body.
+        "struct_specifier" | "enum_specifier" | "union_specifier" => {
+            is_field(parent, "name", node) && parent.child_by_field_name("body").is_some()
+        }
+        kind => DEFINITION_KINDS.contains(&kind) && is_field(parent, "name", node),
+    }
+}
+
+/// Python annotations are plain identifiers under a `type` node.
+fn has_type_ancestor(node: Node) -> bool {
+    let mut ancestor = node.parent();
+    for _ in 0..3 {
+        match ancestor {
+            Some(a) if a.kind() == "type" => return true,
+            Some(a) => ancestor = a.parent(),
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Extract occurrences and attach each to its enclosing chunk, ready for
+/// `Store::insert_occurrences`. `chunk_ids` are the ids of `chunks` as
+/// inserted. Occurrences outside every chunk are dropped, like the code
+/// around them.
+pub fn occurrence_inserts(
+    content: &str,
+    language: Option<&str>,
+    chunks: &[ChunkInsert],
+    chunk_ids: &[i64],
+) -> Vec<OccurrenceInsert> {
+    let owners = line_owners(chunks);
+    extract_occurrences(content, language)
+        .into_iter()
+        .filter_map(|o| {
+            let idx = (*owners.get((o.line as usize).checked_sub(1)?)?)?;
+            Some(OccurrenceInsert {
+                chunk_id: chunk_ids[idx],
+                name: o.name,
+                role: o.role.as_str(),
+                line: o.line,
+                column: o.column,
+            })
+        })
+        .collect()
+}
+
+/// Index of the innermost chunk whose content covers each line (1-based
+/// line `n` at `n - 1`), if any. Container chunks (impl, class, ...) only
+/// cover their header lines, so occurrences inside methods resolve to the
+/// method.
+fn line_owners(chunks: &[ChunkInsert]) -> Vec<Option<usize>> {
+    let spans: Vec<(u32, u32)> = chunks
+        .iter()
+        .map(|c| (c.start_line.max(1), c.start_line + (c.content.lines().count() as u32).max(1) - 1))
+        .collect();
+    let width = |i: usize| chunks[i].end_line - chunks[i].start_line;
+    let mut owners = vec![None; spans.iter().map(|&(_, last)| last as usize).max().unwrap_or(0)];
+    for (i, &(start, last)) in spans.iter().enumerate() {
+        for line in start..=last {
+            let owner = &mut owners[line as usize - 1];
+            if owner.is_none_or(|j| width(i) < width(j)) {
+                *owner = Some(i);
+            }
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles(content: &str, lang: &str, name: &str) -> Vec<&'static str> {
+        extract_occurrences(content, Some(lang))
+            .into_iter()
+            .filter(|o| o.name == name)
+            .map(|o| o.role.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn classifies_rust_roles() {
+        let src = "use crate::store::Store;\n\
+                   struct Config { name: String }\n\
+                   fn load(store: &Store) -> Config {\n    \
+                       let mut cfg = Config::new();\n    \
+                       cfg.name = store.path();\n    \
+                       helper(cfg);\n    \
+                       assert!(check(cfg.name));\n\
+                   }\n";
+        assert_eq!(roles(src, "rust", "Store"), ["import", "type"]);
+        assert_eq!(roles(src, "rust", "Config"), ["definition", "type", "reference"]);
+        assert_eq!(roles(src, "rust", "load"), ["definition"]);
+        assert_eq!(roles(src, "rust", "new"), ["call"]);
+        assert_eq!(roles(src, "rust", "path"), ["call"]);
+        assert_eq!(roles(src, "rust", "helper"), ["call"]);
+        assert_eq!(roles(src, "rust", "check"), ["call"]);
+        assert_eq!(roles(src, "rust", "cfg"), ["write", "reference", "reference", "reference"]);
+        assert_eq!(roles(src, "rust", "name"), ["definition", "write", "reference"]);
+    }
+
+    #[test]
+    fn classifies_python_roles() {
+        let src = "from app import Store\n\n\
+                   def load(path: Store):\n    \
+                       self.cache = Store(path)\n    \
+                       return path\n";
+        assert_eq!(roles(src, "python", "Store"), ["import", "type", "call"]);
+        assert_eq!(roles(src, "python", "load"), ["definition"]);
+        assert_eq!(roles(src, "python", "cache"), ["write"]);
+        let lines: Vec<u32> = extract_occurrences(src, Some("python"))
+            .iter()
+            .filter(|o| o.name == "path")
+            .map(|o| o.line)
+            .collect();
+        assert_eq!(lines, [3, 4, 5]);
+    }
+
+    #[test]
+    fn unsupported_language_has_no_occurrences() {
+        assert!(extract_occurrences("some text", Some("markdown")).is_empty());
+        assert!(extract_occurrences("fn a() {}", None).is_empty());
+    }
+
+    #[test]
+    fn occurrences_attach_to_the_innermost_chunk() {
+        let src = "impl Store {\n    fn open() -> Store {\n        let url = default_url();\n        Store::connect(url)\n    }\n}\n";
+        let chunks = crate::index::chunker::chunk_file(src, Some("rust"));
+        let ids: Vec<i64> = (0..chunks.len() as i64).map(|i| 100 + i).collect();
+        let method = chunks.iter().position(|c| c.name.as_deref() == Some("open")).unwrap();
+        let container = chunks.iter().position(|c| c.kind == "impl").unwrap();
+        let located: Vec<(String, u32, i64)> = occurrence_inserts(src, Some("rust"), &chunks, &ids)
+            .into_iter()
+            .map(|o| (o.name, o.line, o.chunk_id))
+            .collect();
+        assert!(located.contains(&("Store".to_string(), 1, ids[container])), "{located:?}");
+        assert!(located.contains(&("connect".to_string(), 4, ids[method])), "{located:?}");
+    }
+}
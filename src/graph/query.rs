@@ -0,0 +1,216 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use super::extract::Role;
+use crate::index::chunker::get_language;
+use crate::store::sqlite::{SearchFilter, SearchResult, Store, SymbolOccurrence, TestScope};
+
+/// Occurrences of a symbol, split into definitions and everything else.
+pub struct References {
+    pub definitions: Vec<SymbolOccurrence>,
+    pub references: Vec<SymbolOccurrence>,
+}
+
+/// Look up a symbol in the occurrence index, in the chunks `filter` selects.
+///
+/// `scope` limits results to one role: `definition` returns only
+/// definitions, any other role only references of that role. Unknown
+/// scopes are ignored.
+pub fn references(
+    store: &Store,
+    symbol: &str,
+    filter: &SearchFilter,
+    scope: Option<&str>,
+) -> Result<References> {
+    let role = scope.and_then(Role::parse);
+    let mut occurrences = store.find_occurrences(symbol, filter, role.map(|r| r.as_str()))?;
+    occurrences.extend(
+        unparsed_occurrences(store, symbol, filter)?
+            .into_iter()
+            .filter(|o| role.is_none_or(|r| o.role == r.as_str())),
+    );
+    occurrences.sort_by(|a, b| (&a.file_path, a.line, a.column).cmp(&(&b.file_path, b.line, b.column)));
+
+    let (mut definitions, references): (Vec<_>, Vec<_>) = occurrences
+        .into_iter()
+        .partition(|o| o.role == Role::Definition.as_str());
+    // A definition reads best as its chunk's signature.
+    for d in &mut definitions {
+        if d.chunk_name.as_deref() == Some(symbol)
+            && let Some(sig) = &d.chunk_signature
+        {
+            d.text = sig.clone();
+        }
+    }
+    Ok(References { definitions, references })
+}
+
+/// Occurrences of `symbol` in files without a tree-sitter grammar, which
+/// have none in the occurrence index: whole-word matches in their chunks,
+/// with the role guessed from the surrounding text. A chunk named after
+/// the symbol counts as its definition.
+fn unparsed_occurrences(store: &Store, symbol: &str, filter: &SearchFilter) -> Result<Vec<SymbolOccurrence>> {
+    let pattern = regex::Regex::new(&format!(r"\b{}\b", regex::escape(symbol)))?;
+    let chunks = match crate::search::grep::literal_query(&regex::escape(symbol)) {
+        Some(q) => store.trigram_candidates(&q, filter)?,
+        None => store.all_chunks(filter)?,
+    };
+
+    let mut found = Vec::new();
+    for chunk in chunks {
+        if chunk.language.as_deref().and_then(get_language).is_some() {
+            continue;
+        }
+        let occurrence = |line: i64, column: usize, role: Role, text: &str| SymbolOccurrence {
+            file_path: chunk.file_path.clone(),
+            line: line as u32,
+            column: column as u32,
+            role: role.as_str().to_string(),
+            chunk_kind: chunk.chunk_kind.clone(),
+            chunk_name: chunk.chunk_name.clone(),
+            chunk_signature: chunk.signature.clone(),
+            text: text.to_string(),
+        };
+        if chunk.chunk_name.as_deref() == Some(symbol) {
+            let first = chunk.content.lines().next().unwrap_or("").trim();
+            found.push(occurrence(chunk.start_line, 1, Role::Definition, first));
+            continue;
+        }
+        for (i, line) in chunk.content.lines().enumerate() {
+            let Some(m) = pattern.find(line) else { continue };
+            let trimmed = line.trim();
+            let role = if chunk.chunk_kind == "import" {
+                Role::Import
+            } else if trimmed.contains(&format!("{symbol}(")) || trimmed.contains(&format!("{symbol}!(")) {
+                Role::Call
+            } else if trimmed.contains(&format!("<{symbol}>"))
+                || trimmed.contains(&format!(": {symbol}"))
+                || trimmed.contains(&format!("-> {symbol}"))
+            {
+                Role::Type
+            } else {
+                Role::Reference
+            };
+            found.push(occurrence(chunk.start_line + i as i64, m.start() + 1, role, trimmed));
+        }
+    }
+    Ok(found)
+}
+
+/// Find test functions for a symbol: by naming convention (`test_<name>`,
+/// `<name>_test`, `<Name>Test`, ...) or test functions that reference it.
+/// Test code is recognized as in `TestScope::Only` searches.
+pub fn tests_for(store: &Store, symbol: &str) -> Result<Vec<SearchResult>> {
+    // Candidates: test chunks that reference the symbol, and chunks named
+    // after it. Keyed by (path, line) to merge duplicates and keep file/line order.
+    let mut candidates: BTreeMap<(String, i64), (SearchResult, bool)> = BTreeMap::new();
+    for chunk in store.symbols_named_like(symbol)? {
+        candidates.insert((chunk.file_path.clone(), chunk.start_line), (chunk, false));
+    }
+    let only_tests = SearchFilter { tests: TestScope::Only, ..Default::default() };
+    for chunk in store.chunks_referencing(symbol, &only_tests)? {
+        candidates
+            .entry((chunk.file_path.clone(), chunk.start_line))
+            .or_insert((chunk, true))
+            .1 = true;
+    }
+
+    let sym_lower = symbol.to_lowercase();
+    let mut tests = Vec::new();
+
+    for (chunk, referenced_from_test) in candidates.into_values() {
+        let name_lower = chunk.chunk_name.as_deref().unwrap_or("").to_lowercase();
+
+        // Match by naming convention
+        let name_match = name_lower == format!("test_{sym_lower}")
+            || name_lower == format!("{sym_lower}_test")
+            || name_lower == format!("{sym_lower}_spec")
+            || name_lower == format!("test{sym_lower}")
+            || name_lower.starts_with(&format!("test_{sym_lower}_"))
+            || name_lower.ends_with(&format!("_{sym_lower}_test"))
+            || name_lower.contains(&format!("test_{sym_lower}"));
+
+        // Match by reference (test function that uses the symbol)
+        let reference_match = !name_match
+            && referenced_from_test
+            && (chunk.chunk_kind == "function" || chunk.chunk_kind == "method")
+            && chunk.chunk_name.as_deref() != Some(symbol);
+
+        if name_match || reference_match {
+            tests.push(chunk);
+        }
+    }
+    Ok(tests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use tempfile::TempDir;
+
+    fn indexed(files: &[(&str, &str)]) -> (TempDir, Store) {
+        let dir = TempDir::new().unwrap();
+        for (path, content) in files {
+            let p = dir.path().join(path);
+            std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+            std::fs::write(p, content).unwrap();
+        }
+        let config = Config::default();
+        crate::index::index_directory(dir.path(), &config).unwrap();
+        let storage_dir = config.storage_dir(&dir.path().canonicalize().unwrap());
+        let store = Store::open(&storage_dir).unwrap();
+        (dir, store)
+    }
+
+    const LIB: &str = "pub struct Parser;\n\n\
+                       pub fn parse(input: &str) -> Parser {\n    Parser\n}\n\n\
+                       pub fn run() {\n    let p: Parser = parse(\"x\");\n}\n";
+
+    #[test]
+    fn references_come_from_the_occurrence_index() {
+        let (_dir, store) = indexed(&[("src/lib.rs", LIB)]);
+
+        let refs = references(&store, "parse", &SearchFilter::default(), None).unwrap();
+        assert_eq!(refs.definitions.len(), 1);
+        assert_eq!(refs.definitions[0].text, "pub fn parse(input: &str) -> Parser");
+        assert_eq!(refs.references.len(), 1);
+        assert_eq!(refs.references[0].role, "call");
+        assert_eq!((refs.references[0].line, refs.references[0].column), (8, 21));
+        assert_eq!(refs.references[0].chunk_name.as_deref(), Some("run"));
+
+        let types = references(&store, "Parser", &SearchFilter::default(), Some("type")).unwrap();
+        assert!(types.definitions.is_empty());
+        assert_eq!(types.references.len(), 2);
+    }
+
+    #[test]
+    fn references_in_unparsed_files_fall_back_to_word_matches() {
+        let (_dir, store) = indexed(&[
+            ("src/lib.rs", LIB),
+            ("scripts/build.rb", "def build\n  parse(ARGV)\n  parser = 1\nend\n"),
+        ]);
+
+        let refs = references(&store, "parse", &SearchFilter::default(), None).unwrap();
+        let found: Vec<_> = refs.references.iter().map(|r| (r.file_path.as_str(), r.line, r.role.as_str())).collect();
+        assert_eq!(found, [("scripts/build.rb", 2, "call"), ("src/lib.rs", 8, "call")]);
+        assert_eq!(refs.references[0].column, 3);
+
+        let calls = references(&store, "parse", &SearchFilter::default(), Some("type")).unwrap();
+        assert!(calls.references.is_empty());
+    }
+
+    #[test]
+    fn tests_for_finds_named_and_referencing_tests() {
+        let (_dir, store) = indexed(&[
+            ("src/lib.rs", LIB),
+            ("tests/parse.rs", "#[test]\nfn test_parse() {}\n\n#[test]\nfn roundtrip() {\n    parse(\"y\");\n}\n\nfn unrelated() {}\n"),
+        ]);
+        let names: Vec<String> = tests_for(&store, "parse")
+            .unwrap()
+            .into_iter()
+            .filter_map(|c| c.chunk_name)
+            .collect();
+        assert_eq!(names, ["test_parse", "roundtrip"]);
+    }
+}
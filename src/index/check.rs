@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+use super::hasher;
+use super::lock::IndexLock;
+use crate::config::Config;
+use crate::store::sqlite::Store;
+
+/// Result of an index integrity check.
+#[derive(Debug, Default, Serialize)]
+pub struct CheckReport {
+    pub files_checked: usize,
+    pub chunks_checked: i64,
+    /// Problems reported by `PRAGMA integrity_check` (empty when healthy).
+    pub sqlite_errors: Vec<String>,
+    /// Error from the FTS5 `integrity-check` command, if any.
+    pub fts_error: Option<String>,
+    /// FTS rows whose chunk no longer exists.
+    pub fts_orphans: i64,
+    /// Chunks missing from the FTS index.
+    pub fts_missing: i64,
+    /// Embeddings whose chunk no longer exists.
+    pub orphan_embeddings: i64,
+    /// Embeddings whose dimension disagrees with the rest of their model.
+    pub mismatched_embeddings: usize,
+    /// Indexed files whose content hash no longer matches disk.
+    pub stale_files: Vec<String>,
+    /// Indexed files that no longer exist on disk.
+    pub missing_files: Vec<String>,
+    /// Repair actions taken (empty unless repair was requested).
+    pub repairs: Vec<String>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems().is_empty()
+    }
+
+    /// One human-readable line per problem found.
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for e in &self.sqlite_errors {
+            out.push(format!("SQLite integrity: {e}"));
+        }
+        if let Some(e) = &self.fts_error {
+            out.push(format!("FTS integrity: {e}"));
+        }
+        if self.fts_orphans > 0 || self.fts_missing > 0 {
+            out.push(format!(
+                "FTS index out of sync: {} orphan row(s), {} missing row(s)",
+                self.fts_orphans, self.fts_missing,
+            ));
+        }
+        if self.orphan_embeddings > 0 {
+            out.push(format!("{} embedding(s) without a chunk", self.orphan_embeddings));
+        }
+        if self.mismatched_embeddings > 0 {
+            out.push(format!(
+                "{} embedding(s) with mismatched dimensions",
+                self.mismatched_embeddings,
+            ));
+        }
+        if !self.stale_files.is_empty() {
+            out.push(format!(
+                "{} file(s) changed on disk since indexing: {}",
+                self.stale_files.len(),
+                preview(&self.stale_files),
+            ));
+        }
+        if !self.missing_files.is_empty() {
+            out.push(format!(
+                "{} indexed file(s) missing on disk: {}",
+                self.missing_files.len(),
+                preview(&self.missing_files),
+            ));
+        }
+        out
+    }
+}
+
+fn preview(paths: &[String]) -> String {
+    const MAX: usize = 5;
+    let mut s = paths.iter().take(MAX).cloned().collect::<Vec<_>>().join(", ");
+    if paths.len() > MAX {
+        s.push_str(&format!(", ... ({} more)", paths.len() - MAX));
+    }
+    s
+}
+
+/// Format a report as plain text (shared by CLI and MCP).
+pub fn format_report(report: &CheckReport) -> String {
+    let mut out = String::new();
+    if !report.repairs.is_empty() {
+        out.push_str("Repairs:\n");
+        for r in &report.repairs {
+            out.push_str(&format!("  - {r}\n"));
+        }
+        out.push('\n');
+    }
+    let problems = report.problems();
+    if problems.is_empty() {
+        out.push_str(&format!(
+            "Index OK — {} files, {} chunks checked",
+            report.files_checked, report.chunks_checked,
+        ));
+    } else {
+        out.push_str(&format!(
+            "{} problem(s) found ({} files, {} chunks checked):\n",
+            problems.len(),
+            report.files_checked,
+            report.chunks_checked,
+        ));
+        for p in &problems {
+            out.push_str(&format!("  - {p}\n"));
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Verify the index for `root`, optionally repairing what can be repaired.
+///
+/// Checks SQLite and FTS5 integrity, cross-validates `chunks` against the FTS
+/// index and embeddings against `chunks`, and compares stored content hashes
+/// with the files on disk. With `repair`, the FTS index is rebuilt, bad
+/// embeddings are dropped, vanished files are removed and changed files are
+/// re-chunked; the returned report reflects the state after repair.
+pub fn check(root: &Path, config: &Config, repair: bool) -> Result<CheckReport> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let storage_dir = config.storage_dir(&root);
+    let store = Store::open_if_exists(&storage_dir)?
+        .with_context(|| format!("no index found for {}. Run: booger index", root.display()))?;
+
+    let report = inspect(&root, &store)?;
+    if !repair || report.is_ok() {
+        return Ok(report);
+    }
+
+    // Hold the writer lock for the whole repair so no concurrent indexer
+    // writes into a half-repaired index.
+    let _lock = IndexLock::acquire(&storage_dir)?;
+    let mut repairs = Vec::new();
+    if !report.sqlite_errors.is_empty() {
+        store.reindex()?;
+        repairs.push("rebuilt SQLite indices (REINDEX)".to_string());
+    }
+    // Rebuild FTS before touching chunks: deleting rows through the sync
+    // triggers on an out-of-sync index would corrupt it further.
+    if report.fts_error.is_some() || report.fts_orphans > 0 || report.fts_missing > 0 {
+        store.rebuild_fts()?;
+        repairs.push("rebuilt FTS index".to_string());
+    }
+    if report.orphan_embeddings > 0 {
+        let n = store.delete_orphan_embeddings()?;
+        repairs.push(format!("deleted {n} orphan embedding(s)"));
+    }
+    if report.mismatched_embeddings > 0 {
+        let n = store.delete_embeddings(&store.mismatched_embeddings()?)?;
+        repairs.push(format!("deleted {n} mismatched embedding(s); run embed to regenerate"));
+    }
+    if !report.missing_files.is_empty() {
+        store.begin_transaction()?;
+        for path in &report.missing_files {
+            store.remove_file(path)?;
+        }
+        store.commit_transaction()?;
+        repairs.push(format!("removed {} missing file(s) from the index", report.missing_files.len()));
+    }
+    if !report.stale_files.is_empty() {
+        let result = super::index_locked(&root, config)?;
+        repairs.push(format!("re-indexed {} changed file(s)", result.files_indexed));
+    }
+
+    let mut after = inspect(&root, &store)?;
+    after.repairs = repairs;
+    Ok(after)
+}
+
+fn inspect(root: &Path, store: &Store) -> Result<CheckReport> {
+    let mut report = CheckReport {
+        sqlite_errors: store.integrity_check()?,
+        fts_error: store.fts_integrity_check()?,
+        orphan_embeddings: store.orphan_embedding_count()?,
+        mismatched_embeddings: store.mismatched_embeddings()?.len(),
+        chunks_checked: store.chunk_count()?,
+        ..Default::default()
+    };
+    (report.fts_orphans, report.fts_missing) = store.fts_row_mismatches()?;
+
+    let files = store.all_files()?;
+    report.files_checked = files.len();
+    for file in files {
+        let abs = root.join(&file.path);
+        if !abs.is_file() {
+            report.missing_files.push(file.path);
+            continue;
+        }
+        match hasher::hash_file(&abs) {
+            Ok(hash) if hash == file.content_hash => {}
+            _ => report.stale_files.push(file.path),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn indexed_project() -> (TempDir, Config) {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn alpha() -> i32 {\n    1\n}\n").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "fn beta() -> i32 {\n    2\n}\n").unwrap();
+        let config = Config::default();
+        super::super::index_directory(dir.path(), &config).unwrap();
+        (dir, config)
+    }
+
+    #[test]
+    fn clean_index_is_ok() {
+        let (dir, config) = indexed_project();
+        let report = check(dir.path(), &config, false).unwrap();
+        assert!(report.is_ok(), "{:?}", report.problems());
+        assert_eq!(report.files_checked, 2);
+        assert!(format_report(&report).starts_with("Index OK"));
+    }
+
+    #[test]
+    fn missing_index_errors() {
+        let dir = TempDir::new().unwrap();
+        let err = check(dir.path(), &Config::default(), false).unwrap_err();
+        assert!(err.to_string().contains("booger index"));
+    }
+
+    #[test]
+    fn detects_and_repairs_stale_and_missing_files() {
+        let (dir, config) = indexed_project();
+        std::fs::write(dir.path().join("a.rs"), "fn gamma() {}\n").unwrap();
+        std::fs::remove_file(dir.path().join("b.rs")).unwrap();
+
+        let report = check(dir.path(), &config, false).unwrap();
+        assert_eq!(report.stale_files, vec!["a.rs"]);
+        assert_eq!(report.missing_files, vec!["b.rs"]);
+        assert!(report.repairs.is_empty());
+
+        let repaired = check(dir.path(), &config, true).unwrap();
+        assert!(repaired.is_ok(), "{:?}", repaired.problems());
+        assert_eq!(repaired.repairs.len(), 2);
+        assert_eq!(repaired.files_checked, 1);
+    }
+
+    #[test]
+    fn detects_and_repairs_fts_drift() {
+        let (dir, config) = indexed_project();
+        let root = dir.path().canonicalize().unwrap();
+        let db = config.storage_dir(&root).join("index.db");
+        let conn = rusqlite::Connection::open(&db).unwrap();
+        conn.execute_batch("DROP TRIGGER chunks_ad; DELETE FROM chunks WHERE name = 'alpha';")
+            .unwrap();
+        drop(conn);
+
+        let report = check(dir.path(), &config, false).unwrap();
+        assert_eq!(report.fts_orphans, 1);
+        assert!(!report.is_ok());
+
+        let repaired = check(dir.path(), &config, true).unwrap();
+        assert!(repaired.is_ok(), "{:?}", repaired.problems());
+        assert!(repaired.repairs.iter().any(|r| r.contains("FTS")));
+    }
+}
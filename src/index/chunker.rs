@@ -2,7 +2,7 @@ use crate::store::sqlite::ChunkInsert;
 use tree_sitter::{Language, Node, Parser};
 
 /// Get a tree-sitter Language for the given language name.
-fn get_language(lang: &str) -> Option<Language> {
+pub(crate) fn get_language(lang: &str) -> Option<Language> {
     match lang {
         "rust" => Some(tree_sitter_rust::LANGUAGE.into()),
         "python" => Some(tree_sitter_python::LANGUAGE.into()),
@@ -22,14 +22,12 @@ pub fn chunk_file(content: &str, language: Option<&str>) -> Vec<ChunkInsert> {
         return Vec::new();
     }
 
-    if let Some(lang) = language {
-        if let Some(ts_lang) = get_language(lang) {
-            if let Some(chunks) = chunk_with_treesitter(content, ts_lang, lang) {
-                if !chunks.is_empty() {
-                    return chunks;
-                }
-            }
-        }
+    if let Some(lang) = language
+        && let Some(ts_lang) = get_language(lang)
+        && let Some(chunks) = chunk_with_treesitter(content, ts_lang, lang)
+        && !chunks.is_empty()
+    {
+        return chunks;
     }
 
     raw_chunk(content)
@@ -150,7 +148,7 @@ fn collect_chunks(node: Node, source: &[u8], lang: &str, chunks: &mut Vec<ChunkI
 
 /// Classify a tree-sitter node into a chunk kind + optional name.
 /// Returns None for nodes we don't want as standalone chunks.
-fn classify_node<'a>(node: &Node, source: &'a str, lang: &str) -> Option<(String, Option<String>)> {
+fn classify_node(node: &Node, source: &str, lang: &str) -> Option<(String, Option<String>)> {
     let kind = node.kind();
 
     match lang {
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::Path;
+
+/// Advisory single-writer lock on `<storage_dir>/index.lock`.
+///
+/// Only the holder re-indexes; other processes keep reading the last
+/// committed snapshot (the database runs in WAL mode). The OS releases the
+/// lock when the guard is dropped or the holding process dies, so a crashed
+/// indexer never leaves a stale lock behind.
+pub struct IndexLock {
+    _file: File,
+}
+
+impl IndexLock {
+    /// Take the lock without waiting. Returns None if another process
+    /// (or another handle in this process) is already indexing.
+    pub fn try_acquire(storage_dir: &Path) -> Result<Option<Self>> {
+        let file = open_lock_file(storage_dir)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            // Filesystems without lock support: proceed unlocked rather than
+            // refusing to index at all.
+            Err(TryLockError::Error(e)) if e.kind() == std::io::ErrorKind::Unsupported => {
+                Ok(Some(Self { _file: file }))
+            }
+            Err(TryLockError::Error(e)) => Err(e).context("locking index"),
+        }
+    }
+
+    /// Take the lock, blocking until the current holder releases it.
+    pub fn acquire(storage_dir: &Path) -> Result<Self> {
+        let file = open_lock_file(storage_dir)?;
+        match file.lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => Ok(Self { _file: file }),
+            Err(e) => Err(e).context("locking index"),
+        }
+    }
+}
+
+fn open_lock_file(storage_dir: &Path) -> Result<File> {
+    std::fs::create_dir_all(storage_dir)
+        .with_context(|| format!("creating storage dir {}", storage_dir.display()))?;
+    let path = storage_dir.join("index.lock");
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("opening lock file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn second_writer_is_refused_until_release() {
+        let dir = TempDir::new().unwrap();
+        let first = IndexLock::try_acquire(dir.path()).unwrap();
+        assert!(first.is_some());
+        assert!(IndexLock::try_acquire(dir.path()).unwrap().is_none());
+        drop(first);
+        assert!(IndexLock::try_acquire(dir.path()).unwrap().is_some());
+    }
+
+    #[test]
+    fn index_directory_skips_while_locked() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn alpha() {}\n").unwrap();
+        let config = crate::config::Config::default();
+        let storage_dir = config.storage_dir(&dir.path().canonicalize().unwrap());
+
+        let lock = IndexLock::try_acquire(&storage_dir).unwrap();
+        let result = super::super::index_directory(dir.path(), &config).unwrap();
+        assert!(result.busy);
+        assert_eq!(result.files_indexed, 0);
+
+        drop(lock);
+        let result = super::super::index_directory(dir.path(), &config).unwrap();
+        assert!(!result.busy);
+        assert_eq!(result.files_indexed, 1);
+    }
+}
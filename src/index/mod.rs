@@ -1,5 +1,7 @@
+pub mod check;
 pub mod chunker;
 pub mod hasher;
+pub mod lock;
 pub mod walker;
 
 use anyhow::{Context, Result};
@@ -7,6 +9,7 @@ use std::path::Path;
 
 use crate::config::Config;
 use crate::store::sqlite::Store;
+use lock::IndexLock;
 use walker::{WalkConfig, detect_language, is_binary, walk_files};
 
 pub struct IndexResult {
@@ -15,15 +18,52 @@ pub struct IndexResult {
     pub files_skipped: usize,
     pub files_unchanged: usize,
     pub chunks_created: usize,
+    /// Another process held the writer lock, so nothing was indexed.
+    pub busy: bool,
 }
 
-/// Run a full indexing pass on a directory.
+/// Shown by search tools when the auto re-index was skipped.
+pub const INDEX_BUSY_NOTICE: &str =
+    "Index busy (another booger process is re-indexing) — serving stale results from the last committed index.";
+
+/// Run an incremental indexing pass on a directory.
+///
+/// If another process is already indexing this project, returns immediately
+/// with `busy` set instead of duplicating the work; readers keep seeing the
+/// last committed snapshot. Use `index_directory_wait` to wait for the lock.
 pub fn index_directory(root: &Path, config: &Config) -> Result<IndexResult> {
     let root = root
         .canonicalize()
         .with_context(|| format!("resolving path {}", root.display()))?;
 
     let storage_dir = config.storage_dir(&root);
+    let Some(_lock) = IndexLock::try_acquire(&storage_dir)? else {
+        return Ok(IndexResult {
+            files_scanned: 0,
+            files_indexed: 0,
+            files_skipped: 0,
+            files_unchanged: 0,
+            chunks_created: 0,
+            busy: true,
+        });
+    };
+    index_locked(&root, config)
+}
+
+/// Like `index_directory`, but waits for a concurrent indexer to finish
+/// and then indexes whatever it left unchanged.
+pub fn index_directory_wait(root: &Path, config: &Config) -> Result<IndexResult> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+
+    let storage_dir = config.storage_dir(&root);
+    let _lock = IndexLock::acquire(&storage_dir)?;
+    index_locked(&root, config)
+}
+
+fn index_locked(root: &Path, config: &Config) -> Result<IndexResult> {
+    let storage_dir = config.storage_dir(root);
     let store = Store::open(&storage_dir)?;
 
     let walk_config = WalkConfig {
@@ -31,7 +71,7 @@ pub fn index_directory(root: &Path, config: &Config) -> Result<IndexResult> {
         ..Default::default()
     };
 
-    let files = walk_files(&root, &walk_config)?;
+    let files = walk_files(root, &walk_config)?;
     let total = files.len();
 
     let mut result = IndexResult {
@@ -40,11 +80,9 @@ pub fn index_directory(root: &Path, config: &Config) -> Result<IndexResult> {
         files_skipped: 0,
         files_unchanged: 0,
         chunks_created: 0,
+        busy: false,
     };
 
-    store.begin_transaction()?;
-    let mut batch_count = 0;
-
     for path in &files {
         if is_binary(path) {
             result.files_skipped += 1;
@@ -52,7 +90,7 @@ pub fn index_directory(root: &Path, config: &Config) -> Result<IndexResult> {
         }
 
         let rel_path = path
-            .strip_prefix(&root)
+            .strip_prefix(root)
             .unwrap_or(path)
             .to_string_lossy()
             .to_string();
@@ -65,12 +103,10 @@ pub fn index_directory(root: &Path, config: &Config) -> Result<IndexResult> {
             }
         };
 
-        if let Ok(Some(existing)) = store.get_file(&rel_path) {
-            if existing.content_hash == content_hash {
-                result.files_unchanged += 1;
-                continue;
-            }
-            store.delete_chunks_for_file(existing.id)?;
+        let existing = store.get_file(&rel_path).ok().flatten();
+        if existing.as_ref().is_some_and(|f| f.content_hash == content_hash) {
+            result.files_unchanged += 1;
+            continue;
         }
 
         let content = match std::fs::read_to_string(path) {
@@ -83,26 +119,111 @@ pub fn index_directory(root: &Path, config: &Config) -> Result<IndexResult> {
 
         let language = detect_language(path);
         let size_bytes = content.len() as i64;
+        let chunks = chunker::chunk_file(&content, language);
+        let mined: Vec<Vec<(String, String)>> =
+            chunks.iter().map(|chunk| crate::search::synonyms::mine(&chunk.content)).collect();
 
+        // One short transaction per file: other writers (annotations,
+        // feedback, a waiting `index`) never queue behind a whole batch.
+        store.begin_transaction()?;
+        if let Some(existing) = &existing {
+            store.delete_chunks_for_file(existing.id)?;
+        }
         let file_id = store.upsert_file(&rel_path, &content_hash, size_bytes, language)?;
-        let chunks = chunker::chunk_file(&content, language);
+        let chunk_ids = store.insert_chunks(file_id, &chunks)?;
+        let occurrences =
+            crate::graph::extract::occurrence_inserts(&content, language, &chunks, &chunk_ids);
+        store.insert_occurrences(&occurrences)?;
+        let abbreviations: Vec<(i64, String, String)> = mined
+            .into_iter()
+            .zip(&chunk_ids)
+            .flat_map(|(pairs, &id)| pairs.into_iter().map(move |(a, w)| (id, a, w)))
+            .collect();
+        store.insert_abbreviations(&abbreviations)?;
+        store.commit_transaction()?;
         result.chunks_created += chunks.len();
-        store.insert_chunks(file_id, &chunks)?;
         result.files_indexed += 1;
-
-        batch_count += 1;
-        if batch_count >= config.resources.batch_size {
-            store.commit_transaction()?;
-            store.begin_transaction()?;
-            batch_count = 0;
-        }
     }
 
-    store.commit_transaction()?;
+    if result.files_indexed > 0 {
+        store.bump_index_generation()?;
+        store.refresh_synonyms()?;
+    }
+    refresh_history(root, &store, result.files_indexed > 0)?;
 
     Ok(result)
 }
 
+/// How long stored git history is trusted while no file changes. Commits
+/// alone (e.g. of already-indexed edits) do not show up in the walk.
+const HISTORY_TTL_SECS: i64 = 600;
+
+/// Re-read per-file churn and recency from `git log` when files changed or
+/// the stored history is older than `HISTORY_TTL_SECS`.
+fn refresh_history(root: &Path, store: &Store, files_changed: bool) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    let fresh = store
+        .file_history_updated_at()?
+        .is_some_and(|at| now - at < HISTORY_TTL_SECS);
+    if fresh && !files_changed {
+        return Ok(());
+    }
+    // Outside a git repository there is no history; storing the empty
+    // result still keeps every pass from retrying.
+    let history = crate::git::history::file_history(root, crate::git::history::MAX_COMMITS)
+        .unwrap_or_default();
+    store.replace_file_history(&history, now)
+}
+
+pub struct HistoryIndexResult {
+    pub commits_scanned: usize,
+    pub commits_indexed: usize,
+    pub symbols_recorded: usize,
+    /// Commits whose changes git could not report; left out, so the next
+    /// run tries them again.
+    pub commits_failed: usize,
+}
+
+/// Commits changing more files than this are indexed without symbols.
+const MAX_COMMIT_FILES: usize = 100;
+
+/// Add the latest `max_commits` commits to the optional commit history
+/// index, with the symbols each one touched. Commits already indexed are
+/// skipped, so later runs only diff new ones, and a commit git fails to
+/// diff is not stored, so it is retried. The writer lock is held only
+/// while storing, not while diffing.
+pub fn index_history(root: &Path, config: &Config, max_commits: usize) -> Result<HistoryIndexResult> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let storage_dir = config.storage_dir(&root);
+    let store = Store::open(&storage_dir)?;
+    let known = store.indexed_commits()?;
+
+    let log = crate::git::history::commit_log(&root, max_commits)?;
+    let commits_scanned = log.len();
+    let mut commits = Vec::new();
+    let mut commits_failed = 0;
+    for mut commit in log.into_iter().filter(|c| !known.contains(&c.hash)) {
+        match crate::git::diff::commit_symbols(&root, &commit.hash, MAX_COMMIT_FILES) {
+            Ok(symbols) => {
+                commit.symbols = symbols;
+                commits.push(commit);
+            }
+            Err(_) => commits_failed += 1,
+        }
+    }
+
+    let _lock = IndexLock::acquire(&storage_dir)?;
+    store.insert_commits(&commits)?;
+    Ok(HistoryIndexResult {
+        commits_scanned,
+        commits_indexed: commits.len(),
+        symbols_recorded: commits.iter().map(|c| c.symbols.len()).sum(),
+        commits_failed,
+    })
+}
+
 /// Get index statistics for a directory. Returns empty stats if no index exists.
 pub fn index_status(root: &Path, config: &Config) -> Result<crate::store::sqlite::IndexStats> {
     let root = root
@@ -29,13 +29,13 @@ pub fn walk_files(root: &Path, config: &WalkConfig) -> Result<Vec<PathBuf>> {
 
     for entry in walker {
         let entry = entry?;
-        if !entry.file_type().map_or(false, |ft| ft.is_file()) {
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) {
             continue;
         }
-        if let Ok(meta) = entry.metadata() {
-            if meta.len() > config.max_file_size {
-                continue;
-            }
+        if let Ok(meta) = entry.metadata()
+            && meta.len() > config.max_file_size
+        {
+            continue;
         }
         files.push(entry.into_path());
     }
@@ -59,7 +59,7 @@ const BINARY_EXTENSIONS: &[&str] = &[
 pub fn is_binary(path: &Path) -> bool {
     path.extension()
         .and_then(|e| e.to_str())
-        .map_or(false, |ext| {
+        .is_some_and(|ext| {
             BINARY_EXTENSIONS.contains(&ext.to_lowercase().as_str())
         })
 }
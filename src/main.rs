@@ -19,10 +19,16 @@ enum Commands {
         /// Path to the directory to index
         #[arg(default_value = ".")]
         path: String,
+        /// Also index commit messages, authors, dates and touched symbols for history-search
+        #[arg(long)]
+        history: bool,
+        /// How many of the latest commits to index with --history
+        #[arg(long, default_value = "1000")]
+        max_commits: usize,
     },
     /// Full-text search over indexed code
     Search {
-        /// Search query
+        /// Search query; accepts qualifiers like kind:function lang:rust path:src -path:tests name:load*
         query: String,
         /// Filter by language (e.g. rust, python, typescript)
         #[arg(short, long)]
@@ -39,6 +45,15 @@ enum Commands {
         /// Output as JSON (for agent consumption)
         #[arg(long)]
         json: bool,
+        /// Show each result's score breakdown and whether the OR fallback ran
+        #[arg(long)]
+        explain: bool,
+        /// Also show matching annotations as `note` results, before the code
+        #[arg(long)]
+        notes: bool,
+        /// Session ID (scopes --notes to this session's annotations and session-less ones)
+        #[arg(short, long)]
+        session: Option<String>,
     },
     /// Semantic similarity search over indexed code (requires embeddings)
     Semantic {
@@ -56,21 +71,149 @@ enum Commands {
         /// Max results
         #[arg(short = 'n', long, default_value = "20")]
         max_results: usize,
+        /// Scan every embedding instead of the ANN index (exact, slower)
+        #[arg(long)]
+        exact: bool,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Keyword and semantic search fused into one ranking (semantic needs embeddings)
+    Hybrid {
+        /// Search query (searched by both keyword and meaning)
+        query: String,
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Filter by language
+        #[arg(short, long)]
+        language: Option<String>,
+        /// Filter by path prefix
+        #[arg(short, long)]
+        path: Option<String>,
+        /// Max results
+        #[arg(short = 'n', long, default_value = "20")]
+        max_results: usize,
+        /// Fusion method: rrf, zscore or linear (default: [hybrid] fusion in config)
+        #[arg(long)]
+        fusion: Option<String>,
+        /// Weight of keyword vs semantic results, 0.0-1.0 (default: [hybrid] alpha in config)
+        #[arg(long)]
+        alpha: Option<f64>,
         /// Output as JSON
         #[arg(long)]
         json: bool,
+        /// Show each result's sources, fused score and ranking stages
+        #[arg(long)]
+        explain: bool,
     },
-    /// Generate embeddings for indexed chunks (requires ollama)
+    /// Find code like an example chunk (by embedding, or structural fingerprint)
+    Similar {
+        /// Example chunk: path:line, symbol name, or chunk id
+        target: String,
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Filter by kind (function, struct, method, ...)
+        #[arg(short, long)]
+        kind: Option<String>,
+        /// Filter by language
+        #[arg(short, long)]
+        language: Option<String>,
+        /// Filter by path prefix
+        #[arg(short, long)]
+        path: Option<String>,
+        /// Max results
+        #[arg(short = 'n', long, default_value = "10")]
+        max_results: usize,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Find copy-pasted code: exact duplicates and near-clones, grouped by family
+    Duplicates {
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Ignore chunks shorter than this many lines
+        #[arg(long, default_value = "6")]
+        min_lines: usize,
+        /// Minimum similarity for near-clones, 0.0-1.0
+        #[arg(long, default_value = "0.8")]
+        threshold: f64,
+        /// Filter by kind (function, struct, method, ...)
+        #[arg(short, long)]
+        kind: Option<String>,
+        /// Filter by language
+        #[arg(short, long)]
+        language: Option<String>,
+        /// Filter by path prefix
+        #[arg(short, long)]
+        path: Option<String>,
+        /// Max clone families
+        #[arg(short = 'n', long, default_value = "20")]
+        max_families: usize,
+        /// Compare across all registered projects
+        #[arg(long)]
+        all_projects: bool,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Search commit history: commits mentioning words, or that changed a symbol (needs `index --history`)
+    HistorySearch {
+        /// Words to find in commit messages, authors and touched symbol names
+        query: Option<String>,
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Exact name of a symbol the commit changed
+        #[arg(short, long)]
+        symbol: Option<String>,
+        /// Only commits that changed symbols under this path
+        #[arg(short, long)]
+        path: Option<String>,
+        /// Max commits
+        #[arg(short = 'n', long, default_value = "10")]
+        max_results: usize,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Fuzzy lookup of symbol names, tolerant of typos (e.g. IndxStats)
+    FindSymbol {
+        /// Approximate symbol name
+        query: String,
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Filter by kind (function, struct, method, ...)
+        #[arg(short, long)]
+        kind: Option<String>,
+        /// Filter by language
+        #[arg(short, long)]
+        language: Option<String>,
+        /// Filter by path prefix
+        #[arg(short, long)]
+        path: Option<String>,
+        /// Max results
+        #[arg(short = 'n', long, default_value = "20")]
+        max_results: usize,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Generate embeddings for indexed chunks (ollama, or the [embed.backend] in config)
     Embed {
         /// Project root
         #[arg(default_value = ".")]
         path: String,
-        /// Ollama model name
-        #[arg(long, default_value = "nomic-embed-text")]
-        model: String,
-        /// Ollama server URL
-        #[arg(long, default_value = "http://localhost:11434")]
-        url: String,
+        /// Ollama model name (default: nomic-embed-text); overrides the configured backend
+        #[arg(long)]
+        model: Option<String>,
+        /// Ollama server URL (default: http://localhost:11434); overrides the configured backend
+        #[arg(long)]
+        url: Option<String>,
     },
     /// Annotate a file, symbol, or line range with a note
     Annotate {
@@ -103,6 +246,23 @@ enum Commands {
         #[arg(long)]
         json: bool,
     },
+    /// Full-text search over annotation notes and targets
+    AnnotationSearch {
+        /// Words to find in notes and targets
+        query: String,
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Only this session's annotations and session-less ones
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Max number of annotations
+        #[arg(short = 'n', long, default_value = "20")]
+        max_results: usize,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Set focus on specific paths to boost their search results
     Focus {
         /// Paths to focus on
@@ -125,7 +285,33 @@ enum Commands {
         #[arg(short, long)]
         session: Option<String>,
     },
-    /// Clear volatile context (annotations, working set)
+    /// Record the session's goal; its key terms bias search ranking
+    Intent {
+        /// What the session is trying to do
+        goal: String,
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Session ID
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Also embed the goal (requires Ollama)
+        #[arg(long)]
+        embed: bool,
+    },
+    /// List session context: intents, focused and visited paths
+    Intents {
+        /// Project root
+        #[arg(short, long, default_value = ".")]
+        root: String,
+        /// Session ID filter
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Clear volatile context (annotations, working set, intents)
     Forget {
         /// Project root
         #[arg(short, long, default_value = ".")]
@@ -140,6 +326,18 @@ enum Commands {
         #[arg(default_value = ".")]
         path: String,
     },
+    /// Verify index integrity (SQLite, FTS, embeddings, file hashes)
+    Check {
+        /// Path to the indexed directory
+        #[arg(default_value = ".")]
+        path: String,
+        /// Repair problems found (rebuild FTS, drop bad embeddings, re-index changed files)
+        #[arg(long)]
+        repair: bool,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Initialize a .booger config in a directory
     Init {
         /// Path to the directory
@@ -197,6 +395,13 @@ enum ProjectCommands {
         /// Path to the project directory
         #[arg(default_value = ".")]
         path: String,
+        /// Multiplier for this project's scores in workspace-search (default
+        /// 1.0; re-registering keeps the current one)
+        #[arg(long)]
+        weight: Option<f64>,
+        /// Group for workspace-search (repeatable; replaces the current ones)
+        #[arg(short, long = "group")]
+        groups: Vec<String>,
     },
     /// Register and index multiple projects from a parent directory
     AddAll {
@@ -219,17 +424,70 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Index { path } => cmd_index(&path),
+        Commands::Index { path, history, max_commits } => cmd_index(&path, history.then_some(max_commits)),
         Commands::Status { path } => cmd_status(&path),
+        Commands::Check { path, repair, json } => cmd_check(&path, repair, json),
         Commands::Init { path } => cmd_init(&path),
-        Commands::Search { query, language, path, root, max_results, json } => {
-            cmd_search(&root, &query, language.as_deref(), path.as_deref(), max_results, json)
+        Commands::Search { query, language, path, root, max_results, json, explain, notes, session } => {
+            let mut search_query = booger::search::text::SearchQuery::new(query);
+            search_query.language = language;
+            search_query.path_prefix = path;
+            search_query.max_results = max_results;
+            search_query.session_id = session;
+            cmd_search(&root, &search_query, json, explain, notes)
+        }
+        Commands::Semantic { query, root, language, path, max_results, exact, json } => {
+            cmd_semantic(&root, &query, language.as_deref(), path.as_deref(), max_results, exact, json)
+        }
+        Commands::Hybrid { query, root, language, path, max_results, fusion, alpha, json, explain } => {
+            let mut hybrid_query = booger::search::hybrid::HybridQuery::new(query);
+            hybrid_query.language = language;
+            hybrid_query.path_prefix = path;
+            hybrid_query.max_results = max_results;
+            hybrid_query.fusion = fusion.as_deref().map(booger::config::Fusion::parse).transpose()?;
+            hybrid_query.alpha = alpha;
+            cmd_hybrid(&root, &hybrid_query, json, explain)
         }
-        Commands::Semantic { query, root, language, path, max_results, json } => {
-            cmd_semantic(&root, &query, language.as_deref(), path.as_deref(), max_results, json)
+        Commands::Similar { target, root, kind, language, path, max_results, json } => {
+            let mut similar_query =
+                booger::search::similar::SimilarQuery::new(booger::search::similar::Target::parse(&target));
+            similar_query.kind = kind;
+            similar_query.language = language;
+            similar_query.path_prefix = path;
+            similar_query.max_results = max_results;
+            cmd_similar(&root, &similar_query, json)
+        }
+        Commands::Duplicates { root, min_lines, threshold, kind, language, path, max_families, all_projects, json } => {
+            let query = booger::search::duplicates::DuplicatesQuery {
+                min_lines,
+                threshold: threshold.clamp(0.0, 1.0),
+                kind,
+                language,
+                path_prefix: path,
+                max_families,
+                ..Default::default()
+            };
+            cmd_duplicates(&root, &query, all_projects, json)
+        }
+        Commands::HistorySearch { query, root, symbol, path, max_results, json } => {
+            let query = booger::search::history::HistoryQuery {
+                text: query,
+                symbol,
+                path_prefix: path,
+                max_results,
+            };
+            cmd_history_search(&root, &query, json)
+        }
+        Commands::FindSymbol { query, root, kind, language, path, max_results, json } => {
+            let mut symbol_query = booger::search::fuzzy::SymbolQuery::new(query);
+            symbol_query.kind = kind;
+            symbol_query.language = language;
+            symbol_query.path_prefix = path;
+            symbol_query.max_results = max_results;
+            cmd_find_symbol(&root, &symbol_query, json)
         }
         Commands::Embed { path, model, url } => {
-            cmd_embed(&path, &model, &url)
+            cmd_embed(&path, model.as_deref(), url.as_deref())
         }
         Commands::BranchDiff { base, root, json, focus, session } => {
             let base = base.unwrap_or_else(|| booger::git::diff::default_branch(std::path::Path::new(&root)));
@@ -247,12 +505,21 @@ fn main() -> Result<()> {
         Commands::Annotations { target, root, session, json } => {
             cmd_annotations(&root, target.as_deref(), session.as_deref(), json)
         }
+        Commands::AnnotationSearch { query, root, session, max_results, json } => {
+            cmd_annotation_search(&root, &query, session.as_deref(), max_results, json)
+        }
         Commands::Focus { paths, root, session } => {
             cmd_focus(&root, &paths, session.as_deref())
         }
         Commands::Visit { paths, root, session } => {
             cmd_visit(&root, &paths, session.as_deref())
         }
+        Commands::Intent { goal, root, session, embed } => {
+            cmd_intent(&root, &goal, session.as_deref(), embed)
+        }
+        Commands::Intents { root, session, json } => {
+            cmd_intents(&root, session.as_deref(), json)
+        }
         Commands::Forget { root, session } => {
             cmd_forget(&root, session.as_deref())
         }
@@ -260,18 +527,14 @@ fn main() -> Result<()> {
     }
 }
 
-fn cmd_index(path: &str) -> Result<()> {
+/// `max_commits` is set when the commit history index should be updated too.
+fn cmd_index(path: &str, max_commits: Option<usize>) -> Result<()> {
     let root = PathBuf::from(path);
     let config = Config::load(&root).unwrap_or_default();
 
-    eprintln!(
-        "Indexing {} (threads: {}, batch: {})",
-        root.display(),
-        config.effective_threads(),
-        config.resources.batch_size,
-    );
+    eprintln!("Indexing {} (threads: {})", root.display(), config.effective_threads());
 
-    let result = booger::index::index_directory(&root, &config)?;
+    let result = booger::index::index_directory_wait(&root, &config)?;
 
     eprintln!(
         "Done. scanned={} indexed={} unchanged={} skipped={} chunks={}",
@@ -282,6 +545,14 @@ fn cmd_index(path: &str) -> Result<()> {
         result.chunks_created,
     );
 
+    if let Some(max_commits) = max_commits {
+        let history = booger::index::index_history(&root, &config, max_commits)?;
+        eprintln!(
+            "History: scanned={} indexed={} symbols={} failed={}",
+            history.commits_scanned, history.commits_indexed, history.symbols_recorded, history.commits_failed,
+        );
+    }
+
     Ok(())
 }
 
@@ -305,25 +576,63 @@ fn cmd_status(path: &str) -> Result<()> {
     Ok(())
 }
 
+fn cmd_check(path: &str, repair: bool, json: bool) -> Result<()> {
+    let root = PathBuf::from(path);
+    let config = Config::load(&root).unwrap_or_default();
+    let report = booger::index::check::check(&root, &config, repair)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}", booger::index::check::format_report(&report));
+        if !report.is_ok() && !repair {
+            eprintln!("\nRun `booger check --repair` to fix.");
+        }
+    }
+
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn cmd_search(
     root: &str,
-    query: &str,
-    language: Option<&str>,
-    path_prefix: Option<&str>,
-    max_results: usize,
+    search_query: &booger::search::text::SearchQuery,
     json: bool,
+    explain: bool,
+    notes: bool,
 ) -> Result<()> {
     let root = PathBuf::from(root);
     let config = Config::load(&root).unwrap_or_default();
 
-    let mut search_query = booger::search::text::SearchQuery::new(query);
-    search_query.language = language.map(String::from);
-    search_query.path_prefix = path_prefix.map(String::from);
-    search_query.max_results = max_results;
-
-    let results = booger::search::text::search(&root, &config, &search_query)?;
+    let outcome = booger::search::text::search(&root, &config, search_query)?;
+    if outcome.index_busy {
+        eprintln!("{}", booger::index::INDEX_BUSY_NOTICE);
+    }
+    let mut results = Vec::new();
+    if notes {
+        let parsed = search_query.parsed();
+        let session_id = search_query.session_id.as_deref();
+        let max = booger::context::annotations::MAX_NOTE_RESULTS;
+        results = booger::context::annotations::note_results(&root, &config, &parsed.text, &parsed.filter, session_id, max)?;
+    }
+    results.extend(outcome.results.iter().cloned());
 
-    if json {
+    if json && explain {
+        let results: Vec<_> = outcome
+            .results
+            .iter()
+            .zip(&outcome.explanations)
+            .map(|(r, e)| serde_json::json!({ "result": r, "explain": e }))
+            .collect();
+        let out = serde_json::json!({
+            "or_fallback": outcome.or_fallback,
+            "expansions": outcome.expansions,
+            "results": results,
+        });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+    } else if json {
         println!("{}", serde_json::to_string_pretty(&results)?);
     } else {
         if results.is_empty() {
@@ -356,11 +665,44 @@ fn cmd_search(
             }
             println!();
         }
+        if explain {
+            print!("{}", booger::search::text::format_explanations(&outcome));
+        }
     }
 
     Ok(())
 }
 
+fn cmd_find_symbol(
+    root: &str,
+    query: &booger::search::fuzzy::SymbolQuery,
+    json: bool,
+) -> Result<()> {
+    let root = PathBuf::from(root).canonicalize()?;
+    let config = Config::load(&root).unwrap_or_default();
+
+    if booger::index::index_directory(&root, &config)?.busy {
+        eprintln!("{}", booger::index::INDEX_BUSY_NOTICE);
+    }
+    let store = booger::store::sqlite::Store::open(&config.storage_dir(&root))?;
+    let matches = booger::search::fuzzy::find_symbols(&store, query)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&matches)?);
+    } else if matches.is_empty() {
+        eprintln!("No symbols match '{}'.", query.text);
+    } else {
+        for m in &matches {
+            let s = &m.symbol;
+            println!(
+                "{}:{}-{} [{}] {} ({})",
+                s.file_path, s.start_line, s.end_line, s.kind, s.name, m.matched.as_str(),
+            );
+        }
+    }
+    Ok(())
+}
+
 fn cmd_annotate(
     root: &str,
     target: &str,
@@ -384,13 +726,29 @@ fn cmd_annotations(
     let root = PathBuf::from(root);
     let config = Config::load(&root).unwrap_or_default();
     let anns = booger::context::annotations::list(&root, &config, target, session_id)?;
+    print_annotations(&anns, json)
+}
 
+fn cmd_annotation_search(
+    root: &str,
+    query: &str,
+    session_id: Option<&str>,
+    max_results: usize,
+    json: bool,
+) -> Result<()> {
+    let root = PathBuf::from(root);
+    let config = Config::load(&root).unwrap_or_default();
+    let anns = booger::context::annotations::search(&root, &config, query, session_id, max_results)?;
+    print_annotations(&anns, json)
+}
+
+fn print_annotations(anns: &[booger::store::sqlite::Annotation], json: bool) -> Result<()> {
     if json {
         println!("{}", serde_json::to_string_pretty(&anns)?);
     } else if anns.is_empty() {
         eprintln!("No annotations.");
     } else {
-        for a in &anns {
+        for a in anns {
             let expires = a
                 .expires_at
                 .as_deref()
@@ -418,6 +776,59 @@ fn cmd_visit(root: &str, paths: &[String], session_id: Option<&str>) -> Result<(
     Ok(())
 }
 
+fn cmd_intent(root: &str, goal: &str, session_id: Option<&str>, embed: bool) -> Result<()> {
+    let root = PathBuf::from(root);
+    let config = Config::load(&root).unwrap_or_default();
+    let embedder = if embed {
+        Some(booger::embed::from_config(&config.embed)?)
+    } else {
+        None
+    };
+    let intent = booger::context::intent::set(
+        &root,
+        &config,
+        goal,
+        session_id,
+        embedder.as_deref(),
+    )?;
+    eprintln!("Intent #{}: {goal} (terms: {})", intent.id, intent.terms.join(", "));
+    Ok(())
+}
+
+fn cmd_intents(root: &str, session_id: Option<&str>, json: bool) -> Result<()> {
+    let root = PathBuf::from(root);
+    let config = Config::load(&root).unwrap_or_default();
+    let intents = booger::context::intent::list(&root, &config, session_id)?;
+    let workset = booger::context::workset::list(&root, &config, None, session_id)?;
+    let paths = |kind: &str| -> Vec<&str> {
+        workset.iter().filter(|e| e.kind == kind).map(|e| e.path.as_str()).collect()
+    };
+
+    if json {
+        let body = serde_json::json!({
+            "intents": intents,
+            "focus": paths("focus"),
+            "visited": paths("visited"),
+        });
+        println!("{}", serde_json::to_string_pretty(&body)?);
+    } else if intents.is_empty() && workset.is_empty() {
+        eprintln!("No intents, focused or visited paths.");
+    } else {
+        for i in &intents {
+            let session = i.session_id.as_deref().map(|s| format!(" [{s}]")).unwrap_or_default();
+            let embedded = if i.embedding.is_some() { ", embedded" } else { "" };
+            println!("  intent #{}{session} — {} (terms: {}{embedded})", i.id, i.goal, i.terms.join(", "));
+        }
+        for path in paths("focus") {
+            println!("  focus    {path}");
+        }
+        for path in paths("visited") {
+            println!("  visited  {path}");
+        }
+    }
+    Ok(())
+}
+
 fn cmd_forget(root: &str, session_id: Option<&str>) -> Result<()> {
     let root = PathBuf::from(root);
     let config = Config::load(&root).unwrap_or_default();
@@ -427,7 +838,8 @@ fn cmd_forget(root: &str, session_id: Option<&str>) -> Result<()> {
         session_id,
     )?;
     let ws = booger::context::workset::clear(&root, &config, session_id)?;
-    eprintln!("Cleared {anns} annotations, {ws} workset entries");
+    let intents = booger::context::intent::clear(&root, &config, session_id)?;
+    eprintln!("Cleared {anns} annotations, {ws} workset entries, {intents} intents");
     Ok(())
 }
 
@@ -489,15 +901,22 @@ fn cmd_branch_diff(
     Ok(())
 }
 
-fn cmd_embed(path: &str, model: &str, url: &str) -> Result<()> {
+fn cmd_embed(path: &str, model: Option<&str>, url: Option<&str>) -> Result<()> {
     let root = PathBuf::from(path);
     let config = Config::load(&root).unwrap_or_default();
 
-    eprintln!("Connecting to ollama at {url} (model: {model})...");
-    let embedder = booger::embed::ollama::OllamaEmbedder::new(url, model)?;
-    eprintln!("Model loaded ({} dimensions)", embedder.dimensions());
+    let embedder: Box<dyn Embedder> = if model.is_some() || url.is_some() {
+        let url = url.unwrap_or("http://localhost:11434");
+        let model = model.unwrap_or("nomic-embed-text");
+        eprintln!("Connecting to ollama at {url} (model: {model})...");
+        Box::new(booger::embed::ollama::OllamaEmbedder::new(url, model)?)
+    } else {
+        eprintln!("Connecting to the embedding backend...");
+        booger::embed::from_config(&config.embed)?
+    };
+    eprintln!("Model {} loaded ({} dimensions)", embedder.model_name(), embedder.dimensions());
 
-    let stats = booger::search::semantic::embed_chunks(&root, &config, &embedder)?;
+    let stats = booger::search::semantic::embed_chunks(&root, &config, embedder.as_ref())?;
     eprintln!(
         "Done. {}/{} chunks embedded ({} new)",
         stats.embedded, stats.total_chunks, stats.newly_embedded,
@@ -511,19 +930,21 @@ fn cmd_semantic(
     language: Option<&str>,
     path_prefix: Option<&str>,
     max_results: usize,
+    exact: bool,
     json: bool,
 ) -> Result<()> {
     let root = PathBuf::from(root);
     let config = Config::load(&root).unwrap_or_default();
 
-    let embedder = booger::embed::ollama::OllamaEmbedder::default()?;
+    let embedder = booger::embed::from_config(&config.embed)?;
 
     let mut search_query = booger::search::semantic::SemanticQuery::new(query);
     search_query.language = language.map(String::from);
     search_query.path_prefix = path_prefix.map(String::from);
     search_query.max_results = max_results;
+    search_query.exact = exact;
 
-    let results = booger::search::semantic::search(&root, &config, &embedder, &search_query)?;
+    let results = booger::search::semantic::search(&root, &config, embedder.as_ref(), &search_query)?;
 
     if json {
         println!("{}", serde_json::to_string_pretty(&results)?);
@@ -558,6 +979,165 @@ fn cmd_semantic(
     Ok(())
 }
 
+fn cmd_hybrid(root: &str, query: &booger::search::hybrid::HybridQuery, json: bool, explain: bool) -> Result<()> {
+    let root = PathBuf::from(root);
+    let config = Config::load(&root).unwrap_or_default();
+
+    let embedder = booger::embed::from_config(&config.embed).ok();
+    if embedder.is_none() {
+        eprintln!("The embedding backend is not reachable; showing keyword results only.");
+    }
+    let outcome = booger::search::hybrid::search(
+        &root,
+        &config,
+        embedder.as_deref(),
+        query,
+    )?;
+    if outcome.index_busy {
+        eprintln!("{}", booger::index::INDEX_BUSY_NOTICE);
+    }
+
+    if json && explain {
+        let results: Vec<_> = outcome
+            .results
+            .iter()
+            .zip(&outcome.explanations)
+            .map(|(r, e)| serde_json::json!({ "result": r, "explain": e }))
+            .collect();
+        let out = serde_json::json!({
+            "fusion": outcome.fusion,
+            "alpha": outcome.alpha,
+            "or_fallback": outcome.or_fallback,
+            "expansions": outcome.expansions,
+            "results": results,
+        });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+    } else if json {
+        println!("{}", serde_json::to_string_pretty(&outcome.results)?);
+    } else {
+        let results = &outcome.results;
+        if results.is_empty() {
+            eprintln!("No results.");
+            return Ok(());
+        }
+        eprintln!("{} result(s)\n", results.len());
+        for (i, r) in results.iter().enumerate() {
+            let name = r.chunk_name.as_deref().unwrap_or("");
+            let name_display = if name.is_empty() {
+                String::new()
+            } else {
+                format!(" ({name})")
+            };
+            println!(
+                "── [{i}] {}:{}-{} [{}{}] (score: {:.3}) ──",
+                r.file_path, r.start_line, r.end_line, r.chunk_kind, name_display, r.rank,
+            );
+            let preview: String = r.content.lines().take(10).collect::<Vec<_>>().join("\n");
+            println!("{preview}");
+            let total_lines = r.content.lines().count();
+            if total_lines > 10 {
+                println!("  ... ({} more lines)", total_lines - 10);
+            }
+            println!();
+        }
+        if explain {
+            print!("{}", booger::search::hybrid::format_explanations(&outcome));
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_similar(root: &str, query: &booger::search::similar::SimilarQuery, json: bool) -> Result<()> {
+    let root = PathBuf::from(root);
+    let config = Config::load(&root).unwrap_or_default();
+
+    let outcome = booger::search::similar::search(&root, &config, query)?;
+    if outcome.index_busy {
+        eprintln!("{}", booger::index::INDEX_BUSY_NOTICE);
+    }
+
+    if json {
+        let out = serde_json::json!({
+            "source": outcome.source,
+            "method": outcome.method.name(),
+            "results": outcome.results,
+        });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
+    }
+
+    let source = &outcome.source;
+    eprintln!(
+        "Similar to {}:{}-{}{} by {}\n",
+        source.file_path,
+        source.start_line,
+        source.end_line,
+        source.chunk_name.as_deref().map(|n| format!(" ({n})")).unwrap_or_default(),
+        outcome.method.name(),
+    );
+    if outcome.results.is_empty() {
+        eprintln!("No similar code found.");
+        return Ok(());
+    }
+    for (i, r) in outcome.results.iter().enumerate() {
+        let name_display = r.chunk_name.as_deref().map(|n| format!(" ({n})")).unwrap_or_default();
+        println!(
+            "── [{i}] {}:{}-{} [{}{}] (similarity: {:.3}) ──",
+            r.file_path, r.start_line, r.end_line, r.chunk_kind, name_display, -r.rank,
+        );
+        let preview: String = r.content.lines().take(10).collect::<Vec<_>>().join("\n");
+        println!("{preview}");
+        let total_lines = r.content.lines().count();
+        if total_lines > 10 {
+            println!("  ... ({} more lines)", total_lines - 10);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn cmd_duplicates(
+    root: &str,
+    query: &booger::search::duplicates::DuplicatesQuery,
+    all_projects: bool,
+    json: bool,
+) -> Result<()> {
+    let root = PathBuf::from(root);
+    let projects: Vec<(Option<String>, PathBuf)> = if all_projects {
+        booger::config::ProjectRegistry::load()?
+            .workspace(&root, None)
+            .into_iter()
+            .map(|(name, entry)| (Some(name), entry.path))
+            .collect()
+    } else {
+        vec![(None, root)]
+    };
+
+    let outcome = booger::search::duplicates::find(&projects, query)?;
+    if !outcome.busy.is_empty() {
+        eprintln!("{} ({})", booger::index::INDEX_BUSY_NOTICE, outcome.busy.join(", "));
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&outcome)?);
+    } else {
+        print!("{}", booger::search::duplicates::format_outcome(&outcome));
+    }
+    Ok(())
+}
+
+fn cmd_history_search(root: &str, query: &booger::search::history::HistoryQuery, json: bool) -> Result<()> {
+    let root = PathBuf::from(root);
+    let config = Config::load(&root).unwrap_or_default();
+    let outcome = booger::search::history::search(&root, &config, query)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&outcome)?);
+    } else {
+        print!("{}", booger::search::history::format_outcome(&outcome, query.symbol.as_deref()));
+    }
+    Ok(())
+}
+
 fn cmd_draft_commit(root: &str) -> Result<()> {
     let root = PathBuf::from(root);
     let diff = booger::git::diff::staged_diff(&root)?;
@@ -577,12 +1157,18 @@ fn cmd_changelog(root: &str, base_ref: &str) -> Result<()> {
 fn cmd_project(sub: ProjectCommands) -> Result<()> {
     use booger::config::ProjectRegistry;
     match sub {
-        ProjectCommands::Add { name, path } => {
+        ProjectCommands::Add { name, path, weight, groups } => {
             let abs_path = PathBuf::from(&path)
                 .canonicalize()
                 .unwrap_or_else(|_| PathBuf::from(&path));
             let mut reg = ProjectRegistry::load()?;
-            reg.add(name.clone(), abs_path.clone());
+            let entry = reg.add(name.clone(), abs_path.clone());
+            if let Some(weight) = weight {
+                entry.weight = weight.max(0.0);
+            }
+            if !groups.is_empty() {
+                entry.groups = groups;
+            }
             reg.save()?;
             eprintln!("Registered project '{name}' -> {}", abs_path.display());
         }
@@ -627,7 +1213,7 @@ fn cmd_project(sub: ProjectCommands) -> Result<()> {
             if index {
                 for (name, abs) in &added {
                     eprintln!("\nIndexing {name}...");
-                    if let Err(e) = cmd_index(&abs.to_string_lossy()) {
+                    if let Err(e) = cmd_index(&abs.to_string_lossy(), None) {
                         eprintln!("  warning: indexing {name} failed: {e}");
                     }
                 }
@@ -648,7 +1234,14 @@ fn cmd_project(sub: ProjectCommands) -> Result<()> {
                 eprintln!("No registered projects.");
             } else {
                 for (name, entry) in &reg.projects {
-                    println!("  {name}: {}", entry.path.display());
+                    let mut extra = String::new();
+                    if entry.weight != 1.0 {
+                        extra.push_str(&format!(" (weight {})", entry.weight));
+                    }
+                    if !entry.groups.is_empty() {
+                        extra.push_str(&format!(" [{}]", entry.groups.join(", ")));
+                    }
+                    println!("  {name}: {}{extra}", entry.path.display());
                 }
             }
         }
@@ -1,11 +1,11 @@
 use serde_json::json;
-use std::path::PathBuf;
+use std::path::Path;
 
 use super::protocol::{Resource, ResourceContent};
 use crate::config::Config;
 use crate::index;
 
-pub fn list_resources(project_root: &PathBuf) -> Vec<Resource> {
+pub fn list_resources(project_root: &Path) -> Vec<Resource> {
     vec![Resource {
         uri: format!("booger://status/{}", project_root.display()),
         name: "Index Status".into(),
@@ -17,7 +17,7 @@ pub fn list_resources(project_root: &PathBuf) -> Vec<Resource> {
     }]
 }
 
-pub fn read_resource(uri: &str, project_root: &PathBuf) -> Result<Vec<ResourceContent>, String> {
+pub fn read_resource(uri: &str, project_root: &Path) -> Result<Vec<ResourceContent>, String> {
     let expected_uri = format!("booger://status/{}", project_root.display());
     if uri == expected_uri {
         let config = Config::load(project_root).unwrap_or_default();
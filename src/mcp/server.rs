@@ -1,7 +1,7 @@
 use anyhow::Result;
 use serde_json::json;
 use std::io::{self, BufRead, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::protocol::*;
 use super::{resources, tools};
@@ -48,7 +48,7 @@ fn send(out: &mut impl Write, response: &JsonRpcResponse) -> Result<()> {
     Ok(())
 }
 
-fn dispatch(request: &JsonRpcRequest, project_root: &PathBuf) -> Option<JsonRpcResponse> {
+fn dispatch(request: &JsonRpcRequest, project_root: &Path) -> Option<JsonRpcResponse> {
     match request.method.as_str() {
         "initialize" => Some(handle_initialize(request)),
         "initialized" | "notifications/initialized" => None,
@@ -97,7 +97,7 @@ fn handle_tools_list(request: &JsonRpcRequest) -> JsonRpcResponse {
     JsonRpcResponse::success(request.id.clone(), json!({ "tools": tools }))
 }
 
-fn handle_tools_call(request: &JsonRpcRequest, project_root: &PathBuf) -> JsonRpcResponse {
+fn handle_tools_call(request: &JsonRpcRequest, project_root: &Path) -> JsonRpcResponse {
     let name = request
         .params
         .get("name")
@@ -116,12 +116,12 @@ fn handle_tools_call(request: &JsonRpcRequest, project_root: &PathBuf) -> JsonRp
     }
 }
 
-fn handle_resources_list(request: &JsonRpcRequest, project_root: &PathBuf) -> JsonRpcResponse {
+fn handle_resources_list(request: &JsonRpcRequest, project_root: &Path) -> JsonRpcResponse {
     let resources = resources::list_resources(project_root);
     JsonRpcResponse::success(request.id.clone(), json!({ "resources": resources }))
 }
 
-fn handle_resources_read(request: &JsonRpcRequest, project_root: &PathBuf) -> JsonRpcResponse {
+fn handle_resources_read(request: &JsonRpcRequest, project_root: &Path) -> JsonRpcResponse {
     let uri = request
         .params
         .get("uri")
@@ -1,17 +1,39 @@
 use serde_json::{json, Value};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::protocol::{ToolDefinition, ToolResult};
-use crate::config::{Config, ProjectRegistry};
+use crate::config::{Config, Fusion, ProjectRegistry};
 use crate::context;
 use crate::index;
+use crate::search::duplicates::{self, DuplicatesQuery};
+use crate::search::history::{self, HistoryQuery};
+use crate::search::hybrid::{self, HybridQuery};
+use crate::search::similar::{self, SimilarQuery, Target};
+use crate::search::snippet;
+use crate::search::workspace::{self, WorkspaceQuery};
 use crate::search::text::SearchQuery;
-use crate::store::sqlite::Store;
+use crate::store::sqlite::{SearchFilter, Store, TestScope};
 
 fn project_prop() -> Value {
     json!({ "type": "string", "description": "Registered project name or path (use 'projects' tool to list)" })
 }
 
+fn exclude_paths_prop() -> Value {
+    json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "description": "Leave out files under these path prefixes or matching these globs (e.g. [\"vendor/\", \"*.generated.ts\"])"
+    })
+}
+
+fn tests_prop() -> Value {
+    json!({
+        "type": "string",
+        "description": "Test code (test dirs and files, test functions, #[cfg(test)] modules): include (default), exclude, or only",
+        "enum": ["include", "exclude", "only"]
+    })
+}
+
 pub fn list_tools() -> Vec<ToolDefinition> {
     vec![
         ToolDefinition {
@@ -22,7 +44,7 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                 "properties": {
                     "query": {
                         "type": "string",
-                        "description": "Search query (FTS5 syntax: terms, phrases in quotes, OR, NOT)"
+                        "description": "Search query (FTS5 syntax: terms, phrases in quotes, OR, NOT). Inline qualifiers filter results: kind:function, lang:rust, path:src/index, name:load*, tests:exclude|only; prefix with - to exclude (-path:tests); comma-separate alternatives (kind:function,method); * ? [..] make a glob. Qualifiers override the language/path_prefix/kind parameters"
                     },
                     "language": {
                         "type": "string",
@@ -32,6 +54,8 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                         "type": "string",
                         "description": "Filter results to files under this path prefix"
                     },
+                    "exclude_paths": exclude_paths_prop(),
+                    "tests": tests_prop(),
                     "max_results": {
                         "type": "integer",
                         "description": "Maximum number of results (default: 20)"
@@ -44,10 +68,26 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                         "type": "string",
                         "description": "Filter by chunk kind: function, struct, enum, class, method, impl, import, trait, interface, type_alias, raw"
                     },
+                    "diff_boost": {
+                        "type": "boolean",
+                        "description": "Boost results in files and symbols changed on the current branch vs the default branch (default: true)"
+                    },
+                    "explain": {
+                        "type": "boolean",
+                        "description": "Append a per-result score breakdown (BM25 and each ranking stage) and whether the OR fallback ran"
+                    },
+                    "notes": {
+                        "type": "boolean",
+                        "description": "Also return annotations matching the query, as 'note' results at the annotated file, line or symbol, before the code results (default: false)"
+                    },
                     "output_mode": {
                         "type": "string",
-                        "description": "Output mode: \"content\" shows matching lines with line numbers (default), \"files_with_matches\" shows only file locations, \"count\" shows match counts",
-                        "enum": ["content", "files_with_matches", "signatures", "count"]
+                        "description": "Output mode: \"content\" shows matching chunks with line numbers (default), \"snippets\" shows only the matching lines (marked >) with context_lines around them under the chunk signature, \"files_with_matches\" shows only file locations, \"count\" shows match counts",
+                        "enum": ["content", "snippets", "files_with_matches", "signatures", "count"]
+                    },
+                    "context_lines": {
+                        "type": "integer",
+                        "description": "Lines of context around each match in snippets mode (default: 2)"
                     },
                     "head_limit": {
                         "type": "integer",
@@ -71,7 +111,7 @@ pub fn list_tools() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "index".into(),
-            description: "Index a directory for searching. Incremental — only re-indexes changed files.".into(),
+            description: "Index a directory for searching. Incremental — only re-indexes changed files. With history: true, also adds new commits to the commit history index used by history-search.".into(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -79,6 +119,14 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                         "type": "string",
                         "description": "Path to directory to index (default: project root)"
                     },
+                    "history": {
+                        "type": "boolean",
+                        "description": "Also index commit messages, authors, dates and touched symbols (default: false)"
+                    },
+                    "max_commits": {
+                        "type": "integer",
+                        "description": "How many of the latest commits to index with history (default: 1000)"
+                    },
                     "project": project_prop()
                 }
             }),
@@ -97,6 +145,20 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                 }
             }),
         },
+        ToolDefinition {
+            name: "check".into(),
+            description: "Verify index integrity: SQLite and FTS5 integrity checks, chunk/FTS and embedding/chunk cross-validation, and file hashes vs disk. Set repair=true to rebuild the FTS index, drop bad embeddings and re-index changed files.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "repair": {
+                        "type": "boolean",
+                        "description": "Repair problems found (default: false)"
+                    },
+                    "project": project_prop()
+                }
+            }),
+        },
         ToolDefinition {
             name: "annotate".into(),
             description: "Attach a note to a file, symbol, or line range. Notes are included in context and can influence search. Supports TTL for auto-expiry.".into(),
@@ -142,6 +204,64 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                 }
             }),
         },
+        ToolDefinition {
+            name: "annotation-search".into(),
+            description: "Full-text search over annotation notes and targets, e.g. 'did I note anything about transactions?'. Best match first; expired annotations are left out.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Words to find in notes and targets (same syntax as search)"
+                    },
+                    "session_id": {
+                        "type": "string",
+                        "description": "Only this session's annotations and session-less ones"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum annotations to return (default: 20)"
+                    },
+                    "project": project_prop()
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: "feedback".into(),
+            description: "Tell booger whether a search result was useful for a query. Results marked useful rank higher for similar queries in this project; not useful ranks lower. Following up on a result with symbols (on its file) or references (to its symbol) counts as useful automatically.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The query the result was returned for"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File path of the result"
+                    },
+                    "symbol": {
+                        "type": "string",
+                        "description": "Name of the result's chunk, to narrow the feedback to one symbol"
+                    },
+                    "line": {
+                        "type": "integer",
+                        "description": "A line inside the result, for chunks without a name"
+                    },
+                    "useful": {
+                        "type": "boolean",
+                        "description": "Whether the result was useful (default: true)"
+                    },
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID, recorded with the feedback"
+                    },
+                    "project": project_prop()
+                },
+                "required": ["query", "path"]
+            }),
+        },
         ToolDefinition {
             name: "focus".into(),
             description: "Mark paths as focused — search results from these paths are boosted.".into(),
@@ -182,9 +302,46 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                 "required": ["paths"]
             }),
         },
+        ToolDefinition {
+            name: "intent".into(),
+            description: "Record the session's goal, e.g. 'migrate embeddings to the OpenAI backend'. Its key terms (and, with embed=true, its embedding) softly boost matching results in search and hybrid-search for the session. Replaces the session's earlier intent.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "goal": {
+                        "type": "string",
+                        "description": "What the session is trying to do"
+                    },
+                    "embed": {
+                        "type": "boolean",
+                        "description": "Also embed the goal (needs Ollama) so chunks near it in meaning are boosted (default: false)"
+                    },
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID"
+                    },
+                    "project": project_prop()
+                },
+                "required": ["goal"]
+            }),
+        },
+        ToolDefinition {
+            name: "intents".into(),
+            description: "List the session's context: intents with their key terms, focused paths and visited paths.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID filter"
+                    },
+                    "project": project_prop()
+                }
+            }),
+        },
         ToolDefinition {
             name: "forget".into(),
-            description: "Clear volatile context: annotations and working set. Optionally scoped to a session.".into(),
+            description: "Clear volatile context: annotations, working set and intents. Optionally scoped to a session.".into(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -220,17 +377,17 @@ pub fn list_tools() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "embed".into(),
-            description: "Generate embeddings for indexed chunks using ollama. Required before semantic search works. Incremental — only embeds new/changed chunks.".into(),
+            description: "Generate embeddings for indexed chunks using the configured backend ([embed.backend] in config: ollama by default, or an OpenAI-compatible server). Required before semantic search works. Incremental — only embeds new/changed chunks.".into(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "model": {
                         "type": "string",
-                        "description": "Ollama model name (default: nomic-embed-text)"
+                        "description": "Ollama model name (default: nomic-embed-text); overrides the configured backend"
                     },
                     "url": {
                         "type": "string",
-                        "description": "Ollama server URL (default: http://localhost:11434)"
+                        "description": "Ollama server URL (default: http://localhost:11434); overrides the configured backend"
                     },
                     "project": project_prop()
                 }
@@ -258,6 +415,10 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                         "type": "integer",
                         "description": "Maximum number of results (default: 20)"
                     },
+                    "exact": {
+                        "type": "boolean",
+                        "description": "Compare against every embedding instead of the approximate nearest-neighbour index (default: false)"
+                    },
                     "output_mode": {
                         "type": "string",
                         "description": "Output mode: \"content\" shows matching lines with line numbers (default), \"files_with_matches\" shows only file locations, \"count\" shows match counts",
@@ -370,6 +531,8 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                         "type": "string",
                         "description": "Filter to files under this path"
                     },
+                    "exclude_paths": exclude_paths_prop(),
+                    "tests": tests_prop(),
                     "kind": {
                         "type": "string",
                         "description": "Filter by chunk kind: function, struct, import, raw, etc."
@@ -384,7 +547,7 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                     },
                     "output_mode": {
                         "type": "string",
-                        "description": "\"content\" shows matching lines (default), \"files_with_matches\" shows only file paths, \"count\" shows match count",
+                        "description": "\"content\" shows matching lines (default), \"files_with_matches\" shows only file paths, \"count\" shows the exact match count",
                         "enum": ["content", "files_with_matches", "count"]
                     },
                     "project": project_prop()
@@ -394,7 +557,7 @@ pub fn list_tools() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "references".into(),
-            description: "Find all usages of a symbol: definitions, call sites, type references, and imports. Structural — not just text grep. Returns categorized results. Optional scope filter: only return definition, call, type, import, or reference.".into(),
+            description: "Find all usages of a symbol: definitions, call sites, type references, imports, and writes. Indexed from tree-sitter identifiers — not text grep. Returns file:line:column with role and enclosing symbol. Optional scope filter: only return definition, call, type, import, write, or reference.".into(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -404,13 +567,15 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                     },
                     "scope": {
                         "type": "string",
-                        "description": "Filter to this ref kind only: definition, call, type, import, or reference",
-                        "enum": ["definition", "call", "type", "import", "reference"]
+                        "description": "Filter to this role only: definition, call, type, import, write, or reference",
+                        "enum": ["definition", "call", "type", "import", "write", "reference"]
                     },
                     "path_prefix": {
                         "type": "string",
                         "description": "Filter to files under this path"
                     },
+                    "exclude_paths": exclude_paths_prop(),
+                    "tests": tests_prop(),
                     "output_mode": {
                         "type": "string",
                         "description": "Output mode: 'content' shows matching lines (default), 'files_with_matches' shows file paths, 'count' shows counts",
@@ -447,6 +612,8 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                         "type": "string",
                         "description": "Filter results to files under this path prefix"
                     },
+                    "exclude_paths": exclude_paths_prop(),
+                    "tests": tests_prop(),
                     "kind": {
                         "type": "string",
                         "description": "Filter by chunk kind: function, struct, import, raw, etc."
@@ -457,12 +624,29 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                     },
                     "alpha": {
                         "type": "number",
-                        "description": "Weight for FTS vs semantic (0.0 = pure semantic, 1.0 = pure FTS, default: 0.7)"
+                        "description": "Weight for FTS vs semantic (0.0 = pure semantic, 1.0 = pure FTS, default: [hybrid] alpha, 0.7)"
+                    },
+                    "fusion": {
+                        "type": "string",
+                        "description": "How to fuse FTS and semantic results: rrf (reciprocal rank fusion), zscore or linear (min-max blend). Default: [hybrid] fusion, rrf",
+                        "enum": ["rrf", "zscore", "linear"]
+                    },
+                    "diff_boost": {
+                        "type": "boolean",
+                        "description": "Boost results in files and symbols changed on the current branch vs the default branch (default: true)"
+                    },
+                    "explain": {
+                        "type": "boolean",
+                        "description": "Append a per-result score breakdown: FTS and semantic contributions and their ranking stages"
                     },
                     "output_mode": {
                         "type": "string",
-                        "description": "Output mode",
-                        "enum": ["content", "files_with_matches", "signatures", "count"]
+                        "description": "Output mode (snippets: only matching lines with context_lines around them)",
+                        "enum": ["content", "snippets", "files_with_matches", "signatures", "count"]
+                    },
+                    "context_lines": {
+                        "type": "integer",
+                        "description": "Lines of context around each match in snippets mode (default: 2)"
                     },
                     "head_limit": {
                         "type": "integer",
@@ -586,9 +770,133 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                 "required": ["symbol"]
             }),
         },
+        ToolDefinition {
+            name: "find-symbol".into(),
+            description: "Fuzzy lookup of symbol names (ctrl-P style). Tolerates typos, missing letters and case/underscore differences: 'index_dir' finds index_directory, 'IndxStats' finds IndexStats. Ranked by match quality, symbol kind and path depth. A cheap first hop before symbols or search-expand.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Approximate symbol name"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "description": "Filter by kind: function, method, struct, class, enum, trait, interface, type_alias, ..."
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "Filter by language"
+                    },
+                    "path_prefix": {
+                        "type": "string",
+                        "description": "Only symbols in files under this path prefix"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum number of results (default: 20)"
+                    },
+                    "project": project_prop()
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: "similar".into(),
+            description: "Query by example: find code like a given chunk, e.g. other handlers shaped like this one or near-copies of a function. Uses the chunk's embedding if it has one, otherwise a structural fingerprint (token shape with names and literals abstracted). The chunk itself is left out.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "target": {
+                        "type": "string",
+                        "description": "The example: 'path:line' (e.g. 'src/api/users.rs:42'), a symbol name, or a chunk id"
+                    },
+                    "chunk_id": {
+                        "type": "integer",
+                        "description": "Chunk id of the example (alternative to target)"
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "Filter by language"
+                    },
+                    "path_prefix": {
+                        "type": "string",
+                        "description": "Only results under this path"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "description": "Filter by chunk kind: function, method, struct, ..."
+                    },
+                    "exclude_paths": exclude_paths_prop(),
+                    "tests": tests_prop(),
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum number of results (default: 10)"
+                    },
+                    "output_mode": {
+                        "type": "string",
+                        "description": "Output mode",
+                        "enum": ["content", "files_with_matches", "signatures", "count"]
+                    },
+                    "head_limit": {
+                        "type": "integer",
+                        "description": "Limit number of results returned"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Skip first N results"
+                    },
+                    "max_lines": {
+                        "type": "integer",
+                        "description": "Max lines per result in content mode"
+                    },
+                    "project": project_prop()
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "duplicates".into(),
+            description: "Find copy-pasted code: exact duplicate chunks (same code up to whitespace and comments) and near-clones (renamed identifiers, small edits) via MinHash over token shingles. Grouped into clone families with paths and line ranges, most duplicated lines first. Optionally across all registered projects.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "min_lines": {
+                        "type": "integer",
+                        "description": "Ignore chunks shorter than this many lines (default: 6)"
+                    },
+                    "threshold": {
+                        "type": "number",
+                        "description": "Minimum similarity for near-clones, 0.0-1.0 (default: 0.8)"
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "Filter by language"
+                    },
+                    "path_prefix": {
+                        "type": "string",
+                        "description": "Only chunks under this path"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "description": "Filter by chunk kind: function, method, struct, ..."
+                    },
+                    "exclude_paths": exclude_paths_prop(),
+                    "tests": tests_prop(),
+                    "max_families": {
+                        "type": "integer",
+                        "description": "Maximum clone families to return (default: 20)"
+                    },
+                    "all_projects": {
+                        "type": "boolean",
+                        "description": "Compare chunks across all registered projects (default: false)"
+                    },
+                    "project": project_prop()
+                }
+            }),
+        },
         ToolDefinition {
             name: "workspace-search".into(),
-            description: "Search across ALL registered projects at once. Results are tagged with the project name. Useful for finding code across multiple repos.".into(),
+            description: "Search across ALL registered projects at once (or one group of them). Results are tagged with the project name and ranked together: each project's scores are normalized within the project, so separate indexes compare fairly, then multiplied by its registry weight. Modes: text (FTS), hybrid or semantic (need Ollama).".into(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -608,6 +916,20 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                         "type": "integer",
                         "description": "Maximum total results across all projects (default: 20)"
                     },
+                    "mode": {
+                        "type": "string",
+                        "description": "Search run in each project (default: text)",
+                        "enum": ["text", "hybrid", "semantic"]
+                    },
+                    "normalization": {
+                        "type": "string",
+                        "description": "How scores from separate projects are made comparable: zscore (standing within the project), rrf (position), linear (min-max), none (raw scores) (default: zscore)",
+                        "enum": ["zscore", "rrf", "linear", "none"]
+                    },
+                    "group": {
+                        "type": "string",
+                        "description": "Only search registered projects in this group"
+                    },
                     "output_mode": {
                         "type": "string",
                         "description": "Output mode",
@@ -629,6 +951,32 @@ pub fn list_tools() -> Vec<ToolDefinition> {
                 "required": ["query"]
             }),
         },
+        ToolDefinition {
+            name: "history-search".into(),
+            description: "Search the commit history index: commits whose message, author or touched symbols mention the query, and/or commits that added, modified or removed a named symbol. Answers \"why was this written this way?\". Build the index first with index (history: true).".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Words to find in commit messages, authors and touched symbol names"
+                    },
+                    "symbol": {
+                        "type": "string",
+                        "description": "Exact name of a symbol the commit changed"
+                    },
+                    "path_prefix": {
+                        "type": "string",
+                        "description": "Only commits that changed symbols under this path"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum commits to return (default: 10)"
+                    },
+                    "project": project_prop()
+                }
+            }),
+        },
         ToolDefinition {
             name: "projects".into(),
             description: "List all registered projects. Use project names in the 'project' parameter of other tools to target a specific project.".into(),
@@ -640,16 +988,21 @@ pub fn list_tools() -> Vec<ToolDefinition> {
     ]
 }
 
-pub fn call_tool(name: &str, args: &Value, project_root: &PathBuf) -> ToolResult {
+pub fn call_tool(name: &str, args: &Value, project_root: &Path) -> ToolResult {
     match name {
         "search" => tool_search(args, project_root),
         "search-expand" => tool_search_expand(args, project_root),
         "index" => tool_index(args, project_root),
         "status" => tool_status(args, project_root),
+        "check" => tool_check(args, project_root),
         "annotate" => tool_annotate(args, project_root),
         "annotations" => tool_annotations(args, project_root),
+        "annotation-search" => tool_annotation_search(args, project_root),
+        "feedback" => tool_feedback(args, project_root),
         "focus" => tool_focus(args, project_root),
         "visit" => tool_visit(args, project_root),
+        "intent" => tool_intent(args, project_root),
+        "intents" => tool_intents(args, project_root),
         "forget" => tool_forget(args, project_root),
         "branch-diff" => tool_branch_diff(args, project_root),
         "embed" => tool_embed(args, project_root),
@@ -665,6 +1018,10 @@ pub fn call_tool(name: &str, args: &Value, project_root: &PathBuf) -> ToolResult
         "changed-since" => tool_changed_since(args, project_root),
         "directory-summary" => tool_directory_summary(args, project_root),
         "tests-for" => tool_tests_for(args, project_root),
+        "find-symbol" => tool_find_symbol(args, project_root),
+        "similar" => tool_similar(args, project_root),
+        "duplicates" => tool_duplicates(args, project_root),
+        "history-search" => tool_history_search(args, project_root),
         "projects" => tool_projects(),
         _ => ToolResult::error(format!("Unknown tool: {name}")),
     }
@@ -682,6 +1039,21 @@ fn load_annotations(root: &std::path::Path, config: &Config) -> Vec<crate::store
     context::annotations::list(root, config, None, None).unwrap_or_default()
 }
 
+/// Incrementally refresh the index before a read-only query. Returns true
+/// when another process is indexing, so the query sees the last snapshot.
+fn refresh_index(root: &Path, config: &Config) -> bool {
+    index::index_directory(root, config).is_ok_and(|r| r.busy)
+}
+
+/// Append the stale-index notice when the refresh was skipped.
+fn with_busy_notice(mut result: ToolResult, busy: bool) -> ToolResult {
+    if busy && let Some(c) = result.content.first_mut() {
+        c.text.push_str("\n\n");
+        c.text.push_str(index::INDEX_BUSY_NOTICE);
+    }
+    result
+}
+
 fn parse_format_opts(args: &Value, default_mode: &str) -> (String, usize, Option<usize>, Option<usize>) {
     let output_mode = args.get("output_mode").and_then(|v| v.as_str()).unwrap_or(default_mode).to_string();
     let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
@@ -690,8 +1062,37 @@ fn parse_format_opts(args: &Value, default_mode: &str) -> (String, usize, Option
     (output_mode, offset, head_limit, max_lines)
 }
 
+/// `exclude_paths` and `tests` arguments, shared by search, grep,
+/// references and hybrid-search.
+fn parse_exclusions(args: &Value) -> Result<(Vec<String>, TestScope), String> {
+    let exclude_paths = args
+        .get("exclude_paths")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).map(String::from).collect())
+        .unwrap_or_default();
+    let tests = match args.get("tests").and_then(|v| v.as_str()) {
+        Some(t) => TestScope::parse(t)
+            .ok_or_else(|| format!("Invalid tests value '{t}' (expected include, exclude or only)"))?,
+        None => TestScope::Include,
+    };
+    Ok((exclude_paths, tests))
+}
+
+/// Whether `r` is an annotation merged into search output (see
+/// `context::annotations::note_results`) rather than code.
+fn is_note(r: &crate::store::sqlite::SearchResult) -> bool {
+    r.chunk_kind == "note"
+}
+
+/// A note result's text, indented instead of line-numbered.
+fn push_note_body(out: &mut String, r: &crate::store::sqlite::SearchResult) {
+    for line in r.content.lines() {
+        out.push_str(&format!("  {line}\n"));
+    }
+}
+
 fn matching_notes(r: &crate::store::sqlite::SearchResult, annotations: &[crate::store::sqlite::Annotation]) -> Vec<String> {
-    if annotations.is_empty() {
+    if annotations.is_empty() || is_note(r) {
         return Vec::new();
     }
     let mut notes = Vec::new();
@@ -704,14 +1105,12 @@ fn matching_notes(r: &crate::store::sqlite::SearchResult, annotations: &[crate::
             notes.push(ann.note.clone());
             continue;
         }
-        if let Some((file, line_str)) = t.split_once(':') {
-            if let Ok(line) = line_str.parse::<i64>() {
-                if (r.file_path.ends_with(file) || r.file_path == *file)
-                    && line >= r.start_line && line <= r.end_line
-                {
-                    notes.push(ann.note.clone());
-                }
-            }
+        if let Some((file, line_str)) = t.split_once(':')
+            && let Ok(line) = line_str.parse::<i64>()
+            && (r.file_path.ends_with(file) || r.file_path == *file)
+            && line >= r.start_line && line <= r.end_line
+        {
+            notes.push(ann.note.clone());
         }
     }
     notes
@@ -788,6 +1187,10 @@ fn format_results(results: &[crate::store::sqlite::SearchResult], opts: &FormatO
                 for note in matching_notes(r, opts.annotations) {
                     out.push_str(&format!("  [note] {note}\n"));
                 }
+                if is_note(r) {
+                    push_note_body(&mut out, r);
+                    continue;
+                }
                 let lines: Vec<&str> = r.content.lines().collect();
                 let limit = max_lines.unwrap_or(lines.len());
                 let shown = limit.min(lines.len());
@@ -804,11 +1207,85 @@ fn format_results(results: &[crate::store::sqlite::SearchResult], opts: &FormatO
     }
 }
 
-fn tool_search(args: &Value, project_root: &PathBuf) -> ToolResult {
-    let query = match args.get("query").and_then(|v| v.as_str()) {
-        Some(q) => q,
-        None => return ToolResult::error("Missing required parameter: query"),
-    };
+/// Snippets of `results` for `query`. Results whose snippet cannot be
+/// computed (e.g. no index) show their first lines.
+fn result_snippets(
+    root: &Path,
+    config: &Config,
+    query: &str,
+    results: &[crate::store::sqlite::SearchResult],
+    context: usize,
+) -> Vec<snippet::Snippet> {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let store = Store::open_if_exists(&config.storage_dir(&root)).ok().flatten();
+    results
+        .iter()
+        .map(|r| {
+            store
+                .as_ref()
+                .and_then(|s| snippet::snippet(s, query, r, context).ok())
+                .unwrap_or_else(|| snippet::Snippet::head(r.content.lines().count(), context))
+        })
+        .collect()
+}
+
+/// `snippets` output mode: per result, the enclosing signature, then only
+/// the matching lines (marked `>`) with their context.
+fn format_snippets(
+    results: &[crate::store::sqlite::SearchResult],
+    snippets: &[snippet::Snippet],
+    opts: &FormatOpts,
+) -> String {
+    let total = results.len();
+    let end = opts.head_limit.map_or(total, |l| (opts.offset + l).min(total));
+    let start = opts.offset.min(end);
+
+    let mut out = format!("{total} result(s)");
+    if opts.offset > 0 || opts.head_limit.is_some() {
+        out.push_str(&format!(" (showing {}-{})", start + 1, end));
+    }
+    out.push('\n');
+    for i in start..end {
+        let (r, snip) = (&results[i], &snippets[i]);
+        let name_display = r.chunk_name.as_deref().map(|n| format!(" ({n})")).unwrap_or_default();
+        out.push_str(&format!(
+            "\n── [{i}] {}:{}-{} [{}{}] ──\n",
+            r.file_path, r.start_line, r.end_line, r.chunk_kind, name_display,
+        ));
+        for note in matching_notes(r, opts.annotations) {
+            out.push_str(&format!("  [note] {note}\n"));
+        }
+        if is_note(r) {
+            push_note_body(&mut out, r);
+            continue;
+        }
+        let lines: Vec<&str> = r.content.lines().collect();
+        if let Some(sig) = &r.signature
+            && snip.windows.first().is_some_and(|w| w.start > 0)
+        {
+            out.push_str(&format!("  {sig}\n"));
+        }
+        for (w, window) in snip.windows.iter().enumerate() {
+            if w > 0 || window.start > 0 {
+                out.push_str("   ...\n");
+            }
+            for j in window.clone() {
+                let marker = if snip.matches.contains(&j) { '>' } else { ' ' };
+                out.push_str(&format!("{marker}{:>5}|{}\n", r.start_line as usize + j, lines[j]));
+            }
+        }
+        if snip.windows.last().is_some_and(|w| w.end < lines.len()) {
+            out.push_str("   ...\n");
+        }
+    }
+    out
+}
+
+fn tool_search(args: &Value, project_root: &Path) -> ToolResult {
+    let query = match args.get("query").and_then(|v| v.as_str()) {
+        Some(q) => q,
+        None => return ToolResult::error("Missing required parameter: query"),
+    };
 
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
@@ -827,28 +1304,63 @@ fn tool_search(args: &Value, project_root: &PathBuf) -> ToolResult {
     search_query.session_id = args.get("session_id").and_then(|v| v.as_str()).map(String::from);
 
     search_query.kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
+    (search_query.exclude_paths, search_query.tests) = match parse_exclusions(args) {
+        Ok(e) => e,
+        Err(e) => return ToolResult::error(e),
+    };
+    search_query.diff_boost = args.get("diff_boost").and_then(|v| v.as_bool()).unwrap_or(true);
+    let explain = args.get("explain").and_then(|v| v.as_bool()).unwrap_or(false);
+    let include_notes = args.get("notes").and_then(|v| v.as_bool()).unwrap_or(false);
+    let context_lines = args.get("context_lines").and_then(|v| v.as_u64()).unwrap_or(2) as usize;
 
     let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
     let anns = load_annotations(&root, &config);
     let opts = FormatOpts { output_mode: &output_mode, offset, head_limit, max_lines, annotations: &anns };
 
     match crate::search::text::search(&root, &config, &search_query) {
-        Ok(results) => {
-            if results.is_empty() {
-                let msg = crate::search::text::explain_empty_search(
+        Ok(outcome) => {
+            let mut results = Vec::new();
+            if include_notes {
+                let parsed = search_query.parsed();
+                let session_id = search_query.session_id.as_deref();
+                let max = context::annotations::MAX_NOTE_RESULTS;
+                results = match context::annotations::note_results(&root, &config, &parsed.text, &parsed.filter, session_id, max) {
+                    Ok(notes) => notes,
+                    Err(e) => return ToolResult::error(format!("Note search failed: {e}")),
+                };
+                // An unresolved target is no file: keep it out of file lists and counts.
+                if output_mode == "files_with_matches" || output_mode == "count" {
+                    results.retain(|r| r.start_line > 0);
+                }
+            }
+            let result = if outcome.results.is_empty() && results.is_empty() {
+                ToolResult::success(crate::search::text::explain_empty_search(
                     &root,
                     &config,
                     search_query.path_prefix.as_deref(),
-                );
-                return ToolResult::success(msg);
-            }
-            ToolResult::success(format_results(&results, &opts))
+                ))
+            } else {
+                context::feedback::remember(&root, search_query.session_id.as_deref(), query, &outcome.results);
+                results.extend(outcome.results.iter().cloned());
+                let mut out = if output_mode == "snippets" {
+                    let snippets = result_snippets(&root, &config, query, &results, context_lines);
+                    format_snippets(&results, &snippets, &opts)
+                } else {
+                    format_results(&results, &opts)
+                };
+                if explain {
+                    out.push_str("\n\n");
+                    out.push_str(&crate::search::text::format_explanations(&outcome));
+                }
+                ToolResult::success(out)
+            };
+            with_busy_notice(result, outcome.index_busy)
         }
         Err(e) => ToolResult::error(format!("Search failed: {e}")),
     }
 }
 
-fn tool_search_expand(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_search_expand(args: &Value, project_root: &Path) -> ToolResult {
     let query = match args.get("query").and_then(|v| v.as_str()) {
         Some(q) => q,
         None => return ToolResult::error("Missing required parameter: query"),
@@ -873,8 +1385,8 @@ fn tool_search_expand(args: &Value, project_root: &PathBuf) -> ToolResult {
     search_query.session_id = args.get("session_id").and_then(|v| v.as_str()).map(String::from);
     search_query.kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
 
-    let results = match crate::search::text::search(&root, &config, &search_query) {
-        Ok(r) => r,
+    let (results, busy) = match crate::search::text::search(&root, &config, &search_query) {
+        Ok(o) => (o.results, o.index_busy),
         Err(e) => return ToolResult::error(format!("Search failed: {e}")),
     };
     if results.is_empty() {
@@ -883,7 +1395,7 @@ fn tool_search_expand(args: &Value, project_root: &PathBuf) -> ToolResult {
             &config,
             search_query.path_prefix.as_deref(),
         );
-        return ToolResult::success(msg);
+        return with_busy_notice(ToolResult::success(msg), busy);
     }
 
     let mut paths: Vec<&str> = Vec::new();
@@ -912,7 +1424,7 @@ fn tool_search_expand(args: &Value, project_root: &PathBuf) -> ToolResult {
 
     let mut out = format!("Search: {} result(s) (expanding top {} path(s))\n\n", results.len(), paths.len());
     for path in paths {
-        let symbols = store.list_symbols(Some(path), None).unwrap_or_default();
+        let symbols = store.list_symbols(&SearchFilter::new(None, Some(path), None)).unwrap_or_default();
         out.push_str(&format!("--- {} ---\n", path));
         if symbols.is_empty() {
             out.push_str("(no symbols)\n");
@@ -928,10 +1440,10 @@ fn tool_search_expand(args: &Value, project_root: &PathBuf) -> ToolResult {
         }
         out.push('\n');
     }
-    ToolResult::success(out.trim_end().to_string())
+    with_busy_notice(ToolResult::success(out.trim_end().to_string()), busy)
 }
 
-fn tool_index(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_index(args: &Value, project_root: &Path) -> ToolResult {
     let root = if let Some(path) = args.get("path").and_then(|v| v.as_str()) {
         PathBuf::from(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path))
     } else {
@@ -942,22 +1454,37 @@ fn tool_index(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    match index::index_directory(&root, &config) {
+    match index::index_directory_wait(&root, &config) {
         Ok(result) => {
-            let summary = json!({
+            let mut summary = json!({
                 "files_scanned": result.files_scanned,
                 "files_indexed": result.files_indexed,
                 "files_unchanged": result.files_unchanged,
                 "files_skipped": result.files_skipped,
                 "chunks_created": result.chunks_created,
             });
+            if args.get("history").and_then(|v| v.as_bool()).unwrap_or(false) {
+                let max_commits = args
+                    .get("max_commits")
+                    .and_then(|v| v.as_u64())
+                    .map_or(crate::git::history::MAX_COMMITS, |n| n as usize);
+                match index::index_history(&root, &config, max_commits) {
+                    Ok(history) => {
+                        summary["commits_scanned"] = json!(history.commits_scanned);
+                        summary["commits_indexed"] = json!(history.commits_indexed);
+                        summary["symbols_recorded"] = json!(history.symbols_recorded);
+                        summary["commits_failed"] = json!(history.commits_failed);
+                    }
+                    Err(e) => return ToolResult::error(format!("History indexing failed: {e}")),
+                }
+            }
             ToolResult::success(summary.to_string())
         }
         Err(e) => ToolResult::error(format!("Indexing failed: {e}")),
     }
 }
 
-fn tool_status(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_status(args: &Value, project_root: &Path) -> ToolResult {
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
         Err(e) => return ToolResult::error(e),
@@ -994,7 +1521,27 @@ fn tool_status(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_annotate(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_check(args: &Value, project_root: &Path) -> ToolResult {
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
+    let repair = args.get("repair").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    match index::check::check(&root, &config, repair) {
+        Ok(report) => {
+            let mut out = index::check::format_report(&report);
+            if !report.is_ok() && !repair {
+                out.push_str("\n\nCall check with repair=true to fix.");
+            }
+            ToolResult::success(out)
+        }
+        Err(e) => ToolResult::error(format!("Check failed: {e}")),
+    }
+}
+
+fn tool_annotate(args: &Value, project_root: &Path) -> ToolResult {
     let target = match args.get("target").and_then(|v| v.as_str()) {
         Some(t) => t,
         None => return ToolResult::error("Missing required parameter: target"),
@@ -1017,7 +1564,7 @@ fn tool_annotate(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_annotations(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_annotations(args: &Value, project_root: &Path) -> ToolResult {
     let target = args.get("target").and_then(|v| v.as_str());
     let session_id = args.get("session_id").and_then(|v| v.as_str());
     let root = match resolve_project(args, project_root) {
@@ -1035,7 +1582,65 @@ fn tool_annotations(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_focus(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_annotation_search(args: &Value, project_root: &Path) -> ToolResult {
+    let query = match args.get("query").and_then(|v| v.as_str()) {
+        Some(q) => q,
+        None => return ToolResult::error("Missing required parameter: query"),
+    };
+    let session_id = args.get("session_id").and_then(|v| v.as_str());
+    let max_results = args.get("max_results").and_then(|v| v.as_u64()).map_or(20, |n| n as usize);
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
+
+    match context::annotations::search(&root, &config, query, session_id, max_results) {
+        Ok(anns) => match serde_json::to_string_pretty(&anns) {
+            Ok(json) => ToolResult::success(json),
+            Err(e) => ToolResult::error(format!("Serialization error: {e}")),
+        },
+        Err(e) => ToolResult::error(format!("Annotation search failed: {e}")),
+    }
+}
+
+fn tool_feedback(args: &Value, project_root: &Path) -> ToolResult {
+    let query = match args.get("query").and_then(|v| v.as_str()) {
+        Some(q) => q,
+        None => return ToolResult::error("Missing required parameter: query"),
+    };
+    let path = match args.get("path").and_then(|v| v.as_str()) {
+        Some(p) => p,
+        None => return ToolResult::error("Missing required parameter: path"),
+    };
+    let target = context::feedback::Target {
+        path,
+        symbol: args.get("symbol").and_then(|v| v.as_str()),
+        line: args.get("line").and_then(|v| v.as_i64()),
+    };
+    let useful = args.get("useful").and_then(|v| v.as_bool()).unwrap_or(true);
+    let session_id = args.get("session_id").and_then(|v| v.as_str());
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
+
+    let what = match (target.symbol, target.line) {
+        (Some(s), _) => format!("{path} ({s})"),
+        (None, Some(l)) => format!("{path}:{l}"),
+        (None, None) => path.to_string(),
+    };
+    match context::feedback::record(&root, &config, query, &target, useful, session_id) {
+        Ok(id) => {
+            let verdict = if useful { "useful" } else { "not useful" };
+            ToolResult::success(format!("Feedback #{id}: {what} was {verdict} for '{query}'"))
+        }
+        Err(e) => ToolResult::error(format!("Failed to record feedback: {e}")),
+    }
+}
+
+fn tool_focus(args: &Value, project_root: &Path) -> ToolResult {
     let paths = match args.get("paths").and_then(|v| v.as_array()) {
         Some(arr) => arr.iter().filter_map(|v| v.as_str().map(String::from)).collect::<Vec<_>>(),
         None => return ToolResult::error("Missing required parameter: paths"),
@@ -1053,7 +1658,7 @@ fn tool_focus(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_visit(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_visit(args: &Value, project_root: &Path) -> ToolResult {
     let paths = match args.get("paths").and_then(|v| v.as_array()) {
         Some(arr) => arr.iter().filter_map(|v| v.as_str().map(String::from)).collect::<Vec<_>>(),
         None => return ToolResult::error("Missing required parameter: paths"),
@@ -1071,7 +1676,67 @@ fn tool_visit(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_forget(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_intent(args: &Value, project_root: &Path) -> ToolResult {
+    let goal = match args.get("goal").and_then(|v| v.as_str()) {
+        Some(g) => g,
+        None => return ToolResult::error("Missing required parameter: goal"),
+    };
+    let session_id = args.get("session_id").and_then(|v| v.as_str());
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
+
+    let embedder = if args.get("embed").and_then(|v| v.as_bool()).unwrap_or(false) {
+        match crate::embed::from_config(&config.embed) {
+            Ok(e) => Some(e),
+            Err(e) => return ToolResult::error(format!("Failed to connect to the embedding backend: {e}")),
+        }
+    } else {
+        None
+    };
+    match context::intent::set(&root, &config, goal, session_id, embedder.as_deref()) {
+        Ok(intent) => {
+            let embedded = if intent.embedding.is_some() { ", embedded" } else { "" };
+            ToolResult::success(format!("Intent #{}: {goal} (terms: {}{embedded})", intent.id, intent.terms.join(", ")))
+        }
+        Err(e) => ToolResult::error(format!("Failed to set intent: {e}")),
+    }
+}
+
+fn tool_intents(args: &Value, project_root: &Path) -> ToolResult {
+    let session_id = args.get("session_id").and_then(|v| v.as_str());
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
+
+    let listed = context::intent::list(&root, &config, session_id).and_then(|intents| {
+        let workset = context::workset::list(&root, &config, None, session_id)?;
+        Ok((intents, workset))
+    });
+    match listed {
+        Ok((intents, workset)) => {
+            let paths = |kind: &str| -> Vec<&str> {
+                workset.iter().filter(|e| e.kind == kind).map(|e| e.path.as_str()).collect()
+            };
+            let body = json!({
+                "intents": intents,
+                "focus": paths("focus"),
+                "visited": paths("visited"),
+            });
+            match serde_json::to_string_pretty(&body) {
+                Ok(json) => ToolResult::success(json),
+                Err(e) => ToolResult::error(format!("Serialization error: {e}")),
+            }
+        }
+        Err(e) => ToolResult::error(format!("Failed to list intents: {e}")),
+    }
+}
+
+fn tool_forget(args: &Value, project_root: &Path) -> ToolResult {
     let session_id = args.get("session_id").and_then(|v| v.as_str());
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
@@ -1085,14 +1750,17 @@ fn tool_forget(args: &Value, project_root: &PathBuf) -> ToolResult {
         session_id,
     );
     let ws = context::workset::clear(&root, &config, session_id);
+    let intents = context::intent::clear(&root, &config, session_id);
 
-    match (anns, ws) {
-        (Ok(a), Ok(w)) => ToolResult::success(format!("Cleared {a} annotations, {w} workset entries")),
-        (Err(e), _) | (_, Err(e)) => ToolResult::error(format!("Failed to clear: {e}")),
+    match (anns, ws, intents) {
+        (Ok(a), Ok(w), Ok(i)) => {
+            ToolResult::success(format!("Cleared {a} annotations, {w} workset entries, {i} intents"))
+        }
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => ToolResult::error(format!("Failed to clear: {e}")),
     }
 }
 
-fn tool_branch_diff(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_branch_diff(args: &Value, project_root: &Path) -> ToolResult {
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
         Err(e) => return ToolResult::error(e),
@@ -1126,21 +1794,31 @@ fn tool_branch_diff(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_embed(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_embed(args: &Value, project_root: &Path) -> ToolResult {
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
         Err(e) => return ToolResult::error(e),
     };
     let config = Config::load(&root).unwrap_or_default();
-    let model = args.get("model").and_then(|v| v.as_str()).unwrap_or("nomic-embed-text");
-    let url = args.get("url").and_then(|v| v.as_str()).unwrap_or("http://localhost:11434");
-
-    let embedder = match crate::embed::ollama::OllamaEmbedder::new(url, model) {
+    let model = args.get("model").and_then(|v| v.as_str());
+    let url = args.get("url").and_then(|v| v.as_str());
+
+    // Explicit model/url arguments select ollama; otherwise use the configured backend.
+    let embedder: anyhow::Result<Box<dyn crate::embed::Embedder>> = if model.is_some() || url.is_some() {
+        crate::embed::ollama::OllamaEmbedder::new(
+            url.unwrap_or("http://localhost:11434"),
+            model.unwrap_or("nomic-embed-text"),
+        )
+        .map(|e| Box::new(e) as Box<dyn crate::embed::Embedder>)
+    } else {
+        crate::embed::from_config(&config.embed)
+    };
+    let embedder = match embedder {
         Ok(e) => e,
-        Err(e) => return ToolResult::error(format!("Failed to connect to ollama: {e}")),
+        Err(e) => return ToolResult::error(format!("Failed to connect to the embedding backend: {e}")),
     };
 
-    match crate::search::semantic::embed_chunks(&root, &config, &embedder) {
+    match crate::search::semantic::embed_chunks(&root, &config, embedder.as_ref()) {
         Ok(stats) => {
             let summary = json!({
                 "total_chunks": stats.total_chunks,
@@ -1153,7 +1831,7 @@ fn tool_embed(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_semantic_search(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_semantic_search(args: &Value, project_root: &Path) -> ToolResult {
     let query = match args.get("query").and_then(|v| v.as_str()) {
         Some(q) => q,
         None => return ToolResult::error("Missing required parameter: query"),
@@ -1164,21 +1842,22 @@ fn tool_semantic_search(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    let embedder = match crate::embed::ollama::OllamaEmbedder::default() {
+    let embedder = match crate::embed::from_config(&config.embed) {
         Ok(e) => e,
-        Err(e) => return ToolResult::error(format!("Failed to connect to ollama: {e}")),
+        Err(e) => return ToolResult::error(format!("Failed to connect to the embedding backend: {e}")),
     };
 
     let mut search_query = crate::search::semantic::SemanticQuery::new(query);
     search_query.language = args.get("language").and_then(|v| v.as_str()).map(String::from);
     search_query.path_prefix = args.get("path_prefix").and_then(|v| v.as_str()).map(String::from);
     search_query.max_results = args.get("max_results").and_then(|v| v.as_u64()).map(|n| n as usize).unwrap_or(20);
+    search_query.exact = args.get("exact").and_then(|v| v.as_bool()).unwrap_or(false);
 
     let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
     let anns = load_annotations(&root, &config);
     let opts = FormatOpts { output_mode: &output_mode, offset, head_limit, max_lines, annotations: &anns };
 
-    match crate::search::semantic::search(&root, &config, &embedder, &search_query) {
+    match crate::search::semantic::search(&root, &config, embedder.as_ref(), &search_query) {
         Ok(results) => {
             if results.is_empty() {
                 let storage_dir = config.storage_dir(
@@ -1200,7 +1879,7 @@ fn tool_semantic_search(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_draft_commit(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_draft_commit(args: &Value, project_root: &Path) -> ToolResult {
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
         Err(e) => return ToolResult::error(e),
@@ -1214,7 +1893,7 @@ fn tool_draft_commit(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_changelog(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_changelog(args: &Value, project_root: &Path) -> ToolResult {
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
         Err(e) => return ToolResult::error(e),
@@ -1234,14 +1913,14 @@ fn tool_changelog(args: &Value, project_root: &PathBuf) -> ToolResult {
     }
 }
 
-fn tool_symbols(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_symbols(args: &Value, project_root: &Path) -> ToolResult {
     let root = match resolve_project(args, project_root) {
         Ok(r) => r,
         Err(e) => return ToolResult::error(e),
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    let _ = index::index_directory(&root, &config);
+    let busy = refresh_index(&root, &config);
 
     let storage_dir = config.storage_dir(&root);
     let store = match Store::open_if_exists(&storage_dir) {
@@ -1258,11 +1937,13 @@ fn tool_symbols(args: &Value, project_root: &PathBuf) -> ToolResult {
 
     let path_prefix = args.get("path_prefix").and_then(|v| v.as_str());
     let kind = args.get("kind").and_then(|v| v.as_str());
+    // Listing the symbols of a file the last search returned means it helped.
+    let _ = context::feedback::follow_up(&root, &config, path_prefix, None);
     let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "signatures");
     let anns = load_annotations(&root, &config);
     let opts = FormatOpts { output_mode: &output_mode, offset, head_limit, max_lines, annotations: &anns };
 
-    match store.list_symbols(path_prefix, kind) {
+    match store.list_symbols(&SearchFilter::new(None, path_prefix, kind)) {
         Ok(results) => {
             if results.is_empty() {
                 let msg = match store.path_has_chunks(path_prefix) {
@@ -1277,15 +1958,15 @@ fn tool_symbols(args: &Value, project_root: &PathBuf) -> ToolResult {
                     }
                     _ => "No symbols found.".into(),
                 };
-                return ToolResult::success(msg);
+                return with_busy_notice(ToolResult::success(msg), busy);
             }
-            ToolResult::success(format_results(&results, &opts))
+            with_busy_notice(ToolResult::success(format_results(&results, &opts)), busy)
         }
         Err(e) => ToolResult::error(format!("Symbol listing failed: {e}")),
     }
 }
 
-fn tool_grep(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_grep(args: &Value, project_root: &Path) -> ToolResult {
     let pattern = match args.get("pattern").and_then(|v| v.as_str()) {
         Some(p) => p,
         None => return ToolResult::error("Missing required parameter: pattern"),
@@ -1302,7 +1983,7 @@ fn tool_grep(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    let _ = index::index_directory(&root, &config);
+    let busy = refresh_index(&root, &config);
 
     let storage_dir = config.storage_dir(
         &root.canonicalize().unwrap_or_else(|_| root.clone()),
@@ -1315,68 +1996,51 @@ fn tool_grep(args: &Value, project_root: &PathBuf) -> ToolResult {
 
     let path_prefix = args.get("path_prefix").and_then(|v| v.as_str());
     let kind = args.get("kind").and_then(|v| v.as_str());
+    let mut filter = SearchFilter::new(None, path_prefix, kind);
+    (filter.exclude_paths, filter.tests) = match parse_exclusions(args) {
+        Ok(e) => e,
+        Err(e) => return ToolResult::error(e),
+    };
     let max_results = args.get("max_results").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
     let context_lines = args.get("context_lines").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
     let output_mode = args.get("output_mode").and_then(|v| v.as_str()).unwrap_or("content");
 
-    let chunks = match store.all_chunks(path_prefix, kind) {
-        Ok(c) => c,
-        Err(e) => return ToolResult::error(format!("Failed to load chunks: {e}")),
+    let outcome = match crate::search::grep::grep(
+        &store, &regex, &filter, max_results, context_lines,
+    ) {
+        Ok(o) => o,
+        Err(e) => return ToolResult::error(format!("Grep failed: {e}")),
     };
 
-    struct GrepMatch {
-        file: String,
-        context: Vec<(usize, String, bool)>, // (line_no, text, is_match)
-    }
-
-    let mut matches: Vec<GrepMatch> = Vec::new();
-    let mut seen_files: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
-
-    'outer: for chunk in &chunks {
-        let lines: Vec<&str> = chunk.content.lines().collect();
-        for (i, line) in lines.iter().enumerate() {
-            if regex.is_match(line) {
-                seen_files.insert(chunk.file_path.clone());
-
-                let ctx_start = i.saturating_sub(context_lines);
-                let ctx_end = (i + context_lines + 1).min(lines.len());
-                let context: Vec<(usize, String, bool)> = (ctx_start..ctx_end)
-                    .map(|j| {
-                        let ln = chunk.start_line as usize + j;
-                        (ln, lines[j].to_string(), j == i)
-                    })
-                    .collect();
-
-                matches.push(GrepMatch { file: chunk.file_path.clone(), context });
-
-                if matches.len() >= max_results {
-                    break 'outer;
-                }
-            }
-        }
+    if outcome.total_matches == 0 {
+        return with_busy_notice(ToolResult::success("No matches."), busy);
     }
 
-    if matches.is_empty() {
-        return ToolResult::success("No matches.");
-    }
-
-    match output_mode {
+    let result = match output_mode {
         "count" => ToolResult::success(format!(
-            "at least {} match(es) in {} file(s)",
-            matches.len(),
-            seen_files.len()
+            "{} match(es) in {} file(s)",
+            outcome.total_matches,
+            outcome.files.len()
         )),
         "files_with_matches" => {
-            let mut out = format!("{} file(s)\n", seen_files.len());
-            for f in &seen_files {
+            let mut out = format!("{} file(s)\n", outcome.files.len());
+            for f in &outcome.files {
                 out.push_str(f);
                 out.push('\n');
             }
             ToolResult::success(out)
         }
         _ => {
-            let mut out = format!("{} match(es)\n", matches.len());
-            for m in &matches {
+            let mut out = if outcome.total_matches > outcome.matches.len() {
+                format!(
+                    "{} match(es), showing first {}\n",
+                    outcome.total_matches,
+                    outcome.matches.len()
+                )
+            } else {
+                format!("{} match(es)\n", outcome.total_matches)
+            };
+            for m in &outcome.matches {
                 for (ln, text, is_match) in &m.context {
                     let sep = if *is_match { ':' } else { '-' };
                     out.push_str(&format!("{}{sep}{ln:>6}|{text}\n", m.file));
@@ -1387,10 +2051,11 @@ fn tool_grep(args: &Value, project_root: &PathBuf) -> ToolResult {
             }
             ToolResult::success(out)
         }
-    }
+    };
+    with_busy_notice(result, busy)
 }
 
-fn tool_references(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_references(args: &Value, project_root: &Path) -> ToolResult {
     let symbol = match args.get("symbol").and_then(|v| v.as_str()) {
         Some(s) => s,
         None => return ToolResult::error("Missing required parameter: symbol"),
@@ -1401,8 +2066,7 @@ fn tool_references(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    // Trigger incremental index via a dummy search to ensure freshness
-    let _ = crate::search::text::search(&root, &config, &SearchQuery::new(symbol));
+    let busy = refresh_index(&root, &config);
 
     let storage_dir = config.storage_dir(
         &root.canonicalize().unwrap_or_else(|_| root.clone()),
@@ -1421,14 +2085,16 @@ fn tool_references(args: &Value, project_root: &PathBuf) -> ToolResult {
 
     let path_prefix = args.get("path_prefix").and_then(|v| v.as_str());
     let scope = args.get("scope").and_then(|v| v.as_str());
-    let (output_mode, offset, head_limit, _) = parse_format_opts(args, "content");
-
-    let all_chunks = match store.all_chunks(path_prefix, None) {
-        Ok(c) => c,
-        Err(e) => return ToolResult::error(format!("Failed to load chunks: {e}")),
+    // Following a symbol the last search returned means it helped.
+    let _ = context::feedback::follow_up(&root, &config, None, Some(symbol));
+    let mut filter = SearchFilter::new(None, path_prefix, None);
+    (filter.exclude_paths, filter.tests) = match parse_exclusions(args) {
+        Ok(e) => e,
+        Err(e) => return ToolResult::error(e),
     };
+    let (output_mode, offset, head_limit, _) = parse_format_opts(args, "content");
 
-    if all_chunks.is_empty() {
+    if let Ok(false) = store.path_has_chunks(path_prefix) {
         let kind = if path_prefix.is_some() {
             crate::search::text::IndexFirstKind::PathPrefixEmpty
         } else {
@@ -1438,89 +2104,23 @@ fn tool_references(args: &Value, project_root: &PathBuf) -> ToolResult {
         return ToolResult::success(crate::search::text::format_index_first_message(&path, kind));
     }
 
-    let pattern = match regex::Regex::new(&format!(r"\b{}\b", regex::escape(symbol))) {
-        Ok(p) => p,
-        Err(e) => return ToolResult::error(format!("Invalid symbol pattern: {e}")),
-    };
-
-    struct Ref {
-        file_path: String,
-        line_no: usize,
-        line: String,
-        chunk_kind: String,
-        chunk_name: Option<String>,
-        ref_kind: &'static str,
-    }
-
-    let mut definitions: Vec<Ref> = Vec::new();
-    let mut references: Vec<Ref> = Vec::new();
-
-    for chunk in &all_chunks {
-        let is_definition = chunk.chunk_name.as_deref() == Some(symbol);
-
-        if is_definition {
-            definitions.push(Ref {
-                file_path: chunk.file_path.clone(),
-                line_no: chunk.start_line as usize,
-                line: chunk.signature.as_deref()
-                    .or_else(|| chunk.content.lines().next())
-                    .unwrap_or("").to_string(),
-                chunk_kind: chunk.chunk_kind.clone(),
-                chunk_name: chunk.chunk_name.clone(),
-                ref_kind: "definition",
-            });
-        } else {
-            for (i, line) in chunk.content.lines().enumerate() {
-                if pattern.is_match(line) {
-                    let line_no = chunk.start_line as usize + i;
-                    let trimmed = line.trim();
-
-                    let ref_kind = if chunk.chunk_kind == "import" {
-                        "import"
-                    } else if trimmed.contains(&format!("{symbol}("))
-                            || trimmed.contains(&format!("{symbol}!(")) {
-                        "call"
-                    } else if trimmed.contains(&format!("<{symbol}>"))
-                            || trimmed.contains(&format!(": {symbol}"))
-                            || trimmed.contains(&format!("-> {symbol}")) {
-                        "type"
-                    } else {
-                        "reference"
-                    };
-
-                    references.push(Ref {
-                        file_path: chunk.file_path.clone(),
-                        line_no,
-                        line: trimmed.to_string(),
-                        chunk_kind: chunk.chunk_kind.clone(),
-                        chunk_name: chunk.chunk_name.clone(),
-                        ref_kind,
-                    });
-                }
-            }
-        }
-    }
-
-    // Optional scope filter: only include this ref kind
-    if let Some(s) = scope {
-        match s {
-            "definition" => references.clear(),
-            "call" | "type" | "import" | "reference" => {
-                definitions.retain(|_| false);
-                references.retain(|r| r.ref_kind == s);
-            }
-            _ => {}
-        }
-    }
+    let crate::graph::query::References { definitions, references } =
+        match crate::graph::query::references(&store, symbol, &filter, scope) {
+            Ok(r) => r,
+            Err(e) => return ToolResult::error(format!("Reference lookup failed: {e}")),
+        };
 
     let total_defs = definitions.len();
     let total_refs = references.len();
 
     if total_defs == 0 && total_refs == 0 {
-        return ToolResult::success(format!("No matches for symbol '{symbol}'."));
+        return with_busy_notice(
+            ToolResult::success(format!("No matches for symbol '{symbol}'.")),
+            busy,
+        );
     }
 
-    match output_mode.as_str() {
+    let result = match output_mode.as_str() {
         "count" => {
             ToolResult::success(format!("{total_defs} definition(s), {total_refs} reference(s)"))
         }
@@ -1545,8 +2145,10 @@ fn tool_references(args: &Value, project_root: &PathBuf) -> ToolResult {
             ToolResult::success(out)
         }
         _ => {
-            let mut all: Vec<&Ref> = definitions.iter().chain(references.iter()).collect();
-            all.sort_by(|a, b| a.file_path.cmp(&b.file_path).then(a.line_no.cmp(&b.line_no)));
+            let mut all: Vec<_> = definitions.iter().chain(references.iter()).collect();
+            all.sort_by(|a, b| {
+                a.file_path.cmp(&b.file_path).then(a.line.cmp(&b.line)).then(a.column.cmp(&b.column))
+            });
 
             let total = all.len();
             let page = if offset >= total {
@@ -1560,16 +2162,17 @@ fn tool_references(args: &Value, project_root: &PathBuf) -> ToolResult {
             for r in page {
                 let in_fn = r.chunk_name.as_deref().unwrap_or("");
                 out.push_str(&format!(
-                    "{}:{} [{}] in {} ({}) {}\n",
-                    r.file_path, r.line_no, r.ref_kind, r.chunk_kind, in_fn, r.line,
+                    "{}:{}:{} [{}] in {} ({}) {}\n",
+                    r.file_path, r.line, r.column, r.role, r.chunk_kind, in_fn, r.text,
                 ));
             }
             ToolResult::success(out)
         }
-    }
+    };
+    with_busy_notice(result, busy)
 }
 
-fn tool_hybrid_search(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_hybrid_search(args: &Value, project_root: &Path) -> ToolResult {
     let query = match args.get("query").and_then(|v| v.as_str()) {
         Some(q) => q,
         None => return ToolResult::error("Missing required parameter: query"),
@@ -1581,106 +2184,65 @@ fn tool_hybrid_search(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    let alpha = args.get("alpha").and_then(|v| v.as_f64()).unwrap_or(0.7);
     let max_results = args.get("max_results").and_then(|v| v.as_u64()).map(|n| n as usize).unwrap_or(20);
-    let language = args.get("language").and_then(|v| v.as_str()).map(String::from);
-    let path_prefix = args.get("path_prefix").and_then(|v| v.as_str()).map(String::from);
-    let kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
     let session_id = args.get("session_id").and_then(|v| v.as_str()).map(String::from);
+    let explain = args.get("explain").and_then(|v| v.as_bool()).unwrap_or(false);
+    let (exclude_paths, tests) = match parse_exclusions(args) {
+        Ok(e) => e,
+        Err(e) => return ToolResult::error(e),
+    };
+    let fusion = match args.get("fusion").and_then(|v| v.as_str()).map(Fusion::parse).transpose() {
+        Ok(f) => f,
+        Err(e) => return ToolResult::error(e.to_string()),
+    };
 
-    // FTS search
-    let mut fts_query = SearchQuery::new(query);
-    fts_query.language = language.clone();
-    fts_query.path_prefix = path_prefix.clone();
-    fts_query.kind = kind.clone();
-    fts_query.max_results = max_results * 2;
-    fts_query.session_id = session_id;
-
-    let fts_results = crate::search::text::search(&root, &config, &fts_query)
-        .unwrap_or_default();
-
-    // Semantic search
-    let embedder = crate::embed::ollama::OllamaEmbedder::default();
-    let sem_results = if let Ok(embedder) = embedder {
-        let mut sem_query = crate::search::semantic::SemanticQuery::new(query);
-        sem_query.language = language;
-        sem_query.path_prefix = path_prefix;
-        sem_query.max_results = max_results * 2;
-        crate::search::semantic::search(&root, &config, &embedder, &sem_query)
-            .unwrap_or_default()
-    } else {
-        Vec::new()
-    };
-
-    if fts_results.is_empty() && sem_results.is_empty() {
-        return ToolResult::success("No results found.");
-    }
-
-    // Normalize and merge
-    use std::collections::HashMap;
-    type SR = crate::store::sqlite::SearchResult;
-
-    let fts_max = fts_results.iter().map(|r| r.rank.abs()).fold(0.0_f64, f64::max).max(1.0);
-    let sem_max = sem_results.iter().map(|r| r.rank).fold(0.0_f64, f64::max).max(1.0);
-
-    // Key: (file_path, start_line)
-    let mut merged: HashMap<(String, i64), SR> = HashMap::new();
-    let mut scores: HashMap<(String, i64), f64> = HashMap::new();
-
-    for r in &fts_results {
-        let key = (r.file_path.clone(), r.start_line);
-        let norm_score = 1.0 - (r.rank.abs() / fts_max);
-        let weighted = alpha * norm_score;
-        scores.entry(key.clone())
-            .and_modify(|s| *s = s.max(weighted))
-            .or_insert(weighted);
-        merged.entry(key).or_insert_with(|| SR {
-            file_path: r.file_path.clone(),
-            language: r.language.clone(),
-            chunk_kind: r.chunk_kind.clone(),
-            chunk_name: r.chunk_name.clone(),
-            signature: r.signature.clone(),
-            start_line: r.start_line,
-            end_line: r.end_line,
-            content: r.content.clone(),
-            rank: 0.0,
-        });
-    }
-
-    for r in &sem_results {
-        let key = (r.file_path.clone(), r.start_line);
-        let norm_score = r.rank / sem_max;
-        let weighted = (1.0 - alpha) * norm_score;
-        scores.entry(key.clone())
-            .and_modify(|s| *s += weighted)
-            .or_insert(weighted);
-        merged.entry(key).or_insert_with(|| SR {
-            file_path: r.file_path.clone(),
-            language: r.language.clone(),
-            chunk_kind: r.chunk_kind.clone(),
-            chunk_name: r.chunk_name.clone(),
-            signature: r.signature.clone(),
-            start_line: r.start_line,
-            end_line: r.end_line,
-            content: r.content.clone(),
-            rank: 0.0,
-        });
-    }
-
-    let mut results: Vec<SR> = merged.into_iter().map(|(key, mut sr)| {
-        sr.rank = *scores.get(&key).unwrap_or(&0.0);
-        sr
-    }).collect();
-    results.sort_by(|a, b| b.rank.partial_cmp(&a.rank).unwrap_or(std::cmp::Ordering::Equal));
-    results.truncate(max_results);
+    let mut hybrid_query = HybridQuery::new(query);
+    hybrid_query.language = args.get("language").and_then(|v| v.as_str()).map(String::from);
+    hybrid_query.path_prefix = args.get("path_prefix").and_then(|v| v.as_str()).map(String::from);
+    hybrid_query.kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
+    hybrid_query.exclude_paths = exclude_paths;
+    hybrid_query.tests = tests;
+    hybrid_query.max_results = max_results;
+    hybrid_query.session_id = session_id.clone();
+    hybrid_query.diff_boost = args.get("diff_boost").and_then(|v| v.as_bool()).unwrap_or(true);
+    hybrid_query.fusion = fusion;
+    hybrid_query.alpha = args.get("alpha").and_then(|v| v.as_f64());
+
+    let embedder = crate::embed::from_config(&config.embed).ok();
+    let outcome = match hybrid::search(
+        &root,
+        &config,
+        embedder.as_deref(),
+        &hybrid_query,
+    ) {
+        Ok(o) => o,
+        Err(e) => return ToolResult::error(format!("Hybrid search failed: {e}")),
+    };
+    let busy = outcome.index_busy;
+    let results = &outcome.results;
+    if results.is_empty() {
+        return with_busy_notice(ToolResult::success("No results found."), busy);
+    }
+    context::feedback::remember(&root, session_id.as_deref(), query, results);
 
     let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
     let anns = load_annotations(&root, &config);
     let opts = FormatOpts { output_mode: &output_mode, offset, head_limit, max_lines, annotations: &anns };
-    ToolResult::success(format_results(&results, &opts))
+    let mut out = if output_mode == "snippets" {
+        let context_lines = args.get("context_lines").and_then(|v| v.as_u64()).unwrap_or(2) as usize;
+        let snippets = result_snippets(&root, &config, query, results, context_lines);
+        format_snippets(results, &snippets, &opts)
+    } else {
+        format_results(results, &opts)
+    };
+    if explain {
+        out.push_str("\n\n");
+        out.push_str(&hybrid::format_explanations(&outcome));
+    }
+    with_busy_notice(ToolResult::success(out), busy)
 }
 
-fn tool_workspace_search(args: &Value, default_root: &PathBuf) -> ToolResult {
+fn tool_workspace_search(args: &Value, default_root: &Path) -> ToolResult {
     let query = match args.get("query").and_then(|v| v.as_str()) {
         Some(q) => q,
         None => return ToolResult::error("Missing required parameter: query"),
@@ -1691,71 +2253,58 @@ fn tool_workspace_search(args: &Value, default_root: &PathBuf) -> ToolResult {
         Err(e) => return ToolResult::error(format!("Failed to load project registry: {e}")),
     };
 
-    let mut projects: Vec<(String, std::path::PathBuf)> = reg.projects.iter()
-        .map(|(name, entry)| (name.clone(), entry.path.clone()))
-        .collect();
-
-    // Also include the default root as "default" if not already registered
-    let default_canon = default_root.canonicalize().unwrap_or_else(|_| default_root.clone());
-    if !projects.iter().any(|(_, p)| p.canonicalize().unwrap_or_else(|_| p.clone()) == default_canon) {
-        projects.push(("(default)".into(), default_root.clone()));
-    }
+    let group = args.get("group").and_then(|v| v.as_str());
+    let projects = reg.workspace(default_root, group);
 
     if projects.is_empty() {
+        if let Some(group) = group {
+            return ToolResult::error(format!("No registered projects in group '{group}'"));
+        }
         return ToolResult::success("No projects registered. Use 'booger project add <name> <path>' to register projects.");
     }
 
-    let max_results = args.get("max_results").and_then(|v| v.as_u64()).map(|n| n as usize).unwrap_or(20);
-    let language = args.get("language").and_then(|v| v.as_str()).map(String::from);
-    let kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
-    let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
-
-    const MAX_THREADS: usize = 10;
-    let project_count = projects.len();
-    let per_project = (max_results * 2 / project_count).max(5);
-
-    type SR = crate::store::sqlite::SearchResult;
-
-    let mut all_results: Vec<(String, SR)> = Vec::new();
-
-    for batch in projects.chunks(MAX_THREADS) {
-        let handles: Vec<_> = batch.iter().map(|(name, path)| {
-            let name = name.clone();
-            let path = path.clone();
-            let lang = language.clone();
-            let k = kind.clone();
-            let q = query.to_string();
-            let pp = per_project;
-            std::thread::spawn(move || {
-                let config = Config::load(&path).unwrap_or_default();
-                let mut search_query = SearchQuery::new(&q);
-                search_query.language = lang;
-                search_query.kind = k;
-                search_query.max_results = pp;
-                let results = crate::search::text::search(&path, &config, &search_query)
-                    .unwrap_or_default();
-                results.into_iter().map(|r| (name.clone(), r)).collect::<Vec<_>>()
-            })
-        }).collect();
-
-        for h in handles {
-            if let Ok(b) = h.join() {
-                all_results.extend(b);
-            }
+    let mut workspace_query = WorkspaceQuery::new(query);
+    workspace_query.rrf_k = Config::load(default_root).unwrap_or_default().hybrid.rrf_k;
+    workspace_query.max_results = args.get("max_results").and_then(|v| v.as_u64()).map(|n| n as usize).unwrap_or(20);
+    workspace_query.language = args.get("language").and_then(|v| v.as_str()).map(String::from);
+    workspace_query.kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
+    if let Some(mode) = args.get("mode").and_then(|v| v.as_str()) {
+        match workspace::Mode::parse(mode) {
+            Ok(m) => workspace_query.mode = m,
+            Err(e) => return ToolResult::error(e.to_string()),
         }
     }
+    match args.get("normalization").and_then(|v| v.as_str()) {
+        Some("none") => workspace_query.normalization = None,
+        Some(name) => match Fusion::parse(name) {
+            Ok(f) => workspace_query.normalization = Some(f),
+            Err(e) => return ToolResult::error(e.to_string()),
+        },
+        None => {}
+    }
+    let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
+    let project_count = projects.len();
+
+    let embedders: workspace::EmbedderFactory = &crate::embed::from_config;
+    let outcome = match workspace::search(&projects, Some(embedders), &workspace_query) {
+        Ok(o) => o,
+        Err(e) => return ToolResult::error(format!("Workspace search failed: {e:#}")),
+    };
+    let all_results = outcome.results;
+    let busy_projects = outcome.busy;
+    let busy_note = if busy_projects.is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{} ({})", index::INDEX_BUSY_NOTICE, busy_projects.join(", "))
+    };
 
     if all_results.is_empty() {
         return ToolResult::success(format!(
-            "No results found across {} project(s).",
+            "No results found across {} project(s).{busy_note}",
             project_count
         ));
     }
 
-    // Sort by rank (FTS5 rank is negative, more negative = better)
-    all_results.sort_by(|a, b| a.1.rank.partial_cmp(&b.1.rank).unwrap_or(std::cmp::Ordering::Equal));
-    all_results.truncate(max_results);
-
     let total = all_results.len();
     let page = if offset >= total {
         &[][..]
@@ -1764,7 +2313,7 @@ fn tool_workspace_search(args: &Value, default_root: &PathBuf) -> ToolResult {
         &all_results[offset..end]
     };
 
-    match output_mode.as_str() {
+    let mut result = match output_mode.as_str() {
         "count" => {
             let mut counts: Vec<(String, usize)> = Vec::new();
             for (name, _) in &all_results {
@@ -1824,10 +2373,14 @@ fn tool_workspace_search(args: &Value, default_root: &PathBuf) -> ToolResult {
             }
             ToolResult::success(out)
         }
+    };
+    if let Some(c) = result.content.first_mut() {
+        c.text.push_str(&busy_note);
     }
+    result
 }
 
-fn tool_batch(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_batch(args: &Value, project_root: &Path) -> ToolResult {
     const MAX_BATCH: usize = 20;
 
     let calls = match args.get("calls").and_then(|v| v.as_array()) {
@@ -1867,7 +2420,7 @@ fn tool_batch(args: &Value, project_root: &PathBuf) -> ToolResult {
     ToolResult::success(serde_json::to_string_pretty(&results).unwrap_or_default())
 }
 
-fn tool_changed_since(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_changed_since(args: &Value, project_root: &Path) -> ToolResult {
     let since = match args.get("since").and_then(|v| v.as_str()) {
         Some(s) => s,
         None => return ToolResult::error("Missing required parameter: since"),
@@ -1883,8 +2436,7 @@ fn tool_changed_since(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    // Trigger auto-index
-    let _ = crate::search::text::search(&root, &config, &SearchQuery::new("__noop__"));
+    let busy = refresh_index(&root, &config);
 
     let storage_dir = config.storage_dir(
         &root.canonicalize().unwrap_or_else(|_| root.clone()),
@@ -1904,7 +2456,7 @@ fn tool_changed_since(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
 
     if results.is_empty() {
-        return ToolResult::success(format!("No changes since {since}."));
+        return with_busy_notice(ToolResult::success(format!("No changes since {since}.")), busy);
     }
 
     let annotations = load_annotations(&root, &config);
@@ -1915,10 +2467,10 @@ fn tool_changed_since(args: &Value, project_root: &PathBuf) -> ToolResult {
         max_lines,
         annotations: &annotations,
     };
-    ToolResult::success(format_results(&results, &opts))
+    with_busy_notice(ToolResult::success(format_results(&results, &opts)), busy)
 }
 
-fn tool_directory_summary(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_directory_summary(args: &Value, project_root: &Path) -> ToolResult {
     let path = match args.get("path").and_then(|v| v.as_str()) {
         Some(p) => p,
         None => return ToolResult::error("Missing required parameter: path"),
@@ -1929,8 +2481,7 @@ fn tool_directory_summary(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    // Trigger auto-index
-    let _ = crate::search::text::search(&root, &config, &SearchQuery::new("__noop__"));
+    let busy = refresh_index(&root, &config);
 
     let storage_dir = config.storage_dir(
         &root.canonicalize().unwrap_or_else(|_| root.clone()),
@@ -1941,13 +2492,16 @@ fn tool_directory_summary(args: &Value, project_root: &PathBuf) -> ToolResult {
         Err(e) => return ToolResult::error(format!("Failed to open store: {e}")),
     };
 
-    let all = match store.all_chunks(Some(path), None) {
+    let all = match store.all_chunks(&SearchFilter::new(None, Some(path), None)) {
         Ok(c) => c,
         Err(e) => return ToolResult::error(format!("Query failed: {e}")),
     };
 
     if all.is_empty() {
-        return ToolResult::success(format!("No indexed content under '{path}'."));
+        return with_busy_notice(
+            ToolResult::success(format!("No indexed content under '{path}'.")),
+            busy,
+        );
     }
 
     let mut files: std::collections::BTreeSet<&str> = std::collections::BTreeSet::new();
@@ -2008,6 +2562,18 @@ fn tool_directory_summary(args: &Value, project_root: &PathBuf) -> ToolResult {
         }
     }
 
+    // Most-committed files in recent git history, as stored by indexing.
+    let hotspots = store.hotspots(Some(path), 5).unwrap_or_default();
+    if !hotspots.is_empty() {
+        out.push_str("\nHotspots (recent commits):\n");
+        for (file, h) in &hotspots {
+            let date = chrono::DateTime::from_timestamp(h.last_modified, 0)
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            out.push_str(&format!("  {file}: {} commits, last {date}\n", h.commits));
+        }
+    }
+
     // Top-level files
     let mut subdirs: std::collections::BTreeMap<String, usize> = std::collections::BTreeMap::new();
     for f in &files {
@@ -2026,10 +2592,10 @@ fn tool_directory_summary(args: &Value, project_root: &PathBuf) -> ToolResult {
         }
     }
 
-    ToolResult::success(out)
+    with_busy_notice(ToolResult::success(out), busy)
 }
 
-fn tool_tests_for(args: &Value, project_root: &PathBuf) -> ToolResult {
+fn tool_tests_for(args: &Value, project_root: &Path) -> ToolResult {
     let symbol = match args.get("symbol").and_then(|v| v.as_str()) {
         Some(s) => s,
         None => return ToolResult::error("Missing required parameter: symbol"),
@@ -2040,8 +2606,7 @@ fn tool_tests_for(args: &Value, project_root: &PathBuf) -> ToolResult {
     };
     let config = Config::load(&root).unwrap_or_default();
 
-    // Trigger auto-index
-    let _ = crate::search::text::search(&root, &config, &SearchQuery::new(symbol));
+    let busy = refresh_index(&root, &config);
 
     let storage_dir = config.storage_dir(
         &root.canonicalize().unwrap_or_else(|_| root.clone()),
@@ -2052,90 +2617,203 @@ fn tool_tests_for(args: &Value, project_root: &PathBuf) -> ToolResult {
         Err(e) => return ToolResult::error(format!("Failed to open store: {e}")),
     };
 
-    let all = match store.all_chunks(None, None) {
-        Ok(c) => c,
+    let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
+
+    let tests = match crate::graph::query::tests_for(&store, symbol) {
+        Ok(t) => t,
         Err(e) => return ToolResult::error(format!("Query failed: {e}")),
     };
 
-    let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
+    if tests.is_empty() {
+        return with_busy_notice(ToolResult::success(format!("No tests found for '{symbol}'.")), busy);
+    }
 
-    let sym_lower = symbol.to_lowercase();
+    let annotations = load_annotations(&root, &config);
+    let opts = FormatOpts {
+        output_mode: &output_mode,
+        offset,
+        head_limit,
+        max_lines,
+        annotations: &annotations,
+    };
+    with_busy_notice(ToolResult::success(format_results(&tests, &opts)), busy)
+}
+
+fn tool_find_symbol(args: &Value, project_root: &Path) -> ToolResult {
+    let query = match args.get("query").and_then(|v| v.as_str()) {
+        Some(q) => q,
+        None => return ToolResult::error("Missing required parameter: query"),
+    };
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
 
-    let mut test_chunks: Vec<&crate::store::sqlite::SearchResult> = Vec::new();
+    let busy = refresh_index(&root, &config);
 
-    for chunk in &all {
-        let name = chunk.chunk_name.as_deref().unwrap_or("");
-        let name_lower = name.to_lowercase();
-        let is_test_context = chunk.file_path.contains("test")
-            || chunk.file_path.contains("spec")
-            || chunk.file_path.ends_with("_test.go")
-            || chunk.file_path.ends_with("_test.rs")
-            || chunk.content.contains("#[test]")
-            || chunk.content.contains("#[cfg(test)]")
-            || chunk.content.contains("describe(")
-            || chunk.content.contains("it(")
-            || name_lower.starts_with("test_")
-            || name.ends_with("Test")
-            || name.ends_with("_test")
-            || name.ends_with("_spec");
-
-        // Check if this chunk follows a test module declaration in the same file
-        let in_test_module = all.iter().any(|other|
-            other.file_path == chunk.file_path
-            && other.chunk_kind == "module"
-            && other.chunk_name.as_deref().map_or(false, |n| n.contains("test"))
-            && other.start_line <= chunk.start_line
-        );
-        let is_test_context = is_test_context || in_test_module;
-
-        // Match by naming convention
-        let name_match = name_lower == format!("test_{sym_lower}")
-            || name_lower == format!("{sym_lower}_test")
-            || name_lower == format!("{sym_lower}_spec")
-            || name_lower == format!("test{sym_lower}")
-            || name_lower.starts_with(&format!("test_{sym_lower}_"))
-            || name_lower.ends_with(&format!("_{sym_lower}_test"))
-            || name_lower.contains(&format!("test_{sym_lower}"));
-
-        // Match by content (test function that references the symbol)
-        let content_match = !name_match
-            && is_test_context
-            && (chunk.chunk_kind == "function" || chunk.chunk_kind == "method")
-            && chunk.content.contains(symbol);
-
-        if name_match || content_match {
-            test_chunks.push(chunk);
+    let storage_dir = config.storage_dir(&root);
+    let store = match Store::open_if_exists(&storage_dir) {
+        Ok(Some(s)) => s,
+        Ok(None) => {
+            let path = root.canonicalize().unwrap_or_else(|_| root.clone());
+            return ToolResult::success(crate::search::text::format_index_first_message(
+                &path,
+                crate::search::text::IndexFirstKind::NoIndex,
+            ));
         }
+        Err(e) => return ToolResult::error(format!("Failed to open store: {e}")),
+    };
+
+    let mut symbol_query = crate::search::fuzzy::SymbolQuery::new(query);
+    symbol_query.kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
+    symbol_query.language = args.get("language").and_then(|v| v.as_str()).map(String::from);
+    symbol_query.path_prefix = args.get("path_prefix").and_then(|v| v.as_str()).map(String::from);
+    if let Some(n) = args.get("max_results").and_then(|v| v.as_u64()) {
+        symbol_query.max_results = n as usize;
+    }
+
+    let matches = match crate::search::fuzzy::find_symbols(&store, &symbol_query) {
+        Ok(m) => m,
+        Err(e) => return ToolResult::error(format!("Symbol lookup failed: {e}")),
+    };
+    if matches.is_empty() {
+        return with_busy_notice(ToolResult::success(format!("No symbols match '{query}'.")), busy);
+    }
+
+    let mut out = format!("{} symbol(s) matching '{query}'\n", matches.len());
+    for m in &matches {
+        let s = &m.symbol;
+        out.push_str(&format!(
+            "{}:{}-{} [{}] {} ({})\n",
+            s.file_path, s.start_line, s.end_line, s.kind, s.name, m.matched.as_str(),
+        ));
+    }
+    with_busy_notice(ToolResult::success(out), busy)
+}
+
+fn tool_similar(args: &Value, project_root: &Path) -> ToolResult {
+    let target = match (
+        args.get("target").and_then(|v| v.as_str()),
+        args.get("chunk_id").and_then(|v| v.as_i64()),
+    ) {
+        (_, Some(id)) => Target::Chunk(id),
+        (Some(t), None) => Target::parse(t),
+        (None, None) => return ToolResult::error("Missing required parameter: target or chunk_id"),
+    };
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
+    let (exclude_paths, tests) = match parse_exclusions(args) {
+        Ok(e) => e,
+        Err(e) => return ToolResult::error(e),
+    };
+
+    let mut query = SimilarQuery::new(target);
+    query.language = args.get("language").and_then(|v| v.as_str()).map(String::from);
+    query.path_prefix = args.get("path_prefix").and_then(|v| v.as_str()).map(String::from);
+    query.kind = args.get("kind").and_then(|v| v.as_str()).map(String::from);
+    query.exclude_paths = exclude_paths;
+    query.tests = tests;
+    if let Some(n) = args.get("max_results").and_then(|v| v.as_u64()) {
+        query.max_results = n as usize;
     }
 
-    if test_chunks.is_empty() {
-        return ToolResult::success(format!("No tests found for '{symbol}'."));
+    let outcome = match similar::search(&root, &config, &query) {
+        Ok(o) => o,
+        Err(e) => return ToolResult::error(format!("Similar search failed: {e}")),
+    };
+    let busy = outcome.index_busy;
+    let source = &outcome.source;
+    let mut out = format!(
+        "Similar to {}:{}-{}{} by {}:\n",
+        source.file_path,
+        source.start_line,
+        source.end_line,
+        source.chunk_name.as_deref().map(|n| format!(" ({n})")).unwrap_or_default(),
+        outcome.method.name(),
+    );
+    if outcome.results.is_empty() {
+        out.push_str("No similar code found.");
+        return with_busy_notice(ToolResult::success(out), busy);
+    }
+
+    let (output_mode, offset, head_limit, max_lines) = parse_format_opts(args, "content");
+    let anns = load_annotations(&root, &config);
+    let opts = FormatOpts { output_mode: &output_mode, offset, head_limit, max_lines, annotations: &anns };
+    out.push_str(&format_results(&outcome.results, &opts));
+    with_busy_notice(ToolResult::success(out), busy)
+}
+
+fn tool_duplicates(args: &Value, project_root: &Path) -> ToolResult {
+    let (exclude_paths, tests) = match parse_exclusions(args) {
+        Ok(e) => e,
+        Err(e) => return ToolResult::error(e),
+    };
+    let mut query = DuplicatesQuery {
+        language: args.get("language").and_then(|v| v.as_str()).map(String::from),
+        path_prefix: args.get("path_prefix").and_then(|v| v.as_str()).map(String::from),
+        kind: args.get("kind").and_then(|v| v.as_str()).map(String::from),
+        exclude_paths,
+        tests,
+        ..DuplicatesQuery::default()
+    };
+    if let Some(n) = args.get("min_lines").and_then(|v| v.as_u64()) {
+        query.min_lines = n as usize;
+    }
+    if let Some(t) = args.get("threshold").and_then(|v| v.as_f64()) {
+        query.threshold = t.clamp(0.0, 1.0);
+    }
+    if let Some(n) = args.get("max_families").and_then(|v| v.as_u64()) {
+        query.max_families = n as usize;
     }
 
-    // Convert to owned for format_results
-    let owned: Vec<crate::store::sqlite::SearchResult> = test_chunks.iter().map(|r| {
-        crate::store::sqlite::SearchResult {
-            file_path: r.file_path.clone(),
-            language: r.language.clone(),
-            chunk_kind: r.chunk_kind.clone(),
-            chunk_name: r.chunk_name.clone(),
-            signature: r.signature.clone(),
-            start_line: r.start_line,
-            end_line: r.end_line,
-            content: r.content.clone(),
-            rank: 0.0,
+    let projects: Vec<(Option<String>, PathBuf)> = if args.get("all_projects").and_then(|v| v.as_bool()).unwrap_or(false) {
+        match ProjectRegistry::load() {
+            Ok(reg) => reg.workspace(project_root, None).into_iter().map(|(name, entry)| (Some(name), entry.path)).collect(),
+            Err(e) => return ToolResult::error(format!("Failed to load project registry: {e}")),
         }
-    }).collect();
+    } else {
+        match resolve_project(args, project_root) {
+            Ok(root) => vec![(None, root)],
+            Err(e) => return ToolResult::error(e),
+        }
+    };
 
-    let annotations = load_annotations(&root, &config);
-    let opts = FormatOpts {
-        output_mode: &output_mode,
-        offset,
-        head_limit,
-        max_lines,
-        annotations: &annotations,
+    match duplicates::find(&projects, &query) {
+        Ok(outcome) => {
+            let mut out = duplicates::format_outcome(&outcome);
+            if !outcome.busy.is_empty() {
+                out.push_str(&format!("\n{} ({})", index::INDEX_BUSY_NOTICE, outcome.busy.join(", ")));
+            }
+            ToolResult::success(out)
+        }
+        Err(e) => ToolResult::error(format!("Duplicate detection failed: {e}")),
+    }
+}
+
+fn tool_history_search(args: &Value, project_root: &Path) -> ToolResult {
+    let root = match resolve_project(args, project_root) {
+        Ok(r) => r,
+        Err(e) => return ToolResult::error(e),
+    };
+    let config = Config::load(&root).unwrap_or_default();
+    let mut query = HistoryQuery {
+        text: args.get("query").and_then(|v| v.as_str()).map(String::from),
+        symbol: args.get("symbol").and_then(|v| v.as_str()).map(String::from),
+        path_prefix: args.get("path_prefix").and_then(|v| v.as_str()).map(String::from),
+        ..HistoryQuery::default()
     };
-    ToolResult::success(format_results(&owned, &opts))
+    if let Some(n) = args.get("max_results").and_then(|v| v.as_u64()) {
+        query.max_results = n as usize;
+    }
+
+    match history::search(&root, &config, &query) {
+        Ok(outcome) => ToolResult::success(history::format_outcome(&outcome, query.symbol.as_deref())),
+        Err(e) => ToolResult::error(format!("History search failed: {e}")),
+    }
 }
 
 fn tool_projects() -> ToolResult {
@@ -2148,7 +2826,12 @@ fn tool_projects() -> ToolResult {
                 .projects
                 .iter()
                 .map(|(name, entry)| {
-                    json!({ "name": name, "path": entry.path.to_string_lossy() })
+                    json!({
+                        "name": name,
+                        "path": entry.path.to_string_lossy(),
+                        "weight": entry.weight,
+                        "groups": entry.groups,
+                    })
                 })
                 .collect();
             match serde_json::to_string_pretty(&list) {
@@ -2163,16 +2846,16 @@ fn tool_projects() -> ToolResult {
 /// Resolve the project root from tool arguments.
 /// Priority: 'project' (registry lookup) > 'path' (literal) > default root.
 /// Errors if a project name is given but not found in the registry.
-fn resolve_project(args: &Value, default_root: &PathBuf) -> Result<PathBuf, String> {
+fn resolve_project(args: &Value, default_root: &Path) -> Result<PathBuf, String> {
     if let Some(project_name) = args.get("project").and_then(|v| v.as_str()) {
-        if let Ok(reg) = ProjectRegistry::load() {
-            if let Some(path) = reg.resolve(project_name) {
-                return Ok(path);
-            }
+        if let Ok(reg) = ProjectRegistry::load()
+            && let Some(path) = reg.resolve(project_name)
+        {
+            return Ok(path);
         }
         return Err(format!("Unknown project: '{project_name}'. Use 'projects' tool to list registered projects."));
     }
-    Ok(default_root.clone())
+    Ok(default_root.to_path_buf())
 }
 
 #[cfg(test)]
@@ -2201,12 +2884,33 @@ mod tests {
         (dir, root)
     }
 
+    /// Run git in `root` with a throwaway identity, asserting it succeeds.
+    fn git(root: &Path, args: &[&str]) {
+        let ok = std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "init.defaultBranch=main"])
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap()
+            .status
+            .success();
+        assert!(ok, "git {args:?}");
+    }
+
+    /// Turn `root` into a git repo and commit everything but `.booger/`.
+    fn init_git_repo(root: &Path, message: &str) {
+        std::fs::write(root.join(".gitignore"), ".booger/\n").unwrap();
+        git(root, &["init", "-q"]);
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-qm", message]);
+    }
+
     // ── Tool list ──
 
     #[test]
     fn list_tools_returns_all() {
         let tools = list_tools();
-        assert!(tools.len() >= 24);
+        assert!(tools.len() >= 26);
         let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
         assert!(names.contains(&"search"));
         assert!(names.contains(&"search-expand"));
@@ -2215,6 +2919,14 @@ mod tests {
         assert!(names.contains(&"tests-for"));
         assert!(names.contains(&"directory-summary"));
         assert!(names.contains(&"changed-since"));
+        assert!(names.contains(&"check"));
+        assert!(names.contains(&"find-symbol"));
+        assert!(names.contains(&"similar"));
+        assert!(names.contains(&"duplicates"));
+        assert!(names.contains(&"history-search"));
+        assert!(names.contains(&"annotation-search"));
+        assert!(names.contains(&"intent"));
+        assert!(names.contains(&"intents"));
     }
 
     // ── call_tool dispatch ──
@@ -2253,6 +2965,231 @@ mod tests {
         assert_eq!(result.content[0].text.trim(), "No matches.");
     }
 
+    #[test]
+    fn search_while_index_locked_serves_stale_results() {
+        let (_dir, root) = setup_test_project();
+        let storage_dir = Config::default().storage_dir(&root.canonicalize().unwrap());
+        let _lock = crate::index::lock::IndexLock::try_acquire(&storage_dir).unwrap().unwrap();
+        std::fs::write(root.join("src/new.rs"), "fn freshly_added() {}\n").unwrap();
+
+        let result = call_tool("search", &json!({"query": "helper"}), &root);
+        let text = &result.content[0].text;
+        assert!(text.contains("result"), "{text}");
+        assert!(text.contains("Index busy"), "{text}");
+
+        let result = call_tool("search", &json!({"query": "freshly_added"}), &root);
+        let text = &result.content[0].text;
+        assert!(text.starts_with("No matches."), "{text}");
+        assert!(text.contains("Index busy"), "{text}");
+    }
+
+    #[test]
+    fn search_applies_inline_qualifiers() {
+        let (_dir, root) = setup_test_project();
+        let result = call_tool("search", &json!({
+            "query": "helper kind:function -name:test_*",
+            "output_mode": "files_with_matches"
+        }), &root);
+        let text = &result.content[0].text;
+        assert!(text.contains("src/main.rs:5:7 [function]"), "{text}");
+        assert!(!text.contains("[module]"), "{text}");
+        assert!(text.starts_with("1 result(s)"), "{text}");
+    }
+
+    #[test]
+    fn find_symbol_tolerates_typos() {
+        let (_dir, root) = setup_test_project();
+        let result = call_tool("find-symbol", &json!({"query": "hlper"}), &root);
+        let text = &result.content[0].text;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "2 symbol(s) matching 'hlper'");
+        assert_eq!(lines[1], "src/main.rs:5-7 [function] helper (subsequence)");
+        assert!(lines[2].ends_with("test_helper (subsequence)"), "{text}");
+
+        let result = call_tool("find-symbol", &json!({"query": "Confgi", "kind": "function"}), &root);
+        assert_eq!(result.content[0].text, "No symbols match 'Confgi'.");
+    }
+
+    #[test]
+    fn search_explain_breaks_down_ranks() {
+        let (_dir, root) = setup_test_project();
+        let result = call_tool("search", &json!({"query": "helper", "explain": true}), &root);
+        let text = &result.content[0].text;
+        let explain = text.split("── explain").nth(1).expect(text);
+        assert!(explain.contains("] src/main.rs:5 rank "), "{text}");
+        assert!(explain.contains("bm25 -"), "{text}");
+        assert!(explain.contains("code +3.00"), "{text}");
+        assert!(!explain.contains("OR fallback"), "{text}");
+
+        let result = call_tool("search", &json!({"query": "helper xyznope", "explain": true}), &root);
+        assert!(result.content[0].text.contains("OR fallback: no chunk matched all terms"));
+
+        let result = call_tool("search", &json!({"query": "helper"}), &root);
+        assert!(!result.content[0].text.contains("── explain"));
+    }
+
+    #[test]
+    fn search_boosts_what_changed_on_the_branch() {
+        let (_dir, root) = setup_test_project();
+        init_git_repo(&root, "base");
+        git(&root, &["checkout", "-qb", "feature"]);
+        std::fs::write(
+            root.join("src/lib.rs"),
+            "pub struct Config {\n    pub name: String,\n}\n\npub fn search(q: &str) -> Vec<String> {\n    vec![q.to_string(), q.to_uppercase()]\n}\n",
+        ).unwrap();
+
+        let result = call_tool("search", &json!({"query": "fn", "explain": true}), &root);
+        let text = &result.content[0].text;
+        let explain = text.split("── explain").nth(1).expect(text);
+        let lib = explain.lines().find(|l| l.contains("src/lib.rs:5 ")).expect(text);
+        assert!(lib.contains("diff +3.00"), "{text}");
+        let main = explain.lines().find(|l| l.contains("src/main.rs:5 ")).expect(text);
+        assert!(!main.contains("diff"), "{text}");
+
+        let result = call_tool("search", &json!({"query": "fn", "explain": true, "diff_boost": false}), &root);
+        assert!(!result.content[0].text.contains("diff +"));
+
+        // The changes are stored for later processes, until files change.
+        let store = Store::open(&Config::default().storage_dir(&root)).unwrap();
+        let cached = store.branch_changes("").unwrap().expect("stored branch changes");
+        assert!(cached.changes.contains("src/lib.rs") && !cached.changes.contains("src/main.rs"), "{cached:?}");
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n\nfn helper() -> i32 {\n    43\n}\n").unwrap();
+        let result = call_tool("search", &json!({"query": "fn", "explain": true}), &root);
+        let text = &result.content[0].text;
+        let explain = text.split("── explain").nth(1).expect(text);
+        let main = explain.lines().find(|l| l.contains("src/main.rs:3 ")).expect(text);
+        assert!(main.contains("diff +3.00"), "{text}");
+    }
+
+    #[test]
+    fn directory_summary_lists_hotspots_from_git_history() {
+        let (_dir, root) = setup_test_project();
+        init_git_repo(&root, "base");
+        for n in 1..=2 {
+            std::fs::write(root.join("src/lib.rs"), format!("pub fn search() -> u32 {{\n    {n}\n}}\n")).unwrap();
+            git(&root, &["commit", "-qam", "tweak"]);
+        }
+
+        let result = call_tool("directory-summary", &json!({"path": "src"}), &root);
+        let text = &result.content[0].text;
+        let hotspots = text.split("Hotspots (recent commits):\n").nth(1).expect(text);
+        let lines: Vec<&str> = hotspots.lines().take(2).collect();
+        assert!(lines[0].starts_with("  src/lib.rs: 3 commits, last "), "{text}");
+        assert!(lines[1].starts_with("  src/main.rs: 1 commits, last "), "{text}");
+    }
+
+    #[test]
+    fn annotations_are_searchable_and_merge_into_search_as_notes() {
+        let (_dir, root) = setup_test_project();
+        call_tool("annotate", &json!({"target": "helper", "note": "Returns a constant until the config lands"}), &root);
+        call_tool("annotate", &json!({"target": "src/lib.rs:5", "note": "search should trim its query"}), &root);
+
+        let result = call_tool("annotation-search", &json!({"query": "constant"}), &root);
+        let found: Value = serde_json::from_str(&result.content[0].text).unwrap();
+        assert_eq!(found.as_array().unwrap().len(), 1);
+        assert_eq!(found[0]["target"], "helper");
+
+        let result = call_tool("search", &json!({"query": "config", "notes": true}), &root);
+        let text = &result.content[0].text;
+        let note = text.find("── [0] src/main.rs:5-7 [note (helper)] ──\n  Returns a constant").expect(text);
+        let code = text.find("src/lib.rs:1-3 [struct (Config)]").expect(text);
+        assert!(note < code, "{text}");
+
+        // Notes also answer queries no code matches, pointing at the line.
+        let result = call_tool("search", &json!({"query": "trim", "notes": true}), &root);
+        assert!(result.content[0].text.contains("src/lib.rs:5-5 [note (src/lib.rs:5)]"), "{}", result.content[0].text);
+
+        let result = call_tool("search", &json!({"query": "config"}), &root);
+        assert!(!result.content[0].text.contains("[note ("));
+
+        // Notes are filtered like code, and unresolved targets are no files.
+        call_tool("annotate", &json!({"target": "LegacyLoader", "note": "config used to load here"}), &root);
+        let result = call_tool("search", &json!({"query": "config", "notes": true, "path_prefix": "src/lib.rs"}), &root);
+        let text = &result.content[0].text;
+        assert!(text.contains("src/lib.rs:1-3 [struct (Config)]"), "{text}");
+        assert!(!text.contains("[note ("), "{text}");
+        let result = call_tool("search", &json!({"query": "config", "notes": true, "output_mode": "files_with_matches"}), &root);
+        let text = &result.content[0].text;
+        assert!(text.contains("src/main.rs") && !text.contains("LegacyLoader"), "{text}");
+    }
+
+    #[test]
+    fn history_search_finds_commits_by_message_and_by_symbol() {
+        let (_dir, root) = setup_test_project();
+        init_git_repo(&root, "Add search skeleton");
+        std::fs::write(
+            root.join("src/lib.rs"),
+            "pub struct Config {\n    pub name: String,\n}\n\npub fn search(q: &str) -> Vec<String> {\n    vec![normalize(q)]\n}\n\nfn normalize(q: &str) -> String {\n    q.trim().to_lowercase()\n}\n",
+        ).unwrap();
+        git(&root, &["commit", "-qam", "Normalize queries before searching\n\nUsers paste queries with stray whitespace."]);
+
+        let result = call_tool("history-search", &json!({"query": "whitespace"}), &root);
+        assert!(result.content[0].text.contains("No commit history indexed"));
+
+        let result = call_tool("index", &json!({"history": true}), &root);
+        let summary: Value = serde_json::from_str(&result.content[0].text).unwrap();
+        assert_eq!(summary["commits_indexed"], 2);
+        assert_eq!(summary["commits_failed"], 0);
+        let result = call_tool("index", &json!({"history": true}), &root);
+        let summary: Value = serde_json::from_str(&result.content[0].text).unwrap();
+        assert_eq!(summary["commits_indexed"], 0);
+
+        let result = call_tool("history-search", &json!({"query": "whitespace"}), &root);
+        let text = &result.content[0].text;
+        assert!(text.starts_with("1 commit(s) of 2 indexed"), "{text}");
+        assert!(text.contains("Normalize queries before searching"), "{text}");
+        assert!(text.contains("  modified function search (src/lib.rs)"), "{text}");
+        assert!(text.contains("  added function normalize (src/lib.rs)"), "{text}");
+
+        let result = call_tool("history-search", &json!({"symbol": "search"}), &root);
+        let text = &result.content[0].text;
+        let normalize = text.find("Normalize queries").expect(text);
+        let skeleton = text.find("Add search skeleton").expect(text);
+        assert!(normalize < skeleton, "{text}");
+
+        let result = call_tool("history-search", &json!({"symbol": "helper", "path_prefix": "src/lib.rs"}), &root);
+        assert!(result.content[0].text.starts_with("No matching commits"));
+    }
+
+    #[test]
+    fn feedback_is_recorded_explicitly_and_from_follow_ups() {
+        let (_dir, root) = setup_test_project();
+        let config = Config::default();
+        call_tool("search", &json!({"query": "search"}), &root);
+        call_tool("symbols", &json!({"path_prefix": "src/lib.rs"}), &root);
+        call_tool("symbols", &json!({"path_prefix": "src/lib.rs"}), &root);
+        let result = call_tool("feedback", &json!({
+            "query": "search",
+            "path": "src/main.rs",
+            "symbol": "helper",
+            "useful": false
+        }), &root);
+        assert_eq!(result.content[0].text, "Feedback #2: src/main.rs (helper) was not useful for 'search'");
+
+        let entries = context::feedback::list(&root, &config, 10).unwrap();
+        assert_eq!(entries.len(), 2, "follow-ups credit a result once");
+        assert_eq!((entries[1].path.as_str(), entries[1].symbol.as_deref(), entries[1].useful), ("src/lib.rs", None, true));
+
+        let result = call_tool("search", &json!({"query": "search", "explain": true}), &root);
+        let text = &result.content[0].text;
+        let explain = text.split("── explain").nth(1).expect(text);
+        let lib = explain.lines().find(|l| l.contains("src/lib.rs:5 ")).expect(text);
+        assert!(lib.contains("feedback +1.50"), "{text}");
+    }
+
+    #[test]
+    fn search_snippets_show_only_matching_lines() {
+        let (_dir, root) = setup_test_project();
+        let result = call_tool("search", &json!({
+            "query": "println",
+            "output_mode": "snippets",
+            "context_lines": 0
+        }), &root);
+        let text = &result.content[0].text;
+        assert!(text.contains("── [0] src/main.rs:1-3 [function (main)] ──\n  fn main()\n   ...\n>    2|    println!(\"hello\");\n   ...\n"), "{text}");
+        assert!(!text.contains("fn main() {"), "{text}");
+    }
+
     #[test]
     fn search_empty_path_prefix_has_no_indexed_files() {
         let (_dir, root) = setup_test_project();
@@ -2467,6 +3404,79 @@ mod tests {
         assert_eq!(result.is_error, Some(true));
     }
 
+    // ── similar ──
+
+    #[test]
+    fn similar_names_the_source_and_leaves_it_out() {
+        let (_dir, root) = setup_test_project();
+        let result = call_tool("similar", &json!({"target": "src/main.rs:2"}), &root);
+        assert!(result.is_error.is_none());
+        let text = &result.content[0].text;
+        let (header, body) = text.split_once('\n').unwrap();
+        assert_eq!(header, "Similar to src/main.rs:1-3 (main) by structural fingerprint:");
+        assert!(body.contains("test_helper"), "{text}");
+        assert!(!body.contains("fn main"), "{text}");
+
+        let result = call_tool("similar", &json!({"target": "no_such_symbol"}), &root);
+        assert_eq!(result.is_error, Some(true));
+        let result = call_tool("similar", &json!({}), &root);
+        assert_eq!(result.is_error, Some(true));
+    }
+
+    // ── intents ──
+
+    #[test]
+    fn intent_biases_session_search_and_is_listed() {
+        let (_dir, root) = setup_test_project();
+        std::fs::write(root.join("src/retry.rs"), "fn retry_upload() {\n    send();\n}\n").unwrap();
+        std::fs::write(root.join("src/send.rs"), "fn send() {\n    write();\n}\n").unwrap();
+        let search = |session: &str| {
+            let result = call_tool(
+                "search",
+                &json!({"query": "send", "session_id": session, "output_mode": "files_with_matches"}),
+                &root,
+            );
+            result.content[0].text.lines().nth(1).unwrap_or("").to_string()
+        };
+        assert!(search("s1").starts_with("src/send.rs"));
+
+        let result = call_tool("intent", &json!({"goal": "make uploads retry", "session_id": "s1"}), &root);
+        assert!(result.is_error.is_none());
+        assert!(result.content[0].text.contains("terms: uploads, retry"), "{}", result.content[0].text);
+        assert!(search("s1").starts_with("src/retry.rs"));
+        assert!(search("s2").starts_with("src/send.rs"));
+
+        call_tool("focus", &json!({"paths": ["src/retry.rs"], "session_id": "s1"}), &root);
+        let listed: Value = serde_json::from_str(&call_tool("intents", &json!({"session_id": "s1"}), &root).content[0].text).unwrap();
+        assert_eq!(listed["intents"][0]["goal"], "make uploads retry");
+        assert_eq!(listed["focus"], json!(["src/retry.rs"]));
+
+        let result = call_tool("forget", &json!({"session_id": "s1"}), &root);
+        assert!(result.content[0].text.ends_with("1 intents"));
+        assert!(search("s1").starts_with("src/send.rs"));
+    }
+
+    // ── duplicates ──
+
+    #[test]
+    fn duplicates_reports_clone_families() {
+        let (_dir, root) = setup_test_project();
+        let body = "fn load(path: &str) -> String {\n    let text = read(path);\n    let trimmed = text.trim();\n    trimmed.to_string()\n}\n";
+        std::fs::write(root.join("src/a.rs"), body).unwrap();
+        std::fs::write(root.join("src/b.rs"), body.replace("load", "open")).unwrap();
+
+        let result = call_tool("duplicates", &json!({"min_lines": 4}), &root);
+        assert!(result.is_error.is_none());
+        let text = &result.content[0].text;
+        assert!(text.starts_with("1 clone family"), "{text}");
+        assert!(text.contains("2 copies, similarity"), "{text}");
+        assert!(text.contains("src/a.rs:1-5 [function] load"), "{text}");
+        assert!(text.contains("src/b.rs:1-5 [function] open"), "{text}");
+
+        let result = call_tool("duplicates", &json!({"min_lines": 10}), &root);
+        assert!(result.content[0].text.starts_with("No duplicates"));
+    }
+
     // ── resolve_project ──
 
     #[test]
@@ -2547,6 +3557,23 @@ mod tests {
         assert!(text.contains("match") || text.contains("println"));
     }
 
+    #[test]
+    fn grep_count_is_exact_past_max_results() {
+        let (_dir, root) = setup_test_project();
+        let result = call_tool("grep", &json!({
+            "pattern": "fn ",
+            "max_results": 1,
+            "output_mode": "count"
+        }), &root);
+        let text = &result.content[0].text;
+        assert!(!text.contains("at least"), "{text}");
+        let n: usize = text.split_whitespace().next().unwrap().parse().unwrap();
+        assert!(n > 1, "{text}");
+
+        let content = call_tool("grep", &json!({"pattern": "fn ", "max_results": 1}), &root);
+        assert!(content.content[0].text.contains(&format!("{n} match(es), showing first 1")));
+    }
+
     #[test]
     fn grep_invalid_regex() {
         let (_dir, root) = setup_test_project();
@@ -2554,6 +3581,34 @@ mod tests {
         assert_eq!(result.is_error, Some(true));
     }
 
+    #[test]
+    fn exclusions_and_test_scope_apply_to_grep_references_and_search() {
+        let (_dir, root) = setup_test_project();
+        let grep = call_tool("grep", &json!({"pattern": "helper", "tests": "exclude"}), &root);
+        let text = &grep.content[0].text;
+        assert!(text.starts_with("1 match(es)"), "{text}");
+        assert!(text.contains("src/main.rs:     5|fn helper()"), "{text}");
+
+        let refs = call_tool("references", &json!({
+            "symbol": "helper",
+            "tests": "only",
+            "output_mode": "count"
+        }), &root);
+        assert_eq!(refs.content[0].text, "0 definition(s), 1 reference(s)");
+
+        let search = call_tool("search", &json!({
+            "query": "fn",
+            "exclude_paths": ["src/main*"],
+            "output_mode": "files_with_matches"
+        }), &root);
+        let text = &search.content[0].text;
+        assert!(text.contains("src/lib.rs"), "{text}");
+        assert!(!text.contains("src/main.rs"), "{text}");
+
+        let bad = call_tool("search", &json!({"query": "fn", "tests": "maybe"}), &root);
+        assert_eq!(bad.is_error, Some(true));
+    }
+
     // ── references ──
 
     #[test]
@@ -2632,6 +3687,31 @@ mod tests {
         assert!(text.contains("chunks"));
     }
 
+    // ── check ──
+
+    #[test]
+    fn check_on_indexed_project() {
+        let (_dir, root) = setup_test_project();
+        let result = call_tool("check", &json!({}), &root);
+        assert!(result.is_error.is_none());
+        assert!(result.content[0].text.starts_with("Index OK"), "{}", result.content[0].text);
+    }
+
+    #[test]
+    fn check_reports_and_repairs_stale_file() {
+        let (_dir, root) = setup_test_project();
+        std::fs::write(root.join("src/lib.rs"), "pub fn changed() {}\n").unwrap();
+        let result = call_tool("check", &json!({}), &root);
+        let text = &result.content[0].text;
+        assert!(text.contains("changed on disk"), "{text}");
+        assert!(text.contains("repair=true"), "{text}");
+
+        let result = call_tool("check", &json!({"repair": true}), &root);
+        let text = &result.content[0].text;
+        assert!(text.contains("re-indexed 1 changed file"), "{text}");
+        assert!(text.contains("Index OK"), "{text}");
+    }
+
     // ── format opts ──
 
     #[test]
@@ -0,0 +1,661 @@
+use anyhow::{Context, Result, bail};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+use crate::config::AnnConfig;
+use crate::index::lock::IndexLock;
+use crate::store::sqlite::Store;
+
+/// File name of the ANN index, next to `index.db`.
+pub const FILE_NAME: &str = "vectors.hnsw";
+
+const MAGIC: &[u8; 8] = b"BHNSW\0\0\x02";
+
+/// Rebuild from scratch instead of patching once this share of the nodes
+/// belongs to deleted embeddings.
+const MAX_DELETED_RATIO: f64 = 0.25;
+
+/// Highest layer a node can be assigned to.
+const MAX_LEVEL: usize = 16;
+
+/// A node and its distance to the query (1 − cosine similarity).
+#[derive(Clone, Copy, PartialEq)]
+struct Near {
+    dist: f32,
+    node: u32,
+}
+
+impl Eq for Near {}
+
+impl Ord for Near {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist.total_cmp(&other.dist).then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Near {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Hierarchical navigable small world graph over chunk embeddings
+/// (Malkov & Yashunin, 2016). Vectors are stored normalized, so cosine
+/// similarity is a dot product. Removing an embedding leaves its node as a
+/// tombstone: still walked, never returned.
+pub struct Hnsw {
+    dims: usize,
+    m: usize,
+    ef_construction: usize,
+    ids: Vec<i64>,
+    /// Node `i`'s vector is `vectors[i * dims..(i + 1) * dims]`.
+    vectors: Vec<f32>,
+    /// Node → layer → neighbor nodes.
+    links: Vec<Vec<Vec<u32>>>,
+    deleted: Vec<bool>,
+    entry: Option<u32>,
+    /// Chunk id → live node.
+    slots: HashMap<i64, u32>,
+    /// `Store::embeddings_generation` the index reflects; -1 until synced.
+    generation: i64,
+}
+
+impl Hnsw {
+    pub fn new(dims: usize, config: &AnnConfig) -> Self {
+        Self {
+            dims,
+            m: config.m.max(2),
+            ef_construction: config.ef_construction.max(1),
+            ids: Vec::new(),
+            vectors: Vec::new(),
+            links: Vec::new(),
+            deleted: Vec::new(),
+            entry: None,
+            slots: HashMap::new(),
+            generation: -1,
+        }
+    }
+
+    /// Number of live (not deleted) embeddings.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn contains(&self, id: i64) -> bool {
+        self.slots.contains_key(&id)
+    }
+
+    /// Share of nodes that are tombstones.
+    fn deleted_ratio(&self) -> f64 {
+        if self.ids.is_empty() {
+            0.0
+        } else {
+            (self.ids.len() - self.slots.len()) as f64 / self.ids.len() as f64
+        }
+    }
+
+    /// Add an embedding. Replaces the vector of an id already present.
+    pub fn insert(&mut self, id: i64, vector: &[f32]) {
+        if vector.len() != self.dims {
+            return;
+        }
+        self.remove(id);
+        let node = self.ids.len() as u32;
+        let q = normalize(vector);
+        self.ids.push(id);
+        self.vectors.extend_from_slice(&q);
+        self.deleted.push(false);
+        let level = random_level(id, self.m);
+        self.links.push(vec![Vec::new(); level + 1]);
+        self.slots.insert(id, node);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return;
+        };
+        let top = self.links[entry as usize].len() - 1;
+        let mut ep = entry;
+        for layer in (level + 1..=top).rev() {
+            ep = self.greedy(&q, ep, layer);
+        }
+        let mut eps = vec![ep];
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&q, &eps, self.ef_construction, layer);
+            let neighbors: Vec<u32> = found.iter().take(self.m).map(|n| n.node).collect();
+            let max = self.max_links(layer);
+            for &nb in &neighbors {
+                self.links[nb as usize][layer].push(node);
+                if self.links[nb as usize][layer].len() > max {
+                    self.prune(nb, layer, max);
+                }
+            }
+            self.links[node as usize][layer] = neighbors;
+            eps = found.iter().map(|n| n.node).collect();
+        }
+        if level > top {
+            self.entry = Some(node);
+        }
+    }
+
+    /// Tombstone an embedding, if present.
+    pub fn remove(&mut self, id: i64) {
+        if let Some(node) = self.slots.remove(&id) {
+            self.deleted[node as usize] = true;
+        }
+    }
+
+    /// The `k` nearest live embeddings to `query` as `(chunk id, cosine
+    /// similarity)`, most similar first. `ef` trades speed for recall.
+    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(i64, f32)> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        if query.len() != self.dims {
+            return Vec::new();
+        }
+        let q = normalize(query);
+        let mut ep = entry;
+        for layer in (1..self.links[entry as usize].len()).rev() {
+            ep = self.greedy(&q, ep, layer);
+        }
+        // Tombstones take up room in the candidate list; widen it for them.
+        let ef = ef.max(k) + (ef.max(k) as f64 * self.deleted_ratio()).ceil() as usize;
+        self.search_layer(&q, &[ep], ef, 0)
+            .into_iter()
+            .filter(|n| !self.deleted[n.node as usize])
+            .take(k)
+            .map(|n| (self.ids[n.node as usize], 1.0 - n.dist))
+            .collect()
+    }
+
+    fn vector(&self, node: u32) -> &[f32] {
+        let start = node as usize * self.dims;
+        &self.vectors[start..start + self.dims]
+    }
+
+    fn dist(&self, q: &[f32], node: u32) -> f32 {
+        1.0 - dot(q, self.vector(node))
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 { self.m * 2 } else { self.m }
+    }
+
+    /// Walk layer `layer` from `ep` to the node closest to `q`.
+    fn greedy(&self, q: &[f32], mut ep: u32, layer: usize) -> u32 {
+        let mut best = self.dist(q, ep);
+        loop {
+            let mut moved = false;
+            for &nb in &self.links[ep as usize][layer] {
+                let d = self.dist(q, nb);
+                if d < best {
+                    best = d;
+                    ep = nb;
+                    moved = true;
+                }
+            }
+            if !moved {
+                return ep;
+            }
+        }
+    }
+
+    /// Best-first search of one layer, keeping the `ef` closest nodes.
+    /// Returned closest first.
+    fn search_layer(&self, q: &[f32], eps: &[u32], ef: usize, layer: usize) -> Vec<Near> {
+        let mut visited: HashSet<u32> = eps.iter().copied().collect();
+        let mut candidates: BinaryHeap<Reverse<Near>> = BinaryHeap::new();
+        let mut best: BinaryHeap<Near> = BinaryHeap::new();
+        for &node in eps {
+            let near = Near { dist: self.dist(q, node), node };
+            candidates.push(Reverse(near));
+            best.push(near);
+        }
+        while best.len() > ef {
+            best.pop();
+        }
+        while let Some(Reverse(current)) = candidates.pop() {
+            if best.len() >= ef && best.peek().is_some_and(|worst| current.dist > worst.dist) {
+                break;
+            }
+            for &nb in &self.links[current.node as usize][layer] {
+                if !visited.insert(nb) {
+                    continue;
+                }
+                let near = Near { dist: self.dist(q, nb), node: nb };
+                if best.len() < ef || best.peek().is_some_and(|worst| near.dist < worst.dist) {
+                    candidates.push(Reverse(near));
+                    best.push(near);
+                    if best.len() > ef {
+                        best.pop();
+                    }
+                }
+            }
+        }
+        best.into_sorted_vec()
+    }
+
+    /// Keep only the `max` closest neighbors of `node` on `layer`.
+    fn prune(&mut self, node: u32, layer: usize, max: usize) {
+        let v = self.vector(node).to_vec();
+        let mut near: Vec<Near> = self.links[node as usize][layer]
+            .iter()
+            .map(|&nb| Near { dist: self.dist(&v, nb), node: nb })
+            .collect();
+        near.sort();
+        self.links[node as usize][layer] = near.into_iter().take(max).map(|n| n.node).collect();
+    }
+
+    /// Write the index to `path`, replacing it atomically. The temporary
+    /// file is unique to this writer, so concurrent saves never interleave.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = temp_path(path);
+        let written = self.write_to(&tmp).and_then(|()| {
+            std::fs::rename(&tmp, path).with_context(|| format!("replacing {}", path.display()))
+        });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        written
+    }
+
+    fn write_to(&self, tmp: &Path) -> Result<()> {
+        {
+            let mut w = BufWriter::new(File::create(tmp).with_context(|| format!("creating {}", tmp.display()))?);
+            w.write_all(MAGIC)?;
+            for v in [self.dims, self.m, self.ef_construction, self.ids.len()] {
+                w.write_all(&(v as u32).to_le_bytes())?;
+            }
+            w.write_all(&self.entry.map_or(-1, i64::from).to_le_bytes())?;
+            w.write_all(&self.generation.to_le_bytes())?;
+            for node in 0..self.ids.len() {
+                w.write_all(&self.ids[node].to_le_bytes())?;
+                w.write_all(&[self.deleted[node] as u8, self.links[node].len() as u8])?;
+                for x in self.vector(node as u32) {
+                    w.write_all(&x.to_le_bytes())?;
+                }
+                for layer in &self.links[node] {
+                    w.write_all(&(layer.len() as u32).to_le_bytes())?;
+                    for nb in layer {
+                        w.write_all(&nb.to_le_bytes())?;
+                    }
+                }
+            }
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Read an index written by `save`. Sizes in the header are checked
+    /// against the file length and every link against the node count, so
+    /// a truncated or corrupt file is an error (and gets rebuilt) rather
+    /// than a panic during search.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let file_len = file.metadata()?.len();
+        let corrupt = || anyhow::anyhow!("{} is truncated or corrupt", path.display());
+        let mut r = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("{} is not a booger ANN index", path.display());
+        }
+        let dims = read_u32(&mut r)? as usize;
+        let m = read_u32(&mut r)? as usize;
+        let ef_construction = read_u32(&mut r)? as usize;
+        let count = read_u32(&mut r)? as usize;
+        let entry = read_i64(&mut r)?;
+        let generation = read_i64(&mut r)?;
+        // Each node takes at least its id, flags and vector.
+        let min_len = (count as u64).checked_mul(10 + dims as u64 * 4).ok_or_else(corrupt)?;
+        if dims == 0 || min_len > file_len {
+            return Err(corrupt());
+        }
+        let entry = match entry {
+            -1 if count == 0 => None,
+            e if (0..count as i64).contains(&e) => Some(e as u32),
+            _ => return Err(corrupt()),
+        };
+        let mut index = Self {
+            dims,
+            m,
+            ef_construction,
+            ids: Vec::with_capacity(count),
+            vectors: Vec::with_capacity(count * dims),
+            links: Vec::with_capacity(count),
+            deleted: Vec::with_capacity(count),
+            entry,
+            slots: HashMap::with_capacity(count),
+            generation,
+        };
+        let mut buf = vec![0u8; dims * 4];
+        for node in 0..count {
+            let id = read_i64(&mut r)?;
+            let mut flags = [0u8; 2];
+            r.read_exact(&mut flags)?;
+            r.read_exact(&mut buf)?;
+            index.vectors.extend(buf.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+            if flags[1] == 0 || flags[1] as usize > MAX_LEVEL + 1 {
+                return Err(corrupt());
+            }
+            let mut layers = Vec::with_capacity(flags[1] as usize);
+            for _ in 0..flags[1] {
+                let len = read_u32(&mut r)? as usize;
+                if len > count {
+                    return Err(corrupt());
+                }
+                let mut layer = Vec::with_capacity(len);
+                for _ in 0..len {
+                    layer.push(read_u32(&mut r)?);
+                }
+                layers.push(layer);
+            }
+            index.ids.push(id);
+            index.deleted.push(flags[0] != 0);
+            index.links.push(layers);
+            if flags[0] == 0 {
+                index.slots.insert(id, node as u32);
+            }
+        }
+        // A neighbour must exist and have the layer it is linked on.
+        for layers in &index.links {
+            for (layer, neighbors) in layers.iter().enumerate() {
+                if neighbors.iter().any(|&nb| index.links.get(nb as usize).is_none_or(|l| l.len() <= layer)) {
+                    return Err(corrupt());
+                }
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// A temporary name next to `path` that no other writer uses.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    use std::hash::{BuildHasher, Hasher};
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.{random:016x}.tmp", std::process::id()))
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_i64(r: &mut impl Read) -> Result<i64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(i64::from_le_bytes(b))
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalize(v: &[f32]) -> Vec<f32> {
+    let norm = dot(v, v).sqrt();
+    if norm == 0.0 { v.to_vec() } else { v.iter().map(|x| x / norm).collect() }
+}
+
+/// Layer for a new node: geometric with ratio 1/m, derived from the chunk
+/// id so the same embeddings always build the same graph.
+fn random_level(id: i64, m: usize) -> usize {
+    // splitmix64
+    let mut z = (id as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    let u = ((z >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    ((-u.ln() / (m as f64).ln()) as usize).min(MAX_LEVEL)
+}
+
+/// A loaded index and the modification stamp of the file it matches.
+struct Cached {
+    index: Hnsw,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+static CACHE: LazyLock<Mutex<HashMap<PathBuf, Cached>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Nearest embeddings to `query` from the ANN index in `storage_dir`, after
+/// bringing the index in line with the embeddings table (see `sync`).
+pub fn search(
+    store: &Store,
+    storage_dir: &Path,
+    config: &AnnConfig,
+    query: &[f32],
+    k: usize,
+) -> Result<Vec<(i64, f32)>> {
+    let path = storage_dir.join(FILE_NAME);
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let cached = cache.remove(&path);
+    let stamp = file_stamp(&path);
+    let mut index = match cached {
+        Some(c) if c.stamp.is_some() && c.stamp == stamp => c.index,
+        _ => Hnsw::load(&path).unwrap_or_else(|_| Hnsw::new(query.len(), config)),
+    };
+    // Only the writer-lock holder replaces the file; while someone else
+    // holds it, search the synced copy and leave the file to them.
+    let stamp = if sync(&mut index, store, config)? {
+        match IndexLock::try_acquire(storage_dir)? {
+            Some(_lock) => {
+                index.save(&path)?;
+                file_stamp(&path)
+            }
+            None => None,
+        }
+    } else {
+        stamp
+    };
+    let hits = index.search(query, k, config.ef_search);
+    cache.insert(path, Cached { index, stamp });
+    Ok(hits)
+}
+
+/// Build or update the ANN index in `storage_dir` without searching it.
+pub fn refresh(store: &Store, storage_dir: &Path, config: &AnnConfig) -> Result<()> {
+    let Some(dims) = store.embedding_dims()? else {
+        return Ok(());
+    };
+    search(store, storage_dir, config, &vec![0.0; dims], 0).map(|_| ())
+}
+
+/// Bring `index` in line with the embeddings table. Nothing to do while
+/// the store's embedding generation matches the index's. Otherwise
+/// tombstone embeddings that were deleted or rewritten since (a new model,
+/// or a chunk id reused by re-indexing), insert new and rewritten ones, and
+/// rebuild when the dimensions changed or too many nodes are tombstones.
+/// Returns whether the index changed.
+fn sync(index: &mut Hnsw, store: &Store, config: &AnnConfig) -> Result<bool> {
+    let generation = store.embeddings_generation()?;
+    let dims = store.embedding_dims()?.unwrap_or(index.dims);
+    if index.generation == generation && index.dims == dims {
+        return Ok(false);
+    }
+    // A newer index than the store means the database was replaced.
+    if index.dims != dims || index.generation > generation {
+        *index = Hnsw::new(dims, config);
+    }
+
+    let ids: HashSet<i64> = store.embedding_ids()?.into_iter().collect();
+    let rewritten: HashSet<i64> = store.embeddings_written_since(index.generation)?.into_iter().collect();
+    let stale: Vec<i64> = index
+        .slots
+        .keys()
+        .filter(|id| !ids.contains(id) || rewritten.contains(id))
+        .copied()
+        .collect();
+    for id in stale {
+        index.remove(id);
+    }
+    if index.deleted_ratio() > MAX_DELETED_RATIO {
+        *index = Hnsw::new(dims, config);
+    }
+
+    let mut missing: Vec<i64> = ids.iter().filter(|id| !index.contains(**id)).copied().collect();
+    missing.sort_unstable();
+    for batch in missing.chunks(500) {
+        for (id, vector) in store.embeddings_by_ids(batch)? {
+            index.insert(id, &vector);
+        }
+    }
+    index.generation = generation;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embed::cosine_similarity;
+    use crate::search::test_support::{chunk, vectors};
+
+    fn brute_force(data: &[Vec<f32>], query: &[f32], k: usize) -> Vec<i64> {
+        let mut scored: Vec<(i64, f32)> =
+            data.iter().enumerate().map(|(i, v)| (i as i64, cosine_similarity(query, v))).collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(k).map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn recall_matches_brute_force() {
+        let data = vectors(1000, 16, 7);
+        let mut index = Hnsw::new(16, &AnnConfig::default());
+        for (i, v) in data.iter().enumerate() {
+            index.insert(i as i64, v);
+        }
+        let mut found = 0;
+        for query in vectors(50, 16, 99) {
+            let exact = brute_force(&data, &query, 10);
+            let hits = index.search(&query, 10, 64);
+            found += hits.iter().filter(|(id, _)| exact.contains(id)).count();
+        }
+        let recall = found as f64 / 500.0;
+        assert!(recall >= 0.9, "recall {recall}");
+    }
+
+    #[test]
+    fn tombstones_survive_save_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let data = vectors(100, 8, 3);
+        let mut index = Hnsw::new(8, &AnnConfig::default());
+        for (i, v) in data.iter().enumerate() {
+            index.insert(i as i64, v);
+        }
+        index.remove(5);
+        index.save(&path).unwrap();
+
+        let loaded = Hnsw::load(&path).unwrap();
+        assert_eq!(loaded.len(), 99);
+        assert!(!loaded.contains(5));
+        assert_eq!(loaded.search(&data[7], 1, 16)[0].0, 7);
+        assert!(loaded.search(&data[5], 10, 16).iter().all(|(id, _)| *id != 5));
+    }
+
+    #[test]
+    fn search_follows_the_embeddings_table() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let data = vectors(3, 8, 11);
+        let a = store.upsert_file("a.rs", "h", 10, Some("rust")).unwrap();
+        let ids = store.insert_chunks(a, &[chunk(1), chunk(2)]).unwrap();
+        store.upsert_embedding(ids[0], "m", &data[0]).unwrap();
+        store.upsert_embedding(ids[1], "m", &data[1]).unwrap();
+        let config = AnnConfig::default();
+
+        assert_eq!(search(&store, dir.path(), &config, &data[1], 1).unwrap()[0].0, ids[1]);
+        assert!(dir.path().join(FILE_NAME).exists());
+
+        // Re-indexing the file replaces its chunks: old ids go, new ones come.
+        store.delete_chunks_for_file(a).unwrap();
+        let new_ids = store.insert_chunks(a, &[chunk(3)]).unwrap();
+        store.upsert_embedding(new_ids[0], "m", &data[2]).unwrap();
+        let hits = search(&store, dir.path(), &config, &data[1], 5).unwrap();
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), new_ids);
+    }
+
+    #[test]
+    fn search_picks_up_rewritten_embeddings() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let data = vectors(4, 8, 13);
+        let a = store.upsert_file("a.rs", "h", 10, Some("rust")).unwrap();
+        let b = store.upsert_file("b.rs", "h", 10, Some("rust")).unwrap();
+        let a_ids = store.insert_chunks(a, &[chunk(1)]).unwrap();
+        let b_ids = store.insert_chunks(b, &[chunk(2)]).unwrap();
+        store.upsert_embedding(a_ids[0], "m", &data[0]).unwrap();
+        store.upsert_embedding(b_ids[0], "m", &data[1]).unwrap();
+        let config = AnnConfig::default();
+        search(&store, dir.path(), &config, &data[0], 1).unwrap();
+
+        // Re-embedding under a new model rewrites the row in place.
+        store.upsert_embedding(a_ids[0], "m2", &data[2]).unwrap();
+        let hit = search(&store, dir.path(), &config, &data[2], 1).unwrap()[0];
+        assert_eq!(hit.0, a_ids[0]);
+        assert!(hit.1 > 0.999, "stale vector for {}: {}", hit.0, hit.1);
+
+        // Re-indexing the file holding the top id hands that id out again.
+        store.delete_chunks_for_file(b).unwrap();
+        let reused = store.insert_chunks(b, &[chunk(3)]).unwrap();
+        assert_eq!(reused, b_ids);
+        store.upsert_embedding(reused[0], "m2", &data[3]).unwrap();
+        let hit = search(&store, dir.path(), &config, &data[3], 1).unwrap()[0];
+        assert_eq!(hit.0, reused[0]);
+        assert!(hit.1 > 0.999, "stale vector for {}: {}", hit.0, hit.1);
+
+        // A fresh process loads the saved file and agrees.
+        CACHE.lock().unwrap().clear();
+        let hit = search(&store, dir.path(), &config, &data[3], 1).unwrap()[0];
+        assert!(hit.1 > 0.999);
+    }
+
+    #[test]
+    fn load_rejects_truncated_and_corrupt_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let mut index = Hnsw::new(8, &AnnConfig::default());
+        for (i, v) in vectors(20, 8, 5).iter().enumerate() {
+            index.insert(i as i64, v);
+        }
+        index.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(Hnsw::load(&path).is_err());
+
+        // A header claiming far more nodes than the file holds.
+        let mut huge = bytes.clone();
+        huge[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &huge).unwrap();
+        assert!(Hnsw::load(&path).is_err());
+
+        // An entry point past the last node.
+        let mut entry = bytes.clone();
+        entry[24..32].copy_from_slice(&100i64.to_le_bytes());
+        std::fs::write(&path, &entry).unwrap();
+        assert!(Hnsw::load(&path).is_err());
+
+        // A neighbour index past the last node: the last four bytes are the
+        // final node's last layer-0 link.
+        let mut link = bytes;
+        let end = link.len();
+        link[end - 4..].copy_from_slice(&1000u32.to_le_bytes());
+        std::fs::write(&path, &link).unwrap();
+        assert!(Hnsw::load(&path).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::index;
+use crate::search::fingerprint;
+use crate::store::sqlite::{SearchFilter, Store, TestScope};
+
+/// MinHash signature length: `BANDS` bands of `ROWS` rows. Pairs at 0.8
+/// Jaccard become candidates with probability > 0.999, pairs at 0.3 with
+/// probability < 0.13.
+const BANDS: usize = 16;
+const ROWS: usize = 4;
+
+pub struct DuplicatesQuery {
+    /// Chunks shorter than this many lines are ignored.
+    pub min_lines: usize,
+    /// Near-duplicates need at least this share of shingles in common (0..1).
+    pub threshold: f64,
+    pub language: Option<String>,
+    pub path_prefix: Option<String>,
+    pub kind: Option<String>,
+    pub exclude_paths: Vec<String>,
+    pub tests: TestScope,
+    pub max_families: usize,
+}
+
+impl Default for DuplicatesQuery {
+    fn default() -> Self {
+        Self {
+            min_lines: 6,
+            threshold: 0.8,
+            language: None,
+            path_prefix: None,
+            kind: None,
+            exclude_paths: Vec::new(),
+            tests: TestScope::Include,
+            max_families: 20,
+        }
+    }
+}
+
+/// One copy in a clone family.
+#[derive(Debug, Clone, Serialize)]
+pub struct Member {
+    /// Registered project name, when searching several projects.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub file_path: String,
+    pub start_line: i64,
+    pub end_line: i64,
+    pub chunk_kind: String,
+    pub chunk_name: Option<String>,
+}
+
+impl Member {
+    fn lines(&self) -> i64 {
+        self.end_line - self.start_line + 1
+    }
+
+    fn contains(&self, other: &Member) -> bool {
+        self.project == other.project
+            && self.file_path == other.file_path
+            && self.start_line <= other.start_line
+            && self.end_line >= other.end_line
+    }
+
+    fn overlaps(&self, other: &Member) -> bool {
+        self.project == other.project
+            && self.file_path == other.file_path
+            && self.start_line <= other.end_line
+            && self.end_line >= other.start_line
+    }
+}
+
+/// Chunks that are copies of each other.
+#[derive(Debug, Serialize)]
+pub struct Family {
+    /// Every copy is the same code, up to whitespace and comments.
+    pub exact: bool,
+    /// Lowest shingle similarity that joined two copies (1.0 if exact).
+    pub similarity: f64,
+    /// Lines duplicated beyond the first copy.
+    pub duplicated_lines: i64,
+    pub clones: Vec<Member>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DuplicatesOutcome {
+    /// Most duplicated lines first.
+    pub families: Vec<Family>,
+    /// Chunks at least `min_lines` long that were compared.
+    pub chunks_compared: usize,
+    /// Projects whose index was busy and may be stale.
+    pub busy: Vec<String>,
+}
+
+struct Candidate {
+    member: Member,
+    hash: String,
+    shingles: HashSet<u64>,
+}
+
+/// Clone families across `projects` (name, root). Exact copies share a
+/// normalized hash (see `fingerprint::normalized_hash`); near copies are
+/// found by MinHash over token shingles (see `fingerprint::shingles`) and
+/// confirmed by their exact Jaccard similarity. A family nested in a
+/// larger one (the methods of two copied impls) is left out.
+pub fn find(projects: &[(Option<String>, PathBuf)], query: &DuplicatesQuery) -> Result<DuplicatesOutcome> {
+    let mut outcome = DuplicatesOutcome::default();
+    let mut candidates = Vec::new();
+    for (name, root) in projects {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("resolving path {}", root.display()))?;
+        let config = Config::load(&root).unwrap_or_default();
+        if index::index_directory(&root, &config).is_ok_and(|r| r.busy) {
+            outcome.busy.push(name.clone().unwrap_or_else(|| root.display().to_string()));
+        }
+        let Some(store) = Store::open_if_exists(&config.storage_dir(&root))? else {
+            continue;
+        };
+        let mut filter = SearchFilter::new(query.language.as_deref(), query.path_prefix.as_deref(), query.kind.as_deref());
+        filter.exclude_paths = query.exclude_paths.clone();
+        filter.tests = query.tests;
+        for r in store.all_chunks(&filter)? {
+            if r.end_line - r.start_line + 1 < query.min_lines as i64 {
+                continue;
+            }
+            candidates.push(Candidate {
+                hash: fingerprint::normalized_hash(&r.content),
+                shingles: fingerprint::shingles(&r.content),
+                member: Member {
+                    project: name.clone(),
+                    file_path: r.file_path,
+                    start_line: r.start_line,
+                    end_line: r.end_line,
+                    chunk_kind: r.chunk_kind,
+                    chunk_name: r.chunk_name,
+                },
+            });
+        }
+    }
+    outcome.chunks_compared = candidates.len();
+
+    let mut sets = DisjointSets::new(candidates.len());
+    let mut similarity: HashMap<usize, f64> = HashMap::new();
+
+    // Exact copies: one representative per hash goes on to MinHash.
+    let mut by_hash: HashMap<&str, usize> = HashMap::new();
+    let mut representatives = Vec::new();
+    for (i, c) in candidates.iter().enumerate() {
+        match by_hash.get(c.hash.as_str()) {
+            Some(&first) if !candidates[first].member.overlaps(&c.member) => sets.union(first, i),
+            Some(_) => {}
+            None => {
+                by_hash.insert(&c.hash, i);
+                representatives.push(i);
+            }
+        }
+    }
+
+    // Near copies: pairs sharing a MinHash band, confirmed by Jaccard.
+    let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+    for &i in &representatives {
+        if candidates[i].shingles.is_empty() {
+            continue;
+        }
+        let signature = minhash(&candidates[i].shingles);
+        for (band, rows) in signature.chunks(ROWS).enumerate() {
+            let key = rows.iter().fold(0u64, |h, r| splitmix(h ^ r));
+            buckets.entry((band, key)).or_default().push(i);
+        }
+    }
+    let mut checked = HashSet::new();
+    for members in buckets.values() {
+        for (n, &a) in members.iter().enumerate() {
+            for &b in &members[n + 1..] {
+                if !checked.insert((a, b)) || candidates[a].member.overlaps(&candidates[b].member) {
+                    continue;
+                }
+                let s = fingerprint::jaccard(&candidates[a].shingles, &candidates[b].shingles);
+                if s >= query.threshold {
+                    let (ra, rb) = (sets.find(a), sets.find(b));
+                    let lowest = similarity.remove(&ra).unwrap_or(1.0).min(similarity.remove(&rb).unwrap_or(1.0)).min(s);
+                    sets.union(a, b);
+                    similarity.insert(sets.find(a), lowest);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..candidates.len() {
+        groups.entry(sets.find(i)).or_default().push(i);
+    }
+    let mut families: Vec<Family> = groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(root, members)| {
+            let exact = members.iter().all(|&i| candidates[i].hash == candidates[members[0]].hash);
+            let mut clones: Vec<Member> = members.iter().map(|&i| candidates[i].member.clone()).collect();
+            clones.sort_by(|a, b| (&a.project, &a.file_path, a.start_line).cmp(&(&b.project, &b.file_path, b.start_line)));
+            let total: i64 = clones.iter().map(Member::lines).sum();
+            let longest = clones.iter().map(Member::lines).max().unwrap_or(0);
+            Family {
+                exact,
+                similarity: if exact { 1.0 } else { similarity.get(&root).copied().unwrap_or(1.0) },
+                duplicated_lines: total - longest,
+                clones,
+            }
+        })
+        .collect();
+    families.sort_by(|a, b| {
+        b.duplicated_lines
+            .cmp(&a.duplicated_lines)
+            .then_with(|| a.clones[0].file_path.cmp(&b.clones[0].file_path))
+    });
+
+    let mut kept: Vec<Family> = Vec::new();
+    for family in families {
+        let nested = kept
+            .iter()
+            .any(|outer| family.clones.iter().all(|c| outer.clones.iter().any(|o| o.contains(c))));
+        if !nested {
+            kept.push(family);
+        }
+    }
+    kept.truncate(query.max_families);
+    outcome.families = kept;
+    Ok(outcome)
+}
+
+/// Plain-text report: one block per family, one line per copy.
+pub fn format_outcome(outcome: &DuplicatesOutcome) -> String {
+    if outcome.families.is_empty() {
+        return format!("No duplicates among {} chunk(s).\n", outcome.chunks_compared);
+    }
+    let mut out = format!(
+        "{} clone famil{} among {} chunk(s)\n",
+        outcome.families.len(),
+        if outcome.families.len() == 1 { "y" } else { "ies" },
+        outcome.chunks_compared,
+    );
+    for (i, family) in outcome.families.iter().enumerate() {
+        let how = if family.exact {
+            "exact".to_string()
+        } else {
+            format!("similarity ≥ {:.2}", family.similarity)
+        };
+        out.push_str(&format!(
+            "\n[{i}] {} copies, {how}, {} duplicated line(s)\n",
+            family.clones.len(),
+            family.duplicated_lines,
+        ));
+        for c in &family.clones {
+            let project = c.project.as_deref().map(|p| format!("[{p}] ")).unwrap_or_default();
+            let name = c.chunk_name.as_deref().map(|n| format!(" {n}")).unwrap_or_default();
+            out.push_str(&format!(
+                "  {project}{}:{}-{} [{}]{name}\n",
+                c.file_path, c.start_line, c.end_line, c.chunk_kind,
+            ));
+        }
+    }
+    out
+}
+
+/// Smallest value of `BANDS * ROWS` independent hashes over the shingles.
+fn minhash(shingles: &HashSet<u64>) -> [u64; BANDS * ROWS] {
+    let mut signature = [u64::MAX; BANDS * ROWS];
+    for &s in shingles {
+        for (seed, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(splitmix(s ^ (seed as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+        }
+    }
+    signature
+}
+
+fn splitmix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+struct DisjointSets {
+    parent: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect() }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            i = std::mem::replace(&mut self.parent[i], root);
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra != rb {
+            self.parent[rb] = ra;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDLER: &str = "fn get_user(req: Request) -> Response {\n    let id = req.param(\"id\")?;\n    let user = db.find(id)?;\n    if user.is_none() {\n        return Response::not_found();\n    }\n    Response::json(user)\n}\n";
+
+    #[test]
+    fn groups_exact_and_renamed_copies_into_families() {
+        let a = tempfile::TempDir::new().unwrap();
+        let b = tempfile::TempDir::new().unwrap();
+        std::fs::write(a.path().join("users.rs"), HANDLER).unwrap();
+        // Same code, reformatted and commented.
+        std::fs::write(
+            a.path().join("copy.rs"),
+            HANDLER.replace("    let user", "    // look it up\n    let user"),
+        )
+        .unwrap();
+        // Renamed and with a different literal: near, not exact.
+        std::fs::write(
+            b.path().join("orders.rs"),
+            HANDLER
+                .replace("get_user", "get_order")
+                .replace("user", "order")
+                .replace("\"id\"", "\"key\"")
+                .replace("    Response::json", "    audit(order);\n    Response::json"),
+        )
+        .unwrap();
+        std::fs::write(a.path().join("tiny.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+
+        let query = DuplicatesQuery::default();
+        let outcome = find(&[(None, a.path().to_path_buf())], &query).unwrap();
+        assert_eq!(outcome.families.len(), 1);
+        let family = &outcome.families[0];
+        assert!(family.exact);
+        let paths: Vec<&str> = family.clones.iter().map(|c| c.file_path.as_str()).collect();
+        assert_eq!(paths, ["copy.rs", "users.rs"]);
+
+        let projects = [(Some("a".to_string()), a.path().to_path_buf()), (Some("b".to_string()), b.path().to_path_buf())];
+        let outcome = find(&projects, &query).unwrap();
+        assert_eq!(outcome.families.len(), 1);
+        let family = &outcome.families[0];
+        assert!(!family.exact);
+        assert!(family.similarity >= 0.8 && family.similarity < 1.0, "{}", family.similarity);
+        assert_eq!(family.clones.len(), 3);
+        assert_eq!(family.clones[2].project.as_deref(), Some("b"));
+        assert_eq!(family.duplicated_lines, 8 + 9);
+
+        let strict = DuplicatesQuery { min_lines: 20, ..DuplicatesQuery::default() };
+        assert!(find(&projects, &strict).unwrap().families.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+/// Tokens per shingle.
+const SHINGLE: usize = 5;
+
+/// Keywords kept as themselves across the supported languages; every other
+/// identifier becomes `$`.
+const KEYWORDS: &[&str] = &[
+    "and", "as", "async", "await", "break", "case", "catch", "chan", "class", "const", "continue",
+    "def", "default", "defer", "dyn", "elif", "else", "enum", "except", "export", "extends", "false",
+    "finally", "fn", "for", "from", "func", "function", "go", "if", "impl", "import", "in", "interface",
+    "is", "lambda", "let", "loop", "match", "mod", "move", "mut", "new", "nil", "None", "not", "null",
+    "of", "or", "pub", "raise", "ref", "return", "select", "self", "static", "struct", "switch", "this",
+    "throw", "trait", "true", "try", "type", "unsafe", "use", "var", "where", "while", "with", "yield",
+];
+
+/// The shape of code as tokens: keywords and punctuation as they are,
+/// identifiers as `$`, numbers as `0`, strings as `"`. Comments are
+/// dropped, so renamed copies and reworded comments look the same.
+pub fn tokens(content: &str) -> Vec<&str> {
+    lex(content)
+        .into_iter()
+        .map(|lexeme| match lexeme.as_bytes()[0] {
+            b'"' | b'`' => "\"",
+            c if c.is_ascii_digit() => "0",
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                if KEYWORDS.contains(&lexeme) { lexeme } else { "$" }
+            }
+            _ => lexeme,
+        })
+        .collect()
+}
+
+/// BLAKE3 of the code with comments and whitespace dropped; equal for
+/// copies that differ only in formatting or comments.
+pub fn normalized_hash(content: &str) -> String {
+    crate::index::hasher::hash_bytes(lex(content).join("\0").as_bytes())
+}
+
+/// Words, numbers, string literals and single punctuation characters, as
+/// written. Whitespace and comments are skipped.
+fn lex(content: &str) -> Vec<&str> {
+    let bytes = content.as_bytes();
+    let mut lexemes = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let rest = &content[i..];
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if rest.starts_with("//") || rest.starts_with("# ") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            i += comment.find("*/").map_or(rest.len(), |end| end + 4);
+        } else if c == b'"' || c == b'`' {
+            let mut j = i + 1;
+            while j < bytes.len() && bytes[j] != c {
+                j += if bytes[j] == b'\\' { 2 } else { 1 };
+            }
+            let end = (j + 1).min(bytes.len());
+            lexemes.push(content.get(i..end).unwrap_or(rest));
+            i = end;
+        } else if c.is_ascii_alphanumeric() || c == b'_' {
+            let len = rest
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_' || (c.is_ascii_digit() && ch == '.')))
+                .unwrap_or(rest.len());
+            lexemes.push(&rest[..len]);
+            i += len;
+        } else {
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            lexemes.push(&rest[..len]);
+            i += len;
+        }
+    }
+    lexemes
+}
+
+/// Hashes of every run of `SHINGLE` consecutive tokens (see `tokens`).
+/// Short code gets one shingle of all its tokens.
+pub fn shingles(content: &str) -> HashSet<u64> {
+    let tokens = tokens(content);
+    if tokens.is_empty() {
+        return HashSet::new();
+    }
+    tokens.windows(SHINGLE.min(tokens.len())).map(hash).collect()
+}
+
+/// Share of shingles two fingerprints have in common.
+pub fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// FNV-1a, so fingerprints are stable across runs and builds.
+fn hash(tokens: &[&str]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for token in tokens {
+        for b in token.bytes().chain([0]) {
+            h ^= b as u64;
+            h = h.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renamed_copies_share_a_fingerprint() {
+        let a = "fn load(path: &str) -> Result<Config> {\n    // read it\n    let text = fs::read(path)?;\n    parse(&text, 4)\n}";
+        let b = "fn open(file: &str) -> Result<Store> {\n    let data = fs::read(file)?; /* cached */\n    build(&data, 16)\n}";
+        let c = "for item in items {\n    if item.done {\n        continue;\n    }\n    queue.push(item);\n}";
+        assert_eq!(tokens("let x = \"a b\" + 42; // note"), ["let", "$", "=", "\"", "+", "0", ";"]);
+        assert_eq!(jaccard(&shingles(a), &shingles(b)), 1.0);
+        assert!(jaccard(&shingles(a), &shingles(c)) < 0.1);
+        assert_eq!(normalized_hash(a), normalized_hash("fn load(path: &str)\n  -> Result<Config> { let text = fs::read(path)?; parse(&text, 4) }"));
+        assert_ne!(normalized_hash(a), normalized_hash(b));
+    }
+}
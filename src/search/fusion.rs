@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::config::Fusion;
+
+/// Merge ranked result lists into one, best first. Each list is a weight
+/// and `(key, score)` pairs in rank order, best first, with higher scores
+/// better. Fused scores fall in 0..1 whatever the method:
+///
+/// - `rrf`: Σ weight / (k + position), scaled so first in every list is 1.
+///   Uses positions only, so it does not care how either retriever scores.
+/// - `zscore`: Σ weight × (score − mean) / stddev, through a logistic curve.
+/// - `linear`: Σ weight × min-max normalized score.
+///
+/// A key missing from a list gets that list's worst value: nothing for
+/// `rrf` and `linear`, the lowest z-score for `zscore`.
+pub fn fuse<K: Clone + Eq + Hash>(method: Fusion, rrf_k: f64, lists: &[(f64, Vec<(K, f64)>)]) -> Vec<(K, f64)> {
+    let mut keys: Vec<K> = Vec::new();
+    let mut fused: HashMap<K, f64> = HashMap::new();
+    for (_, list) in lists {
+        for (key, _) in list {
+            if !fused.contains_key(key) {
+                fused.insert(key.clone(), 0.0);
+                keys.push(key.clone());
+            }
+        }
+    }
+
+    let total_weight: f64 = lists.iter().map(|(w, _)| w.max(0.0)).sum();
+    if total_weight == 0.0 {
+        return keys.into_iter().map(|k| (k, 0.0)).collect();
+    }
+    for (weight, list) in lists {
+        let weight = weight.max(0.0);
+        let normalized = normalize(method, rrf_k, list);
+        let missing = match method {
+            Fusion::Zscore => normalized.iter().copied().fold(f64::INFINITY, f64::min).min(0.0),
+            _ => 0.0,
+        };
+        let by_key: HashMap<&K, f64> = list.iter().map(|(k, _)| k).zip(normalized).collect();
+        for key in &keys {
+            *fused.get_mut(key).unwrap() += weight * by_key.get(key).copied().unwrap_or(missing);
+        }
+    }
+
+    let mut out: Vec<(K, f64)> = keys
+        .into_iter()
+        .map(|key| {
+            let score = fused[&key] / total_weight;
+            let score = match method {
+                Fusion::Rrf => score * (rrf_k + 1.0),
+                Fusion::Zscore => 1.0 / (1.0 + (-1.7 * score).exp()),
+                Fusion::Linear => score,
+            };
+            (key, score)
+        })
+        .collect();
+    out.sort_by(|a, b| b.1.total_cmp(&a.1));
+    out
+}
+
+/// Merge lists that share no keys (results from separate indexes) into
+/// one, best first. Each list is put on the method's 0..1 scale on its own
+/// and multiplied by its weight, so a list's corpus statistics do not leak
+/// into the others: `rrf` by position, `zscore` by standing within the
+/// list, `linear` min-max. `None` keeps the raw scores, times the weight.
+pub fn interleave<K>(method: Option<Fusion>, rrf_k: f64, lists: Vec<(f64, Vec<(K, f64)>)>) -> Vec<(K, f64)> {
+    let mut out = Vec::new();
+    for (weight, list) in lists {
+        let scores = match method {
+            Some(method) => normalize(method, rrf_k, &list),
+            None => list.iter().map(|(_, s)| *s).collect(),
+        };
+        out.extend(list.into_iter().zip(scores).map(|((key, _), score)| {
+            let score = match method {
+                Some(Fusion::Rrf) => score * (rrf_k + 1.0),
+                Some(Fusion::Zscore) => 1.0 / (1.0 + (-1.7 * score).exp()),
+                _ => score,
+            };
+            (key, weight * score)
+        }));
+    }
+    out.sort_by(|a, b| b.1.total_cmp(&a.1));
+    out
+}
+
+/// One list's scores on the method's scale, aligned with the list.
+fn normalize<K>(method: Fusion, rrf_k: f64, list: &[(K, f64)]) -> Vec<f64> {
+    let scores = list.iter().map(|(_, s)| *s);
+    match method {
+        Fusion::Rrf => (1..=list.len()).map(|pos| 1.0 / (rrf_k + pos as f64)).collect(),
+        Fusion::Zscore => {
+            let n = list.len() as f64;
+            let mean = scores.clone().sum::<f64>() / n;
+            let stddev = (scores.clone().map(|s| (s - mean).powi(2)).sum::<f64>() / n).sqrt();
+            scores
+                .map(|s| if stddev > 0.0 { (s - mean) / stddev } else { 0.0 })
+                .collect()
+        }
+        Fusion::Linear => {
+            let max = scores.clone().fold(f64::NEG_INFINITY, f64::max);
+            let min = scores.clone().fold(f64::INFINITY, f64::min);
+            scores
+                .map(|s| if max > min { (s - min) / (max - min) } else { 1.0 })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(fused: &[(&'static str, f64)]) -> Vec<&'static str> {
+        fused.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn results_in_both_lists_rise_to_the_top() {
+        let fts = vec![("a", 12.0), ("b", 9.0), ("c", 2.0)];
+        let sem = vec![("b", 0.81), ("d", 0.80), ("a", 0.40)];
+        let lists = [(0.5, fts), (0.5, sem)];
+
+        let rrf = fuse(Fusion::Rrf, 60.0, &lists);
+        assert_eq!(keys(&rrf), ["b", "a", "d", "c"]);
+        assert!((rrf[0].1 - 61.0 * (1.0 / 62.0 + 1.0 / 61.0) / 2.0).abs() < 1e-9);
+
+        let linear = fuse(Fusion::Linear, 60.0, &lists);
+        assert_eq!(keys(&linear), ["b", "a", "d", "c"]);
+        assert_eq!(linear[0].1, 0.5 * 0.7 + 0.5);
+
+        let zscore = fuse(Fusion::Zscore, 60.0, &lists);
+        assert_eq!(keys(&zscore), ["b", "a", "d", "c"]);
+        assert!(zscore.iter().all(|(_, s)| (0.0..=1.0).contains(s)));
+    }
+
+    #[test]
+    fn fusion_ignores_the_scale_of_scores() {
+        let fts = vec![("a", 12.0), ("b", 9.0), ("c", 2.0)];
+        let sem = vec![("c", 0.9), ("a", 0.5), ("b", 0.1)];
+        let scaled: Vec<_> = fts.iter().map(|(k, s)| (*k, s * 10.0)).collect();
+        for method in [Fusion::Rrf, Fusion::Zscore, Fusion::Linear] {
+            let a = fuse(method, 60.0, &[(0.7, fts.clone()), (0.3, sem.clone())]);
+            let b = fuse(method, 60.0, &[(0.7, scaled.clone()), (0.3, sem.clone())]);
+            for ((ka, sa), (kb, sb)) in a.iter().zip(&b) {
+                assert_eq!(ka, kb, "{method:?}");
+                assert!((sa - sb).abs() < 1e-9, "{method:?}");
+            }
+        }
+        // Weighted towards semantic, its first result wins.
+        assert_eq!(keys(&fuse(Fusion::Rrf, 60.0, &[(0.3, fts), (0.7, sem)]))[0], "c");
+    }
+
+    #[test]
+    fn interleave_compares_lists_by_their_own_spread() {
+        // A small corpus inflates BM25: its weak hit outscores a strong one.
+        let small = vec![("s1", 14.0), ("s2", 13.5), ("s3", 13.0)];
+        let large = vec![("l1", 11.0), ("l2", 4.0), ("l3", 3.0)];
+        let lists = || vec![(1.0, small.clone()), (1.0, large.clone())];
+
+        assert_eq!(keys(&interleave(None, 60.0, lists()))[0], "s1");
+        let zscore = interleave(Some(Fusion::Zscore), 60.0, lists());
+        assert_eq!(keys(&zscore)[0], "l1");
+        assert!(zscore.iter().all(|(_, s)| (0.0..=1.0).contains(s)));
+        let rrf = interleave(Some(Fusion::Rrf), 60.0, lists());
+        assert_eq!(rrf[0].1, 1.0);
+        assert_eq!(rrf[1].1, 1.0);
+
+        // Weights scale a whole list.
+        let weighted = interleave(Some(Fusion::Linear), 60.0, vec![(0.5, small), (1.0, large)]);
+        assert_eq!(keys(&weighted)[..2], ["l1", "s1"]);
+        assert_eq!(weighted[1].1, 0.5);
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::store::sqlite::{Store, SymbolEntry};
+
+pub struct SymbolQuery {
+    pub text: String,
+    pub kind: Option<String>,
+    pub language: Option<String>,
+    pub path_prefix: Option<String>,
+    pub max_results: usize,
+}
+
+impl SymbolQuery {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            kind: None,
+            language: None,
+            path_prefix: None,
+            max_results: 20,
+        }
+    }
+}
+
+/// How a symbol name matched the query, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    Substring,
+    Subsequence,
+    Typo,
+}
+
+impl MatchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchKind::Exact => "exact",
+            MatchKind::Prefix => "prefix",
+            MatchKind::Substring => "substring",
+            MatchKind::Subsequence => "subsequence",
+            MatchKind::Typo => "typo",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SymbolMatch {
+    #[serde(flatten)]
+    pub symbol: SymbolEntry,
+    pub matched: MatchKind,
+    /// Match quality plus kind bonus minus path depth; higher is better.
+    pub score: f64,
+}
+
+/// Fuzzy-match `query.text` against every indexed symbol name.
+///
+/// Names match, best first, exactly, by prefix, as a substring, as a
+/// subsequence (`IndxStats` → `IndexStats`) or within a small edit
+/// distance (`IdnexStats`). Case, `_` and `-` are ignored. Ties go to
+/// declarations over imports and to shallower paths.
+pub fn find_symbols(store: &Store, query: &SymbolQuery) -> Result<Vec<SymbolMatch>> {
+    let entries = store.symbol_entries(
+        query.kind.as_deref(),
+        query.language.as_deref(),
+        query.path_prefix.as_deref(),
+    )?;
+
+    let mut matches: Vec<SymbolMatch> = entries
+        .into_iter()
+        .filter_map(|symbol| {
+            let (matched, quality) = match_name(&query.text, &symbol.name)?;
+            let score = quality + kind_bonus(&symbol.kind) - depth_penalty(&symbol.file_path);
+            Some(SymbolMatch { symbol, matched, score })
+        })
+        .collect();
+
+    matches.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.symbol.name.len().cmp(&b.symbol.name.len()))
+            .then_with(|| a.symbol.file_path.cmp(&b.symbol.file_path))
+            .then_with(|| a.symbol.start_line.cmp(&b.symbol.start_line))
+    });
+    matches.truncate(query.max_results);
+    Ok(matches)
+}
+
+/// Lowercase with `_` and `-` removed, so `index_dir` ≈ `indexDir`.
+fn fold(s: &str) -> Vec<char> {
+    s.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Match quality of `name` for `query` (0–100), or None if unrelated.
+pub fn match_name(query: &str, name: &str) -> Option<(MatchKind, f64)> {
+    let q = fold(query);
+    let n = fold(name);
+    if q.is_empty() || n.is_empty() {
+        return None;
+    }
+    // How much of the name the query covers.
+    let coverage = q.len() as f64 / n.len() as f64;
+
+    if query == name {
+        return Some((MatchKind::Exact, 100.0));
+    }
+    if q == n {
+        return Some((MatchKind::Exact, 95.0));
+    }
+    if n.starts_with(&q) {
+        return Some((MatchKind::Prefix, 80.0 + 10.0 * coverage));
+    }
+    if n.windows(q.len()).any(|w| w == q.as_slice()) {
+        return Some((MatchKind::Substring, 60.0 + 10.0 * coverage));
+    }
+    if q.len() >= 2
+        && let Some(boundaries) = subsequence_boundaries(query, name)
+    {
+        let boundary_ratio = boundaries as f64 / q.len() as f64;
+        return Some((MatchKind::Subsequence, 40.0 + 15.0 * boundary_ratio + 5.0 * coverage));
+    }
+
+    let allowed = match q.len() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    if allowed == 0 {
+        return None;
+    }
+    let distance = edit_distance(&q, &n);
+    if distance <= allowed {
+        return Some((MatchKind::Typo, 35.0 - 10.0 * distance as f64));
+    }
+    // A typo in the beginning of a longer name: `IdnexSt` → `IndexStats`.
+    if n.len() > q.len() {
+        let distance = edit_distance(&q, &n[..q.len()]);
+        if distance <= allowed {
+            return Some((MatchKind::Typo, 30.0 - 10.0 * distance as f64));
+        }
+    }
+    None
+}
+
+/// If every query character appears in `name` in order (ignoring case,
+/// `_` and `-`), the number matched at word starts: the first character,
+/// after a separator, or a camelCase hump.
+fn subsequence_boundaries(query: &str, name: &str) -> Option<usize> {
+    let name: Vec<char> = name.chars().collect();
+    let mut pos = 0;
+    let mut boundaries = 0;
+    for qc in query.chars().filter(|c| *c != '_' && *c != '-') {
+        let qc = qc.to_lowercase().next()?;
+        loop {
+            let nc = *name.get(pos)?;
+            pos += 1;
+            if nc.to_lowercase().next() == Some(qc) {
+                let i = pos - 1;
+                let at_boundary = i == 0
+                    || matches!(name[i - 1], '_' | '-' | '.' | ':')
+                    || (nc.is_uppercase() && name[i - 1].is_lowercase());
+                if at_boundary {
+                    boundaries += 1;
+                }
+                break;
+            }
+        }
+    }
+    Some(boundaries)
+}
+
+/// Optimal string alignment distance: insertions, deletions,
+/// substitutions and adjacent transpositions.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Declarations an agent is usually looking for rank above containers and
+/// imports that merely mention the name.
+fn kind_bonus(kind: &str) -> f64 {
+    match kind {
+        "function" | "method" | "struct" | "class" | "enum" | "trait" | "interface"
+        | "type_alias" => 5.0,
+        "constant" | "macro" | "type" => 3.0,
+        "impl" | "module" => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Deeply nested files rank slightly lower (0.5 per directory, up to 3).
+fn depth_penalty(path: &str) -> f64 {
+    (path.matches('/').count() as f64 * 0.5).min(3.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::sqlite::ChunkInsert;
+    use tempfile::TempDir;
+
+    fn kind_of(query: &str, name: &str) -> Option<MatchKind> {
+        match_name(query, name).map(|(k, _)| k)
+    }
+
+    #[test]
+    fn match_kinds_and_order() {
+        assert_eq!(kind_of("IndexStats", "IndexStats"), Some(MatchKind::Exact));
+        assert_eq!(kind_of("index_stats", "IndexStats"), Some(MatchKind::Exact));
+        assert_eq!(kind_of("index_dir", "index_directory"), Some(MatchKind::Prefix));
+        assert_eq!(kind_of("stats", "IndexStats"), Some(MatchKind::Substring));
+        assert_eq!(kind_of("IndxStats", "IndexStats"), Some(MatchKind::Subsequence));
+        assert_eq!(kind_of("IdnexStats", "IndexStats"), Some(MatchKind::Typo));
+        assert_eq!(kind_of("IdnexSt", "IndexStats"), Some(MatchKind::Typo));
+        assert_eq!(kind_of("xyz", "IndexStats"), None);
+        assert_eq!(kind_of("abd", "abc"), None);
+
+        let score = |q, n| match_name(q, n).unwrap().1;
+        assert!(score("index", "index") > score("index", "index_directory"));
+        assert!(score("index_dir", "index_directory") > score("dir", "index_directory"));
+        // Subsequences along word starts beat scattered ones.
+        assert!(score("ist", "IndexStats") > score("ist", "inspect"));
+    }
+
+    #[test]
+    fn ranks_by_quality_kind_and_depth_with_filters() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let add = |path: &str, lang: &str, kind: &str, name: &str| {
+            let fid = store.upsert_file(path, "h", 10, Some(lang)).unwrap();
+            store.insert_chunks(fid, &[ChunkInsert {
+                kind: kind.into(),
+                name: Some(name.into()),
+                content: format!("{kind} {name}"),
+                signature: None,
+                start_line: 1,
+                end_line: 1,
+                start_byte: 0,
+                end_byte: 10,
+            }]).unwrap();
+        };
+        add("src/index/mod.rs", "rust", "function", "index_directory");
+        add("src/a/deep.rs", "rust", "function", "index_dir");
+        add("src/main.rs", "rust", "import", "index_dir");
+        add("app/index.py", "python", "function", "index_dirs");
+
+        let names = |q: &SymbolQuery| -> Vec<(String, String)> {
+            find_symbols(&store, q).unwrap().into_iter().map(|m| (m.symbol.name, m.symbol.file_path)).collect()
+        };
+
+        let query = SymbolQuery::new("index_dir");
+        let found = names(&query);
+        assert_eq!(found[0], ("index_dir".into(), "src/a/deep.rs".into()));
+        assert_eq!(found[1], ("index_dir".into(), "src/main.rs".into()));
+        assert_eq!(found.len(), 4);
+
+        let mut query = SymbolQuery::new("indx_dir");
+        query.language = Some("rust".into());
+        query.kind = Some("function".into());
+        assert_eq!(
+            names(&query),
+            [
+                ("index_dir".into(), "src/a/deep.rs".into()),
+                ("index_directory".into(), "src/index/mod.rs".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::Result;
+use regex::Regex;
+use regex_syntax::hir::{Hir, HirKind};
+use std::collections::BTreeSet;
+
+use crate::store::sqlite::{SearchFilter, SearchResult, Store};
+
+/// Shortest literal the trigram index can look up.
+const MIN_LITERAL: usize = 3;
+
+/// A matching line with its surrounding context.
+pub struct GrepMatch {
+    pub file: String,
+    pub context: Vec<(usize, String, bool)>, // (line_no, text, is_match)
+}
+
+/// Result of a grep over the index.
+pub struct GrepOutcome {
+    /// The first `max_results` matches, in path/line order.
+    pub matches: Vec<GrepMatch>,
+    /// Total matching lines, including those past `max_results`.
+    pub total_matches: usize,
+    /// Every file with at least one match.
+    pub files: BTreeSet<String>,
+    /// Chunks the regex was run against (after trigram prefiltering).
+    pub chunks_scanned: usize,
+}
+
+/// Literals a regex match requires, as a boolean expression.
+#[derive(Debug, PartialEq)]
+enum Required {
+    /// No usable literal: any chunk may match.
+    Any,
+    Literal(String),
+    All(Vec<Required>),
+    OneOf(Vec<Required>),
+}
+
+impl Required {
+    fn all(parts: Vec<Required>) -> Required {
+        let mut parts: Vec<Required> = parts.into_iter().filter(|p| *p != Required::Any).collect();
+        match parts.len() {
+            0 => Required::Any,
+            1 => parts.remove(0),
+            _ => Required::All(parts),
+        }
+    }
+
+    fn one_of(parts: Vec<Required>) -> Required {
+        // One unconstrained branch makes the whole alternation unconstrained.
+        if parts.is_empty() || parts.contains(&Required::Any) {
+            return Required::Any;
+        }
+        Required::OneOf(parts)
+    }
+
+    fn to_fts(&self) -> Option<String> {
+        match self {
+            Required::Any => None,
+            Required::Literal(s) => Some(format!("\"{}\"", s.replace('"', "\"\""))),
+            Required::All(parts) => {
+                let parts: Vec<String> = parts.iter().filter_map(Required::to_fts).collect();
+                Some(format!("({})", parts.join(" AND ")))
+            }
+            Required::OneOf(parts) => {
+                let parts: Vec<String> = parts.iter().filter_map(Required::to_fts).collect();
+                Some(format!("({})", parts.join(" OR ")))
+            }
+        }
+    }
+}
+
+fn required(hir: &Hir) -> Required {
+    match hir.kind() {
+        HirKind::Literal(lit) => match std::str::from_utf8(&lit.0) {
+            Ok(s) if s.chars().count() >= MIN_LITERAL => Required::Literal(s.to_string()),
+            _ => Required::Any,
+        },
+        HirKind::Capture(cap) => required(&cap.sub),
+        HirKind::Repetition(rep) if rep.min > 0 => required(&rep.sub),
+        HirKind::Concat(subs) => Required::all(subs.iter().map(required).collect()),
+        HirKind::Alternation(subs) => Required::one_of(subs.iter().map(required).collect()),
+        // Classes, look-arounds, empty and optional repetitions pin nothing down.
+        _ => Required::Any,
+    }
+}
+
+/// Build an FTS5 trigram query from the literals `pattern` requires, or None
+/// if it requires none of at least three characters (e.g. `\w+`, `a|b.*`).
+///
+/// The query is case-insensitive, so it selects a superset of the chunks
+/// the regex can match; the regex is still run on every candidate.
+pub fn literal_query(pattern: &str) -> Option<String> {
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+    required(&hir).to_fts()
+}
+
+/// Run `regex` over the content of the chunks `filter` selects, line by line.
+///
+/// Candidate chunks are prefiltered through the trigram index when the
+/// pattern has required literals, so only chunks that can match are
+/// scanned. All candidates are scanned to completion so `total_matches`
+/// and `files` are exact; only the first `max_results` matches keep their
+/// context lines.
+pub fn grep(
+    store: &Store,
+    regex: &Regex,
+    filter: &SearchFilter,
+    max_results: usize,
+    context_lines: usize,
+) -> Result<GrepOutcome> {
+    let chunks: Vec<SearchResult> = match literal_query(regex.as_str()) {
+        Some(q) => store.trigram_candidates(&q, filter)?,
+        None => store.all_chunks(filter)?,
+    };
+
+    let mut outcome = GrepOutcome {
+        matches: Vec::new(),
+        total_matches: 0,
+        files: BTreeSet::new(),
+        chunks_scanned: chunks.len(),
+    };
+
+    for chunk in &chunks {
+        let lines: Vec<&str> = chunk.content.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            outcome.total_matches += 1;
+            outcome.files.insert(chunk.file_path.clone());
+            if outcome.matches.len() >= max_results {
+                continue;
+            }
+
+            let ctx_start = i.saturating_sub(context_lines);
+            let ctx_end = (i + context_lines + 1).min(lines.len());
+            let context = (ctx_start..ctx_end)
+                .map(|j| (chunk.start_line as usize + j, lines[j].to_string(), j == i))
+                .collect();
+            outcome.matches.push(GrepMatch { file: chunk.file_path.clone(), context });
+        }
+    }
+
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::sqlite::ChunkInsert;
+    use tempfile::TempDir;
+
+    #[test]
+    fn extracts_required_literals() {
+        assert_eq!(literal_query("SecurityToken"), Some("\"SecurityToken\"".into()));
+        // "fn" is too short for a trigram lookup and is dropped.
+        assert_eq!(literal_query(r"fn\s+parse_\w+"), Some("\"parse_\"".into()));
+        assert_eq!(literal_query("TODO|FIXME"), Some("(\"TODO\" OR \"FIXME\")".into()));
+        assert_eq!(
+            literal_query(r"Store::open\(.*config"),
+            Some("(\"Store::open(\" AND \"config\")".into()),
+        );
+    }
+
+    #[test]
+    fn no_query_without_usable_literals() {
+        assert_eq!(literal_query(r"\w+"), None);
+        assert_eq!(literal_query("ab"), None);
+        assert_eq!(literal_query("TODO|x+"), None);
+        assert_eq!(literal_query("(?:TODO)?"), None);
+        assert_eq!(literal_query("[invalid"), None);
+    }
+
+    fn store_with_chunks(contents: &[&str]) -> (TempDir, Store) {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        for (i, content) in contents.iter().enumerate() {
+            let path = format!("src/f{i}.rs");
+            let fid = store.upsert_file(&path, "h", 10, Some("rust")).unwrap();
+            store.insert_chunks(fid, &[ChunkInsert {
+                kind: "raw".into(),
+                name: None,
+                content: content.to_string(),
+                signature: None,
+                start_line: 1,
+                end_line: content.lines().count() as u32,
+                start_byte: 0,
+                end_byte: content.len() as u32,
+            }]).unwrap();
+        }
+        (dir, store)
+    }
+
+    #[test]
+    fn prefilter_scans_only_candidates_and_counts_exactly() {
+        let (_dir, store) = store_with_chunks(&[
+            "// TODO: one\n// TODO: two\n// todo lowercase",
+            "fn clean() {}",
+            "// TODO: three",
+        ]);
+        let regex = Regex::new("TODO").unwrap();
+        let outcome = grep(&store, &regex, &SearchFilter::default(), 1, 0).unwrap();
+        // The trigram lookup is case-insensitive: the "todo" line is scanned
+        // but not counted. The chunk without the literal is never scanned.
+        assert_eq!(outcome.chunks_scanned, 2);
+        assert_eq!(outcome.total_matches, 3);
+        assert_eq!(outcome.matches.len(), 1);
+        assert_eq!(outcome.files.len(), 2);
+    }
+
+    #[test]
+    fn falls_back_to_full_scan_without_literals() {
+        let (_dir, store) = store_with_chunks(&["let a = 1;", "let b = 2;"]);
+        let regex = Regex::new(r"\d").unwrap();
+        let outcome = grep(&store, &regex, &SearchFilter::default(), 50, 0).unwrap();
+        assert_eq!(outcome.chunks_scanned, 2);
+        assert_eq!(outcome.total_matches, 2);
+    }
+}
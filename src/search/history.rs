@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::store::sqlite::{Commit, Store};
+
+/// Symbols listed per commit before the rest are counted.
+const MAX_SYMBOLS_SHOWN: usize = 8;
+
+/// What to look for in the commit history index. Either filter may be
+/// left out; with neither, the newest commits are listed.
+pub struct HistoryQuery {
+    /// Words in the message, author or touched symbol names.
+    pub text: Option<String>,
+    /// Exact name of a symbol the commit added, modified or removed.
+    pub symbol: Option<String>,
+    /// Only commits touching symbols under this path.
+    pub path_prefix: Option<String>,
+    pub max_results: usize,
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        Self { text: None, symbol: None, path_prefix: None, max_results: 10 }
+    }
+}
+
+#[derive(Serialize)]
+pub struct HistoryOutcome {
+    pub commits: Vec<Commit>,
+    /// Commits in the history index; 0 means it was never built.
+    pub indexed: i64,
+}
+
+/// Search the commit history index built by `index::index_history`.
+pub fn search(root: &Path, config: &Config, query: &HistoryQuery) -> Result<HistoryOutcome> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let Some(store) = Store::open_if_exists(&config.storage_dir(&root))? else {
+        return Ok(HistoryOutcome { commits: Vec::new(), indexed: 0 });
+    };
+    let commits = store.search_commits(
+        query.text.as_deref(),
+        query.symbol.as_deref(),
+        query.path_prefix.as_deref(),
+        query.max_results,
+    )?;
+    Ok(HistoryOutcome { commits, indexed: store.commit_count()? })
+}
+
+/// One block per commit: short hash, date, author and subject, then the
+/// symbols it touched (those named `symbol` first).
+pub fn format_outcome(outcome: &HistoryOutcome, symbol: Option<&str>) -> String {
+    if outcome.indexed == 0 {
+        return "No commit history indexed. Run `booger index --history` (or the `index` tool with history: true) first.\n"
+            .to_string();
+    }
+    if outcome.commits.is_empty() {
+        return format!("No matching commits among {} indexed.\n", outcome.indexed);
+    }
+    let mut out = format!("{} commit(s) of {} indexed\n", outcome.commits.len(), outcome.indexed);
+    for c in &outcome.commits {
+        let date = chrono::DateTime::from_timestamp(c.time, 0)
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let short = &c.hash[..c.hash.len().min(10)];
+        let subject = c.message.lines().next().unwrap_or("");
+        out.push_str(&format!("\n{short} {date} {}: {subject}\n", c.author));
+        let mut symbols: Vec<_> = c.symbols.iter().collect();
+        symbols.sort_by_key(|s| Some(s.name.as_str()) != symbol);
+        for s in symbols.iter().take(MAX_SYMBOLS_SHOWN) {
+            out.push_str(&format!("  {} {} {} ({})\n", s.change, s.kind, s.name, s.path));
+        }
+        if symbols.len() > MAX_SYMBOLS_SHOWN {
+            out.push_str(&format!("  … {} more symbol(s)\n", symbols.len() - MAX_SYMBOLS_SHOWN));
+        }
+    }
+    out
+}
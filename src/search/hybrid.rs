@@ -0,0 +1,324 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::{Config, Fusion};
+use crate::embed::Embedder;
+use crate::search::fusion;
+use crate::search::ranking::{Pipeline, RankContext, StageScore};
+use crate::search::semantic::{self, SemanticQuery};
+use crate::search::text::{self, SearchQuery, parse_query};
+use crate::store::sqlite::{SearchResult, Store, TestScope};
+
+pub struct HybridQuery {
+    pub text: String,
+    pub language: Option<String>,
+    pub path_prefix: Option<String>,
+    pub kind: Option<String>,
+    pub exclude_paths: Vec<String>,
+    pub tests: TestScope,
+    pub max_results: usize,
+    pub session_id: Option<String>,
+    pub diff_boost: bool,
+    /// Fusion method; `[hybrid] fusion` if unset.
+    pub fusion: Option<Fusion>,
+    /// Weight of the keyword results; `[hybrid] alpha` if unset.
+    pub alpha: Option<f64>,
+}
+
+impl HybridQuery {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            language: None,
+            path_prefix: None,
+            kind: None,
+            exclude_paths: Vec::new(),
+            tests: TestScope::Include,
+            max_results: 20,
+            session_id: None,
+            diff_boost: true,
+            fusion: None,
+            alpha: None,
+        }
+    }
+}
+
+/// A result's position (1-based) and raw score in one retriever's list.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Source {
+    pub position: usize,
+    pub score: f64,
+}
+
+/// How a hybrid result's score was reached.
+#[derive(Debug, Clone, Serialize)]
+pub struct HybridExplanation {
+    /// In the keyword results, scored by BM25 (negated: higher is better).
+    pub fts: Option<Source>,
+    /// In the semantic results, scored by cosine similarity.
+    pub semantic: Option<Source>,
+    /// Score after fusion, before the ranking stages (0..1).
+    pub fused: f64,
+    /// Ranking stage boosts, in BM25 units; `boost_scale` converts them.
+    pub stages: Vec<StageScore>,
+    /// Final score: `fused` plus the scaled boosts.
+    pub score: f64,
+}
+
+impl HybridExplanation {
+    /// `fused 0.480 (fts #2 bm25 -7.12, semantic #1 similarity 0.812), focus +5.00`
+    pub fn summary(&self) -> String {
+        let mut sources = Vec::new();
+        if let Some(s) = self.fts {
+            sources.push(format!("fts #{} bm25 {:.2}", s.position, -s.score));
+        }
+        if let Some(s) = self.semantic {
+            sources.push(format!("semantic #{} similarity {:.3}", s.position, s.score));
+        }
+        let mut parts = vec![format!("fused {:.3} ({})", self.fused, sources.join(", "))];
+        for s in self.stages.iter().filter(|s| s.boost != 0.0) {
+            parts.push(format!("{} {:+.2}", s.stage, s.boost));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Hybrid results plus how they were fused.
+#[derive(Default)]
+pub struct HybridOutcome {
+    /// Best first; `rank` is the final score, higher is better.
+    pub results: Vec<SearchResult>,
+    /// Aligned with `results`.
+    pub explanations: Vec<HybridExplanation>,
+    pub fusion: Fusion,
+    pub alpha: f64,
+    /// The keyword search fell back to OR (see `text::SearchOutcome`).
+    pub or_fallback: bool,
+    /// Synonym variants the keyword search also ran.
+    pub expansions: Vec<String>,
+    pub index_busy: bool,
+}
+
+type Key = (String, i64);
+
+fn key(r: &SearchResult) -> Key {
+    (r.file_path.clone(), r.start_line)
+}
+
+/// Keyword and semantic search fused into one ranking, then re-ranked by
+/// the `[ranking]` stages. Each retriever contributes its raw scores (BM25
+/// before boosts, cosine similarity), so session context (focus, visited,
+/// annotations, ...) is applied once, after fusion. Without an embedder,
+/// or when semantic search fails, only keyword results are fused.
+pub fn search(
+    root: &Path,
+    config: &Config,
+    embedder: Option<&dyn Embedder>,
+    query: &HybridQuery,
+) -> Result<HybridOutcome> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let fusion = query.fusion.unwrap_or(config.hybrid.fusion);
+    let alpha = query.alpha.unwrap_or(config.hybrid.alpha).clamp(0.0, 1.0);
+
+    let mut fts_query = SearchQuery::new(&query.text);
+    fts_query.language = query.language.clone();
+    fts_query.path_prefix = query.path_prefix.clone();
+    fts_query.kind = query.kind.clone();
+    fts_query.exclude_paths = query.exclude_paths.clone();
+    fts_query.tests = query.tests;
+    fts_query.max_results = query.max_results * 2;
+    fts_query.session_id = query.session_id.clone();
+    fts_query.diff_boost = query.diff_boost;
+    let fts = text::candidates(&root, config, &fts_query)?;
+
+    let sem_results = match embedder {
+        Some(embedder) => {
+            let mut sem_query = SemanticQuery::new(&query.text);
+            sem_query.language = query.language.clone();
+            sem_query.path_prefix = query.path_prefix.clone();
+            sem_query.kind = query.kind.clone();
+            sem_query.exclude_paths = query.exclude_paths.clone();
+            sem_query.tests = query.tests;
+            sem_query.max_results = query.max_results * 2;
+            semantic::search(&root, config, embedder, &sem_query).unwrap_or_default()
+        }
+        None => Vec::new(),
+    };
+
+    // Keyword candidates come in BM25 order; negate so higher is better.
+    let fts_ranked = fts.results.into_iter().map(|r| {
+        let score = -r.rank;
+        (r, score)
+    });
+
+    let mut sources: HashMap<Key, (Option<Source>, Option<Source>)> = HashMap::new();
+    let mut fts_list = Vec::new();
+    let mut sem_list = Vec::with_capacity(sem_results.len());
+    let mut by_key: HashMap<Key, SearchResult> = HashMap::new();
+    for (i, (r, score)) in fts_ranked.enumerate() {
+        let k = key(&r);
+        sources.entry(k.clone()).or_default().0 = Some(Source { position: i + 1, score });
+        fts_list.push((k.clone(), score));
+        by_key.entry(k).or_insert(r);
+    }
+    for (i, r) in sem_results.into_iter().enumerate() {
+        let k = key(&r);
+        let score = -r.rank;
+        sources.entry(k.clone()).or_default().1 = Some(Source { position: i + 1, score });
+        sem_list.push((k.clone(), score));
+        by_key.entry(k).or_insert(r);
+    }
+
+    let fused = fusion::fuse(fusion, config.hybrid.rrf_k, &[(alpha, fts_list), (1.0 - alpha, sem_list)]);
+    let mut results: Vec<SearchResult> = Vec::with_capacity(fused.len());
+    let mut fused_scores = Vec::with_capacity(fused.len());
+    for (k, score) in fused {
+        if let Some(mut r) = by_key.remove(&k) {
+            r.rank = score;
+            results.push(r);
+            fused_scores.push((k, score));
+        }
+    }
+
+    let pipeline = Pipeline::from_config(&config.ranking)?;
+    let ctx = match Store::open_if_exists(&config.storage_dir(&root))? {
+        Some(store) => pipeline.load_context(
+            &store,
+            &root,
+            config,
+            &parse_query(&query.text).text,
+            query.session_id.as_deref(),
+            query.diff_boost,
+        )?,
+        None => RankContext::default(),
+    };
+    let stage_scores = pipeline.stage_scores(&results, &ctx);
+
+    let mut ranked: Vec<(SearchResult, HybridExplanation)> = results
+        .into_iter()
+        .zip(fused_scores)
+        .zip(stage_scores)
+        .map(|((mut r, (k, fused)), stages)| {
+            let boost: f64 = stages.iter().map(|s| s.boost).sum();
+            r.rank = fused + config.hybrid.boost_scale * boost;
+            let (fts, semantic) = sources.remove(&k).unwrap_or_default();
+            let explanation = HybridExplanation { fts, semantic, fused, stages, score: r.rank };
+            (r, explanation)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.rank.total_cmp(&a.0.rank));
+    ranked.truncate(query.max_results);
+    let (results, explanations) = ranked.into_iter().unzip();
+
+    Ok(HybridOutcome {
+        results,
+        explanations,
+        fusion,
+        alpha,
+        or_fallback: fts.or_fallback,
+        expansions: fts.expansions,
+        index_busy: fts.index_busy,
+    })
+}
+
+/// Explanation of a hybrid search: the fusion used, then one line per
+/// result with its sources and every stage that moved it.
+pub fn format_explanations(outcome: &HybridOutcome) -> String {
+    let mut out = format!(
+        "── explain (score = {} fusion of fts and semantic + boosts; higher is better, alpha {}) ──\n",
+        outcome.fusion.name(),
+        outcome.alpha,
+    );
+    if outcome.or_fallback {
+        out.push_str("OR fallback: no chunk matched all terms; FTS results match any term\n");
+    }
+    for variant in &outcome.expansions {
+        out.push_str(&format!("Synonyms: also searched '{variant}'; FTS results only it found rank lower\n"));
+    }
+    for (i, (r, e)) in outcome.results.iter().zip(&outcome.explanations).enumerate() {
+        out.push_str(&format!(
+            "[{i}] {}:{} score {:.3}: {}\n",
+            r.file_path, r.start_line, e.score, e.summary(),
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embed::Embedding;
+
+    /// Anything about waiting points one way, everything else the other.
+    struct StubEmbedder;
+
+    impl Embedder for StubEmbedder {
+        fn embed(&self, text: &str) -> Result<Embedding> {
+            Ok(if text.contains("wait") || text == "retry" { vec![1.0, 0.0] } else { vec![0.0, 1.0] })
+        }
+        fn dimensions(&self) -> usize {
+            2
+        }
+        fn model_name(&self) -> &str {
+            "stub"
+        }
+    }
+
+    #[test]
+    fn fuses_both_retrievers_then_applies_session_boosts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("retry.rs"), "fn retry() {\n    attempt();\n}\n").unwrap();
+        std::fs::write(dir.path().join("backoff.rs"), "fn backoff() {\n    wait();\n}\n").unwrap();
+        let mut config = Config::default();
+        config.hybrid.boost_scale = 0.1;
+        semantic::embed_chunks(dir.path(), &config, &StubEmbedder).unwrap();
+
+        let mut query = HybridQuery::new("retry");
+        query.alpha = Some(0.3);
+        let outcome = search(dir.path(), &config, Some(&StubEmbedder), &query).unwrap();
+        let files: Vec<&str> = outcome.results.iter().map(|r| r.file_path.as_str()).collect();
+        assert_eq!(files, ["retry.rs", "backoff.rs"]);
+        let e = &outcome.explanations[0];
+        assert_eq!(e.fts.map(|s| s.position), Some(1));
+        assert_eq!(e.semantic.map(|s| s.position), Some(2));
+        assert!(outcome.explanations[1].fts.is_none());
+
+        // Focus is applied to the fused scores, so it can reorder them.
+        crate::context::workset::focus(dir.path(), &config, &["backoff.rs".into()], None).unwrap();
+        let outcome = search(dir.path(), &config, Some(&StubEmbedder), &query).unwrap();
+        assert_eq!(outcome.results[0].file_path, "backoff.rs");
+        let e = &outcome.explanations[0];
+        let boost: f64 = e.stages.iter().map(|s| s.boost).sum();
+        assert!((e.score - (e.fused + 0.1 * boost)).abs() < 1e-9);
+        assert!(e.summary().contains("focus +5.00"), "{}", e.summary());
+    }
+
+    #[test]
+    fn keyword_sources_are_bm25_positions_before_boosts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn retry() {\n    retry_later(); retry_now();\n}\n").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "fn attempt() {\n    // retry once\n    go(); go(); go(); go(); go(); go();\n}\n").unwrap();
+        let config = Config::default();
+        let query = HybridQuery::new("retry");
+        let before = search(dir.path(), &config, None, &query).unwrap();
+        let position = |outcome: &HybridOutcome, file: &str| {
+            let i = outcome.results.iter().position(|r| r.file_path == file).unwrap();
+            outcome.explanations[i].fts.unwrap().position
+        };
+        assert_eq!(position(&before, "a.rs"), 1);
+
+        // Focus moves b.rs to the top once, after fusion; its keyword
+        // position is still its BM25 one.
+        crate::context::workset::focus(dir.path(), &config, &["b.rs".into()], None).unwrap();
+        let after = search(dir.path(), &config, None, &query).unwrap();
+        assert_eq!(after.results[0].file_path, "b.rs");
+        assert_eq!(position(&after, "a.rs"), 1);
+        assert_eq!(position(&after, "b.rs"), 2);
+        let focus = after.explanations[0].stages.iter().filter(|s| s.stage == "focus").count();
+        assert_eq!(focus, 1);
+    }
+}
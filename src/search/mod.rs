@@ -1,3 +1,18 @@
+pub mod ann;
+pub mod duplicates;
+pub mod fingerprint;
+pub mod fuzzy;
+pub mod fusion;
+pub mod grep;
+pub mod history;
+pub mod hybrid;
+pub mod quantized;
 pub mod ranking;
 pub mod semantic;
+pub mod similar;
+pub mod snippet;
+pub mod synonyms;
 pub mod text;
+#[cfg(test)]
+mod test_support;
+pub mod workspace;
@@ -0,0 +1,391 @@
+use anyhow::{Context, Result, bail};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::config::{Quantization, VectorConfig};
+use crate::embed::cosine_similarity;
+use crate::search::ann::temp_path;
+use crate::store::sqlite::Store;
+
+/// File name of the quantized vectors, next to `index.db`.
+pub const FILE_NAME: &str = "vectors.quant";
+
+const MAGIC: &[u8; 8] = b"BVECQ\0\0\x02";
+
+/// Magic, mode, dims, count, stride, padding, embeddings generation.
+const HEADER_LEN: usize = 40;
+
+/// Quantized copies of the embeddings in one memory-mapped file, laid out
+/// for a linear scan:
+///
+/// `header | codes (stride bytes per vector) | scales (f32, int8 only) | chunk ids (i64, ascending)`
+///
+/// Int8 codes are the normalized vector scaled so its largest component is
+/// ±127; binary codes keep one sign bit per dimension.
+pub struct VectorFile {
+    mmap: Mmap,
+    mode: Quantization,
+    dims: usize,
+    count: usize,
+    stride: usize,
+    /// `Store::embeddings_generation` the file was built from.
+    generation: i64,
+}
+
+impl VectorFile {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        // SAFETY: the file is only ever replaced by rename, never written in
+        // place, so the mapping stays valid while we hold it.
+        let mmap = unsafe { Mmap::map(&file) }.with_context(|| format!("mapping {}", path.display()))?;
+        if mmap.len() < HEADER_LEN || &mmap[..8] != MAGIC {
+            bail!("{} is not a booger vector file", path.display());
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(mmap[at..at + 4].try_into().unwrap()) as usize;
+        let mode = match u32_at(8) {
+            1 => Quantization::Int8,
+            2 => Quantization::Binary,
+            m => bail!("{}: unknown quantization {m}", path.display()),
+        };
+        let dims = u32_at(12);
+        let count = u64::from_le_bytes(mmap[16..24].try_into().unwrap()) as usize;
+        let stride = u32_at(24);
+        let generation = i64::from_le_bytes(mmap[32..40].try_into().unwrap());
+        if stride != stride_for(mode, dims) || Some(mmap.len()) != file_len(mode, stride, count) {
+            bail!("{} is truncated or corrupt", path.display());
+        }
+        Ok(Self { mmap, mode, dims, count, stride, generation })
+    }
+
+    /// Quantize every embedding in `store` into a new file at `path`,
+    /// replacing it atomically.
+    pub fn build(path: &Path, mode: Quantization, store: &Store) -> Result<()> {
+        let generation = store.embeddings_generation()?;
+        let mut ids = store.embedding_ids()?;
+        ids.sort_unstable();
+        let dims = store.embedding_dims()?.unwrap_or(0);
+
+        let tmp = temp_path(path);
+        let written = Self::write(&tmp, mode, store, &ids, dims, generation).and_then(|()| {
+            std::fs::rename(&tmp, path).with_context(|| format!("replacing {}", path.display()))
+        });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        written
+    }
+
+    fn write(tmp: &Path, mode: Quantization, store: &Store, ids: &[i64], dims: usize, generation: i64) -> Result<()> {
+        let stride = stride_for(mode, dims);
+        {
+            let mut w = BufWriter::new(File::create(tmp).with_context(|| format!("creating {}", tmp.display()))?);
+            w.write_all(&[0; HEADER_LEN])?;
+            let mut written = Vec::with_capacity(ids.len());
+            let mut scales = Vec::new();
+            for batch in ids.chunks(500) {
+                let mut rows = store.embeddings_by_ids(batch)?;
+                rows.sort_unstable_by_key(|(id, _)| *id);
+                for (id, vector) in rows {
+                    // An embedding of the wrong size (see `booger check`) keeps its
+                    // slot but matches nothing.
+                    let vector = if vector.len() == dims { vector } else { vec![0.0; dims] };
+                    let (code, scale) = quantize(mode, &vector);
+                    w.write_all(&code)?;
+                    scales.push(scale);
+                    written.push(id);
+                }
+            }
+            if mode == Quantization::Int8 {
+                for scale in &scales {
+                    w.write_all(&scale.to_le_bytes())?;
+                }
+            }
+            for id in &written {
+                w.write_all(&id.to_le_bytes())?;
+            }
+
+            w.seek(SeekFrom::Start(0))?;
+            w.write_all(MAGIC)?;
+            let mode_tag: u32 = if mode == Quantization::Binary { 2 } else { 1 };
+            for v in [mode_tag, dims as u32] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            w.write_all(&(written.len() as u64).to_le_bytes())?;
+            w.write_all(&(stride as u32).to_le_bytes())?;
+            w.write_all(&[0; 4])?;
+            w.write_all(&generation.to_le_bytes())?;
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Chunk ids, ascending.
+    pub fn ids(&self) -> impl Iterator<Item = i64> + '_ {
+        let start = self.mmap.len() - self.count * 8;
+        self.mmap[start..]
+            .chunks_exact(8)
+            .map(|b| i64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn codes(&self) -> &[u8] {
+        &self.mmap[HEADER_LEN..HEADER_LEN + self.count * self.stride]
+    }
+
+    fn scales(&self) -> impl Iterator<Item = f32> + '_ {
+        let start = HEADER_LEN + self.count * self.stride;
+        self.mmap[start..start + self.count * 4]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    /// The `n` chunk ids whose quantized vectors score highest against
+    /// `query`: approximate cosine for int8, fewest differing sign bits for
+    /// binary.
+    pub fn scan(&self, query: &[f32], n: usize) -> Vec<i64> {
+        if n == 0 || self.is_empty() || query.len() != self.dims {
+            return Vec::new();
+        }
+        let (q, q_scale) = quantize(self.mode, query);
+        let codes = self.codes().chunks_exact(self.stride);
+        let mut scored: Vec<(f32, usize)> = match self.mode {
+            Quantization::Binary => codes
+                .enumerate()
+                .map(|(i, code)| (-(hamming(&q, code) as f32), i))
+                .collect(),
+            _ => codes
+                .zip(self.scales())
+                .enumerate()
+                .map(|(i, (code, scale))| (dot_i8(&q, code) as f32 * q_scale * scale, i))
+                .collect(),
+        };
+        let by_score = |a: &(f32, usize), b: &(f32, usize)| b.0.total_cmp(&a.0);
+        if scored.len() > n {
+            scored.select_nth_unstable_by(n - 1, by_score);
+            scored.truncate(n);
+        }
+        scored.sort_unstable_by(by_score);
+        let ids_start = self.mmap.len() - self.count * 8;
+        scored
+            .into_iter()
+            .map(|(_, i)| {
+                let at = ids_start + i * 8;
+                i64::from_le_bytes(self.mmap[at..at + 8].try_into().unwrap())
+            })
+            .collect()
+    }
+}
+
+fn stride_for(mode: Quantization, dims: usize) -> usize {
+    match mode {
+        Quantization::Binary => dims.div_ceil(64) * 8,
+        _ => dims,
+    }
+}
+
+fn file_len(mode: Quantization, stride: usize, count: usize) -> Option<usize> {
+    let scales = if mode == Quantization::Int8 { 4 } else { 0 };
+    count.checked_mul(stride + scales + 8)?.checked_add(HEADER_LEN)
+}
+
+/// Code and scale of one vector; for int8, `code · other_code × scale ×
+/// other_scale` approximates the cosine similarity.
+fn quantize(mode: Quantization, vector: &[f32]) -> (Vec<u8>, f32) {
+    match mode {
+        Quantization::Binary => {
+            let mut code = vec![0u8; stride_for(mode, vector.len())];
+            for (i, x) in vector.iter().enumerate() {
+                if *x > 0.0 {
+                    code[i / 8] |= 1 << (i % 8);
+                }
+            }
+            (code, 1.0)
+        }
+        _ => {
+            let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let max = vector.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+            if norm == 0.0 || max == 0.0 {
+                return (vec![0; vector.len()], 0.0);
+            }
+            let scale = max / 127.0;
+            let code = vector.iter().map(|x| (x / scale).round() as i8 as u8).collect();
+            (code, scale / norm)
+        }
+    }
+}
+
+fn dot_i8(a: &[u8], b: &[u8]) -> i32 {
+    a.iter().zip(b).map(|(&x, &y)| x as i8 as i32 * y as i8 as i32).sum()
+}
+
+fn hamming(a: &[u8], b: &[u8]) -> u32 {
+    a.chunks_exact(8)
+        .zip(b.chunks_exact(8))
+        .map(|(x, y)| (u64::from_le_bytes(x.try_into().unwrap()) ^ u64::from_le_bytes(y.try_into().unwrap())).count_ones())
+        .sum()
+}
+
+/// Nearest embeddings to `query`: scan the quantized vectors in
+/// `storage_dir` for `k × rescore_factor` candidates, then rank those by
+/// exact cosine similarity. Rebuilds the file first when it no longer
+/// matches the embeddings table.
+pub fn search(
+    store: &Store,
+    storage_dir: &Path,
+    config: &VectorConfig,
+    query: &[f32],
+    k: usize,
+) -> Result<Vec<(i64, f32)>> {
+    let path = storage_dir.join(FILE_NAME);
+    let file = match VectorFile::open(&path) {
+        Ok(file) if is_current(&file, store, config.quantization)? => file,
+        _ => {
+            VectorFile::build(&path, config.quantization, store)?;
+            VectorFile::open(&path)?
+        }
+    };
+
+    let candidates = file.scan(query, k.saturating_mul(config.rescore_factor.max(1)));
+    let mut scored = Vec::with_capacity(candidates.len());
+    for batch in candidates.chunks(500) {
+        for (id, vector) in store.embeddings_by_ids(batch)? {
+            scored.push((id, cosine_similarity(query, &vector)));
+        }
+    }
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(k);
+    Ok(scored)
+}
+
+/// After embedding: rebuild the quantized vectors in `storage_dir`. The
+/// stored embeddings stay full precision, for rescoring and for turning
+/// quantization off again.
+pub fn refresh(store: &Store, storage_dir: &Path, config: &VectorConfig) -> Result<()> {
+    VectorFile::build(&storage_dir.join(FILE_NAME), config.quantization, store)
+}
+
+/// Whether `file` was built from the embeddings currently in `store`,
+/// quantized as `mode`. Every write or delete bumps the store's
+/// generation, so comparing it covers new, removed and rewritten rows.
+fn is_current(file: &VectorFile, store: &Store, mode: Quantization) -> Result<bool> {
+    Ok(file.mode == mode && file.generation == store.embeddings_generation()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::test_support::{chunk, vectors};
+
+    #[test]
+    fn rescored_scan_matches_exact_search() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let data = vectors(300, 32, 5);
+        let file = store.upsert_file("a.rs", "h", 10, Some("rust")).unwrap();
+        let ids = store.insert_chunks(file, &(0..300).map(chunk).collect::<Vec<_>>()).unwrap();
+        for (id, v) in ids.iter().zip(&data) {
+            store.upsert_embedding(*id, "m", v).unwrap();
+        }
+        let exact = |query: &[f32]| {
+            let mut scored: Vec<(i64, f32)> =
+                ids.iter().zip(&data).map(|(id, v)| (*id, cosine_similarity(query, v))).collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            scored.into_iter().take(10).map(|(id, _)| id).collect::<Vec<_>>()
+        };
+
+        for mode in [Quantization::Int8, Quantization::Binary] {
+            let config = VectorConfig { quantization: mode, rescore_factor: 4 };
+            // A stored vector always finds itself, with its exact similarity.
+            let hits = search(&store, dir.path(), &config, &data[42], 3).unwrap();
+            assert_eq!(hits[0].0, ids[42]);
+            assert!((hits[0].1 - 1.0).abs() < 1e-5);
+
+            let mut found = 0;
+            for query in vectors(20, 32, 77) {
+                let expected = exact(&query);
+                let hits = search(&store, dir.path(), &config, &query, 10).unwrap();
+                found += hits.iter().filter(|(id, _)| expected.contains(id)).count();
+            }
+            let recall = found as f64 / 200.0;
+            let min = if mode == Quantization::Int8 { 0.95 } else { 0.6 };
+            assert!(recall >= min, "{mode:?} recall {recall}");
+        }
+    }
+
+    #[test]
+    fn file_is_rebuilt_when_embeddings_or_mode_change() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let data = vectors(3, 768, 9);
+        let a = store.upsert_file("a.rs", "h", 10, Some("rust")).unwrap();
+        let ids = store.insert_chunks(a, &[chunk(1), chunk(2)]).unwrap();
+        store.upsert_embedding(ids[0], "m", &data[0]).unwrap();
+        store.upsert_embedding(ids[1], "m", &data[1]).unwrap();
+        let mut config = VectorConfig { quantization: Quantization::Int8, rescore_factor: 4 };
+
+        search(&store, dir.path(), &config, &data[0], 1).unwrap();
+        let file = VectorFile::open(&path).unwrap();
+        assert_eq!(file.ids().collect::<Vec<_>>(), ids);
+        // One byte per dimension instead of four, plus a scale and the id.
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 40 + 2 * (768 + 4 + 8));
+
+        // Re-indexing the file replaces its chunks: old ids go, new ones come.
+        store.delete_chunks_for_file(a).unwrap();
+        let new_ids = store.insert_chunks(a, &[chunk(3)]).unwrap();
+        store.upsert_embedding(new_ids[0], "m", &data[2]).unwrap();
+        let hits = search(&store, dir.path(), &config, &data[1], 5).unwrap();
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), new_ids);
+
+        // Re-embedding under a new model rewrites the row in place.
+        store.upsert_embedding(new_ids[0], "m2", &data[0]).unwrap();
+        let hits = search(&store, dir.path(), &config, &data[0], 1).unwrap();
+        assert!((hits[0].1 - 1.0).abs() < 1e-5);
+
+        config.quantization = Quantization::Binary;
+        search(&store, dir.path(), &config, &data[2], 1).unwrap();
+        assert_eq!(VectorFile::open(&path).unwrap().mode, Quantization::Binary);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 40 + 96 + 8);
+    }
+
+    #[test]
+    fn refresh_keeps_the_stored_embeddings_full_precision() {
+        use crate::config::Config;
+        use crate::search::semantic;
+        use crate::store::sqlite::SearchFilter;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let data = vectors(50, 64, 21);
+        let file = store.upsert_file("a.rs", "h", 10, Some("rust")).unwrap();
+        let ids = store.insert_chunks(file, &(0..50).map(chunk).collect::<Vec<_>>()).unwrap();
+        for (id, v) in ids.iter().zip(&data) {
+            store.upsert_embedding(*id, "m", v).unwrap();
+        }
+        let mut config = Config::default();
+        config.vectors.quantization = Quantization::Int8;
+        config.ann.min_vectors = 1;
+
+        let generation = store.embeddings_generation().unwrap();
+        refresh(&store, dir.path(), &config.vectors).unwrap();
+        assert_eq!(store.embeddings_generation().unwrap(), generation);
+        let stored = store.embeddings_by_ids(&ids[..2]).unwrap();
+        assert_eq!(stored.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>(), data[..2]);
+
+        // Quantization wins over the ANN index even above its threshold.
+        let filter = SearchFilter::new(None, None, None);
+        let hits = semantic::nearest(&store, dir.path(), &config, &data[7], &filter, 1, false).unwrap();
+        assert_eq!(hits[0].0, ids[7]);
+        assert!(dir.path().join(FILE_NAME).exists());
+        assert!(!dir.path().join(crate::search::ann::FILE_NAME).exists());
+    }
+}
@@ -0,0 +1,772 @@
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::{Config, RankingConfig};
+use crate::context::intent;
+use crate::git::diff::{self, BranchChanges};
+use crate::search::semantic;
+use crate::search::text::parse_query;
+use crate::store::sqlite::{
+    Annotation, CachedBranchChanges, Feedback, FileHistory, Intent, SearchFilter, SearchResult, Store,
+};
+use crate::store::tokens;
+
+/// Stage names accepted in `[ranking] stages`.
+pub const STAGES: &[&str] = &[
+    "code", "oversize", "focus", "visited", "annotation", "diff", "feedback", "intent", "churn", "recency",
+];
+
+/// How many of the latest feedback entries the `feedback` stage reads.
+const FEEDBACK_LIMIT: usize = 2000;
+
+/// How many chunks nearest an intent's embedding the `intent` stage boosts.
+const INTENT_NEIGHBOURS: usize = 50;
+
+/// Volatile context the ranking stages read: the session's focused and
+/// visited paths, active annotations and intents and, if loaded, the
+/// chunks nearest the intents, the branch's changes, feedback on similar
+/// queries and the files' git history.
+#[derive(Default)]
+pub struct RankContext {
+    pub focus_paths: Vec<String>,
+    pub visited_paths: Vec<String>,
+    pub annotations: Vec<Annotation>,
+    pub intents: Vec<Intent>,
+    /// Cosine similarity to an intent's embedding of the chunks nearest
+    /// it, by (path, start line).
+    pub intent_neighbours: HashMap<(String, i64), f64>,
+    pub changes: Option<BranchChanges>,
+    /// Feedback on queries similar to the current one, each with its
+    /// similarity (negative for "not useful").
+    pub feedback: Vec<(f64, Feedback)>,
+    pub history: Option<History>,
+}
+
+/// Git history of the indexed files, with the repository-wide maxima the
+/// `churn` and `recency` stages scale against.
+pub struct History {
+    files: HashMap<String, FileHistory>,
+    max_commits: u32,
+    newest: i64,
+}
+
+impl History {
+    pub fn new(files: HashMap<String, FileHistory>) -> Self {
+        let max_commits = files.values().map(|h| h.commits).max().unwrap_or(0);
+        let newest = files.values().map(|h| h.last_modified).max().unwrap_or(0);
+        Self { files, max_commits, newest }
+    }
+}
+
+impl RankContext {
+    pub fn load(store: &Store, session_id: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            focus_paths: store.get_focus_paths(session_id)?,
+            visited_paths: store.get_visited_paths(session_id)?,
+            annotations: store.get_annotations(None, session_id)?,
+            intents: store.get_intents(session_id)?,
+            intent_neighbours: HashMap::new(),
+            changes: None,
+            feedback: Vec::new(),
+            history: None,
+        })
+    }
+
+    /// Load the feedback recorded for queries sharing terms with `query`.
+    pub fn load_feedback(&mut self, store: &Store, query: &str) -> Result<()> {
+        let terms = query_terms(query);
+        self.feedback = store
+            .get_feedback(FEEDBACK_LIMIT)?
+            .into_iter()
+            .filter_map(|f| {
+                let similarity = jaccard(&terms, &query_terms(&f.query));
+                let sign = if f.useful { 1.0 } else { -1.0 };
+                (similarity > 0.0).then_some((sign * similarity, f))
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Load the chunks nearest each embedded intent, for the `intent` stage.
+    /// Intents embedded by another model (other dimensions) are skipped.
+    /// Neighbours are stored with the intent and only looked up again once
+    /// the embeddings have changed.
+    pub fn load_intent_neighbours(&mut self, store: &Store, storage_dir: &Path, config: &Config) -> Result<()> {
+        let Some(dims) = store.embedding_dims()? else {
+            return Ok(());
+        };
+        let generation = store.embeddings_generation()?;
+        let filter = SearchFilter::new(None, None, None);
+        for intent in &self.intents {
+            let Some(vector) = intent.embedding.as_ref().filter(|v| v.len() == dims) else {
+                continue;
+            };
+            let neighbours = if intent.neighbours_generation == Some(generation) {
+                store.intent_neighbours(intent.id)?
+            } else {
+                let found: Vec<(String, i64, f64)> =
+                    semantic::nearest(store, storage_dir, config, vector, &filter, INTENT_NEIGHBOURS, false)?
+                        .into_iter()
+                        .map(|(_, r)| (r.file_path, r.start_line, -r.rank))
+                        .collect();
+                // Best effort: a busy or read-only database looks them up
+                // again next time.
+                let _ = store.set_intent_neighbours(intent.id, generation, &found);
+                found
+            };
+            for (path, line, found) in neighbours {
+                let similarity = self.intent_neighbours.entry((path, line)).or_insert(0.0);
+                *similarity = similarity.max(found);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the files' git history, as last stored by indexing, for the
+    /// `churn` and `recency` stages.
+    pub fn load_history(&mut self, store: &Store) -> Result<()> {
+        self.history = Some(History::new(store.file_history()?));
+        Ok(())
+    }
+
+    /// Load what changed on the current branch for the `diff` stage. The
+    /// changes are stored in the index and reused, across processes, while
+    /// HEAD and the index generation are unchanged, for up to
+    /// `diff_ttl_secs`, so searches don't re-diff the branch every time.
+    pub fn load_branch_changes(&mut self, store: &Store, root: &Path, config: &RankingConfig) -> Result<()> {
+        let Some(head) = diff::head_commit(root) else {
+            self.changes = Some(BranchChanges::default());
+            return Ok(());
+        };
+        let base = config.diff_base.as_deref().unwrap_or("");
+        let index_generation = store.index_generation()?;
+        let now = chrono::Utc::now().timestamp();
+        let ttl = i64::try_from(config.diff_ttl_secs).unwrap_or(i64::MAX);
+        if let Some(cached) = store.branch_changes(base)?
+            && cached.head == head
+            && cached.index_generation == index_generation
+            && now - cached.computed_at < ttl
+            && let Ok(changes) = serde_json::from_str(&cached.changes)
+        {
+            self.changes = Some(changes);
+            return Ok(());
+        }
+        let changes = diff::branch_changes(root, config.diff_base.as_deref());
+        if let Ok(json) = serde_json::to_string(&changes) {
+            // Best effort: a busy or read-only database diffs again next time.
+            let cached = CachedBranchChanges { head, index_generation, computed_at: now, changes: json };
+            let _ = store.set_branch_changes(base, &cached);
+        }
+        self.changes = Some(changes);
+        Ok(())
+    }
+}
+
+/// What a stage sees besides the result itself.
+pub struct Batch<'a> {
+    pub ctx: &'a RankContext,
+    /// Average chunk length, in lines, over the results being ranked.
+    pub avg_lines: f64,
+}
+
+/// One scoring step. Returns a boost in BM25 rank units: positive moves a
+/// result up, negative moves it down.
+pub trait Stage: Send + Sync {
+    /// Name shown in explanations.
+    fn name(&self) -> &str;
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64;
+}
+
+/// One stage's contribution to a result's rank.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageScore {
+    pub stage: String,
+    pub boost: f64,
+}
+
+/// How a result's final rank was reached.
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    /// Rank before re-ranking (BM25 for FTS; lower is better).
+    pub base: f64,
+    /// Boost of each stage, in pipeline order. Boosts are subtracted.
+    pub stages: Vec<StageScore>,
+    /// Final rank: `base` minus every boost.
+    pub rank: f64,
+}
+
+impl Explanation {
+    /// `bm25 -4.12, code +3.00, focus +5.00`: the base rank and every
+    /// stage that changed it.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("bm25 {:.2}", self.base)];
+        for s in self.stages.iter().filter(|s| s.boost != 0.0) {
+            parts.push(format!("{} {:+.2}", s.stage, s.boost));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Structural chunks (functions, structs, ...) are more useful than
+/// raw/doc chunks for a code search tool.
+struct CodeBoost(f64);
+
+impl Stage for CodeBoost {
+    fn name(&self) -> &str {
+        "code"
+    }
+
+    fn boost(&self, result: &SearchResult, _: &Batch) -> f64 {
+        if matches!(result.chunk_kind.as_str(), "raw" | "module") { 0.0 } else { self.0 }
+    }
+}
+
+/// A 272-line README matches everything but is rarely what you want.
+/// Penalty scales with how much larger than average the chunk is.
+struct OversizePenalty {
+    threshold: f64,
+    factor: f64,
+    max: f64,
+}
+
+impl Stage for OversizePenalty {
+    fn name(&self) -> &str {
+        "oversize"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let lines = (result.end_line - result.start_line + 1) as f64;
+        if batch.avg_lines > 0.0 && lines > batch.avg_lines * self.threshold {
+            -(lines / batch.avg_lines * self.factor).min(self.max)
+        } else {
+            0.0
+        }
+    }
+}
+
+struct FocusBoost(f64);
+
+impl Stage for FocusBoost {
+    fn name(&self) -> &str {
+        "focus"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let focused = batch.ctx.focus_paths.iter().any(|p| result.file_path.starts_with(p.as_str()));
+        if focused { self.0 } else { 0.0 }
+    }
+}
+
+struct VisitedPenalty(f64);
+
+impl Stage for VisitedPenalty {
+    fn name(&self) -> &str {
+        "visited"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let visited = batch.ctx.visited_paths.iter().any(|p| result.file_path.starts_with(p.as_str()));
+        if visited { -self.0 } else { 0.0 }
+    }
+}
+
+/// Annotated files, or symbols annotated by name.
+struct AnnotationBoost(f64);
+
+impl Stage for AnnotationBoost {
+    fn name(&self) -> &str {
+        "annotation"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let annotated = batch.ctx.annotations.iter().any(|a| {
+            result.file_path == a.target || result.chunk_name.as_deref() == Some(a.target.as_str())
+        });
+        if annotated { self.0 } else { 0.0 }
+    }
+}
+
+/// Files changed on the current branch, and more so the changed symbols.
+/// Nothing unless the branch changes were loaded into the context.
+struct DiffBoost {
+    file: f64,
+    symbol: f64,
+}
+
+impl Stage for DiffBoost {
+    fn name(&self) -> &str {
+        "diff"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let Some(changes) = &batch.ctx.changes else {
+            return 0.0;
+        };
+        let mut boost = 0.0;
+        if changes.files.contains(&result.file_path) {
+            boost += self.file;
+        }
+        if changes.touches_lines(&result.file_path, result.start_line, result.end_line) {
+            boost += self.symbol;
+        }
+        boost
+    }
+}
+
+/// Results marked useful for similar queries, penalized if marked not
+/// useful. Each entry counts by its query's similarity to this one; the
+/// boost approaches the weight as a result keeps being useful.
+struct FeedbackBoost(f64);
+
+impl Stage for FeedbackBoost {
+    fn name(&self) -> &str {
+        "feedback"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let score: f64 = batch
+            .ctx
+            .feedback
+            .iter()
+            .filter(|(_, f)| feedback_matches(f, result))
+            .map(|(weight, _)| weight)
+            .sum();
+        self.0 * score / (1.0 + score.abs())
+    }
+}
+
+/// Whether feedback is about this result: same file and, if given, the
+/// chunk's name or a line inside it.
+fn feedback_matches(f: &Feedback, result: &SearchResult) -> bool {
+    if f.path != result.file_path {
+        return false;
+    }
+    match (&f.symbol, f.line) {
+        (Some(symbol), _) => result.chunk_name.as_ref() == Some(symbol),
+        (None, Some(line)) => (result.start_line..=result.end_line).contains(&line),
+        (None, None) => true,
+    }
+}
+
+/// Query terms (see `tokens::query_terms`) of a query without its
+/// qualifiers, sorted.
+fn query_terms(query: &str) -> Vec<String> {
+    let mut terms = tokens::query_terms(&parse_query(query).text);
+    terms.sort();
+    terms
+}
+
+/// Shared terms over all terms of two sorted, deduplicated lists.
+fn jaccard(a: &[String], b: &[String]) -> f64 {
+    let shared = a.iter().filter(|t| b.binary_search(t).is_ok()).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 { 0.0 } else { shared as f64 / total as f64 }
+}
+
+/// Results on the session's intents: the share of an intent's key terms
+/// they contain or, if the intent was embedded, their similarity to it
+/// when among its nearest chunks, whichever is higher. A soft prior, so
+/// its weight is kept below focus.
+struct IntentBoost(f64);
+
+impl Stage for IntentBoost {
+    fn name(&self) -> &str {
+        "intent"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        if batch.ctx.intents.is_empty() {
+            return 0.0;
+        }
+        let terms = batch
+            .ctx
+            .intents
+            .iter()
+            .map(|i| intent::term_share(&i.terms, result))
+            .fold(0.0, f64::max);
+        let key = (result.file_path.clone(), result.start_line);
+        let similarity = batch.ctx.intent_neighbours.get(&key).copied().unwrap_or(0.0);
+        self.0 * terms.max(similarity)
+    }
+}
+
+/// Files committed to often in recent history. Scales with the log of the
+/// file's commit count, relative to the most-committed file.
+struct ChurnBoost(f64);
+
+impl Stage for ChurnBoost {
+    fn name(&self) -> &str {
+        "churn"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let Some(history) = &batch.ctx.history else {
+            return 0.0;
+        };
+        match history.files.get(&result.file_path) {
+            Some(h) if history.max_commits > 0 => {
+                self.0 * (1.0 + h.commits as f64).ln() / (1.0 + history.max_commits as f64).ln()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Recently committed files. Halves every `half_life_days` before the
+/// newest commit, so results do not drift while the repository is idle.
+struct RecencyBoost {
+    weight: f64,
+    half_life_days: f64,
+}
+
+impl Stage for RecencyBoost {
+    fn name(&self) -> &str {
+        "recency"
+    }
+
+    fn boost(&self, result: &SearchResult, batch: &Batch) -> f64 {
+        let Some(history) = &batch.ctx.history else {
+            return 0.0;
+        };
+        match history.files.get(&result.file_path) {
+            Some(h) if self.half_life_days > 0.0 => {
+                let age_days = (history.newest - h.last_modified) as f64 / 86_400.0;
+                self.weight * 0.5f64.powf(age_days / self.half_life_days)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// An ordered sequence of ranking stages, built from `[ranking]`.
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    pub fn from_config(config: &RankingConfig) -> Result<Self> {
+        let mut stages: Vec<Box<dyn Stage>> = Vec::new();
+        for name in &config.stages {
+            stages.push(match name.as_str() {
+                "code" => Box::new(CodeBoost(config.code_boost)),
+                "oversize" => Box::new(OversizePenalty {
+                    threshold: config.oversize_threshold,
+                    factor: config.oversize_factor,
+                    max: config.oversize_max,
+                }),
+                "focus" => Box::new(FocusBoost(config.focus_boost)),
+                "visited" => Box::new(VisitedPenalty(config.visited_penalty)),
+                "annotation" => Box::new(AnnotationBoost(config.annotation_boost)),
+                "diff" => Box::new(DiffBoost {
+                    file: config.diff_file_boost,
+                    symbol: config.diff_symbol_boost,
+                }),
+                "feedback" => Box::new(FeedbackBoost(config.feedback_boost)),
+                "intent" => Box::new(IntentBoost(config.intent_boost)),
+                "churn" => Box::new(ChurnBoost(config.churn_boost)),
+                "recency" => Box::new(RecencyBoost {
+                    weight: config.recency_boost,
+                    half_life_days: config.recency_half_life_days,
+                }),
+                other => bail!(
+                    "unknown ranking stage '{other}' (expected one of: {})",
+                    STAGES.join(", ")
+                ),
+            });
+        }
+        Ok(Self { stages })
+    }
+
+    /// Whether a stage with this name runs.
+    pub fn uses(&self, name: &str) -> bool {
+        self.stages.iter().any(|s| s.name() == name)
+    }
+
+    /// The context this pipeline's stages read for `query`: always the
+    /// session's, plus the branch changes (unless `diff_boost` is off),
+    /// the intents' nearest chunks, feedback and git history when a stage
+    /// uses them.
+    pub fn load_context(
+        &self,
+        store: &Store,
+        root: &Path,
+        config: &Config,
+        query: &str,
+        session_id: Option<&str>,
+        diff_boost: bool,
+    ) -> Result<RankContext> {
+        let mut ctx = RankContext::load(store, session_id)?;
+        if diff_boost && self.uses("diff") {
+            ctx.load_branch_changes(store, root, &config.ranking)?;
+        }
+        if self.uses("intent") && ctx.intents.iter().any(|i| i.embedding.is_some()) {
+            ctx.load_intent_neighbours(store, &config.storage_dir(root), config)?;
+        }
+        if self.uses("feedback") {
+            ctx.load_feedback(store, query)?;
+        }
+        if self.uses("churn") || self.uses("recency") {
+            ctx.load_history(store)?;
+        }
+        Ok(ctx)
+    }
+
+    /// Add a custom stage after the configured ones.
+    pub fn push(&mut self, stage: Box<dyn Stage>) {
+        self.stages.push(stage);
+    }
+
+    /// Each stage's boost, per result.
+    fn stage_boosts(&self, results: &[SearchResult], ctx: &RankContext) -> Vec<Vec<f64>> {
+        let avg_lines = if results.is_empty() {
+            0.0
+        } else {
+            let total: f64 = results.iter().map(|r| (r.end_line - r.start_line + 1) as f64).sum();
+            total / results.len() as f64
+        };
+        let batch = Batch { ctx, avg_lines };
+        results
+            .iter()
+            .map(|r| self.stages.iter().map(|s| s.boost(r, &batch)).collect())
+            .collect()
+    }
+
+    /// Total boost of every stage, per result.
+    pub fn boosts(&self, results: &[SearchResult], ctx: &RankContext) -> Vec<f64> {
+        self.stage_boosts(results, ctx).iter().map(|b| b.iter().sum()).collect()
+    }
+
+    /// Each stage's boost, by name, per result.
+    pub fn stage_scores(&self, results: &[SearchResult], ctx: &RankContext) -> Vec<Vec<StageScore>> {
+        self.stage_boosts(results, ctx)
+            .into_iter()
+            .map(|boosts| {
+                self.stages
+                    .iter()
+                    .zip(boosts)
+                    .map(|(s, boost)| StageScore { stage: s.name().to_string(), boost })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Apply the boosts to FTS ranks (lower is better) and re-sort.
+    /// Returns how each result's rank was reached, in the new order.
+    pub fn rerank(&self, results: &mut Vec<SearchResult>, ctx: &RankContext) -> Vec<Explanation> {
+        let scores = self.stage_scores(results, ctx);
+        let mut ranked: Vec<(SearchResult, Explanation)> = std::mem::take(results)
+            .into_iter()
+            .zip(scores)
+            .map(|(mut result, stages)| {
+                let base = result.rank;
+                result.rank -= stages.iter().map(|s| s.boost).sum::<f64>();
+                let rank = result.rank;
+                (result, Explanation { base, stages, rank })
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.0.rank.partial_cmp(&b.0.rank).unwrap_or(std::cmp::Ordering::Equal));
+
+        let (sorted, explanations) = ranked.into_iter().unzip();
+        *results = sorted;
+        explanations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, kind: &str, name: Option<&str>, lines: i64, rank: f64) -> SearchResult {
+        SearchResult {
+            file_path: path.into(),
+            language: Some("rust".into()),
+            chunk_kind: kind.into(),
+            chunk_name: name.map(String::from),
+            signature: None,
+            start_line: 1,
+            end_line: lines,
+            content: String::new(),
+            rank,
+        }
+    }
+
+    fn names(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.file_path.as_str()).collect()
+    }
+
+    #[test]
+    fn default_stages_match_previous_weights() {
+        let pipeline = Pipeline::from_config(&RankingConfig::default()).unwrap();
+        let ctx = RankContext { focus_paths: vec!["src/a".into()], ..Default::default() };
+        let results = vec![
+            result("src/a.rs", "function", Some("f"), 10, -1.0),
+            result("README.md", "raw", None, 100, -1.0),
+            result("src/b.rs", "struct", None, 10, -1.0),
+        ];
+        // avg 40 lines: README is 2.5x → penalty 1.25; focus +5; code +3.
+        assert_eq!(pipeline.boosts(&results, &ctx), [8.0, -1.25, 3.0]);
+    }
+
+    #[test]
+    fn stages_and_weights_follow_config() {
+        let config = RankingConfig {
+            stages: vec!["visited".into(), "annotation".into()],
+            visited_penalty: 10.0,
+            ..Default::default()
+        };
+        let pipeline = Pipeline::from_config(&config).unwrap();
+        let ctx = RankContext { visited_paths: vec!["src/old".into()], ..Default::default() };
+        let mut results = vec![
+            result("src/old.rs", "function", None, 5, -9.0),
+            result("README.md", "raw", None, 5, -2.0),
+        ];
+        let explained = pipeline.rerank(&mut results, &ctx);
+        assert_eq!(names(&results), ["README.md", "src/old.rs"]);
+        assert_eq!(results[1].rank, 1.0);
+        assert_eq!(explained[1].base, -9.0);
+        assert_eq!(explained[1].rank, 1.0);
+        assert_eq!(explained[1].summary(), "bm25 -9.00, visited -10.00");
+        assert_eq!(explained[0].stages.len(), 2);
+    }
+
+    #[test]
+    fn diff_stage_boosts_changed_files_and_symbols() {
+        let config = RankingConfig { stages: vec!["diff".into()], ..Default::default() };
+        let pipeline = Pipeline::from_config(&config).unwrap();
+        assert!(pipeline.uses("diff") && !pipeline.uses("focus"));
+
+        let mut changes = BranchChanges::default();
+        changes.files.insert("src/a.rs".into());
+        changes.symbols.insert("src/a.rs".into(), vec![(8, 12)]);
+        let results = vec![
+            result("src/a.rs", "function", None, 10, -1.0),
+            result("src/a.rs", "function", None, 5, -1.0),
+            result("src/b.rs", "function", None, 10, -1.0),
+        ];
+        // Changes not loaded (diff_boost off): the stage does nothing.
+        assert_eq!(pipeline.boosts(&results, &RankContext::default()), [0.0, 0.0, 0.0]);
+
+        let ctx = RankContext { changes: Some(changes), ..Default::default() };
+        assert_eq!(pipeline.boosts(&results, &ctx), [3.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn feedback_stage_weighs_entries_by_query_similarity() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        store.add_feedback("parse config", "src/a.rs", Some("parse_config"), None, true, None).unwrap();
+        store.add_feedback("parse config", "src/a.rs", Some("parse_config"), None, true, None).unwrap();
+        store.add_feedback("load config", "src/b.rs", None, None, false, None).unwrap();
+        store.add_feedback("render html", "src/c.rs", None, None, true, None).unwrap();
+
+        let config = RankingConfig { stages: vec!["feedback".into()], ..Default::default() };
+        let pipeline = Pipeline::from_config(&config).unwrap();
+        let mut ctx = RankContext::default();
+        ctx.load_feedback(&store, "parse config").unwrap();
+        let results = vec![
+            result("src/a.rs", "function", Some("parse_config"), 5, -1.0),
+            result("src/a.rs", "function", Some("other"), 5, -1.0),
+            result("src/b.rs", "function", None, 5, -1.0),
+            result("src/c.rs", "function", None, 5, -1.0),
+        ];
+        // a: two exact matches, 3 * 2/3; b: 1/3 of the terms, not useful, 3 * -(1/3)/(4/3).
+        assert_eq!(pipeline.boosts(&results, &ctx), [2.0, 0.0, -0.75, 0.0]);
+    }
+
+    #[test]
+    fn intent_stage_boosts_results_on_the_session_goal() {
+        let config = RankingConfig { stages: vec!["intent".into()], ..Default::default() };
+        let pipeline = Pipeline::from_config(&config).unwrap();
+        let goal = "migrate embeddings to the OpenAI backend";
+        let ctx = RankContext {
+            intents: vec![Intent {
+                id: 1,
+                goal: goal.into(),
+                terms: intent::key_terms(goal),
+                embedding: None,
+                session_id: None,
+                created_at: String::new(),
+                neighbours_generation: None,
+            }],
+            intent_neighbours: HashMap::from([(("src/db.rs".to_string(), 1), 0.8)]),
+            ..Default::default()
+        };
+        let mut results = vec![
+            result("src/embed/openai.rs", "function", Some("embedding_backend"), 5, -1.0),
+            result("src/embed/ollama.rs", "function", Some("embed"), 5, -1.0),
+            result("src/db.rs", "function", None, 5, -1.0),
+        ];
+        results[1].content = "fn embed() { /* migration */ }".into();
+        // Terms migrate, embeddings, openai, open, ai, backend. openai.rs has
+        // embeddings, openai and backend; ollama.rs has embed(ding) and
+        // migrat(ion); db.rs none, but is near the intent's embedding.
+        assert_eq!(pipeline.boosts(&results, &ctx), [1.0, 2.0 / 3.0, 1.6]);
+        assert_eq!(pipeline.boosts(&results, &RankContext::default()), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn intent_neighbours_are_stored_until_the_embeddings_change() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let config = Config::default();
+        let file = store.upsert_file("src/db.rs", "h", 10, Some("rust")).unwrap();
+        let chunk = crate::search::test_support::chunk;
+        let ids = store.insert_chunks(file, &[chunk(1), chunk(9)]).unwrap();
+        store.upsert_embedding(ids[0], "m", &[1.0, 0.0]).unwrap();
+        store.upsert_embedding(ids[1], "m", &[0.0, 1.0]).unwrap();
+        let intent = store.set_intent("goal", &["goal".into()], Some(&[1.0, 0.0]), None).unwrap();
+        let load = || {
+            let mut ctx = RankContext::load(&store, None).unwrap();
+            ctx.load_intent_neighbours(&store, dir.path(), &config).unwrap();
+            ctx.intent_neighbours
+        };
+
+        let found = load();
+        assert!((found[&("src/db.rs".to_string(), 1)] - 1.0).abs() < 1e-6);
+        let generation = store.embeddings_generation().unwrap();
+        assert_eq!(store.get_intents(None).unwrap()[0].neighbours_generation, Some(generation));
+
+        // Stored neighbours are used as they are while nothing changed...
+        store.set_intent_neighbours(intent, generation, &[("src/db.rs".into(), 9, 0.5)]).unwrap();
+        assert_eq!(load(), HashMap::from([(("src/db.rs".to_string(), 9), 0.5)]));
+
+        // ...and looked up again once an embedding is rewritten.
+        store.upsert_embedding(ids[1], "m", &[1.0, 0.1]).unwrap();
+        let found = load();
+        assert!(found[&("src/db.rs".to_string(), 9)] > 0.99);
+    }
+
+    #[test]
+    fn churn_and_recency_stages_follow_git_history() {
+        let config = RankingConfig {
+            stages: vec!["churn".into(), "recency".into()],
+            recency_half_life_days: 10.0,
+            ..Default::default()
+        };
+        let pipeline = Pipeline::from_config(&config).unwrap();
+        let day = 86_400;
+        let ctx = RankContext {
+            history: Some(History::new(HashMap::from([
+                ("src/hot.rs".to_string(), FileHistory { commits: 15, last_modified: 100 * day }),
+                ("src/old.rs".to_string(), FileHistory { commits: 3, last_modified: 90 * day }),
+            ]))),
+            ..Default::default()
+        };
+        let results = vec![
+            result("src/hot.rs", "function", None, 5, -1.0),
+            result("src/old.rs", "function", None, 5, -1.0),
+            result("src/new.rs", "function", None, 5, -1.0),
+        ];
+        // hot: churn 1.0 + recency 2.0; old: ln 4 / ln 16 = 0.5, ten days = one half-life.
+        assert_eq!(pipeline.boosts(&results, &ctx), [3.0, 1.5, 0.0]);
+    }
+
+    #[test]
+    fn unknown_stage_is_an_error() {
+        let config = RankingConfig { stages: vec!["popularity".into()], ..Default::default() };
+        let err = Pipeline::from_config(&config).err().unwrap();
+        assert!(err.to_string().contains("unknown ranking stage 'popularity'"));
+    }
+}
@@ -1,15 +1,22 @@
 use anyhow::{Context, Result};
 use std::path::Path;
 
-use crate::config::Config;
+use crate::config::{Config, Quantization};
 use crate::embed::{cosine_similarity, Embedder};
-use crate::store::sqlite::{SearchResult, Store};
+use crate::search::{ann, quantized};
+use crate::store::sqlite::{SearchFilter, SearchResult, Store, TestScope};
 
 pub struct SemanticQuery {
     pub text: String,
     pub max_results: usize,
     pub path_prefix: Option<String>,
     pub language: Option<String>,
+    pub kind: Option<String>,
+    pub exclude_paths: Vec<String>,
+    pub tests: TestScope,
+    /// Compare against every stored embedding instead of using
+    /// the ANN index or quantized vectors.
+    pub exact: bool,
 }
 
 impl SemanticQuery {
@@ -19,6 +26,10 @@ impl SemanticQuery {
             max_results: 20,
             path_prefix: None,
             language: None,
+            kind: None,
+            exclude_paths: Vec::new(),
+            tests: TestScope::Include,
+            exact: false,
         }
     }
 }
@@ -62,12 +73,23 @@ pub fn embed_chunks(root: &Path, config: &Config, embedder: &dyn Embedder) -> Re
     for batch in pending.chunks(batch_size) {
         let mut batch_entries: Vec<(i64, &str, Vec<f32>)> = Vec::new();
 
-        for (id, content) in batch {
-            match embedder.embed(content) {
-                Ok(emb) => batch_entries.push((*id, model, emb)),
-                Err(e) => {
-                    eprintln!("  warning: skipping chunk {id} ({}): {e}", &content[..content.len().min(50)]);
-                    continue;
+        // One request per batch where the backend supports it; if the batch
+        // fails, retry chunk by chunk so one bad input only skips itself.
+        let texts: Vec<&str> = batch.iter().map(|(_, content)| content.as_str()).collect();
+        match embedder.embed_batch(&texts) {
+            Ok(embeddings) => {
+                batch_entries.extend(batch.iter().zip(embeddings).map(|((id, _), emb)| (*id, model, emb)));
+            }
+            Err(_) => {
+                for (id, content) in batch {
+                    match embedder.embed(content) {
+                        Ok(emb) => batch_entries.push((*id, model, emb)),
+                        Err(e) => {
+                            let end = content.char_indices().nth(50).map_or(content.len(), |(i, _)| i);
+                            eprintln!("  warning: skipping chunk {id} ({}): {e}", &content[..end]);
+                            continue;
+                        }
+                    }
                 }
             }
         }
@@ -81,6 +103,13 @@ pub fn embed_chunks(root: &Path, config: &Config, embedder: &dyn Embedder) -> Re
         newly_embedded += entries.len();
     }
 
+    // Build the quantized vectors or ANN index now rather than on the first query.
+    if config.vectors.quantization != Quantization::None {
+        quantized::refresh(&store, &storage_dir, &config.vectors)?;
+    } else if already_embedded + newly_embedded as i64 >= config.ann.min_vectors as i64 {
+        ann::refresh(&store, &storage_dir, &config.ann)?;
+    }
+
     Ok(EmbedStats {
         total_chunks,
         embedded: already_embedded + newly_embedded as i64,
@@ -88,7 +117,10 @@ pub fn embed_chunks(root: &Path, config: &Config, embedder: &dyn Embedder) -> Re
     })
 }
 
-/// Semantic search: embed the query, then find nearest chunks by cosine similarity.
+/// Semantic search: embed the query, then find nearest chunks by cosine
+/// similarity. Uses the quantized vectors if `[vectors]` enables them, else
+/// the ANN index once there are `[ann] min_vectors` embeddings, unless
+/// `query.exact` asks for a full scan.
 pub fn search(
     root: &Path,
     config: &Config,
@@ -105,43 +137,81 @@ pub fn search(
         None => return Ok(Vec::new()),
     };
 
-    let all_embeddings = store.all_embeddings()?;
-    if all_embeddings.is_empty() {
+    if store.embedding_count()? == 0 {
         return Ok(Vec::new());
     }
-
     let query_embedding = embedder.embed(&query.text)?;
 
-    // Score all chunks by cosine similarity
-    let mut scored: Vec<(i64, f32)> = all_embeddings
-        .iter()
-        .map(|(id, emb)| (*id, cosine_similarity(&query_embedding, emb)))
-        .collect();
+    let mut filter = SearchFilter::new(query.language.as_deref(), query.path_prefix.as_deref(), query.kind.as_deref());
+    filter.exclude_paths = query.exclude_paths.clone();
+    filter.tests = query.tests;
 
-    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    scored.truncate(query.max_results * 3);
-
-    // Resolve chunk IDs to full results, applying filters
-    let mut results = Vec::new();
-    for (chunk_id, similarity) in scored {
-        if let Some(mut result) = store.chunk_by_id(chunk_id)? {
-            if let Some(ref lang) = query.language {
-                if result.language.as_deref() != Some(lang.as_str()) {
-                    continue;
-                }
-            }
-            if let Some(ref prefix) = query.path_prefix {
-                if !result.file_path.starts_with(prefix.as_str()) {
-                    continue;
+    let hits = nearest(&store, &storage_dir, config, &query_embedding, &filter, query.max_results, query.exact)?;
+    Ok(hits.into_iter().map(|(_, r)| r).collect())
+}
+
+/// The `max_results` chunks selected by `filter` whose embeddings are most
+/// similar to `vector`, with their chunk ids; `rank` is −similarity. Uses
+/// the ANN index or quantized vectors as `search` does, unless `exact`.
+pub fn nearest(
+    store: &Store,
+    storage_dir: &Path,
+    config: &Config,
+    vector: &[f32],
+    filter: &SearchFilter,
+    max_results: usize,
+    exact: bool,
+) -> Result<Vec<(i64, SearchResult)>> {
+    let count = store.embedding_count()? as usize;
+    if count == 0 || max_results == 0 {
+        return Ok(Vec::new());
+    }
+    let use_quantized = !exact && config.vectors.quantization != Quantization::None;
+    let use_ann = !exact && !use_quantized && count >= config.ann.min_vectors;
+    let mut all_embeddings = None;
+
+    // Filters are applied after the nearest-neighbour lookup; when they drop
+    // too many candidates, look further out.
+    let mut k = max_results * 3;
+    loop {
+        let scored = if use_quantized {
+            quantized::search(store, storage_dir, &config.vectors, vector, k)?
+        } else if use_ann {
+            ann::search(store, storage_dir, &config.ann, vector, k)?
+        } else {
+            let all = match &all_embeddings {
+                Some(all) => all,
+                None => all_embeddings.insert(store.all_embeddings()?),
+            };
+            brute_force(all, vector, k)
+        };
+        let exhausted = scored.len() < k;
+
+        // Resolve chunk IDs to full results, applying filters
+        let mut results = Vec::new();
+        for (chunk_id, similarity) in scored {
+            if let Some(mut result) = store.chunk_by_id(chunk_id, filter)? {
+                result.rank = -(similarity as f64);
+                results.push((chunk_id, result));
+                if results.len() >= max_results {
+                    break;
                 }
             }
-            result.rank = -(similarity as f64);
-            results.push(result);
-            if results.len() >= query.max_results {
-                break;
-            }
         }
+        if results.len() >= max_results || exhausted || k >= count {
+            return Ok(results);
+        }
+        k *= 8;
     }
+}
 
-    Ok(results)
+/// The `k` embeddings most similar to `query`, scanning all of them.
+fn brute_force(embeddings: &[(i64, Vec<f32>)], query: &[f32], k: usize) -> Vec<(i64, f32)> {
+    let mut scored: Vec<(i64, f32)> = embeddings
+        .iter()
+        .map(|(id, emb)| (*id, cosine_similarity(query, emb)))
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(k);
+    scored
 }
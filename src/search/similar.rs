@@ -0,0 +1,202 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::index;
+use crate::search::{fingerprint, semantic};
+use crate::store::sqlite::{SearchFilter, SearchResult, Store, TestScope};
+
+/// The chunk to find more of.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Chunk(i64),
+    Location { path: String, line: i64 },
+    Symbol(String),
+}
+
+impl Target {
+    /// `123` is a chunk id, `src/a.rs:42` a location, anything else a symbol.
+    pub fn parse(s: &str) -> Self {
+        if let Ok(id) = s.parse() {
+            return Target::Chunk(id);
+        }
+        if let Some((path, line)) = s.rsplit_once(':')
+            && !path.is_empty()
+            && let Ok(line) = line.parse()
+        {
+            return Target::Location { path: path.to_string(), line };
+        }
+        Target::Symbol(s.to_string())
+    }
+}
+
+pub struct SimilarQuery {
+    pub target: Target,
+    pub max_results: usize,
+    pub language: Option<String>,
+    pub path_prefix: Option<String>,
+    pub kind: Option<String>,
+    pub exclude_paths: Vec<String>,
+    pub tests: TestScope,
+}
+
+impl SimilarQuery {
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            max_results: 10,
+            language: None,
+            path_prefix: None,
+            kind: None,
+            exclude_paths: Vec::new(),
+            tests: TestScope::Include,
+        }
+    }
+}
+
+/// How similarity was measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Cosine similarity of the stored embeddings.
+    Embedding,
+    /// Shared token shingles with names and literals abstracted away
+    /// (see `search::fingerprint`).
+    Fingerprint,
+}
+
+impl Method {
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Embedding => "embedding",
+            Method::Fingerprint => "structural fingerprint",
+        }
+    }
+}
+
+pub struct SimilarOutcome {
+    pub source: SearchResult,
+    pub method: Method,
+    /// Most similar first; `rank` is −similarity.
+    pub results: Vec<SearchResult>,
+    pub index_busy: bool,
+}
+
+/// Chunks most like the target chunk. Uses the target's embedding when it
+/// has one, its structural fingerprint otherwise. The target itself and
+/// chunks overlapping it (its enclosing impl, its methods) are left out.
+pub fn search(root: &Path, config: &Config, query: &SimilarQuery) -> Result<SimilarOutcome> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let index_busy = index::index_directory(&root, config).is_ok_and(|r| r.busy);
+
+    let storage_dir = config.storage_dir(&root);
+    let Some(store) = Store::open_if_exists(&storage_dir)? else {
+        bail!("no index found; run 'booger index' first");
+    };
+    let source_id = match &query.target {
+        Target::Chunk(id) => Some(*id),
+        Target::Location { path, line } => store.chunk_at(path, *line)?,
+        Target::Symbol(name) => store.chunks_named(name)?.first().copied(),
+    };
+    let Some(source) = source_id.map(|id| store.chunk_by_id(id, &SearchFilter::default())).transpose()?.flatten()
+    else {
+        bail!("no chunk found for {}", describe(&query.target));
+    };
+    let source_id = source_id.unwrap_or_default();
+
+    let mut filter = SearchFilter::new(query.language.as_deref(), query.path_prefix.as_deref(), query.kind.as_deref());
+    filter.exclude_paths = query.exclude_paths.clone();
+    filter.tests = query.tests;
+    let overlaps = |r: &SearchResult| {
+        r.file_path == source.file_path && r.start_line <= source.end_line && r.end_line >= source.start_line
+    };
+
+    let embedding = store.embeddings_by_ids(&[source_id])?.pop();
+    let (method, mut results) = match embedding {
+        Some((_, vector)) => {
+            // Overlapping chunks are usually the nearest; ask for enough to drop them.
+            let overlapping = store.count_overlapping_chunks(&source.file_path, source.start_line, source.end_line)?;
+            let hits = semantic::nearest(&store, &storage_dir, config, &vector, &filter, query.max_results + overlapping, false)?;
+            let results = hits.into_iter().filter(|(id, r)| *id != source_id && !overlaps(r)).map(|(_, r)| r).collect();
+            (Method::Embedding, results)
+        }
+        None => {
+            let shingles = fingerprint::shingles(&source.content);
+            let mut scored: Vec<(i64, f64)> = store
+                .chunk_contents(&filter)?
+                .into_iter()
+                .filter(|(id, _)| *id != source_id)
+                .map(|(id, content)| (id, fingerprint::jaccard(&shingles, &fingerprint::shingles(&content))))
+                .filter(|(_, s)| *s > 0.0)
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let mut results = Vec::new();
+            for (id, similarity) in scored {
+                if let Some(mut r) = store.chunk_by_id(id, &filter)?
+                    && !overlaps(&r)
+                {
+                    r.rank = -similarity;
+                    results.push(r);
+                    if results.len() >= query.max_results {
+                        break;
+                    }
+                }
+            }
+            (Method::Fingerprint, results)
+        }
+    };
+    results.truncate(query.max_results);
+    Ok(SimilarOutcome { source, method, results, index_busy })
+}
+
+fn describe(target: &Target) -> String {
+    match target {
+        Target::Chunk(id) => format!("chunk id {id}"),
+        Target::Location { path, line } => format!("{path}:{line}"),
+        Target::Symbol(name) => format!("symbol '{name}'"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(Target::parse("42"), Target::Chunk(42));
+        assert_eq!(Target::parse("src/a.rs:7"), Target::Location { path: "src/a.rs".into(), line: 7 });
+        assert_eq!(Target::parse("Store::open"), Target::Symbol("Store::open".into()));
+    }
+
+    #[test]
+    fn finds_code_shaped_like_the_target() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("handlers.rs"),
+            "fn get_user(req: Request) -> Response {\n    let id = req.param(\"id\")?;\n    let user = db.find(id)?;\n    Response::json(user)\n}\n\n\
+             fn get_order(r: Request) -> Response {\n    let key = r.param(\"key\")?;\n    let order = store.load(key)?;\n    Response::json(order)\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("math.rs"),
+            "fn sum(values: &[u32]) -> u32 {\n    let mut total = 0;\n    for v in values {\n        total += v;\n    }\n    total\n}\n",
+        )
+        .unwrap();
+        let config = Config::default();
+
+        let outcome = search(dir.path(), &config, &SimilarQuery::new(Target::parse("get_user"))).unwrap();
+        assert_eq!(outcome.method, Method::Fingerprint);
+        assert_eq!(outcome.source.chunk_name.as_deref(), Some("get_user"));
+        assert_eq!(outcome.results[0].chunk_name.as_deref(), Some("get_order"));
+        assert!(outcome.results.iter().all(|r| r.chunk_name.as_deref() != Some("get_user")));
+
+        let mut query = SimilarQuery::new(Target::parse("handlers.rs:8"));
+        query.path_prefix = Some("math".into());
+        let outcome = search(dir.path(), &config, &query).unwrap();
+        assert_eq!(outcome.source.chunk_name.as_deref(), Some("get_order"));
+        assert!(outcome.results.iter().all(|r| r.file_path == "math.rs"));
+
+        assert!(search(dir.path(), &config, &SimilarQuery::new(Target::parse("missing"))).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use std::ops::Range;
+
+use crate::search::text::parse_query;
+use crate::store::sqlite::{SearchResult, Store};
+use crate::store::tokens::query_terms;
+
+/// The matching parts of one result.
+pub struct Snippet {
+    /// 0-based lines of the chunk content that match the query.
+    pub matches: Vec<usize>,
+    /// Line ranges to show: every match with its context, merged.
+    pub windows: Vec<Range<usize>>,
+}
+
+impl Snippet {
+    /// No match: the first `context + 1` of `total` lines.
+    pub fn head(total: usize, context: usize) -> Self {
+        Snippet {
+            matches: Vec::new(),
+            windows: std::iter::once(0..total.min(context + 1)).collect(),
+        }
+    }
+}
+
+/// Find the lines of `result` that match `query` and the windows of
+/// `context` lines around them.
+///
+/// Lines come from FTS5 `highlight()`. When the chunk matched only through
+/// identifier sub-tokens (e.g. `embedding` in `upsertEmbeddingsBatch`),
+/// nothing is highlighted and lines containing a query term are used
+/// instead. With no match at all, the window is the first line(s).
+pub fn snippet(store: &Store, query: &str, result: &SearchResult, context: usize) -> Result<Snippet> {
+    let text = parse_query(query).text;
+    let total = result.content.lines().count();
+
+    let mut matches = store.highlight_lines(&text, &result.file_path, result.start_line, result.end_line)?;
+    if matches.is_empty() {
+        matches = term_lines(&text, &result.content);
+    }
+    if matches.is_empty() {
+        return Ok(Snippet::head(total, context));
+    }
+    let windows = windows(&matches, total, context);
+    Ok(Snippet { matches, windows })
+}
+
+/// Lines containing one of the query's terms (see `tokens::query_terms`),
+/// ignoring case.
+fn term_lines(query: &str, content: &str) -> Vec<usize> {
+    let terms = query_terms(query);
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.to_lowercase();
+            terms.iter().any(|t| line.contains(t.as_str()))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Merge `context` lines around each match into non-overlapping ranges.
+pub fn windows(matches: &[usize], total: usize, context: usize) -> Vec<Range<usize>> {
+    let mut out: Vec<Range<usize>> = Vec::new();
+    for &m in matches {
+        let start = m.saturating_sub(context);
+        let end = (m + context + 1).min(total);
+        match out.last_mut() {
+            // Adjacent or overlapping: extend the previous window.
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => out.push(start..end),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::sqlite::ChunkInsert;
+    use tempfile::TempDir;
+
+    #[test]
+    fn windows_merge_overlapping_context() {
+        assert_eq!(windows(&[2, 4, 20], 30, 1), [1..6, 19..22]);
+        assert_eq!(windows(&[0, 29], 30, 2), [0..3, 27..30]);
+        assert!(windows(&[], 30, 2).is_empty());
+    }
+
+    #[test]
+    fn snippet_uses_highlight_then_sub_tokens() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        let content: String = (0..40)
+            .map(|i| match i {
+                25 => "    let total = parse_config(path);\n".to_string(),
+                33 => "    upsertEmbeddingsBatch(&rows);\n".to_string(),
+                _ => format!("    step({i});\n"),
+            })
+            .collect();
+        let fid = store.upsert_file("src/lib.rs", "h", 10, Some("rust")).unwrap();
+        store.insert_chunks(fid, &[ChunkInsert {
+            kind: "function".into(),
+            name: Some("run".into()),
+            content: content.clone(),
+            signature: Some("fn run()".into()),
+            start_line: 10,
+            end_line: 49,
+            start_byte: 0,
+            end_byte: content.len() as u32,
+        }]).unwrap();
+        let result = store.search("parse_config", None, None, None, 1).unwrap().remove(0);
+
+        let s = snippet(&store, "parse_config kind:function", &result, 1).unwrap();
+        assert_eq!(s.matches, [25]);
+        assert_eq!((s.windows[0].start, s.windows[0].end, s.windows.len()), (24, 27, 1));
+
+        let s = snippet(&store, "embedding", &result, 0).unwrap();
+        assert_eq!(s.matches, [33]);
+    }
+}
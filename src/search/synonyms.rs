@@ -0,0 +1,191 @@
+use anyhow::Result;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::LazyLock;
+
+use crate::config::Config;
+use crate::store::sqlite::Store;
+use crate::store::tokens::{query_terms, split_identifier};
+
+/// At most this many synonyms per query term, and variants per query.
+const MAX_PER_TERM: usize = 3;
+const MAX_VARIANTS: usize = 8;
+
+/// `cfg: &Config`, `conn: *Connection`, `ctx: RankContext`: a short name
+/// annotated with a type.
+static TYPED_BINDING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b([a-z][a-z0-9]{1,5})\s*:\s*(?:&\s*)?(?:'\w+\s+)?(?:mut\s+)?\*?([A-Z][A-Za-z0-9]*)").unwrap()
+});
+
+/// `let conn = Connection::open(..)`, `cfg := Config{..}`, `tx = new
+/// Transaction(..)`: a short name bound to a constructed value.
+static CONSTRUCTED_BINDING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b([a-z][a-z0-9]{1,5})\s*:?=\s*(?:new\s+)?&?([A-Z][A-Za-z0-9]*)\s*(?:\(|::|\{)").unwrap()
+});
+
+/// `ctx (context)` or `context (ctx)` in a comment.
+static SPELLED_OUT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b([a-z]{2,})\s*\(([a-z]{2,})\)").unwrap());
+
+/// Abbreviation pairs `(abbr, word)` mined from code: short names whose
+/// type spells them out (`cfg: &Config`, `let conn = Connection::open()`)
+/// and comments that do (`// ctx (context)`).
+pub fn mine(content: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    for re in [&*TYPED_BINDING, &*CONSTRUCTED_BINDING] {
+        for caps in re.captures_iter(content) {
+            let abbr = &caps[1];
+            // A binding is good evidence on its own: two-letter names like
+            // `tx` only need to share the first letter with the type's word.
+            let word = split_identifier(&caps[2])
+                .into_iter()
+                .find(|w| is_abbreviation(abbr, w) || (abbr.len() == 2 && w.len() > 3 && w.starts_with(&abbr[..1])));
+            if let Some(word) = word {
+                pairs.push((abbr.to_string(), word));
+            }
+        }
+    }
+    for line in content.lines() {
+        let line = line.trim_start();
+        if !["//", "#", "*", "/*", "--", ";"].iter().any(|m| line.starts_with(m)) {
+            continue;
+        }
+        for caps in SPELLED_OUT.captures_iter(line) {
+            let (a, b) = (&caps[1], &caps[2]);
+            if is_abbreviation(a, b) {
+                pairs.push((a.to_string(), b.to_string()));
+            } else if is_abbreviation(b, a) {
+                pairs.push((b.to_string(), a.to_string()));
+            }
+        }
+    }
+    pairs.sort();
+    pairs.dedup();
+    pairs
+}
+
+/// Whether `abbr` abbreviates `word`: shorter, same first letter, and its
+/// letters appear in `word` in order (`cfg`/`config`, `conn`/`connection`).
+fn is_abbreviation(abbr: &str, word: &str) -> bool {
+    if abbr.len() < 2 || abbr.len() >= word.len() || abbr.chars().next() != word.chars().next() {
+        return false;
+    }
+    let mut rest = word.chars();
+    abbr.chars().all(|c| rest.any(|w| w == c))
+}
+
+/// Query expansions: `[synonyms]` from config plus mined abbreviations,
+/// both directions (`config` ↔ `cfg`). Only a query's own terms are
+/// looked up in the index.
+#[derive(Debug, Default)]
+pub struct Synonyms {
+    map: HashMap<String, Vec<String>>,
+}
+
+impl Synonyms {
+    /// Synonyms for the terms of `text`: `[synonyms]` first, then mined
+    /// pairs by how many files use them.
+    pub fn load(store: &Store, config: &Config, text: &str) -> Result<Self> {
+        let mut synonyms = Self::from_table(&config.synonyms);
+        for (_, term) in terms(text) {
+            for alt in store.synonyms_for(&term, MAX_PER_TERM)? {
+                synonyms.add(&term, &alt);
+            }
+        }
+        Ok(synonyms)
+    }
+
+    fn from_table(table: &BTreeMap<String, Vec<String>>) -> Self {
+        let mut synonyms = Self::default();
+        for (term, alternatives) in table {
+            for alt in alternatives {
+                synonyms.add(term, alt);
+            }
+        }
+        synonyms
+    }
+
+    fn add(&mut self, a: &str, b: &str) {
+        let (a, b) = (a.to_lowercase(), b.to_lowercase());
+        if a == b {
+            return;
+        }
+        for (from, to) in [(&a, &b), (&b, &a)] {
+            let list = self.map.entry(from.clone()).or_default();
+            if !list.contains(to) {
+                list.push(to.clone());
+            }
+        }
+    }
+
+    /// Queries with one term replaced by one of its synonyms. Quoted
+    /// phrases and FTS5 operators are left alone.
+    pub fn variants(&self, text: &str) -> Vec<String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let mut variants = Vec::new();
+        for (i, term) in terms(text) {
+            let Some(alternatives) = self.map.get(&term) else {
+                continue;
+            };
+            for alt in alternatives.iter().take(MAX_PER_TERM) {
+                let mut variant = words.clone();
+                variant[i] = alt;
+                variants.push(variant.join(" "));
+            }
+        }
+        variants.truncate(MAX_VARIANTS);
+        variants
+    }
+}
+
+/// Unquoted words of `text` that are query terms themselves (see
+/// `tokens::query_terms`), lowercased, by position among its words.
+fn terms(text: &str) -> Vec<(usize, String)> {
+    let mut in_phrase = false;
+    let mut terms = Vec::new();
+    for (i, word) in text.split_whitespace().enumerate() {
+        let quoted = in_phrase || word.contains('"');
+        if word.matches('"').count() % 2 == 1 {
+            in_phrase = !in_phrase;
+        }
+        let term = word.to_lowercase();
+        if !quoted && query_terms(word).contains(&term) {
+            terms.push((i, term));
+        }
+    }
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mines_abbreviations_from_bindings_and_comments() {
+        let content = "\
+fn open(cfg: &Config, ctx: &mut RankContext) -> Result<()> {
+    let conn = Connection::open(path)?;
+    let tx = conn.transaction()?;
+    let map: HashMap<String, u32> = HashMap::new();
+    // The buf (buffer) is reused across calls.
+    commit(tx: Transaction)
+}
+";
+        assert_eq!(mine(content), [
+            ("buf".to_string(), "buffer".to_string()),
+            ("cfg".into(), "config".into()),
+            ("conn".into(), "connection".into()),
+            ("ctx".into(), "context".into()),
+            ("tx".into(), "transaction".into()),
+        ]);
+    }
+
+    #[test]
+    fn variants_replace_one_term_at_a_time_both_ways() {
+        let table = BTreeMap::from([("config".to_string(), vec!["cfg".to_string(), "conf".to_string()])]);
+        let synonyms = Synonyms::from_table(&table);
+        assert_eq!(synonyms.variants("load config"), ["load cfg", "load conf"]);
+        assert_eq!(synonyms.variants("cfg OR \"cfg file\""), ["config OR \"cfg file\""]);
+        assert!(synonyms.variants("parse").is_empty());
+    }
+}
@@ -0,0 +1,32 @@
+use crate::store::sqlite::ChunkInsert;
+
+/// Deterministic pseudo-random vectors in [-1, 1).
+pub fn vectors(n: usize, dims: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed;
+    (0..n)
+        .map(|_| {
+            (0..dims)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// A one-line function chunk numbered `i`.
+pub fn chunk(i: u32) -> ChunkInsert {
+    ChunkInsert {
+        kind: "function".into(),
+        name: Some(format!("f{i}")),
+        content: format!("fn f{i}() {{}}"),
+        signature: None,
+        start_line: i,
+        end_line: i,
+        start_byte: i * 10,
+        end_byte: i * 10 + 9,
+    }
+}
@@ -1,17 +1,25 @@
 use anyhow::{Context, Result};
+use std::collections::HashSet;
 use std::path::Path;
 
 use crate::config::Config;
 use crate::index;
-use crate::store::sqlite::{SearchResult, Store};
+use crate::search::ranking::{Explanation, Pipeline};
+use crate::search::synonyms::Synonyms;
+use crate::store::sqlite::{SearchFilter, SearchResult, Store, TestScope};
 
 pub struct SearchQuery {
     pub text: String,
     pub language: Option<String>,
     pub path_prefix: Option<String>,
     pub kind: Option<String>,
+    /// Paths (prefixes or globs) left out of the results.
+    pub exclude_paths: Vec<String>,
+    pub tests: TestScope,
     pub max_results: usize,
     pub session_id: Option<String>,
+    /// Let the `diff` ranking stage boost what changed on the current branch.
+    pub diff_boost: bool,
 }
 
 impl SearchQuery {
@@ -21,131 +29,271 @@ impl SearchQuery {
             language: None,
             path_prefix: None,
             kind: None,
+            exclude_paths: Vec::new(),
+            tests: TestScope::Include,
             max_results: 20,
             session_id: None,
+            diff_boost: true,
         }
     }
+
+    /// The search text and the filter the query applies: its inline
+    /// qualifiers, which win over the separate language/path/kind options,
+    /// plus the exclusions and test scope.
+    pub fn parsed(&self) -> ParsedQuery {
+        let ParsedQuery { text, mut filter } = parse_query(&self.text);
+        if filter.languages.is_empty() {
+            filter.languages.extend(self.language.clone());
+        }
+        if filter.paths.is_empty() {
+            filter.paths.extend(self.path_prefix.clone());
+        }
+        if filter.kinds.is_empty() {
+            filter.kinds.extend(self.kind.clone());
+        }
+        if filter.tests == TestScope::Include {
+            filter.tests = self.tests;
+        }
+        filter.exclude_paths.extend(self.exclude_paths.iter().cloned());
+        ParsedQuery { text, filter }
+    }
 }
 
-/// Execute a full-text search against the index, applying volatile context
-/// (focus boost, visited penalty) to re-rank results.
+/// Query text with its inline field qualifiers split off.
+#[derive(Debug, PartialEq)]
+pub struct ParsedQuery {
+    /// Search terms left for FTS5, qualifiers removed.
+    pub text: String,
+    pub filter: SearchFilter,
+}
+
+/// Split inline qualifiers off a query:
+///
+/// - `kind:function`, `lang:rust` (or `language:`), `path:src/index`, `name:load*`
+/// - a leading `-` excludes: `-path:tests`
+/// - `tests:exclude` or `tests:only` scopes test code (see `TestScope`)
+/// - comma-separated or repeated values are alternatives: `kind:function,method`
+/// - `*`, `?` and `[...]` make a value a glob: `path:src/*/mod.rs`
+///
+/// Everything else, including quoted phrases, is left as search text.
+pub fn parse_query(input: &str) -> ParsedQuery {
+    let mut terms = Vec::new();
+    let mut filter = SearchFilter::default();
+    let mut in_phrase = false;
+
+    for token in input.split_whitespace() {
+        let starts_phrase = token.matches('"').count() % 2 == 1;
+        if in_phrase || !apply_qualifier(&mut filter, token) {
+            terms.push(token);
+        }
+        if starts_phrase {
+            in_phrase = !in_phrase;
+        }
+    }
+    ParsedQuery { text: terms.join(" "), filter }
+}
+
+/// Add `token` to `filter` if it is a qualifier. Returns false otherwise.
+fn apply_qualifier(filter: &mut SearchFilter, token: &str) -> bool {
+    let (negated, rest) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+    let Some((key, value)) = rest.split_once(':') else {
+        return false;
+    };
+    if key == "tests" && !negated {
+        return match TestScope::parse(value) {
+            Some(scope) => {
+                filter.tests = scope;
+                true
+            }
+            None => false,
+        };
+    }
+    let values: Vec<String> = value.split(',').filter(|v| !v.is_empty()).map(String::from).collect();
+    if values.is_empty() {
+        return false;
+    }
+    let list = match (key, negated) {
+        ("kind", false) => &mut filter.kinds,
+        ("kind", true) => &mut filter.exclude_kinds,
+        ("lang" | "language", false) => &mut filter.languages,
+        ("lang" | "language", true) => &mut filter.exclude_languages,
+        ("path", false) => &mut filter.paths,
+        ("path", true) => &mut filter.exclude_paths,
+        ("name", false) => &mut filter.names,
+        ("name", true) => &mut filter.exclude_names,
+        _ => return false,
+    };
+    list.extend(values);
+    true
+}
+
+/// Search results plus whether they may be stale.
+#[derive(Default)]
+pub struct SearchOutcome {
+    pub results: Vec<SearchResult>,
+    /// How each result's rank was reached, aligned with `results`.
+    pub explanations: Vec<Explanation>,
+    /// No chunk matched every term, so the terms were retried with OR.
+    pub or_fallback: bool,
+    /// Synonym variants of the query that added results (see `search::synonyms`).
+    pub expansions: Vec<String>,
+    /// The auto re-index was skipped because another process holds the
+    /// writer lock; results come from the last committed index.
+    pub index_busy: bool,
+}
+
+/// Execute a full-text search against the index, re-ranking results with
+/// the `[ranking]` pipeline (code boost, focus boost, visited penalty, ...).
 /// Automatically ensures the index is up-to-date before searching.
-pub fn search(root: &Path, config: &Config, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+pub fn search(root: &Path, config: &Config, query: &SearchQuery) -> Result<SearchOutcome> {
     let root = root
         .canonicalize()
         .with_context(|| format!("resolving path {}", root.display()))?;
 
     // Auto-index: incrementally update before searching so results are never stale.
     // This is cheap when nothing changed (walk + hash comparison only).
-    let _ = index::index_directory(&root, config);
+    // If another process is already indexing, search its last committed snapshot.
+    let index_busy = index::index_directory(&root, config).is_ok_and(|r| r.busy);
 
-    let storage_dir = config.storage_dir(&root);
+    let outcome = search_index(&root, config, query)?;
+    Ok(SearchOutcome { index_busy, ..outcome })
+}
+
+/// Like `search`, but the keyword matches come in BM25 order (lower `rank`
+/// is better) without the `[ranking]` stages, and `explanations` is empty.
+/// For callers that fuse them with another retriever and re-rank once
+/// afterwards (see `search::hybrid`).
+pub fn candidates(root: &Path, config: &Config, query: &SearchQuery) -> Result<SearchOutcome> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", root.display()))?;
+    let index_busy = index::index_directory(&root, config).is_ok_and(|r| r.busy);
+
+    let Some(store) = Store::open_if_exists(&config.storage_dir(&root))? else {
+        return Ok(SearchOutcome { index_busy, ..Default::default() });
+    };
+    let Some(found) = matches(&store, config, query, query.max_results)? else {
+        return Ok(SearchOutcome { index_busy, ..Default::default() });
+    };
+    Ok(SearchOutcome {
+        results: found.results,
+        explanations: Vec::new(),
+        or_fallback: found.or_fallback,
+        expansions: found.expansions,
+        index_busy,
+    })
+}
+
+/// Keyword matches in BM25 order, before re-ranking.
+struct Matches {
+    /// The query text without its qualifiers.
+    text: String,
+    results: Vec<SearchResult>,
+    or_fallback: bool,
+    expansions: Vec<String>,
+}
+
+fn search_index(root: &Path, config: &Config, query: &SearchQuery) -> Result<SearchOutcome> {
+    let storage_dir = config.storage_dir(root);
     let store = match Store::open_if_exists(&storage_dir)? {
         Some(s) => s,
-        None => return Ok(Vec::new()),
+        None => return Ok(SearchOutcome::default()),
     };
 
     // Fetch more results than requested so re-ranking has room to work
-    let fetch_limit = query.max_results * 5;
-
-    let mut results = store.search(
-        &query.text,
-        query.language.as_deref(),
-        query.path_prefix.as_deref(),
-        query.kind.as_deref(),
-        fetch_limit,
-    )?;
-
-    // OR fallback: if AND-style query returned nothing and query has multiple
-    // terms, retry with OR so at least partial matches surface.
-    if results.is_empty() && query.text.split_whitespace().count() > 1 {
-        let or_query = query.text.split_whitespace().collect::<Vec<_>>().join(" OR ");
-        results = store.search(
-            &or_query,
-            query.language.as_deref(),
-            query.path_prefix.as_deref(),
-            query.kind.as_deref(),
-            fetch_limit,
-        )?;
-    }
-
+    let Some(Matches { text, mut results, or_fallback, expansions }) =
+        matches(&store, config, query, query.max_results * 5)?
+    else {
+        return Ok(SearchOutcome::default());
+    };
     if results.is_empty() {
-        return Ok(results);
+        return Ok(SearchOutcome { or_fallback, expansions, ..Default::default() });
     }
 
-    // ── Static re-ranking: code boost + chunk size penalty ──
-
-    let avg_lines: f64 = {
-        let total: f64 = results.iter().map(|r| (r.end_line - r.start_line + 1) as f64).sum();
-        total / results.len() as f64
-    };
-
-    for result in &mut results {
-        let mut boost: f64 = 0.0;
-
-        // Code chunks (function, struct, enum, etc.) are more useful than
-        // raw/doc chunks for a code search tool. Boost structural chunks.
-        let is_code = !matches!(result.chunk_kind.as_str(), "raw" | "module");
-        if is_code {
-            boost += 3.0;
-        }
-
-        // Penalize oversized chunks. A 272-line README matches everything
-        // but is rarely what you want. Scale penalty by how much larger
-        // than average the chunk is (capped at 4.0).
-        let lines = (result.end_line - result.start_line + 1) as f64;
-        if avg_lines > 0.0 && lines > avg_lines * 2.0 {
-            let oversize_ratio = lines / avg_lines;
-            boost -= (oversize_ratio * 0.5).min(4.0);
-        }
+    // Re-rank with the configured stages: code boost, oversize penalty,
+    // focus/visited/annotation context, branch changes, git history.
+    let pipeline = Pipeline::from_config(&config.ranking)?;
+    let ctx = pipeline.load_context(
+        &store,
+        root,
+        config,
+        &text,
+        query.session_id.as_deref(),
+        query.diff_boost,
+    )?;
+    let mut explanations = pipeline.rerank(&mut results, &ctx);
+    results.truncate(query.max_results);
+    explanations.truncate(query.max_results);
+    Ok(SearchOutcome { results, explanations, or_fallback, expansions, index_busy: false })
+}
 
-        result.rank -= boost;
+/// Up to `fetch_limit` keyword matches for `query`, including synonym
+/// variants and the OR fallback. None for a query with neither text nor
+/// filters.
+fn matches(store: &Store, config: &Config, query: &SearchQuery, fetch_limit: usize) -> Result<Option<Matches>> {
+    let ParsedQuery { text, filter } = query.parsed();
+    if text.is_empty() && filter == SearchFilter::default() {
+        return Ok(None);
     }
 
-    // ── Volatile context re-ranking ──
-
-    let focus_paths = store.get_focus_paths(query.session_id.as_deref())?;
-    let visited_paths = store.get_visited_paths(query.session_id.as_deref())?;
-    let annotations = store.get_annotations(None, query.session_id.as_deref())?;
-
-    if !focus_paths.is_empty() || !visited_paths.is_empty() || !annotations.is_empty() {
-        for result in &mut results {
-            let mut boost: f64 = 0.0;
+    let mut results = store.search_filtered(&text, &filter, fetch_limit)?;
 
-            for fp in &focus_paths {
-                if result.file_path.starts_with(fp.as_str()) {
-                    boost += 5.0;
-                    break;
+    // Synonym expansion: also search with `cfg` for `config` and the like.
+    // Chunks found only that way rank below exact matches.
+    let mut expansions = Vec::new();
+    let variants = Synonyms::load(store, config, &text)?.variants(&text);
+    if !variants.is_empty() {
+        let mut seen: HashSet<(String, i64)> =
+            results.iter().map(|r| (r.file_path.clone(), r.start_line)).collect();
+        for variant in variants {
+            let mut added = false;
+            for mut r in store.search_filtered(&variant, &filter, fetch_limit)? {
+                if seen.insert((r.file_path.clone(), r.start_line)) {
+                    r.rank *= config.ranking.synonym_weight;
+                    results.push(r);
+                    added = true;
                 }
             }
-
-            for vp in &visited_paths {
-                if result.file_path.starts_with(vp.as_str()) {
-                    boost -= 3.0;
-                    break;
-                }
-            }
-
-            for ann in &annotations {
-                if result.file_path == ann.target
-                    || result
-                        .chunk_name
-                        .as_ref()
-                        .map_or(false, |n| n == &ann.target)
-                {
-                    boost += 2.0;
-                    break;
-                }
+            if added {
+                expansions.push(variant);
             }
-
-            result.rank -= boost;
         }
+        results.sort_by(|a, b| a.rank.partial_cmp(&b.rank).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(fetch_limit);
     }
 
-    // Final sort and truncate
-    results.sort_by(|a, b| a.rank.partial_cmp(&b.rank).unwrap_or(std::cmp::Ordering::Equal));
-    results.truncate(query.max_results);
-    Ok(results)
+    // OR fallback: if AND-style query returned nothing and query has multiple
+    // terms, retry with OR so at least partial matches surface.
+    let mut or_fallback = false;
+    if results.is_empty() && text.split_whitespace().count() > 1 {
+        let or_query = text.split_whitespace().collect::<Vec<_>>().join(" OR ");
+        results = store.search_filtered(&or_query, &filter, fetch_limit)?;
+        or_fallback = true;
+    }
+    Ok(Some(Matches { text, results, or_fallback, expansions }))
+}
+
+/// Explanation of a search for humans and agents: whether the OR fallback
+/// ran, then one line per result with its rank and every stage that moved it.
+/// Lower ranks are better; boosts are subtracted from the BM25 rank.
+pub fn format_explanations(outcome: &SearchOutcome) -> String {
+    let mut out = String::from("── explain (rank = bm25 − boosts; lower is better) ──\n");
+    if outcome.or_fallback {
+        out.push_str("OR fallback: no chunk matched all terms; results match any term\n");
+    }
+    for variant in &outcome.expansions {
+        out.push_str(&format!("Synonyms: also searched '{variant}'; results only it found rank lower\n"));
+    }
+    for (i, (r, e)) in outcome.results.iter().zip(&outcome.explanations).enumerate() {
+        out.push_str(&format!(
+            "[{i}] {}:{} rank {:.2}: {}\n",
+            r.file_path, r.start_line, e.rank, e.summary(),
+        ));
+    }
+    out
 }
 
 /// Kind of "index first" message so we can suggest the exact booger index command.
@@ -192,3 +340,50 @@ pub fn explain_empty_search(root: &Path, config: &Config, path_prefix: Option<&s
         _ => format_index_first_message(&root, IndexFirstKind::NoIndex),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_qualifiers_out_of_the_query() {
+        let parsed = parse_query("parse config kind:function lang:rust path:src/index -path:tests name:load*");
+        assert_eq!(parsed.text, "parse config");
+        assert_eq!(parsed.filter, SearchFilter {
+            kinds: vec!["function".into()],
+            languages: vec!["rust".into()],
+            paths: vec!["src/index".into()],
+            exclude_paths: vec!["tests".into()],
+            names: vec!["load*".into()],
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn multiple_values_and_plain_text() {
+        let parsed = parse_query("kind:function,method kind:trait -language:c Store::open \"path:x y\" todo: tests:only");
+        assert_eq!(parsed.filter.kinds, ["function", "method", "trait"]);
+        assert_eq!(parsed.filter.exclude_languages, ["c"]);
+        assert_eq!(parsed.filter.tests, TestScope::Only);
+        assert_eq!(parsed.text, "Store::open \"path:x y\" todo:");
+        assert_eq!(parse_query("tests:maybe").text, "tests:maybe");
+    }
+
+    #[test]
+    fn search_expands_mined_and_configured_synonyms() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("open.rs"), "fn open(conn: &Connection) {}\n").unwrap();
+        std::fs::write(dir.path().join("close.rs"), "fn close(conn: &mut Handle) {\n    conn.flush();\n}\n").unwrap();
+        std::fs::write(dir.path().join("stop.rs"), "fn stop() {\n    reset();\n}\n").unwrap();
+        let mut config = Config::default();
+        config.synonyms.insert("teardown".into(), vec!["reset".into()]);
+
+        let outcome = search(dir.path(), &config, &SearchQuery::new("connection")).unwrap();
+        let files: Vec<&str> = outcome.results.iter().map(|r| r.file_path.as_str()).collect();
+        assert_eq!(files, ["open.rs", "close.rs"]);
+        assert_eq!(outcome.expansions, ["conn"]);
+
+        let outcome = search(dir.path(), &config, &SearchQuery::new("teardown")).unwrap();
+        assert_eq!(outcome.results[0].file_path, "stop.rs");
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::config::{Config, EmbedBackend, EmbedConfig, Fusion, ProjectEntry};
+use crate::embed::{Embedder, Embedding};
+use crate::search::fusion;
+use crate::search::hybrid::{self, HybridQuery};
+use crate::search::semantic::{self, SemanticQuery};
+use crate::search::text::{self, SearchQuery};
+use crate::store::sqlite::SearchResult;
+
+/// Projects searched at once.
+const MAX_THREADS: usize = 10;
+
+/// Makes the embedder a project's `[embed]` config asks for, e.g.
+/// `embed::from_config`.
+pub type EmbedderFactory<'a> = &'a (dyn Fn(&EmbedConfig) -> Result<Box<dyn Embedder>> + Sync);
+
+/// Which search runs in each project.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Text,
+    Hybrid,
+    Semantic,
+}
+
+impl Mode {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "text" => Ok(Self::Text),
+            "hybrid" => Ok(Self::Hybrid),
+            "semantic" => Ok(Self::Semantic),
+            other => anyhow::bail!("unknown mode '{other}' (expected one of: text, hybrid, semantic)"),
+        }
+    }
+}
+
+pub struct WorkspaceQuery {
+    pub text: String,
+    pub mode: Mode,
+    pub language: Option<String>,
+    pub kind: Option<String>,
+    pub max_results: usize,
+    /// How each project's scores are made comparable; `None` keeps them raw.
+    pub normalization: Option<Fusion>,
+    /// Position constant for `Fusion::Rrf` normalization (`[hybrid] rrf_k`).
+    pub rrf_k: f64,
+}
+
+impl WorkspaceQuery {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            mode: Mode::Text,
+            language: None,
+            kind: None,
+            max_results: 20,
+            normalization: Some(Fusion::Zscore),
+            rrf_k: Config::default().hybrid.rrf_k,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WorkspaceOutcome {
+    /// Best first, tagged with the project name; `rank` is the normalized,
+    /// weighted score (higher is better).
+    pub results: Vec<(String, SearchResult)>,
+    /// Projects whose index was busy and may be stale.
+    pub busy: Vec<String>,
+}
+
+/// Search every project in `projects` (threaded) and rank the results
+/// together. Each project's FTS index has its own corpus statistics, so
+/// its scores are normalized within the project (see
+/// `fusion::interleave`) and multiplied by its registry weight before the
+/// lists are merged. `Hybrid` and `Semantic` need `embedders`: each
+/// project gets the embedder its own `[embed]` config names, made once per
+/// distinct backend, and the query is embedded once per backend. A project
+/// that fails, including one whose backend is unreachable, contributes
+/// nothing; if no backend is reachable, that is the error.
+pub fn search(
+    projects: &[(String, ProjectEntry)],
+    embedders: Option<EmbedderFactory>,
+    query: &WorkspaceQuery,
+) -> Result<WorkspaceOutcome> {
+    let configs: Vec<Config> = projects
+        .iter()
+        .map(|(_, entry)| Config::load(&entry.path).unwrap_or_default())
+        .collect();
+    let mut backends: Vec<(&EmbedBackend, Result<QueryCache>)> = Vec::new();
+    if query.mode != Mode::Text {
+        let Some(make) = embedders else {
+            anyhow::bail!("{:?} workspace search needs an embedder", query.mode);
+        };
+        for config in &configs {
+            if !backends.iter().any(|(backend, _)| **backend == config.embed.backend) {
+                let embedder = make(&config.embed)
+                    .map(|inner| QueryCache { inner, cache: Mutex::new(HashMap::new()) });
+                backends.push((&config.embed.backend, embedder));
+            }
+        }
+        if !backends.is_empty()
+            && backends.iter().all(|(_, embedder)| embedder.is_err())
+            && let (_, Err(e)) = backends.swap_remove(0)
+        {
+            return Err(e.context("connecting to the embedding backend"));
+        }
+    }
+    let embedder_for = |config: &Config| {
+        backends
+            .iter()
+            .find(|(backend, _)| **backend == config.embed.backend)
+            .and_then(|(_, embedder)| embedder.as_ref().ok())
+            .map(|e| e as &dyn Embedder)
+    };
+    // Every project returns enough results for its scores to have a spread.
+    let per_project = query.max_results.max(5);
+
+    let mut lists = Vec::new();
+    let mut outcome = WorkspaceOutcome::default();
+    let projects: Vec<_> = projects.iter().zip(&configs).collect();
+    for batch in projects.chunks(MAX_THREADS) {
+        let searched: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|((name, entry), config)| {
+                    let embedder = embedder_for(config);
+                    s.spawn(move || {
+                        (name, entry.weight, search_project(entry, config, embedder, query, per_project))
+                    })
+                })
+                .collect();
+            handles.into_iter().filter_map(|h| h.join().ok()).collect()
+        });
+        for (name, weight, searched) in searched {
+            let Ok((results, busy)) = searched else {
+                continue;
+            };
+            if busy {
+                outcome.busy.push(name.clone());
+            }
+            let list = results
+                .into_iter()
+                .map(|(r, score)| ((name.clone(), r), score))
+                .collect();
+            lists.push((weight, list));
+        }
+    }
+    outcome.busy.sort();
+
+    let mut merged = fusion::interleave(query.normalization, query.rrf_k, lists);
+    merged.truncate(query.max_results);
+    outcome.results = merged
+        .into_iter()
+        .map(|((name, mut r), score)| {
+            r.rank = score;
+            (name, r)
+        })
+        .collect();
+    Ok(outcome)
+}
+
+/// One project's results, best first, with scores where higher is better.
+fn search_project(
+    entry: &ProjectEntry,
+    config: &Config,
+    embedder: Option<&dyn Embedder>,
+    query: &WorkspaceQuery,
+    max_results: usize,
+) -> Result<(Vec<(SearchResult, f64)>, bool)> {
+    let scored = |results: Vec<SearchResult>, sign: f64| {
+        results
+            .into_iter()
+            .map(|r| {
+                let score = sign * r.rank;
+                (r, score)
+            })
+            .collect()
+    };
+    match (query.mode, embedder) {
+        (Mode::Hybrid, Some(embedder)) => {
+            let mut q = HybridQuery::new(&query.text);
+            q.language = query.language.clone();
+            q.kind = query.kind.clone();
+            q.max_results = max_results;
+            let o = hybrid::search(&entry.path, config, Some(embedder), &q)?;
+            Ok((scored(o.results, 1.0), o.index_busy))
+        }
+        (Mode::Semantic, Some(embedder)) => {
+            let mut q = SemanticQuery::new(&query.text);
+            q.language = query.language.clone();
+            q.kind = query.kind.clone();
+            q.max_results = max_results;
+            let results = semantic::search(&entry.path, config, embedder, &q)?;
+            Ok((scored(results, -1.0), false))
+        }
+        (Mode::Hybrid | Mode::Semantic, None) => anyhow::bail!("no embedder for {}", entry.path.display()),
+        (Mode::Text, _) => {
+            let mut q = SearchQuery::new(&query.text);
+            q.language = query.language.clone();
+            q.kind = query.kind.clone();
+            q.max_results = max_results;
+            let o = text::search(&entry.path, config, &q)?;
+            Ok((scored(o.results, -1.0), o.index_busy))
+        }
+    }
+}
+
+/// Embeds each distinct text once, however many projects ask for it.
+struct QueryCache {
+    inner: Box<dyn Embedder>,
+    cache: Mutex<HashMap<String, Embedding>>,
+}
+
+impl Embedder for QueryCache {
+    fn embed(&self, text: &str) -> Result<Embedding> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(v) = cache.get(text) {
+            return Ok(v.clone());
+        }
+        let v = self.inner.embed(text)?;
+        cache.insert(text.to_string(), v.clone());
+        Ok(v)
+    }
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_projects_by_normalized_weighted_scores() {
+        let big = tempfile::TempDir::new().unwrap();
+        let small = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            big.path().join("retry.rs"),
+            "fn retry_request() {\n    retry();\n    retry();\n}\n\nfn send() {\n    connect();\n    retry();\n}\n",
+        )
+        .unwrap();
+        for i in 0..6 {
+            std::fs::write(big.path().join(format!("other{i}.rs")), format!("fn other{i}() {{\n    work();\n}}\n")).unwrap();
+        }
+        std::fs::write(small.path().join("lib.rs"), "fn start() {\n    retry();\n}\n").unwrap();
+
+        let projects = vec![
+            ("big".to_string(), ProjectEntry::new(big.path().to_path_buf())),
+            ("small".to_string(), ProjectEntry::new(small.path().to_path_buf())),
+        ];
+        let query = WorkspaceQuery::new("retry");
+        let outcome = search(&projects, None, &query).unwrap();
+        let tagged: Vec<(&str, i64)> = outcome.results.iter().map(|(n, r)| (n.as_str(), r.start_line)).collect();
+        assert_eq!(tagged, [("big", 1), ("small", 1), ("big", 6)]);
+        assert!(outcome.results.iter().all(|(_, r)| (0.0..=1.0).contains(&r.rank)));
+
+        // Weighting the small project lifts its only hit to the top.
+        let mut weighted = projects.clone();
+        weighted[1].1.weight = 3.0;
+        let outcome = search(&weighted, None, &query).unwrap();
+        assert_eq!(outcome.results[0].0, "small");
+
+        let mut semantic = WorkspaceQuery::new("retry");
+        semantic.mode = Mode::Semantic;
+        assert!(search(&projects, None, &semantic).is_err());
+    }
+
+    /// Every text embeds to `dims` ones.
+    struct Stub(usize);
+
+    impl Embedder for Stub {
+        fn embed(&self, _text: &str) -> Result<Embedding> {
+            Ok(vec![1.0; self.0])
+        }
+        fn dimensions(&self) -> usize {
+            self.0
+        }
+        fn model_name(&self) -> &str {
+            "stub"
+        }
+    }
+
+    #[test]
+    fn semantic_search_embeds_per_backend_and_filters_kinds() {
+        let dirs = [tempfile::TempDir::new().unwrap(), tempfile::TempDir::new().unwrap()];
+        let projects: Vec<(String, ProjectEntry)> = [("small", 2), ("large", 3)]
+            .into_iter()
+            .zip(&dirs)
+            .map(|((name, dims), dir)| {
+                let dir = dir.path().to_path_buf();
+                std::fs::write(dir.join("lib.rs"), "struct Retry;\n\nfn retry() {\n    go();\n}\n").unwrap();
+                let mut config = Config::default();
+                config.embed.backend = EmbedBackend::Ollama { model: format!("m{dims}"), url: "http://x".into() };
+                std::fs::create_dir_all(dir.join(".booger")).unwrap();
+                std::fs::write(dir.join(".booger/config.toml"), toml::to_string(&config).unwrap()).unwrap();
+                semantic::embed_chunks(&dir, &config, &Stub(dims)).unwrap();
+                (name.to_string(), ProjectEntry::new(dir))
+            })
+            .collect();
+        let made = Mutex::new(Vec::new());
+        let factory = |config: &EmbedConfig| -> Result<Box<dyn Embedder>> {
+            let EmbedBackend::Ollama { model, .. } = &config.backend else { unreachable!() };
+            made.lock().unwrap().push(model.clone());
+            Ok(Box::new(Stub(model[1..].parse().unwrap())))
+        };
+
+        let mut query = WorkspaceQuery::new("retry");
+        query.mode = Mode::Semantic;
+        let outcome = search(&projects, Some(&factory), &query).unwrap();
+        let mut names: Vec<&str> = outcome.results.iter().map(|(n, _)| n.as_str()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names, ["large", "small"]);
+        assert_eq!(made.lock().unwrap().len(), 2);
+
+        query.kind = Some("struct".into());
+        let outcome = search(&projects, Some(&factory), &query).unwrap();
+        assert_eq!(outcome.results.len(), 2);
+        assert!(outcome.results.iter().all(|(_, r)| r.chunk_kind == "struct"));
+    }
+}
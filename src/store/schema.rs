@@ -1,11 +1,126 @@
 use anyhow::Result;
-use rusqlite::Connection;
+use rusqlite::{params, Connection};
+
+use super::tokens;
+
+const SCHEMA_VERSION: i64 = 11;
+
+// FTS5 virtual table for full-text search over chunk names, content and
+// identifier sub-tokens. content='chunks' makes it an external-content table —
+// we manage sync ourselves via triggers, which avoids doubling storage.
+// '_' is a token character so snake_case identifiers index whole; their
+// parts (and camelCase parts) live in the `terms` column instead.
+// prefix='2 3' speeds up the short prefix queries used for partial identifiers.
+const CHUNKS_FTS: &str = "
+    CREATE VIRTUAL TABLE chunks_fts USING fts5(
+        name,
+        content,
+        terms,
+        content='chunks',
+        content_rowid='id',
+        tokenize=\"porter unicode61 tokenchars '_'\",
+        prefix='2 3'
+    );
+    -- Sub-token matches count for less than whole-identifier matches.
+    INSERT INTO chunks_fts(chunks_fts, rank) VALUES('rank', 'bm25(1.0, 1.0, 0.5)');
+";
+
+// Trigram index over chunk content, used to prefilter `grep` candidates by
+// the literals a regex requires. Also external-content, so it only stores
+// the trigram postings.
+const CHUNKS_TRIGRAM: &str = "
+    CREATE VIRTUAL TABLE chunks_trigram USING fts5(
+        content,
+        content='chunks',
+        content_rowid='id',
+        tokenize='trigram'
+    );
+    INSERT INTO chunks_trigram(chunks_trigram) VALUES('rebuild');
+";
+
+// Full-text index over the commit history index (see git::history). Rows
+// are written alongside `commits` and share its ids; commits are only ever
+// added or cleared together, so no triggers are needed. `terms` holds the
+// identifier sub-tokens of the message and symbols, as in chunks_fts.
+const COMMITS_FTS: &str = "
+    CREATE VIRTUAL TABLE commits_fts USING fts5(
+        message,
+        author,
+        symbols,
+        terms,
+        tokenize=\"porter unicode61 tokenchars '_'\"
+    );
+";
+
+// Mined abbreviations aggregated per query term, in both directions
+// (cfg → config, config → cfg), with the number of files using the pair.
+// Rebuilt from `abbreviations` by `refresh_synonyms` after index passes
+// that changed files, so query expansion is a keyed lookup.
+const SYNONYMS: &str = "
+    CREATE TABLE synonyms (
+        term  TEXT NOT NULL,
+        alt   TEXT NOT NULL,
+        files INTEGER NOT NULL,
+        PRIMARY KEY (term, alt)
+    ) WITHOUT ROWID;
+";
+
+// Full-text index over annotation notes and targets (see
+// context::annotations). Rows share the annotation's id and are written by
+// `index_annotation`, which also fills `terms` with identifier sub-tokens
+// as in chunks_fts; a trigger drops them with the annotation.
+const ANNOTATIONS_FTS: &str = "
+    CREATE VIRTUAL TABLE annotations_fts USING fts5(
+        note,
+        target,
+        terms,
+        tokenize=\"porter unicode61 tokenchars '_'\"
+    );
+";
+
+const ANNOTATIONS_FTS_TRIGGER: &str = "
+    CREATE TRIGGER IF NOT EXISTS annotations_fts_ad AFTER DELETE ON annotations BEGIN
+        DELETE FROM annotations_fts WHERE rowid = old.id;
+    END;
+";
+
+// Triggers to keep both FTS indexes in sync with the chunks table.
+const CHUNKS_FTS_TRIGGERS: &str = "
+    CREATE TRIGGER IF NOT EXISTS chunks_ai AFTER INSERT ON chunks BEGIN
+        INSERT INTO chunks_fts(rowid, name, content, terms) VALUES (new.id, new.name, new.content, new.terms);
+    END;
+    CREATE TRIGGER IF NOT EXISTS chunks_ad AFTER DELETE ON chunks BEGIN
+        INSERT INTO chunks_fts(chunks_fts, rowid, name, content, terms) VALUES('delete', old.id, old.name, old.content, old.terms);
+    END;
+    CREATE TRIGGER IF NOT EXISTS chunks_au AFTER UPDATE ON chunks BEGIN
+        INSERT INTO chunks_fts(chunks_fts, rowid, name, content, terms) VALUES('delete', old.id, old.name, old.content, old.terms);
+        INSERT INTO chunks_fts(rowid, name, content, terms) VALUES (new.id, new.name, new.content, new.terms);
+    END;
+    CREATE TRIGGER IF NOT EXISTS chunks_trigram_ai AFTER INSERT ON chunks BEGIN
+        INSERT INTO chunks_trigram(rowid, content) VALUES (new.id, new.content);
+    END;
+    CREATE TRIGGER IF NOT EXISTS chunks_trigram_ad AFTER DELETE ON chunks BEGIN
+        INSERT INTO chunks_trigram(chunks_trigram, rowid, content) VALUES('delete', old.id, old.content);
+    END;
+    CREATE TRIGGER IF NOT EXISTS chunks_trigram_au AFTER UPDATE OF content ON chunks BEGIN
+        INSERT INTO chunks_trigram(chunks_trigram, rowid, content) VALUES('delete', old.id, old.content);
+        INSERT INTO chunks_trigram(rowid, content) VALUES (new.id, new.content);
+    END;
+";
 
 pub fn run_migrations(conn: &Connection) -> Result<()> {
     conn.execute_batch("
-        PRAGMA journal_mode = WAL;
         PRAGMA synchronous = NORMAL;
         PRAGMA foreign_keys = ON;
+    ")?;
+    // A current schema needs no writes, so opening the index to read never
+    // waits on (or fails behind) an indexer's write transaction.
+    if table_exists(conn, "meta")? && schema_version(conn).is_ok_and(|v| v >= SCHEMA_VERSION) {
+        return Ok(());
+    }
+
+    conn.execute_batch("
+        PRAGMA journal_mode = WAL;
 
         CREATE TABLE IF NOT EXISTS meta (
             key   TEXT PRIMARY KEY,
@@ -34,41 +149,62 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
             end_line   INTEGER NOT NULL,
             start_byte INTEGER NOT NULL,
             end_byte   INTEGER NOT NULL,
+            terms      TEXT,           -- identifier sub-tokens for FTS (see store::tokens)
             UNIQUE(file_id, start_byte, end_byte)
         );
         CREATE INDEX IF NOT EXISTS idx_chunks_file ON chunks(file_id);
         CREATE INDEX IF NOT EXISTS idx_chunks_kind ON chunks(kind);
         CREATE INDEX IF NOT EXISTS idx_chunks_name ON chunks(name) WHERE name IS NOT NULL;
 
-        -- FTS5 virtual table for full-text search over chunk content and symbol names.
-        -- content='' makes it an external-content table — we manage sync ourselves,
-        -- which avoids doubling storage.
-        CREATE VIRTUAL TABLE IF NOT EXISTS chunks_fts USING fts5(
-            name,
-            content,
-            content='chunks',
-            content_rowid='id',
-            tokenize='porter unicode61'
+        -- Symbol table: one row per declaration chunk (every non-raw chunk),
+        -- so symbol listings and definition lookups don't scan chunk text.
+        CREATE TABLE IF NOT EXISTS symbols (
+            id         INTEGER PRIMARY KEY,
+            chunk_id   INTEGER NOT NULL REFERENCES chunks(id) ON DELETE CASCADE,
+            name       TEXT,
+            kind       TEXT NOT NULL,
+            line       INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_symbols_chunk ON symbols(chunk_id);
+        CREATE INDEX IF NOT EXISTS idx_symbols_name ON symbols(name) WHERE name IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS idx_symbols_kind ON symbols(kind);
+
+        -- Occurrences: every identifier inside a chunk, from tree-sitter.
+        -- chunk_id is the innermost enclosing chunk (which gives the file).
+        CREATE TABLE IF NOT EXISTS occurrences (
+            id         INTEGER PRIMARY KEY,
+            chunk_id   INTEGER NOT NULL REFERENCES chunks(id) ON DELETE CASCADE,
+            name       TEXT NOT NULL,
+            role       TEXT NOT NULL,  -- 'definition', 'call', 'type', 'import', 'write', 'reference'
+            line       INTEGER NOT NULL,
+            col        INTEGER NOT NULL
         );
+        CREATE INDEX IF NOT EXISTS idx_occurrences_name ON occurrences(name, role);
+        CREATE INDEX IF NOT EXISTS idx_occurrences_chunk ON occurrences(chunk_id);
+
+        -- Abbreviations mined from each chunk (see search::synonyms), e.g.
+        -- cfg → config. Expand queries alongside [synonyms] in config.toml.
+        CREATE TABLE IF NOT EXISTS abbreviations (
+            chunk_id INTEGER NOT NULL REFERENCES chunks(id) ON DELETE CASCADE,
+            abbr     TEXT NOT NULL,
+            word     TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_abbreviations_chunk ON abbreviations(chunk_id);
 
-        -- Triggers to keep FTS in sync with the chunks table.
-        CREATE TRIGGER IF NOT EXISTS chunks_ai AFTER INSERT ON chunks BEGIN
-            INSERT INTO chunks_fts(rowid, name, content) VALUES (new.id, new.name, new.content);
-        END;
-        CREATE TRIGGER IF NOT EXISTS chunks_ad AFTER DELETE ON chunks BEGIN
-            INSERT INTO chunks_fts(chunks_fts, rowid, name, content) VALUES('delete', old.id, old.name, old.content);
-        END;
-        CREATE TRIGGER IF NOT EXISTS chunks_au AFTER UPDATE ON chunks BEGIN
-            INSERT INTO chunks_fts(chunks_fts, rowid, name, content) VALUES('delete', old.id, old.name, old.content);
-            INSERT INTO chunks_fts(rowid, name, content) VALUES (new.id, new.name, new.content);
-        END;
         -- Embeddings: vector representations of chunks for semantic search.
-        -- embedding is a BLOB of packed f32 values (dimensions × 4 bytes).
+        -- embedding is a BLOB of packed f32 values (dimensions × 4 bytes),
+        -- or with encoding 1 an f32 scale followed by one int8 code per
+        -- dimension (only in indexes compacted by earlier versions; the
+        -- quantized copy now lives in vectors.quant, see search::quantized).
         -- model tracks which model generated the embedding for invalidation.
+        -- generation is meta.embeddings_generation when the row was written,
+        -- so vector indexes can tell rewritten rows from unchanged ones.
         CREATE TABLE IF NOT EXISTS embeddings (
             chunk_id   INTEGER PRIMARY KEY REFERENCES chunks(id) ON DELETE CASCADE,
             model      TEXT NOT NULL,
-            embedding  BLOB NOT NULL
+            embedding  BLOB NOT NULL,
+            generation INTEGER NOT NULL DEFAULT 0,
+            encoding   INTEGER NOT NULL DEFAULT 0
         );
 
         -- Volatile context: annotations attached to files/symbols/line-ranges.
@@ -97,18 +233,241 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         );
         CREATE INDEX IF NOT EXISTS idx_workset_kind ON workset(kind);
         CREATE INDEX IF NOT EXISTS idx_workset_session ON workset(session_id);
+
+        -- Agent feedback: whether a result was useful for a query. Persistent
+        -- per repo; symbol or line narrow the target below the whole file.
+        CREATE TABLE IF NOT EXISTS feedback (
+            id         INTEGER PRIMARY KEY,
+            query      TEXT NOT NULL,
+            path       TEXT NOT NULL,
+            symbol     TEXT,
+            line       INTEGER,
+            useful     INTEGER NOT NULL,  -- 1 = useful, 0 = not useful
+            session_id TEXT,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_feedback_path ON feedback(path);
+
+        -- Session goals (see context::intent): one per session, NULL being the
+        -- default session. terms are the goal's key terms, space-separated;
+        -- embedding is packed f32 like embeddings.embedding, if one was made.
+        -- neighbours_generation is meta.embeddings_generation when the
+        -- chunks nearest the embedding were last stored in intent_neighbours.
+        CREATE TABLE IF NOT EXISTS intents (
+            id         INTEGER PRIMARY KEY,
+            goal       TEXT NOT NULL,
+            terms      TEXT NOT NULL,
+            embedding  BLOB,
+            session_id TEXT,
+            created_at TEXT NOT NULL,
+            neighbours_generation INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS intent_neighbours (
+            intent_id  INTEGER NOT NULL REFERENCES intents(id) ON DELETE CASCADE,
+            path       TEXT NOT NULL,
+            start_line INTEGER NOT NULL,
+            similarity REAL NOT NULL,
+            PRIMARY KEY (intent_id, path, start_line)
+        ) WITHOUT ROWID;
+
+        -- Git history per file, refreshed while indexing (see git::history):
+        -- commits among the latest ones and the newest commit time (Unix seconds).
+        CREATE TABLE IF NOT EXISTS file_history (
+            path          TEXT PRIMARY KEY,
+            commits       INTEGER NOT NULL,
+            last_modified INTEGER NOT NULL
+        );
+
+        -- Branch changes for the `diff` ranking stage (see git::diff), per
+        -- base ref ('' = default branch), as JSON. Reused across processes
+        -- while HEAD and meta.index_generation match and for the TTL.
+        CREATE TABLE IF NOT EXISTS branch_changes (
+            base             TEXT PRIMARY KEY,
+            head             TEXT NOT NULL,
+            index_generation INTEGER NOT NULL,
+            computed_at      INTEGER NOT NULL,
+            changes          TEXT NOT NULL
+        );
+
+        -- Optional commit history index, filled by `index --history`:
+        -- one row per commit (time in Unix seconds) and one per symbol it
+        -- added, modified or removed (change), found by diffing chunks.
+        CREATE TABLE IF NOT EXISTS commits (
+            id      INTEGER PRIMARY KEY,
+            hash    TEXT NOT NULL UNIQUE,
+            author  TEXT NOT NULL,
+            time    INTEGER NOT NULL,
+            message TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS commit_symbols (
+            commit_id INTEGER NOT NULL REFERENCES commits(id) ON DELETE CASCADE,
+            path      TEXT NOT NULL,
+            kind      TEXT NOT NULL,
+            name      TEXT NOT NULL,
+            change    TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_commit_symbols_commit ON commit_symbols(commit_id);
+        CREATE INDEX IF NOT EXISTS idx_commit_symbols_name ON commit_symbols(name);
     ")?;
 
+    let fts_exists = table_exists(conn, "chunks_fts")?;
+
     conn.execute(
-        "INSERT OR IGNORE INTO meta (key, value) VALUES ('schema_version', '5')",
-        [],
+        "INSERT OR IGNORE INTO meta (key, value) VALUES ('schema_version', ?1)",
+        params![SCHEMA_VERSION.to_string()],
     )?;
+    let version = schema_version(conn)?;
 
     // Migration: add signature column (v5)
     let _ = conn.execute(
         "ALTER TABLE chunks ADD COLUMN signature TEXT",
         [],
     );
+    // Embedding generations: bumped on every write and delete; vector
+    // indexes record the generation they reflect.
+    let _ = conn.execute(
+        "ALTER TABLE embeddings ADD COLUMN generation INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE embeddings ADD COLUMN encoding INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE intents ADD COLUMN neighbours_generation INTEGER", []);
+    conn.execute_batch(
+        "INSERT OR IGNORE INTO meta (key, value) VALUES ('embeddings_generation', '0');
+         CREATE INDEX IF NOT EXISTS idx_embeddings_generation ON embeddings(generation);
+         CREATE TRIGGER IF NOT EXISTS embeddings_ad AFTER DELETE ON embeddings BEGIN
+             UPDATE meta SET value = CAST(value AS INTEGER) + 1 WHERE key = 'embeddings_generation';
+         END;",
+    )?;
+
+    if !fts_exists {
+        conn.execute_batch(CHUNKS_FTS)?;
+    } else if version < 6 {
+        migrate_identifier_terms(conn)?;
+    }
+    // Migration v7: trigram index. 'rebuild' backfills existing chunks.
+    if !table_exists(conn, "chunks_trigram")? {
+        conn.execute_batch(CHUNKS_TRIGRAM)?;
+    }
+    conn.execute_batch(CHUNKS_FTS_TRIGGERS)?;
+    if !table_exists(conn, "commits_fts")? {
+        conn.execute_batch(COMMITS_FTS)?;
+    }
+    if !table_exists(conn, "synonyms")? {
+        conn.execute_batch(SYNONYMS)?;
+        refresh_synonyms(conn)?;
+    }
+    if !table_exists(conn, "annotations_fts")? {
+        create_annotations_fts(conn)?;
+    }
+    conn.execute_batch(ANNOTATIONS_FTS_TRIGGER)?;
+    // Migration v8: symbols and occurrences come from parsing whole files,
+    // which the index doesn't keep. Clear content hashes so the next index
+    // pass re-parses every file. v9 does the same to mine abbreviations.
+    if version < 9 {
+        conn.execute("UPDATE files SET content_hash = ''", [])?;
+    }
+    conn.execute(
+        "UPDATE meta SET value = ?1 WHERE key = 'schema_version' AND CAST(value AS INTEGER) < ?2",
+        params![SCHEMA_VERSION.to_string(), SCHEMA_VERSION],
+    )?;
 
     Ok(())
 }
+
+/// Add an annotation to `annotations_fts`.
+pub(super) fn index_annotation(conn: &Connection, id: i64, target: &str, note: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO annotations_fts (rowid, note, target, terms) VALUES (?1, ?2, ?3, ?4)",
+        params![id, note, target, tokens::chunk_terms(Some(target), note)],
+    )?;
+    Ok(())
+}
+
+/// Rebuild `synonyms` from the mined `abbreviations`.
+pub(super) fn refresh_synonyms(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DELETE FROM synonyms;
+         INSERT INTO synonyms (term, alt, files)
+         SELECT term, alt, MAX(files) FROM (
+             SELECT lower(a.abbr) AS term, lower(a.word) AS alt, COUNT(DISTINCT c.file_id) AS files
+             FROM abbreviations a JOIN chunks c ON c.id = a.chunk_id
+             GROUP BY 1, 2
+             UNION ALL
+             SELECT lower(a.word), lower(a.abbr), COUNT(DISTINCT c.file_id)
+             FROM abbreviations a JOIN chunks c ON c.id = a.chunk_id
+             GROUP BY 1, 2
+         )
+         WHERE term <> alt
+         GROUP BY term, alt;",
+    )?;
+    Ok(())
+}
+
+/// Create `annotations_fts` and index the annotations made before it existed.
+fn create_annotations_fts(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(ANNOTATIONS_FTS)?;
+    {
+        let mut select = tx.prepare("SELECT id, target, note FROM annotations")?;
+        let rows = select.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?;
+        for row in rows {
+            let (id, target, note) = row?;
+            index_annotation(&tx, id, &target, &note)?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE name = ?1",
+        params![name],
+        |r| r.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn schema_version(conn: &Connection) -> Result<i64> {
+    let v: String = conn.query_row(
+        "SELECT value FROM meta WHERE key = 'schema_version'",
+        [],
+        |r| r.get(0),
+    )?;
+    Ok(v.parse().unwrap_or(0))
+}
+
+/// Migration v6: identifier-aware tokenization. Adds `chunks.terms`,
+/// backfills it and recreates `chunks_fts` with the new column and tokenizer.
+fn migrate_identifier_terms(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let _ = tx.execute("ALTER TABLE chunks ADD COLUMN terms TEXT", []);
+    tx.execute_batch(
+        "DROP TRIGGER IF EXISTS chunks_ai;
+         DROP TRIGGER IF EXISTS chunks_ad;
+         DROP TRIGGER IF EXISTS chunks_au;
+         DROP TABLE IF EXISTS chunks_fts;",
+    )?;
+    {
+        let mut select = tx.prepare("SELECT id, name, content FROM chunks")?;
+        let mut update = tx.prepare("UPDATE chunks SET terms = ?1 WHERE id = ?2")?;
+        let rows = select.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?))
+        })?;
+        for row in rows {
+            let (id, name, content) = row?;
+            update.execute(params![tokens::chunk_terms(name.as_deref(), &content), id])?;
+        }
+    }
+    tx.execute_batch(CHUNKS_FTS)?;
+    tx.execute("INSERT INTO chunks_fts(chunks_fts) VALUES('rebuild')", [])?;
+    tx.execute("UPDATE meta SET value = '6' WHERE key = 'schema_version'", [])?;
+    tx.commit()?;
+    Ok(())
+}
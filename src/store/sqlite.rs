@@ -1,16 +1,25 @@
 use anyhow::{Context, Result};
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::time::Duration;
 
-use super::schema;
+use super::{schema, tokens};
+
+/// How long a connection waits on another process's write lock before
+/// failing with "database is locked".
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// External-content FTS5 tables synced from `chunks` by triggers.
+const FTS_TABLES: [&str; 2] = ["chunks_fts", "chunks_trigram"];
 
 pub struct Store {
     conn: Connection,
 }
 
 /// A search result returned from FTS queries.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SearchResult {
     pub file_path: String,
     pub language: Option<String>,
@@ -23,6 +32,195 @@ pub struct SearchResult {
     pub rank: f64,
 }
 
+/// Field filters for `Store::search_filtered`, `all_chunks`,
+/// `list_symbols` and `find_occurrences`.
+///
+/// Values of one field are alternatives (OR); fields and exclusions
+/// combine with AND. A value containing `*`, `?` or `[` is a GLOB over the
+/// whole field; otherwise paths match as a prefix and other fields exactly.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchFilter {
+    pub languages: Vec<String>,
+    pub kinds: Vec<String>,
+    pub paths: Vec<String>,
+    pub names: Vec<String>,
+    pub exclude_languages: Vec<String>,
+    pub exclude_kinds: Vec<String>,
+    pub exclude_paths: Vec<String>,
+    pub exclude_names: Vec<String>,
+    pub tests: TestScope,
+}
+
+/// Whether test code (see `TEST_CHUNK`) is searched.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TestScope {
+    #[default]
+    Include,
+    Exclude,
+    Only,
+}
+
+impl TestScope {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "include" => Some(TestScope::Include),
+            "exclude" => Some(TestScope::Exclude),
+            "only" => Some(TestScope::Only),
+            _ => None,
+        }
+    }
+}
+
+/// True for chunks in test code, over `f` (files) and `c` (chunks):
+///
+/// - files under `test/`, `tests/`, `__tests__/`, `spec/`, `testdata/`, or
+///   named `test_*`, `*_test.*`, `*_spec.*`, `*.test.*`, `*.spec.*`, `*Test.*`
+/// - functions named `test_*`, `TestXxx`, `*_test`, `*Test`, `*_spec`
+/// - `#[test]`, `#[tokio::test]`, `#[cfg(test)]`, `describe(` and `it(` blocks
+/// - anything after a test module (`test`, `tests`, `test_*`, `*_test`,
+///   `*_tests`) in the same file; `latest` or `attestation` are not tests
+///
+/// May be NULL for unnamed chunks; wrap it in `COALESCE(.., 0)`.
+const TEST_CHUNK: &str = "(
+    ('/' || f.path) GLOB '*/test/*' OR ('/' || f.path) GLOB '*/tests/*'
+    OR ('/' || f.path) GLOB '*/__tests__/*' OR ('/' || f.path) GLOB '*/spec/*'
+    OR ('/' || f.path) GLOB '*/testdata/*' OR ('/' || f.path) GLOB '*/test_*'
+    OR f.path GLOB '*_test.*' OR f.path GLOB '*_spec.*' OR f.path GLOB '*.test.*'
+    OR f.path GLOB '*.spec.*' OR f.path GLOB '*Test.*' OR f.path GLOB '*Tests.*'
+    OR c.name GLOB 'test_*' OR c.name GLOB 'Test[A-Z_]*' OR c.name GLOB '*_test'
+    OR c.name GLOB '*Test' OR c.name GLOB '*_spec'
+    OR instr(c.content, '#[test]') OR instr(c.content, '#[tokio::test')
+    OR instr(c.content, '#[cfg(test)]') OR instr(c.content, 'describe(')
+    OR c.content GLOB 'it(*' OR instr(c.content, ' it(') OR instr(c.content, char(10) || 'it(')
+    OR EXISTS (
+        SELECT 1 FROM chunks m
+        WHERE m.file_id = c.file_id AND m.kind = 'module'
+          AND (lower(m.name) IN ('test', 'tests') OR lower(m.name) GLOB 'test_*'
+               OR lower(m.name) GLOB '*_test' OR lower(m.name) GLOB '*_tests')
+          AND m.start_line <= c.start_line
+    )
+)";
+
+impl SearchFilter {
+    /// The classic single-valued options; `None` means no restriction.
+    pub fn new(language: Option<&str>, path_prefix: Option<&str>, kind: Option<&str>) -> Self {
+        SearchFilter {
+            languages: language.map(String::from).into_iter().collect(),
+            paths: path_prefix.map(String::from).into_iter().collect(),
+            kinds: kind.map(String::from).into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Append ` AND ...` conditions on `f` (files) and `c` (chunks),
+    /// numbering parameters after those already in `params`.
+    fn push_sql(&self, sql: &mut String, params: &mut Vec<Box<dyn rusqlite::types::ToSql>>) {
+        let fields = [
+            ("f.language", &self.languages, &self.exclude_languages, false),
+            ("c.kind", &self.kinds, &self.exclude_kinds, false),
+            ("f.path", &self.paths, &self.exclude_paths, true),
+            ("c.name", &self.names, &self.exclude_names, false),
+        ];
+        for (column, include, exclude, prefix) in fields {
+            if let Some(any) = any_value(column, include, prefix, params) {
+                sql.push_str(&format!(" AND {any}"));
+            }
+            // NULL (no language, unnamed chunk) never counts as excluded.
+            if let Some(any) = any_value(column, exclude, prefix, params) {
+                sql.push_str(&format!(" AND NOT COALESCE({any}, 0)"));
+            }
+        }
+        match self.tests {
+            TestScope::Include => {}
+            TestScope::Exclude => sql.push_str(&format!(" AND NOT COALESCE({TEST_CHUNK}, 0)")),
+            TestScope::Only => sql.push_str(&format!(" AND COALESCE({TEST_CHUNK}, 0)")),
+        }
+    }
+}
+
+/// `(m1 OR m2 ...)` matching `column` against each value, or None if empty.
+fn any_value(
+    column: &str,
+    values: &[String],
+    prefix: bool,
+    params: &mut Vec<Box<dyn rusqlite::types::ToSql>>,
+) -> Option<String> {
+    if values.is_empty() {
+        return None;
+    }
+    let matches: Vec<String> = values
+        .iter()
+        .map(|v| {
+            params.push(Box::new(v.clone()));
+            let n = params.len();
+            if v.contains(['*', '?', '[']) {
+                format!("{column} GLOB ?{n}")
+            } else if prefix {
+                format!("substr({column}, 1, length(?{n})) = ?{n}")
+            } else {
+                format!("{column} = ?{n}")
+            }
+        })
+        .collect();
+    Some(format!("({})", matches.join(" OR ")))
+}
+
+/// A named symbol without its content, for name lookups.
+#[derive(Debug, Serialize)]
+pub struct SymbolEntry {
+    pub name: String,
+    pub kind: String,
+    pub file_path: String,
+    pub language: Option<String>,
+    pub start_line: i64,
+    pub end_line: i64,
+    pub signature: Option<String>,
+}
+
+/// How often and how recently a file changed, from `git log`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileHistory {
+    /// Commits touching the file, among the last `git::history::MAX_COMMITS`.
+    pub commits: u32,
+    /// Commit time of the newest of them, in Unix seconds.
+    pub last_modified: i64,
+}
+
+/// Branch changes stored for the `diff` ranking stage (see
+/// `git::diff::BranchChanges`), with what they were computed against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedBranchChanges {
+    /// The commit HEAD pointed at.
+    pub head: String,
+    /// `Store::index_generation` at the time.
+    pub index_generation: i64,
+    /// Unix seconds.
+    pub computed_at: i64,
+    /// The changes, as JSON.
+    pub changes: String,
+}
+
+/// A commit in the history index (see `git::history::commits`).
+#[derive(Debug, Clone, Serialize)]
+pub struct Commit {
+    pub hash: String,
+    pub author: String,
+    /// Commit time in Unix seconds.
+    pub time: i64,
+    pub message: String,
+    pub symbols: Vec<CommitSymbol>,
+}
+
+/// A symbol a commit added, modified or removed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommitSymbol {
+    pub path: String,
+    pub kind: String,
+    pub name: String,
+    /// `added`, `modified` or `removed`.
+    pub change: String,
+}
+
 /// A file record as stored in the index.
 pub struct FileRecord {
     pub id: i64,
@@ -33,6 +231,29 @@ pub struct FileRecord {
     pub indexed_at: String,
 }
 
+/// An identifier occurrence for insertion (see `graph::extract`).
+pub struct OccurrenceInsert {
+    pub chunk_id: i64,
+    pub name: String,
+    pub role: &'static str,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// An identifier occurrence with its enclosing chunk.
+#[derive(Debug, Serialize)]
+pub struct SymbolOccurrence {
+    pub file_path: String,
+    pub line: u32,
+    pub column: u32,
+    pub role: String,
+    pub chunk_kind: String,
+    pub chunk_name: Option<String>,
+    pub chunk_signature: Option<String>,
+    /// The source line, trimmed.
+    pub text: String,
+}
+
 /// A chunk record for insertion.
 pub struct ChunkInsert {
     pub kind: String,
@@ -55,6 +276,39 @@ pub struct Annotation {
     pub expires_at: Option<String>,
 }
 
+/// Whether a result was useful for a query, as told by an agent.
+#[derive(Debug, Clone, Serialize)]
+pub struct Feedback {
+    pub id: i64,
+    pub query: String,
+    pub path: String,
+    /// The chunk's name, if the feedback is about one symbol.
+    pub symbol: Option<String>,
+    /// A line inside the chunk, if the feedback is about one chunk without a name.
+    pub line: Option<i64>,
+    pub useful: bool,
+    pub session_id: Option<String>,
+    pub created_at: String,
+}
+
+/// A session's goal, biasing search ranking (see `context::intent`).
+#[derive(Debug, Clone, Serialize)]
+pub struct Intent {
+    pub id: i64,
+    pub goal: String,
+    /// Key terms of the goal, lowercased.
+    pub terms: Vec<String>,
+    /// Embedding of the goal, if one was made.
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
+    pub session_id: Option<String>,
+    pub created_at: String,
+    /// Embeddings generation the stored neighbours were found at, if any
+    /// (see `Store::intent_neighbours`).
+    #[serde(skip)]
+    pub neighbours_generation: Option<i64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct WorksetEntry {
     pub id: i64,
@@ -92,6 +346,7 @@ impl Store {
         let db_path = storage_dir.join("index.db");
         let conn = Connection::open(&db_path)
             .with_context(|| format!("opening database at {}", db_path.display()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         schema::run_migrations(&conn)?;
         Ok(Self { conn })
     }
@@ -105,6 +360,7 @@ impl Store {
         }
         let conn = Connection::open(&db_path)
             .with_context(|| format!("opening database at {}", db_path.display()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         schema::run_migrations(&conn)?;
         Ok(Some(Self { conn }))
     }
@@ -140,27 +396,36 @@ impl Store {
         language: Option<&str>,
     ) -> Result<i64> {
         let now = chrono::Utc::now().to_rfc3339();
-        self.conn.execute(
+        // RETURNING rather than last_insert_rowid(): the latter is not updated
+        // when the upsert takes the UPDATE branch.
+        let id = self.conn.query_row(
             "INSERT INTO files (path, content_hash, size_bytes, language, indexed_at, mtime)
              VALUES (?1, ?2, ?3, ?4, ?5, NULL)
              ON CONFLICT(path) DO UPDATE SET
                 content_hash = excluded.content_hash,
                 size_bytes = excluded.size_bytes,
                 language = excluded.language,
-                indexed_at = excluded.indexed_at",
+                indexed_at = excluded.indexed_at
+             RETURNING id",
             params![path, content_hash, size_bytes, language, now],
+            |row| row.get(0),
         )?;
-        Ok(self.conn.last_insert_rowid())
+        Ok(id)
     }
 
     /// Bulk insert chunks for a file. Call within a transaction for performance.
-    pub fn insert_chunks(&self, file_id: i64, chunks: &[ChunkInsert]) -> Result<()> {
+    pub fn insert_chunks(&self, file_id: i64, chunks: &[ChunkInsert]) -> Result<Vec<i64>> {
         let mut stmt = self.conn.prepare(
-            "INSERT OR REPLACE INTO chunks (file_id, kind, name, content, signature, start_line, end_line, start_byte, end_byte)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            "INSERT OR REPLACE INTO chunks (file_id, kind, name, content, signature, start_line, end_line, start_byte, end_byte, terms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             RETURNING id",
+        )?;
+        let mut symbol_stmt = self.conn.prepare(
+            "INSERT INTO symbols (chunk_id, name, kind, line) VALUES (?1, ?2, ?3, ?4)",
         )?;
+        let mut ids = Vec::with_capacity(chunks.len());
         for chunk in chunks {
-            stmt.execute(params![
+            let id: i64 = stmt.query_row(params![
                 file_id,
                 chunk.kind,
                 chunk.name,
@@ -170,11 +435,54 @@ impl Store {
                 chunk.end_line,
                 chunk.start_byte,
                 chunk.end_byte,
-            ])?;
+                tokens::chunk_terms(chunk.name.as_deref(), &chunk.content),
+            ], |r| r.get(0))?;
+            if chunk.kind != "raw" {
+                symbol_stmt.execute(params![id, chunk.name, chunk.kind, chunk.start_line])?;
+            }
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    /// Insert identifier occurrences (their chunks must already exist).
+    pub fn insert_occurrences(&self, occurrences: &[OccurrenceInsert]) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "INSERT INTO occurrences (chunk_id, name, role, line, col) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for o in occurrences {
+            stmt.execute(params![o.chunk_id, o.name, o.role, o.line, o.column])?;
+        }
+        Ok(())
+    }
+
+    /// Store `(chunk_id, abbr, word)` abbreviation pairs mined from chunks.
+    pub fn insert_abbreviations(&self, abbreviations: &[(i64, String, String)]) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "INSERT INTO abbreviations (chunk_id, abbr, word) VALUES (?1, ?2, ?3)",
+        )?;
+        for (chunk_id, abbr, word) in abbreviations {
+            stmt.execute(params![chunk_id, abbr, word])?;
         }
         Ok(())
     }
 
+    /// Re-aggregate the mined abbreviations into the `synonyms` lookup
+    /// table. Run after index passes that changed files.
+    pub fn refresh_synonyms(&self) -> Result<()> {
+        schema::refresh_synonyms(&self.conn)
+    }
+
+    /// Up to `limit` mined alternatives for a lowercase `term` (`cfg` for
+    /// `config` and vice versa), most used first.
+    pub fn synonyms_for(&self, term: &str, limit: usize) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT alt FROM synonyms WHERE term = ?1 ORDER BY files DESC, alt LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![term, limit as i64], |row| row.get(0))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
     /// Delete all chunks for a file (used before re-indexing).
     pub fn delete_chunks_for_file(&self, file_id: i64) -> Result<()> {
         self.conn
@@ -189,9 +497,11 @@ impl Store {
         Ok(())
     }
 
-    /// Begin a transaction. Returns the inner connection for batch ops.
+    /// Begin a write transaction. IMMEDIATE takes the write lock up front so
+    /// contention waits on the busy timeout instead of failing mid-transaction
+    /// when a read has to be upgraded to a write.
     pub fn begin_transaction(&self) -> Result<()> {
-        self.conn.execute_batch("BEGIN TRANSACTION")?;
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
         Ok(())
     }
 
@@ -213,50 +523,51 @@ impl Store {
         path_prefix: Option<&str>,
         kind: Option<&str>,
         max_results: usize,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_filtered(query, &SearchFilter::new(language, path_prefix, kind), max_results)
+    }
+
+    /// Full-text search restricted by a `SearchFilter`. An empty query
+    /// lists the chunks the filter selects, in path/line order, with rank 0.
+    pub fn search_filtered(
+        &self,
+        query: &str,
+        filter: &SearchFilter,
+        max_results: usize,
     ) -> Result<Vec<SearchResult>> {
         let query = sanitize_fts_query(query);
-        let query = query.as_str();
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
 
-        let mut sql = String::from(
-            "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content,
-                    chunks_fts.rank
-             FROM chunks_fts
-             JOIN chunks c ON c.id = chunks_fts.rowid
-             JOIN files f ON f.id = c.file_id
-             WHERE chunks_fts MATCH ?1",
-        );
-        let mut param_idx = 2;
+        let mut sql = if query.trim().is_empty() {
+            String::from(
+                "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content,
+                        0.0
+                 FROM chunks c
+                 JOIN files f ON f.id = c.file_id
+                 WHERE 1",
+            )
+        } else {
+            params_vec.push(Box::new(query));
+            String::from(
+                "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content,
+                        chunks_fts.rank
+                 FROM chunks_fts
+                 JOIN chunks c ON c.id = chunks_fts.rowid
+                 JOIN files f ON f.id = c.file_id
+                 WHERE chunks_fts MATCH ?1",
+            )
+        };
+        let ranked = !params_vec.is_empty();
+        filter.push_sql(&mut sql, &mut params_vec);
 
-        if language.is_some() {
-            sql.push_str(&format!(" AND f.language = ?{param_idx}"));
-            param_idx += 1;
-        }
-        if path_prefix.is_some() {
-            sql.push_str(&format!(" AND f.path LIKE ?{param_idx} || '%'"));
-            param_idx += 1;
-        }
-        if kind.is_some() {
-            sql.push_str(&format!(" AND c.kind = ?{param_idx}"));
-            param_idx += 1;
+        params_vec.push(Box::new(max_results as i64));
+        if ranked {
+            sql.push_str(&format!(" ORDER BY chunks_fts.rank LIMIT ?{}", params_vec.len()));
+        } else {
+            sql.push_str(&format!(" ORDER BY f.path, c.start_line LIMIT ?{}", params_vec.len()));
         }
 
-        sql.push_str(&format!(" ORDER BY chunks_fts.rank LIMIT ?{param_idx}"));
-
         let mut stmt = self.conn.prepare(&sql)?;
-
-        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
-        params_vec.push(Box::new(query.to_string()));
-        if let Some(lang) = language {
-            params_vec.push(Box::new(lang.to_string()));
-        }
-        if let Some(prefix) = path_prefix {
-            params_vec.push(Box::new(prefix.to_string()));
-        }
-        if let Some(k) = kind {
-            params_vec.push(Box::new(k.to_string()));
-        }
-        params_vec.push(Box::new(max_results as i64));
-
         let param_refs: Vec<&dyn rusqlite::types::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
 
         let rows = stmt.query_map(param_refs.as_slice(), |row| {
@@ -280,40 +591,108 @@ impl Store {
         Ok(results)
     }
 
-    /// List all symbols (chunks) in a file or directory, optionally filtered by kind.
-    pub fn list_symbols(
+    /// 0-based lines of a chunk's content where FTS5 `highlight()` marks a
+    /// match for `query`. Matches found only through the identifier
+    /// sub-tokens in `terms` are not marked in the content.
+    pub fn highlight_lines(
+        &self,
+        query: &str,
+        file_path: &str,
+        start_line: i64,
+        end_line: i64,
+    ) -> Result<Vec<usize>> {
+        let query = sanitize_fts_query(query);
+        if query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT highlight(chunks_fts, 1, char(1), char(2))
+             FROM chunks_fts
+             JOIN chunks c ON c.id = chunks_fts.rowid
+             JOIN files f ON f.id = c.file_id
+             WHERE chunks_fts MATCH ?1 AND f.path = ?2 AND c.start_line = ?3 AND c.end_line = ?4
+             LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map(params![query, file_path, start_line, end_line], |row| {
+            row.get::<_, String>(0)
+        })?;
+        let highlighted = match rows.next() {
+            Some(row) => row?,
+            None => return Ok(Vec::new()),
+        };
+        Ok(highlighted
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.contains('\u{1}'))
+            .map(|(i, _)| i)
+            .collect())
+    }
+
+    /// Every named symbol, optionally filtered by kind, language and path
+    /// prefix. Content is not loaded.
+    pub fn symbol_entries(
         &self,
-        path_prefix: Option<&str>,
         kind: Option<&str>,
-    ) -> Result<Vec<SearchResult>> {
+        language: Option<&str>,
+        path_prefix: Option<&str>,
+    ) -> Result<Vec<SymbolEntry>> {
         let mut sql = String::from(
-            "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content
-             FROM chunks c
+            "SELECT s.name, s.kind, f.path, f.language, c.start_line, c.end_line, c.signature
+             FROM symbols s
+             JOIN chunks c ON c.id = s.chunk_id
              JOIN files f ON f.id = c.file_id
-             WHERE c.kind != 'raw'",
+             WHERE s.name IS NOT NULL",
         );
-        let mut param_idx = 1;
-
-        if path_prefix.is_some() {
-            sql.push_str(&format!(" AND f.path LIKE ?{param_idx} || '%'"));
-            param_idx += 1;
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+        if let Some(k) = kind {
+            params_vec.push(Box::new(k.to_string()));
+            sql.push_str(&format!(" AND s.kind = ?{}", params_vec.len()));
         }
-        if kind.is_some() {
-            sql.push_str(&format!(" AND c.kind = ?{param_idx}"));
+        if let Some(lang) = language {
+            params_vec.push(Box::new(lang.to_string()));
+            sql.push_str(&format!(" AND f.language = ?{}", params_vec.len()));
         }
-
-        sql.push_str(" ORDER BY f.path, c.start_line");
-
-        let mut stmt = self.conn.prepare(&sql)?;
-
-        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
         if let Some(prefix) = path_prefix {
             params_vec.push(Box::new(prefix.to_string()));
+            let n = params_vec.len();
+            sql.push_str(&format!(" AND substr(f.path, 1, length(?{n})) = ?{n}"));
         }
-        if let Some(k) = kind {
-            params_vec.push(Box::new(k.to_string()));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::types::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(param_refs.as_slice(), |row| {
+            Ok(SymbolEntry {
+                name: row.get(0)?,
+                kind: row.get(1)?,
+                file_path: row.get(2)?,
+                language: row.get(3)?,
+                start_line: row.get(4)?,
+                end_line: row.get(5)?,
+                signature: row.get(6)?,
+            })
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(row?);
         }
+        Ok(entries)
+    }
+
+    /// List all symbols (chunks) selected by `filter`, in path/line order.
+    pub fn list_symbols(&self, filter: &SearchFilter) -> Result<Vec<SearchResult>> {
+        let mut sql = String::from(
+            "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content
+             FROM symbols s
+             JOIN chunks c ON c.id = s.chunk_id
+             JOIN files f ON f.id = c.file_id
+             WHERE 1=1",
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+        filter.push_sql(&mut sql, &mut params_vec);
+        sql.push_str(" ORDER BY f.path, s.line");
 
+        let mut stmt = self.conn.prepare(&sql)?;
         let param_refs: Vec<&dyn rusqlite::types::ToSql> =
             params_vec.iter().map(|p| p.as_ref()).collect();
 
@@ -344,7 +723,8 @@ impl Store {
     pub fn path_has_chunks(&self, path_prefix: Option<&str>) -> Result<bool> {
         let count: i64 = if let Some(prefix) = path_prefix {
             self.conn.query_row(
-                "SELECT COUNT(1) FROM chunks c JOIN files f ON f.id = c.file_id WHERE f.path LIKE ?1 || '%'",
+                "SELECT COUNT(1) FROM chunks c JOIN files f ON f.id = c.file_id
+                 WHERE substr(f.path, 1, length(?1)) = ?1",
                 params![prefix],
                 |row| row.get(0),
             )?
@@ -354,41 +734,158 @@ impl Store {
         Ok(count > 0)
     }
 
-    /// Return all chunks, optionally filtered by path and/or kind.
-    /// Unlike `list_symbols`, this includes raw chunks.
-    pub fn all_chunks(
+    /// Occurrences of an identifier in the chunks `filter` selects,
+    /// optionally limited to a role, in file/line order.
+    pub fn find_occurrences(
         &self,
-        path_prefix: Option<&str>,
-        kind: Option<&str>,
-    ) -> Result<Vec<SearchResult>> {
+        name: &str,
+        filter: &SearchFilter,
+        role: Option<&str>,
+    ) -> Result<Vec<SymbolOccurrence>> {
         let mut sql = String::from(
-            "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content
-             FROM chunks c
+            "SELECT f.path, o.line, o.col, o.role, c.kind, c.name, c.signature, c.start_line, c.content
+             FROM occurrences o
+             JOIN chunks c ON c.id = o.chunk_id
              JOIN files f ON f.id = c.file_id
-             WHERE 1=1",
+             WHERE o.name = ?1",
         );
-        let mut param_idx = 1;
-
-        if path_prefix.is_some() {
-            sql.push_str(&format!(" AND f.path LIKE ?{param_idx} || '%'"));
-            param_idx += 1;
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(name.to_string())];
+        if let Some(r) = role {
+            params_vec.push(Box::new(r.to_string()));
+            sql.push_str(&format!(" AND o.role = ?{}", params_vec.len()));
         }
-        if kind.is_some() {
-            sql.push_str(&format!(" AND c.kind = ?{param_idx}"));
+        filter.push_sql(&mut sql, &mut params_vec);
+        sql.push_str(" ORDER BY f.path, o.line, o.col");
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+            params_vec.iter().map(|p| p.as_ref()).collect();
+
+        let rows = stmt.query_map(param_refs.as_slice(), |row| {
+            let line: u32 = row.get(1)?;
+            let start_line: u32 = row.get(7)?;
+            let content: String = row.get(8)?;
+            let text = content
+                .lines()
+                .nth(line.saturating_sub(start_line) as usize)
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            Ok(SymbolOccurrence {
+                file_path: row.get(0)?,
+                line,
+                column: row.get(2)?,
+                role: row.get(3)?,
+                chunk_kind: row.get(4)?,
+                chunk_name: row.get(5)?,
+                chunk_signature: row.get(6)?,
+                text,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
         }
+        Ok(results)
+    }
+
+    /// Chunks selected by `filter` that contain an occurrence of `name`, in
+    /// file/line order.
+    pub fn chunks_referencing(&self, name: &str, filter: &SearchFilter) -> Result<Vec<SearchResult>> {
+        self.chunks_where(
+            "c.id IN (SELECT chunk_id FROM occurrences WHERE name = ?1)",
+            name,
+            filter,
+        )
+    }
+
+    /// Declaration chunks whose name contains `fragment` (case-insensitive
+    /// for ASCII), in file/line order. `instr` rather than LIKE, whose `_`
+    /// wildcard would match most snake_case fragments anywhere.
+    pub fn symbols_named_like(&self, fragment: &str) -> Result<Vec<SearchResult>> {
+        self.chunks_where(
+            "c.id IN (SELECT chunk_id FROM symbols WHERE instr(lower(name), lower(?1)) > 0)",
+            fragment,
+            &SearchFilter::default(),
+        )
+    }
 
+    fn chunks_where(&self, condition: &str, param: &str, filter: &SearchFilter) -> Result<Vec<SearchResult>> {
+        let mut sql = format!(
+            "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content
+             FROM chunks c
+             JOIN files f ON f.id = c.file_id
+             WHERE {condition}"
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(param.to_string())];
+        filter.push_sql(&mut sql, &mut params_vec);
         sql.push_str(" ORDER BY f.path, c.start_line");
 
         let mut stmt = self.conn.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+            params_vec.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(param_refs.as_slice(), |row| {
+            Ok(SearchResult {
+                file_path: row.get(0)?,
+                language: row.get(1)?,
+                chunk_kind: row.get(2)?,
+                chunk_name: row.get(3)?,
+                signature: row.get(4)?,
+                start_line: row.get(5)?,
+                end_line: row.get(6)?,
+                content: row.get(7)?,
+                rank: 0.0,
+            })
+        })?;
 
-        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
-        if let Some(prefix) = path_prefix {
-            params_vec.push(Box::new(prefix.to_string()));
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
         }
-        if let Some(k) = kind {
-            params_vec.push(Box::new(k.to_string()));
+        Ok(results)
+    }
+
+    /// Return all chunks selected by `filter`, in path/line order.
+    /// Unlike `list_symbols`, this includes raw chunks.
+    pub fn all_chunks(&self, filter: &SearchFilter) -> Result<Vec<SearchResult>> {
+        self.chunks_matching(None, filter)
+    }
+
+    /// Return chunks whose content matches a trigram query (see
+    /// `search::grep::literal_query`). A superset of the chunks containing
+    /// the literals: matching is case-insensitive.
+    pub fn trigram_candidates(
+        &self,
+        trigram_query: &str,
+        filter: &SearchFilter,
+    ) -> Result<Vec<SearchResult>> {
+        self.chunks_matching(Some(trigram_query), filter)
+    }
+
+    fn chunks_matching(
+        &self,
+        trigram_query: Option<&str>,
+        filter: &SearchFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let mut sql = String::from(
+            "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content
+             FROM chunks c
+             JOIN files f ON f.id = c.file_id
+             WHERE 1=1",
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+        if let Some(q) = trigram_query {
+            params_vec.push(Box::new(q.to_string()));
+            sql.push_str(&format!(
+                " AND c.id IN (SELECT rowid FROM chunks_trigram WHERE chunks_trigram MATCH ?{})",
+                params_vec.len()
+            ));
         }
+        filter.push_sql(&mut sql, &mut params_vec);
+        sql.push_str(" ORDER BY f.path, c.start_line");
 
+        let mut stmt = self.conn.prepare(&sql)?;
         let param_refs: Vec<&dyn rusqlite::types::ToSql> =
             params_vec.iter().map(|p| p.as_ref()).collect();
 
@@ -484,12 +981,52 @@ impl Store {
         let expires_at = ttl_seconds.map(|s| {
             (now + chrono::Duration::seconds(s)).to_rfc3339()
         });
-        self.conn.execute(
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
             "INSERT INTO annotations (target, note, session_id, created_at, expires_at)
              VALUES (?1, ?2, ?3, ?4, ?5)",
             params![target, note, session_id, created_at, expires_at],
         )?;
-        Ok(self.conn.last_insert_rowid())
+        let id = tx.last_insert_rowid();
+        schema::index_annotation(&tx, id, target, note)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Unexpired annotations whose note or target match `query` (full
+    /// text, like chunk search), best match first. A session sees its own
+    /// and session-less annotations; without one, every annotation.
+    pub fn search_annotations(
+        &self,
+        query: &str,
+        session_id: Option<&str>,
+        max_results: usize,
+    ) -> Result<Vec<Annotation>> {
+        let query = sanitize_fts_query(query);
+        if query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut stmt = self.conn.prepare(
+            "SELECT a.id, a.target, a.note, a.session_id, a.created_at, a.expires_at
+             FROM annotations_fts
+             JOIN annotations a ON a.id = annotations_fts.rowid
+             WHERE annotations_fts MATCH ?1
+               AND (a.expires_at IS NULL OR a.expires_at > ?2)
+               AND (?3 IS NULL OR a.session_id IS NULL OR a.session_id = ?3)
+             ORDER BY annotations_fts.rank LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(params![query, now, session_id, max_results as i64], |row| {
+            Ok(Annotation {
+                id: row.get(0)?,
+                target: row.get(1)?,
+                note: row.get(2)?,
+                session_id: row.get(3)?,
+                created_at: row.get(4)?,
+                expires_at: row.get(5)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
     pub fn get_annotations(
@@ -561,6 +1098,141 @@ impl Store {
         Ok(count)
     }
 
+    // ── Feedback ──
+
+    pub fn add_feedback(
+        &self,
+        query: &str,
+        path: &str,
+        symbol: Option<&str>,
+        line: Option<i64>,
+        useful: bool,
+        session_id: Option<&str>,
+    ) -> Result<i64> {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.conn.execute(
+            "INSERT INTO feedback (query, path, symbol, line, useful, session_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![query, path, symbol, line, useful, session_id, now],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// The latest `limit` feedback entries, newest first.
+    pub fn get_feedback(&self, limit: usize) -> Result<Vec<Feedback>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, query, path, symbol, line, useful, session_id, created_at FROM feedback
+             ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok(Feedback {
+                id: row.get(0)?,
+                query: row.get(1)?,
+                path: row.get(2)?,
+                symbol: row.get(3)?,
+                line: row.get(4)?,
+                useful: row.get(5)?,
+                session_id: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    // ── Intents ──
+
+    /// Set the session's goal, replacing any earlier one.
+    pub fn set_intent(
+        &self,
+        goal: &str,
+        terms: &[String],
+        embedding: Option<&[f32]>,
+        session_id: Option<&str>,
+    ) -> Result<i64> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM intents WHERE session_id IS ?1", params![session_id])?;
+        tx.execute(
+            "INSERT INTO intents (goal, terms, embedding, session_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![goal, terms.join(" "), embedding.map(embedding_to_blob), session_id, now],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Intents of `session_id` plus session-less ones; every intent if no
+    /// session is given. Newest first.
+    pub fn get_intents(&self, session_id: Option<&str>) -> Result<Vec<Intent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, goal, terms, embedding, session_id, created_at, neighbours_generation FROM intents
+             WHERE ?1 IS NULL OR session_id IS NULL OR session_id = ?1
+             ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map(params![session_id], |row| {
+            let terms: String = row.get(2)?;
+            let embedding: Option<Vec<u8>> = row.get(3)?;
+            Ok(Intent {
+                id: row.get(0)?,
+                goal: row.get(1)?,
+                terms: terms.split_whitespace().map(String::from).collect(),
+                embedding: embedding.map(|blob| blob_to_embedding(&blob)),
+                session_id: row.get(4)?,
+                created_at: row.get(5)?,
+                neighbours_generation: row.get(6)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Chunks nearest an intent's embedding, as last stored by
+    /// `set_intent_neighbours`: `(path, start line, similarity)`.
+    pub fn intent_neighbours(&self, intent_id: i64) -> Result<Vec<(String, i64, f64)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT path, start_line, similarity FROM intent_neighbours WHERE intent_id = ?1",
+        )?;
+        let rows = stmt.query_map(params![intent_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Replace an intent's stored neighbours, found at embeddings
+    /// `generation`.
+    pub fn set_intent_neighbours(
+        &self,
+        intent_id: i64,
+        generation: i64,
+        neighbours: &[(String, i64, f64)],
+    ) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM intent_neighbours WHERE intent_id = ?1", params![intent_id])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO intent_neighbours (intent_id, path, start_line, similarity)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (path, line, similarity) in neighbours {
+                stmt.execute(params![intent_id, path, line, similarity])?;
+            }
+        }
+        tx.execute(
+            "UPDATE intents SET neighbours_generation = ?2 WHERE id = ?1",
+            params![intent_id, generation],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn clear_intents(&self, session_id: Option<&str>) -> Result<usize> {
+        let count = if let Some(s) = session_id {
+            self.conn
+                .execute("DELETE FROM intents WHERE session_id = ?1", params![s])?
+        } else {
+            self.conn.execute("DELETE FROM intents", [])?
+        };
+        Ok(count)
+    }
+
     // ── Working Set ──
 
     pub fn add_to_workset(
@@ -645,33 +1317,262 @@ impl Store {
         Ok(entries.into_iter().map(|e| e.path).collect())
     }
 
-    // ── Embeddings ──
+    // ── File History ──
 
-    pub fn upsert_embedding(&self, chunk_id: i64, model: &str, embedding: &[f32]) -> Result<()> {
-        let blob = embedding_to_blob(embedding);
+    /// Replace the stored git history of every file and record `now` (Unix
+    /// seconds) as the time it was read.
+    pub fn replace_file_history(&self, history: &HashMap<String, FileHistory>, now: i64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM file_history", [])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO file_history (path, commits, last_modified) VALUES (?1, ?2, ?3)",
+            )?;
+            for (path, h) in history {
+                stmt.execute(params![path, h.commits, h.last_modified])?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('history_updated_at', ?1)",
+            params![now.to_string()],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Counter bumped by every index pass that changed files, so caches of
+    /// what the working tree looks like can tell they are stale.
+    pub fn index_generation(&self) -> Result<i64> {
+        let generation: Option<i64> = self
+            .conn
+            .query_row("SELECT CAST(value AS INTEGER) FROM meta WHERE key = 'index_generation'", [], |r| r.get(0))
+            .optional()?;
+        Ok(generation.unwrap_or(0))
+    }
+
+    pub fn bump_index_generation(&self) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO meta (key, value) VALUES ('index_generation', '1')
+             ON CONFLICT(key) DO UPDATE SET value = CAST(value AS INTEGER) + 1",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Branch changes last stored against `base` ('' for the default branch).
+    pub fn branch_changes(&self, base: &str) -> Result<Option<CachedBranchChanges>> {
+        let cached = self
+            .conn
+            .query_row(
+                "SELECT head, index_generation, computed_at, changes FROM branch_changes WHERE base = ?1",
+                params![base],
+                |r| {
+                    Ok(CachedBranchChanges {
+                        head: r.get(0)?,
+                        index_generation: r.get(1)?,
+                        computed_at: r.get(2)?,
+                        changes: r.get(3)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(cached)
+    }
+
+    pub fn set_branch_changes(&self, base: &str, cached: &CachedBranchChanges) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO embeddings (chunk_id, model, embedding)
-             VALUES (?1, ?2, ?3)
-             ON CONFLICT(chunk_id) DO UPDATE SET model = ?2, embedding = ?3",
-            params![chunk_id, model, blob],
+            "INSERT OR REPLACE INTO branch_changes (base, head, index_generation, computed_at, changes)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![base, cached.head, cached.index_generation, cached.computed_at, cached.changes],
         )?;
         Ok(())
     }
 
+    /// When the git history was last read, in Unix seconds, if ever.
+    pub fn file_history_updated_at(&self) -> Result<Option<i64>> {
+        let value: Option<String> = self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = 'history_updated_at'", [], |r| r.get(0))
+            .optional()?;
+        Ok(value.and_then(|v| v.parse().ok()))
+    }
+
+    /// Git history of every indexed file, keyed by path.
+    pub fn file_history(&self) -> Result<HashMap<String, FileHistory>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT h.path, h.commits, h.last_modified
+             FROM file_history h
+             JOIN files f ON f.path = h.path",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, FileHistory { commits: row.get(1)?, last_modified: row.get(2)? }))
+        })?;
+        let mut history = HashMap::new();
+        for row in rows {
+            let (path, h) = row?;
+            history.insert(path, h);
+        }
+        Ok(history)
+    }
+
+    /// The indexed files under `path_prefix` with the most commits, the most
+    /// recently changed first among ties.
+    pub fn hotspots(&self, path_prefix: Option<&str>, limit: usize) -> Result<Vec<(String, FileHistory)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT h.path, h.commits, h.last_modified
+             FROM file_history h
+             JOIN files f ON f.path = h.path
+             WHERE ?1 IS NULL OR substr(h.path, 1, length(?1)) = ?1
+             ORDER BY h.commits DESC, h.last_modified DESC, h.path
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![path_prefix, limit as i64], |row| {
+            Ok((row.get::<_, String>(0)?, FileHistory { commits: row.get(1)?, last_modified: row.get(2)? }))
+        })?;
+        let mut hotspots = Vec::new();
+        for row in rows {
+            hotspots.push(row?);
+        }
+        Ok(hotspots)
+    }
+
+    // ── Commit History ──
+
+    /// Hashes of the commits already in the history index.
+    pub fn indexed_commits(&self) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare("SELECT hash FROM commits")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<Result<HashSet<_>, _>>().map_err(Into::into)
+    }
+
+    /// Add commits and the symbols they touched to the history index.
+    /// Commits already in it are left alone.
+    pub fn insert_commits(&self, commits: &[Commit]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut insert_commit = tx.prepare(
+                "INSERT OR IGNORE INTO commits (hash, author, time, message) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let mut insert_symbol = tx.prepare(
+                "INSERT INTO commit_symbols (commit_id, path, kind, name, change)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            let mut insert_fts = tx.prepare(
+                "INSERT INTO commits_fts (rowid, message, author, symbols, terms) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for c in commits {
+                if insert_commit.execute(params![c.hash, c.author, c.time, c.message])? == 0 {
+                    continue;
+                }
+                let id = tx.last_insert_rowid();
+                let mut names: Vec<&str> = Vec::new();
+                for s in &c.symbols {
+                    insert_symbol.execute(params![id, s.path, s.kind, s.name, s.change])?;
+                    if !names.contains(&s.name.as_str()) {
+                        names.push(&s.name);
+                    }
+                }
+                let names = names.join(" ");
+                let terms = tokens::chunk_terms(None, &format!("{}\n{names}", c.message));
+                insert_fts.execute(params![id, c.message, c.author, names, terms])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn commit_count(&self) -> Result<i64> {
+        let count = self.conn.query_row("SELECT COUNT(*) FROM commits", [], |r| r.get(0))?;
+        Ok(count)
+    }
+
+    /// Commits matching `query` (full text over message, author and the
+    /// names of touched symbols) that touched a symbol named `symbol` under
+    /// `path_prefix`; each filter is optional. Best text match first when
+    /// there is a query, newest first otherwise.
+    pub fn search_commits(
+        &self,
+        query: Option<&str>,
+        symbol: Option<&str>,
+        path_prefix: Option<&str>,
+        max_results: usize,
+    ) -> Result<Vec<Commit>> {
+        let query = query.map(sanitize_fts_query).filter(|q| !q.trim().is_empty());
+        let touched = "(?2 IS NULL AND ?3 IS NULL) OR EXISTS (
+                SELECT 1 FROM commit_symbols s
+                WHERE s.commit_id = c.id
+                  AND (?2 IS NULL OR s.name = ?2)
+                  AND (?3 IS NULL OR substr(s.path, 1, length(?3)) = ?3))";
+        let sql = if query.is_some() {
+            format!(
+                "SELECT c.id, c.hash, c.author, c.time, c.message
+                 FROM commits_fts
+                 JOIN commits c ON c.id = commits_fts.rowid
+                 WHERE commits_fts MATCH ?1 AND ({touched})
+                 ORDER BY commits_fts.rank LIMIT ?4"
+            )
+        } else {
+            format!(
+                "SELECT c.id, c.hash, c.author, c.time, c.message
+                 FROM commits c
+                 WHERE ?1 IS NULL AND ({touched})
+                 ORDER BY c.time DESC, c.id LIMIT ?4"
+            )
+        };
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![query, symbol, path_prefix, max_results as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                Commit {
+                    hash: row.get(1)?,
+                    author: row.get(2)?,
+                    time: row.get(3)?,
+                    message: row.get(4)?,
+                    symbols: Vec::new(),
+                },
+            ))
+        })?;
+        let mut commits = Vec::new();
+        let mut symbols = self.conn.prepare(
+            "SELECT path, kind, name, change FROM commit_symbols WHERE commit_id = ?1 ORDER BY rowid",
+        )?;
+        for row in rows {
+            let (id, mut commit) = row?;
+            let touched = symbols.query_map(params![id], |row| {
+                Ok(CommitSymbol { path: row.get(0)?, kind: row.get(1)?, name: row.get(2)?, change: row.get(3)? })
+            })?;
+            commit.symbols = touched.collect::<Result<Vec<_>, _>>()?;
+            commits.push(commit);
+        }
+        Ok(commits)
+    }
+
+    // ── Embeddings ──
+
+    pub fn upsert_embedding(&self, chunk_id: i64, model: &str, embedding: &[f32]) -> Result<()> {
+        self.upsert_embeddings_batch(&[(chunk_id, model, embedding)])
+    }
+
     pub fn upsert_embeddings_batch(
         &self,
         entries: &[(i64, &str, &[f32])],
     ) -> Result<()> {
         let tx = self.conn.unchecked_transaction()?;
         {
+            let generation: i64 = tx.query_row(
+                "UPDATE meta SET value = CAST(value AS INTEGER) + 1 WHERE key = 'embeddings_generation'
+                 RETURNING CAST(value AS INTEGER)",
+                [],
+                |r| r.get(0),
+            )?;
             let mut stmt = tx.prepare(
-                "INSERT INTO embeddings (chunk_id, model, embedding)
-                 VALUES (?1, ?2, ?3)
-                 ON CONFLICT(chunk_id) DO UPDATE SET model = ?2, embedding = ?3",
+                "INSERT INTO embeddings (chunk_id, model, embedding, generation, encoding)
+                 VALUES (?1, ?2, ?3, ?4, 0)
+                 ON CONFLICT(chunk_id) DO UPDATE SET model = ?2, embedding = ?3, generation = ?4, encoding = 0",
             )?;
             for (chunk_id, model, embedding) in entries {
                 let blob = embedding_to_blob(embedding);
-                stmt.execute(params![chunk_id, model, blob])?;
+                stmt.execute(params![chunk_id, model, blob, generation])?;
             }
         }
         tx.commit()?;
@@ -694,28 +1595,57 @@ impl Store {
     /// Load all embeddings for vector search. Returns (chunk_id, embedding).
     pub fn all_embeddings(&self) -> Result<Vec<(i64, Vec<f32>)>> {
         let mut stmt = self.conn.prepare(
-            "SELECT chunk_id, embedding FROM embeddings",
+            "SELECT chunk_id, embedding, encoding FROM embeddings",
         )?;
         let rows = stmt.query_map([], |row| {
             let blob: Vec<u8> = row.get(1)?;
-            Ok((row.get::<_, i64>(0)?, blob))
+            Ok((row.get::<_, i64>(0)?, decode_embedding(&blob, row.get(2)?)))
         })?;
-        let mut result = Vec::new();
-        for row in rows {
-            let (id, blob) = row?;
-            result.push((id, blob_to_embedding(&blob)));
-        }
-        Ok(result)
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
-    /// Load a chunk by ID (for building search results from vector matches).
-    pub fn chunk_by_id(&self, chunk_id: i64) -> Result<Option<SearchResult>> {
-        let mut stmt = self.conn.prepare(
+    /// Load a chunk by ID if `filter` selects it (for building search results
+    /// from vector matches).
+    /// Whether a chunk of `path` overlapping lines `start..=end` passes
+    /// `filter`, i.e. whether a search with `filter` could show those lines.
+    pub fn lines_pass(&self, path: &str, start: i64, end: i64, filter: &SearchFilter) -> Result<bool> {
+        let mut sql = String::from(
+            "SELECT 1 FROM chunks c JOIN files f ON c.file_id = f.id
+             WHERE f.path = ?1 AND c.start_line <= ?3 AND c.end_line >= ?2",
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> =
+            vec![Box::new(path.to_string()), Box::new(start), Box::new(end)];
+        filter.push_sql(&mut sql, &mut params_vec);
+        sql.push_str(" LIMIT 1");
+        let param_refs: Vec<&dyn rusqlite::types::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+        let found = self.conn.query_row(&sql, param_refs.as_slice(), |_| Ok(())).optional()?;
+        Ok(found.is_some())
+    }
+
+    /// How many chunks of exactly `path` overlap lines `start..=end`.
+    pub fn count_overlapping_chunks(&self, path: &str, start: i64, end: i64) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM chunks c JOIN files f ON c.file_id = f.id
+             WHERE f.path = ?1 AND c.start_line <= ?3 AND c.end_line >= ?2",
+            params![path, start, end],
+            |r| r.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    pub fn chunk_by_id(&self, chunk_id: i64, filter: &SearchFilter) -> Result<Option<SearchResult>> {
+        let mut sql = String::from(
             "SELECT f.path, f.language, c.kind, c.name, c.signature, c.start_line, c.end_line, c.content
              FROM chunks c JOIN files f ON c.file_id = f.id
              WHERE c.id = ?1",
-        )?;
-        let mut rows = stmt.query_map(params![chunk_id], |row| {
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(chunk_id)];
+        filter.push_sql(&mut sql, &mut params_vec);
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+            params_vec.iter().map(|p| p.as_ref()).collect();
+        let mut rows = stmt.query_map(param_refs.as_slice(), |row| {
             Ok(SearchResult {
                 file_path: row.get(0)?,
                 language: row.get(1)?,
@@ -728,11 +1658,213 @@ impl Store {
                 rank: 0.0,
             })
         })?;
-        match rows.next() {
-            Some(Ok(r)) => Ok(Some(r)),
-            Some(Err(e)) => Err(e.into()),
-            None => Ok(None),
-        }
+        match rows.next() {
+            Some(Ok(r)) => Ok(Some(r)),
+            Some(Err(e)) => Err(e.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// The innermost chunk of `path` (or a file ending in `/path`) that
+    /// spans `line`.
+    pub fn chunk_at(&self, path: &str, line: i64) -> Result<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT c.id FROM chunks c JOIN files f ON f.id = c.file_id
+                 WHERE (f.path = ?1 OR substr(f.path, -length(?1) - 1) = '/' || ?1)
+                   AND c.start_line <= ?2 AND c.end_line >= ?2
+                 ORDER BY c.end_line - c.start_line, c.kind = 'raw'
+                 LIMIT 1",
+                params![path, line],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// Chunks named `name`, declarations before raw chunks, in path/line order.
+    pub fn chunks_named(&self, name: &str) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.id FROM chunks c JOIN files f ON f.id = c.file_id
+             WHERE c.name = ?1
+             ORDER BY c.kind = 'raw', f.path, c.start_line",
+        )?;
+        let rows = stmt.query_map(params![name], |r| r.get(0))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Id and content of every chunk `filter` selects.
+    pub fn chunk_contents(&self, filter: &SearchFilter) -> Result<Vec<(i64, String)>> {
+        let mut sql = String::from(
+            "SELECT c.id, c.content FROM chunks c JOIN files f ON f.id = c.file_id WHERE 1=1",
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+        filter.push_sql(&mut sql, &mut params_vec);
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+            params_vec.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(param_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    // ── Integrity ──
+
+    /// Run `PRAGMA integrity_check`. Returns an empty list when the database
+    /// is healthy, otherwise the problems SQLite reported.
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut problems = Vec::new();
+        for row in rows {
+            let msg = row?;
+            if msg != "ok" {
+                problems.push(msg);
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Run the FTS5 `integrity-check` command on both full-text indexes.
+    /// With rank = 1 it also verifies each index against the `chunks` content
+    /// table. Returns the error message if an index is corrupt or out of sync.
+    pub fn fts_integrity_check(&self) -> Result<Option<String>> {
+        for table in FTS_TABLES {
+            match self.conn.execute(
+                &format!("INSERT INTO {table}({table}, rank) VALUES('integrity-check', 1)"),
+                [],
+            ) {
+                Ok(_) => {}
+                Err(rusqlite::Error::SqliteFailure(e, msg))
+                    if e.code == rusqlite::ErrorCode::DatabaseCorrupt =>
+                {
+                    let msg = msg.unwrap_or_else(|| e.to_string());
+                    return Ok(Some(format!("{table}: {msg}")));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Cross-check chunk ids against the full-text indexes.
+    /// Returns (index rows without a chunk, chunks without an index row),
+    /// summed over both indexes.
+    pub fn fts_row_mismatches(&self) -> Result<(i64, i64)> {
+        let (mut orphans, mut missing) = (0, 0);
+        for table in FTS_TABLES {
+            orphans += self.conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {table}_docsize d
+                     WHERE NOT EXISTS (SELECT 1 FROM chunks c WHERE c.id = d.id)"
+                ),
+                [],
+                |r| r.get::<_, i64>(0),
+            )?;
+            missing += self.conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM chunks c
+                     WHERE NOT EXISTS (SELECT 1 FROM {table}_docsize d WHERE d.id = c.id)"
+                ),
+                [],
+                |r| r.get::<_, i64>(0),
+            )?;
+        }
+        Ok((orphans, missing))
+    }
+
+    /// Rebuild the full-text indexes from the `chunks` table.
+    pub fn rebuild_fts(&self) -> Result<()> {
+        for table in FTS_TABLES {
+            self.conn
+                .execute(&format!("INSERT INTO {table}({table}) VALUES('rebuild')"), [])?;
+        }
+        Ok(())
+    }
+
+    /// Rebuild all regular indices (repairs index-level corruption).
+    pub fn reindex(&self) -> Result<()> {
+        self.conn.execute_batch("REINDEX")?;
+        Ok(())
+    }
+
+    /// Count embeddings whose chunk no longer exists.
+    pub fn orphan_embedding_count(&self) -> Result<i64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM embeddings e
+             WHERE NOT EXISTS (SELECT 1 FROM chunks c WHERE c.id = e.chunk_id)",
+            [],
+            |r| r.get(0),
+        )?;
+        Ok(count)
+    }
+
+    pub fn delete_orphan_embeddings(&self) -> Result<usize> {
+        let count = self.conn.execute(
+            "DELETE FROM embeddings
+             WHERE NOT EXISTS (SELECT 1 FROM chunks c WHERE c.id = embeddings.chunk_id)",
+            [],
+        )?;
+        Ok(count)
+    }
+
+    /// Chunk ids of embeddings whose dimension disagrees with the majority
+    /// dimension of their model (or whose blob is not a whole number of f32s).
+    pub fn mismatched_embeddings(&self) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "WITH sized AS (
+                SELECT chunk_id, model, encoding, length(embedding) AS len,
+                       CASE encoding WHEN 1 THEN length(embedding) - 4 ELSE length(embedding) / 4 END AS dims
+                FROM embeddings
+             ),
+             counts AS (
+                SELECT model, dims, COUNT(*) AS n FROM sized GROUP BY model, dims
+             ),
+             expected AS (
+                SELECT model, dims FROM counts c
+                WHERE n = (SELECT MAX(n) FROM counts c2 WHERE c2.model = c.model)
+             )
+             SELECT s.chunk_id FROM sized s
+             WHERE s.dims <= 0
+                OR (s.encoding = 0 AND s.len % 4 != 0)
+                OR s.encoding NOT IN (0, 1)
+                OR NOT EXISTS (
+                    SELECT 1 FROM expected x
+                    WHERE x.model = s.model AND x.dims = s.dims
+                )
+             ORDER BY s.chunk_id",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    pub fn delete_embeddings(&self, chunk_ids: &[i64]) -> Result<usize> {
+        let mut stmt = self
+            .conn
+            .prepare("DELETE FROM embeddings WHERE chunk_id = ?1")?;
+        let mut count = 0;
+        for id in chunk_ids {
+            count += stmt.execute(params![id])?;
+        }
+        Ok(count)
+    }
+
+    /// All indexed files, ordered by path.
+    pub fn all_files(&self) -> Result<Vec<FileRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path, content_hash, size_bytes, language, indexed_at FROM files ORDER BY path",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(FileRecord {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                content_hash: row.get(2)?,
+                size_bytes: row.get(3)?,
+                language: row.get(4)?,
+                indexed_at: row.get(5)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
     pub fn chunk_count(&self) -> Result<i64> {
@@ -742,6 +1874,64 @@ impl Store {
         Ok(count)
     }
 
+    /// Counter bumped by every embedding write and delete. A vector index
+    /// built at the current generation is up to date.
+    pub fn embeddings_generation(&self) -> Result<i64> {
+        let generation: i64 = self.conn.query_row(
+            "SELECT CAST(value AS INTEGER) FROM meta WHERE key = 'embeddings_generation'",
+            [],
+            |r| r.get(0),
+        )?;
+        Ok(generation)
+    }
+
+    /// Chunk ids of embeddings written after `generation`: new ones, and
+    /// ones rewritten in place (a new model, or a reused chunk id).
+    pub fn embeddings_written_since(&self, generation: i64) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare("SELECT chunk_id FROM embeddings WHERE generation > ?1")?;
+        let rows = stmt.query_map(params![generation], |row| row.get(0))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Chunk ids of every embedding.
+    pub fn embedding_ids(&self) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare("SELECT chunk_id FROM embeddings")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Embeddings of the given chunks; ids without one are left out.
+    pub fn embeddings_by_ids(&self, ids: &[i64]) -> Result<Vec<(i64, Vec<f32>)>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let placeholders: Vec<String> = (1..=ids.len()).map(|i| format!("?{i}")).collect();
+        let sql = format!(
+            "SELECT chunk_id, embedding, encoding FROM embeddings WHERE chunk_id IN ({})",
+            placeholders.join(", "),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(ids), |row| {
+            let blob: Vec<u8> = row.get(1)?;
+            Ok((row.get::<_, i64>(0)?, decode_embedding(&blob, row.get(2)?)))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Dimensions of the stored embeddings, if there are any.
+    pub fn embedding_dims(&self) -> Result<Option<usize>> {
+        let dims: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT CASE encoding WHEN 1 THEN length(embedding) - 4 ELSE length(embedding) / 4 END
+                 FROM embeddings LIMIT 1",
+                [],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(dims.map(|d| d.max(0) as usize))
+    }
+
     pub fn embedding_count(&self) -> Result<i64> {
         let count: i64 = self.conn.query_row(
             "SELECT COUNT(*) FROM embeddings", [], |r| r.get(0),
@@ -799,6 +1989,103 @@ fn blob_to_embedding(blob: &[u8]) -> Vec<f32> {
         .collect()
 }
 
+/// Decode an `embeddings.embedding` blob stored with `encoding`. Only
+/// indexes compacted by earlier versions hold int8 rows (encoding 1).
+fn decode_embedding(blob: &[u8], encoding: i64) -> Vec<f32> {
+    match (encoding, blob) {
+        (1, [a, b, c, d, codes @ ..]) => {
+            let scale = f32::from_le_bytes([*a, *b, *c, *d]);
+            codes.iter().map(|&q| q as i8 as f32 * scale).collect()
+        }
+        _ => blob_to_embedding(blob),
+    }
+}
+
+/// Sanitize user input for FTS5 MATCH queries.
+/// FTS5 treats `-`, `AND`, `OR`, `NOT`, `NEAR` as operators.
+/// We quote bare terms that contain special characters, and preserve
+/// user-supplied phrases (already in double quotes) and operators.
+///
+/// Identifier terms are expanded to also match the `terms` column (see
+/// `store::tokens`): a compound identifier matches its sub-token phrase
+/// (`indexDirectory` → `(indexDirectory OR "index directory")`), and a plain
+/// word matches as a sub-token prefix (`embedding` → `(embedding OR
+/// terms:embedding*)`) so it finds `upsertEmbeddingsBatch`.
+fn sanitize_fts_query(input: &str) -> String {
+    // (text, is_group): FTS5 needs an explicit AND next to a parenthesized
+    // group, so track which operands are groups when joining.
+    let mut items: Vec<(String, bool)> = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&ch) = chars.peek() {
+        if ch == '"' {
+            // Pass through quoted phrases verbatim
+            let mut phrase = String::from(ch);
+            chars.next();
+            while let Some(&c) = chars.peek() {
+                phrase.push(c);
+                chars.next();
+                if c == '"' {
+                    break;
+                }
+            }
+            items.push((phrase, false));
+        } else if ch.is_whitespace() {
+            chars.next();
+        } else {
+            // Collect a bare token
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            items.push(expand_token(token));
+        }
+    }
+
+    let mut result = String::new();
+    let mut prev_operand: Option<bool> = None;
+    for (text, is_group) in items {
+        let is_operator = matches!(text.as_str(), "AND" | "OR" | "NOT" | "NEAR");
+        if let Some(prev_group) = prev_operand {
+            result.push_str(if !is_operator && (prev_group || is_group) { " AND " } else { " " });
+        } else if !result.is_empty() {
+            result.push(' ');
+        }
+        result.push_str(&text);
+        prev_operand = (!is_operator).then_some(is_group);
+    }
+    result
+}
+
+/// Quote or expand one bare query token. Returns the FTS5 text and whether
+/// it is a parenthesized group.
+fn expand_token(token: String) -> (String, bool) {
+    let needs_quoting = token.contains('-')
+        || token.contains('.')
+        || token.contains('/')
+        || token.contains(':')
+        || token.contains('*')
+        || token.contains('^');
+    if needs_quoting {
+        return (format!("\"{token}\""), false);
+    }
+    if matches!(token.as_str(), "AND" | "OR" | "NOT" | "NEAR") || !tokens::is_identifier(&token) {
+        return (token, false);
+    }
+    let parts = tokens::split_identifier(&token);
+    if parts.len() >= 2 {
+        (format!("({token} OR \"{}\")", parts.join(" ")), true)
+    } else if token.chars().count() >= 3 {
+        (format!("({token} OR terms:{token}*)"), true)
+    } else {
+        (token, false)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -869,6 +2156,21 @@ mod tests {
         assert!(result.is_some());
     }
 
+    #[test]
+    fn readers_open_without_waiting_on_a_write_transaction() {
+        let (dir, writer) = test_store();
+        insert_test_file(&writer, "src/main.rs", "rust");
+        writer.begin_transaction().unwrap();
+        writer.upsert_file("src/lib.rs", "hash456", 10, Some("rust")).unwrap();
+
+        let started = std::time::Instant::now();
+        let reader = Store::open_if_exists(dir.path()).unwrap().unwrap();
+        assert!(reader.get_file("src/main.rs").unwrap().is_some());
+        assert!(reader.get_file("src/lib.rs").unwrap().is_none(), "uncommitted writes stay invisible");
+        assert!(started.elapsed() < BUSY_TIMEOUT / 2, "{:?}", started.elapsed());
+        writer.commit_transaction().unwrap();
+    }
+
     #[test]
     fn upsert_and_get_file() {
         let (_dir, store) = test_store();
@@ -887,8 +2189,10 @@ mod tests {
     #[test]
     fn upsert_file_updates_on_conflict() {
         let (_dir, store) = test_store();
-        store.upsert_file("src/main.rs", "hash1", 100, Some("rust")).unwrap();
-        store.upsert_file("src/main.rs", "hash2", 200, Some("rust")).unwrap();
+        let id1 = store.upsert_file("src/main.rs", "hash1", 100, Some("rust")).unwrap();
+        store.upsert_file("src/other.rs", "hash3", 10, Some("rust")).unwrap();
+        let id2 = store.upsert_file("src/main.rs", "hash2", 200, Some("rust")).unwrap();
+        assert_eq!(id1, id2);
 
         let file = store.get_file("src/main.rs").unwrap().unwrap();
         assert_eq!(file.content_hash, "hash2");
@@ -950,6 +2254,107 @@ mod tests {
         assert_eq!(src[0].file_path, "src/lib.rs");
     }
 
+    #[test]
+    fn search_filtered_globs_and_exclusions() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/index/mod.rs", "rust");
+        insert_test_file(&store, "src/store/mod.rs", "rust");
+        insert_test_file(&store, "tests/index.rs", "rust");
+        insert_test_file(&store, "app/main.py", "python");
+
+        let paths = |filter: &SearchFilter, query: &str| -> Vec<String> {
+            store.search_filtered(query, filter, 20).unwrap().into_iter().map(|r| r.file_path).collect()
+        };
+
+        let filter = SearchFilter {
+            paths: vec!["src/*/mod.rs".into()],
+            exclude_paths: vec!["src/store".into()],
+            ..Default::default()
+        };
+        assert_eq!(paths(&filter, "hello"), ["src/index/mod.rs"]);
+
+        let filter = SearchFilter {
+            kinds: vec!["struct".into(), "function".into()],
+            exclude_languages: vec!["python".into()],
+            names: vec!["wor*".into()],
+            ..Default::default()
+        };
+        assert_eq!(paths(&filter, "fn"), ["src/index/mod.rs", "src/store/mod.rs", "tests/index.rs"]);
+
+        // No search terms: list what the filter selects, in path order.
+        let filter = SearchFilter { kinds: vec!["struct".into()], ..Default::default() };
+        assert_eq!(paths(&filter, ""), ["app/main.py", "src/index/mod.rs", "src/store/mod.rs", "tests/index.rs"]);
+    }
+
+    #[test]
+    fn test_scope_and_path_exclusions_apply_to_every_listing() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/lib.rs", "rust");
+        insert_test_file(&store, "tests/index.rs", "rust");
+        insert_test_file(&store, "pkg/parser_test.go", "go");
+        insert_test_file(&store, "vendor/dep.rs", "rust");
+        let fid = store.upsert_file("src/util.rs", "h", 100, Some("rust")).unwrap();
+        let chunk = |kind: &str, name: &str, content: &str, line: u32| ChunkInsert {
+            kind: kind.into(),
+            name: Some(name.into()),
+            content: content.into(),
+            signature: None,
+            start_line: line,
+            end_line: line + 1,
+            start_byte: line * 10,
+            end_byte: line * 10 + 9,
+        };
+        let ids = store.insert_chunks(fid, &[
+            chunk("function", "submit", "fn submit() { emit(hello()) }", 1),
+            chunk("module", "tests", "#[cfg(test)]\nmod tests {", 10),
+            chunk("function", "check", "fn check() { hello(); }", 12),
+        ]).unwrap();
+        let release = store.upsert_file("src/release.rs", "h", 100, Some("rust")).unwrap();
+        store.insert_chunks(release, &[
+            chunk("module", "latest", "mod latest {", 1),
+            chunk("function", "bump", "fn bump() { hello() }", 3),
+        ]).unwrap();
+
+        let sorted = |results: Vec<SearchResult>| -> Vec<String> {
+            let mut out: Vec<String> = results
+                .into_iter()
+                .map(|r| format!("{}:{}", r.file_path, r.chunk_name.unwrap_or_default()))
+                .collect();
+            out.sort();
+            out.dedup();
+            out
+        };
+
+        let filter = SearchFilter {
+            exclude_paths: vec!["vendor/".into()],
+            tests: TestScope::Exclude,
+            ..Default::default()
+        };
+        assert_eq!(
+            sorted(store.search_filtered("hello", &filter, 20).unwrap()),
+            ["src/lib.rs:hello", "src/release.rs:bump", "src/util.rs:submit"]
+        );
+        assert_eq!(
+            sorted(store.list_symbols(&SearchFilter { paths: vec!["src/".into()], ..filter.clone() }).unwrap()),
+            ["src/lib.rs:Config", "src/lib.rs:hello", "src/lib.rs:world", "src/release.rs:bump", "src/release.rs:latest", "src/util.rs:submit"]
+        );
+        let call = |chunk_id: i64, line: u32| OccurrenceInsert { chunk_id, name: "hello".into(), role: "call", line, column: 5 };
+        store.insert_occurrences(&[call(ids[0], 1), call(ids[2], 12)]).unwrap();
+        let occurrences = |filter: &SearchFilter| -> Vec<u32> {
+            store.find_occurrences("hello", filter, None).unwrap().into_iter().map(|o| o.line).collect()
+        };
+        assert_eq!(occurrences(&filter), [1]);
+        assert_eq!(occurrences(&SearchFilter { tests: TestScope::Only, ..Default::default() }), [12]);
+
+        let only = SearchFilter { tests: TestScope::Only, paths: vec!["src/".into(), "pkg/".into()], ..Default::default() };
+        assert_eq!(
+            sorted(store.all_chunks(&only).unwrap()),
+            ["pkg/parser_test.go:Config", "pkg/parser_test.go:hello", "pkg/parser_test.go:world", "src/util.rs:check", "src/util.rs:tests"]
+        );
+        assert_eq!(TestScope::parse("only"), Some(TestScope::Only));
+        assert_eq!(TestScope::parse("yes"), None);
+    }
+
     #[test]
     fn search_returns_signature() {
         let (_dir, store) = test_store();
@@ -969,6 +2374,90 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    fn insert_identifier_chunks(store: &Store) {
+        let fid = store.upsert_file("src/store.ts", "h", 100, Some("typescript")).unwrap();
+        store.insert_chunks(fid, &[
+            ChunkInsert {
+                kind: "function".into(),
+                name: Some("upsertEmbeddingsBatch".into()),
+                content: "function upsertEmbeddingsBatch(rows) { return db.write(rows); }".into(),
+                signature: None,
+                start_line: 1,
+                end_line: 1,
+                start_byte: 0,
+                end_byte: 60,
+            },
+            ChunkInsert {
+                kind: "function".into(),
+                name: Some("index_directory".into()),
+                content: "fn index_directory(root: &Path) { walk(root); }".into(),
+                signature: None,
+                start_line: 3,
+                end_line: 3,
+                start_byte: 61,
+                end_byte: 110,
+            },
+        ]).unwrap();
+    }
+
+    #[test]
+    fn search_matches_camel_case_sub_tokens() {
+        let (_dir, store) = test_store();
+        insert_identifier_chunks(&store);
+
+        let results = store.search("embedding", None, None, None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_name.as_deref(), Some("upsertEmbeddingsBatch"));
+
+        let results = store.search("embeddingsBatch", None, None, None, 10).unwrap();
+        assert_eq!(results[0].chunk_name.as_deref(), Some("upsertEmbeddingsBatch"));
+    }
+
+    #[test]
+    fn search_matches_snake_case_sub_tokens() {
+        let (_dir, store) = test_store();
+        insert_identifier_chunks(&store);
+
+        let results = store.search("index dir", None, None, None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_name.as_deref(), Some("index_directory"));
+
+        let exact = store.search("index_directory", None, None, None, 10).unwrap();
+        assert_eq!(exact.len(), 1);
+    }
+
+    #[test]
+    fn migration_backfills_identifier_terms() {
+        let dir = TempDir::new().unwrap();
+        let conn = Connection::open(dir.path().join("index.db")).unwrap();
+        // Minimal v5 layout: no terms column, default tokenizer.
+        conn.execute_batch("
+            CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO meta VALUES ('schema_version', '5');
+            CREATE TABLE files (id INTEGER PRIMARY KEY, path TEXT NOT NULL UNIQUE,
+                content_hash TEXT NOT NULL, size_bytes INTEGER NOT NULL, language TEXT,
+                indexed_at TEXT NOT NULL, mtime TEXT);
+            CREATE TABLE chunks (id INTEGER PRIMARY KEY, file_id INTEGER NOT NULL,
+                kind TEXT NOT NULL, name TEXT, content TEXT NOT NULL, start_line INTEGER NOT NULL,
+                end_line INTEGER NOT NULL, start_byte INTEGER NOT NULL, end_byte INTEGER NOT NULL,
+                signature TEXT, UNIQUE(file_id, start_byte, end_byte));
+            CREATE VIRTUAL TABLE chunks_fts USING fts5(name, content, content='chunks',
+                content_rowid='id', tokenize='porter unicode61');
+            INSERT INTO files VALUES (1, 'a.rs', 'h', 1, 'rust', '2024-01-01', NULL);
+            INSERT INTO chunks VALUES (1, 1, 'function', 'index_directory',
+                'fn index_directory() {}', 1, 1, 0, 23, NULL);
+            INSERT INTO chunks_fts(chunks_fts) VALUES('rebuild');
+        ").unwrap();
+        drop(conn);
+
+        let store = Store::open(dir.path()).unwrap();
+        let results = store.search("directory", None, None, None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(store.fts_integrity_check().unwrap().is_none());
+        // Forced re-index so symbols and occurrences get populated.
+        assert_eq!(store.get_file("a.rs").unwrap().unwrap().content_hash, "");
+    }
+
     #[test]
     fn path_has_chunks_empty_and_prefix() {
         let (_dir, store) = test_store();
@@ -982,6 +2471,66 @@ mod tests {
         assert!(!store.path_has_chunks(Some("other/")).unwrap());
     }
 
+    #[test]
+    fn path_prefixes_and_suffixes_match_literally() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/axb/lib.rs", "rust");
+        let history = HashMap::from([("src/axb/lib.rs".to_string(), FileHistory { commits: 3, last_modified: 1 })]);
+        store.replace_file_history(&history, 1).unwrap();
+
+        // `_` and `%` are ordinary characters, not LIKE wildcards.
+        for prefix in ["src/a_b/", "src/%/", "SRC/"] {
+            assert!(!store.path_has_chunks(Some(prefix)).unwrap(), "{prefix}");
+            let filter = SearchFilter::new(None, Some(prefix), None);
+            assert!(store.search_filtered("hello", &filter, 10).unwrap().is_empty(), "{prefix}");
+            assert!(store.hotspots(Some(prefix), 10).unwrap().is_empty(), "{prefix}");
+        }
+        assert_eq!(store.hotspots(Some("src/axb/"), 10).unwrap().len(), 1);
+
+        assert!(store.chunk_at("axb/lib.rs", 1).unwrap().is_some());
+        assert!(store.chunk_at("lib.rs", 1).unwrap().is_some());
+        assert!(store.chunk_at("a_b/lib.rs", 1).unwrap().is_none());
+        assert!(store.chunk_at("b/lib.rs", 1).unwrap().is_none());
+
+        // Overlap counts take the exact path, not siblings sharing its prefix.
+        insert_test_file(&store, "src/axb/lib.rs.bak", "rust");
+        assert_eq!(store.count_overlapping_chunks("src/axb/lib.rs", 1, 5).unwrap(), 2);
+    }
+
+    #[test]
+    fn synonyms_are_aggregated_both_ways_by_file_count() {
+        let (_dir, store) = test_store();
+        let a = insert_test_file(&store, "src/a.rs", "rust");
+        let b = insert_test_file(&store, "src/b.rs", "rust");
+        let chunk = |file_id: i64| -> i64 {
+            store.conn.query_row("SELECT MIN(id) FROM chunks WHERE file_id = ?1", [file_id], |r| r.get(0)).unwrap()
+        };
+        store.insert_abbreviations(&[
+            (chunk(a), "cfg".into(), "config".into()),
+            (chunk(b), "cfg".into(), "config".into()),
+            (chunk(b), "cfg".into(), "configuration".into()),
+        ]).unwrap();
+        store.refresh_synonyms().unwrap();
+        assert_eq!(store.synonyms_for("cfg", 3).unwrap(), ["config", "configuration"]);
+        assert_eq!(store.synonyms_for("config", 3).unwrap(), ["cfg"]);
+
+        store.delete_chunks_for_file(b).unwrap();
+        store.refresh_synonyms().unwrap();
+        assert_eq!(store.synonyms_for("cfg", 3).unwrap(), ["config"]);
+    }
+
+    #[test]
+    fn symbols_named_like_matches_literal_fragments() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/lib.rs", "rust");
+        let names = |fragment| -> Vec<String> {
+            store.symbols_named_like(fragment).unwrap().into_iter().filter_map(|r| r.chunk_name).collect()
+        };
+        assert_eq!(names("CONF"), ["Config"]);
+        // `_` is not a wildcard.
+        assert!(names("h_llo").is_empty());
+    }
+
     #[test]
     fn list_symbols_excludes_raw() {
         let (_dir, store) = test_store();
@@ -991,7 +2540,7 @@ mod tests {
             ChunkInsert { kind: "raw".into(), name: None, content: "raw text".into(), signature: None, start_line: 2, end_line: 2, start_byte: 7, end_byte: 15 },
         ]).unwrap();
 
-        let symbols = store.list_symbols(None, None).unwrap();
+        let symbols = store.list_symbols(&SearchFilter::default()).unwrap();
         assert_eq!(symbols.len(), 1);
         assert_eq!(symbols[0].chunk_kind, "function");
     }
@@ -1005,7 +2554,7 @@ mod tests {
             ChunkInsert { kind: "raw".into(), name: None, content: "raw text".into(), signature: None, start_line: 2, end_line: 2, start_byte: 7, end_byte: 15 },
         ]).unwrap();
 
-        let all = store.all_chunks(None, None).unwrap();
+        let all = store.all_chunks(&SearchFilter::default()).unwrap();
         assert_eq!(all.len(), 2);
     }
 
@@ -1014,14 +2563,48 @@ mod tests {
         let (_dir, store) = test_store();
         let fid = insert_test_file(&store, "src/lib.rs", "rust");
 
-        let before = store.all_chunks(None, None).unwrap();
+        let before = store.all_chunks(&SearchFilter::default()).unwrap();
         assert_eq!(before.len(), 3);
 
         store.delete_chunks_for_file(fid).unwrap();
-        let after = store.all_chunks(None, None).unwrap();
+        let after = store.all_chunks(&SearchFilter::default()).unwrap();
         assert!(after.is_empty());
     }
 
+    #[test]
+    fn occurrences_follow_their_chunks() {
+        let (_dir, store) = test_store();
+        let fid = store.upsert_file("src/lib.rs", "h", 10, Some("rust")).unwrap();
+        let ids = store.insert_chunks(fid, &[ChunkInsert {
+            kind: "function".into(),
+            name: Some("run".into()),
+            content: "fn run() {\n    hello();\n}".into(),
+            signature: Some("fn run()".into()),
+            start_line: 4,
+            end_line: 6,
+            start_byte: 0,
+            end_byte: 25,
+        }]).unwrap();
+        store.insert_occurrences(&[OccurrenceInsert {
+            chunk_id: ids[0],
+            name: "hello".into(),
+            role: "call",
+            line: 5,
+            column: 5,
+        }]).unwrap();
+
+        let occ = store.find_occurrences("hello", &SearchFilter::default(), Some("call")).unwrap();
+        assert_eq!(occ.len(), 1);
+        assert_eq!(occ[0].text, "hello();");
+        assert_eq!(occ[0].chunk_name.as_deref(), Some("run"));
+        assert_eq!(store.chunks_referencing("hello", &SearchFilter::default()).unwrap().len(), 1);
+        assert_eq!(store.list_symbols(&SearchFilter::default()).unwrap().len(), 1);
+
+        store.delete_chunks_for_file(fid).unwrap();
+        assert!(store.find_occurrences("hello", &SearchFilter::default(), None).unwrap().is_empty());
+        assert!(store.list_symbols(&SearchFilter::default()).unwrap().is_empty());
+    }
+
     #[test]
     fn remove_file_cascades() {
         let (_dir, store) = test_store();
@@ -1029,7 +2612,7 @@ mod tests {
 
         store.remove_file("src/lib.rs").unwrap();
         assert!(store.get_file("src/lib.rs").unwrap().is_none());
-        assert!(store.all_chunks(None, None).unwrap().is_empty());
+        assert!(store.all_chunks(&SearchFilter::default()).unwrap().is_empty());
     }
 
     #[test]
@@ -1100,6 +2683,29 @@ mod tests {
         assert_eq!(filtered[0].target, "a.rs");
     }
 
+    #[test]
+    fn annotation_full_text_search() {
+        let (_dir, store) = test_store();
+        let writes = store.add_annotation("src/store.rs", "Writes must stay in one transaction", None, None).unwrap();
+        store.add_annotation("commit_batch", "Calls begin_transaction twice", Some("s1"), None).unwrap();
+        store.add_annotation("src/old.rs", "transaction bug, fixed", None, Some(-10)).unwrap();
+        store.add_annotation("src/lib.rs", "unrelated", None, None).unwrap();
+
+        // Stemmed words and identifier sub-tokens both match; expired notes don't.
+        let found = store.search_annotations("transactions", None, 10).unwrap();
+        let mut targets: Vec<&str> = found.iter().map(|a| a.target.as_str()).collect();
+        targets.sort();
+        assert_eq!(targets, ["commit_batch", "src/store.rs"]);
+
+        assert_eq!(store.search_annotations("transaction", Some("s2"), 10).unwrap().len(), 1);
+        assert_eq!(store.search_annotations("store", None, 10).unwrap()[0].id, writes);
+
+        store.delete_annotation(writes).unwrap();
+        assert_eq!(store.search_annotations("transaction", None, 10).unwrap().len(), 1);
+        store.clear_annotations(None).unwrap();
+        assert!(store.search_annotations("transaction", None, 10).unwrap().is_empty());
+    }
+
     #[test]
     fn clear_annotations_all() {
         let (_dir, store) = test_store();
@@ -1154,6 +2760,29 @@ mod tests {
         assert!(store.get_focus_paths(None).unwrap().is_empty());
     }
 
+    // ── Intents ──
+
+    #[test]
+    fn intents_replace_per_session() {
+        let (_dir, store) = test_store();
+        let terms = |t: &str| t.split(' ').map(String::from).collect::<Vec<_>>();
+        store.set_intent("fix login", &terms("fix login"), None, Some("s1")).unwrap();
+        store.set_intent("speed up search", &terms("speed search"), Some(&[0.5, -1.0]), Some("s1")).unwrap();
+        store.set_intent("document api", &terms("document api"), None, None).unwrap();
+        store.set_intent("other", &terms("other"), None, Some("s2")).unwrap();
+
+        let s1 = store.get_intents(Some("s1")).unwrap();
+        let goals: Vec<&str> = s1.iter().map(|i| i.goal.as_str()).collect();
+        assert_eq!(goals, ["document api", "speed up search"]);
+        assert_eq!(s1[1].terms, ["speed", "search"]);
+        assert_eq!(s1[1].embedding.as_deref(), Some(&[0.5, -1.0][..]));
+        assert!(s1[0].embedding.is_none());
+        assert_eq!(store.get_intents(None).unwrap().len(), 3);
+
+        assert_eq!(store.clear_intents(Some("s1")).unwrap(), 1);
+        assert_eq!(store.clear_intents(None).unwrap(), 2);
+    }
+
     // ── Changed Since ──
 
     #[test]
@@ -1198,11 +2827,73 @@ mod tests {
         assert_eq!(count, 1);
     }
 
+    // ── Integrity ──
+
+    #[test]
+    fn integrity_checks_pass_on_fresh_store() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/lib.rs", "rust");
+        assert!(store.integrity_check().unwrap().is_empty());
+        assert!(store.fts_integrity_check().unwrap().is_none());
+        assert_eq!(store.fts_row_mismatches().unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn fts_orphans_detected_and_rebuilt() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/lib.rs", "rust");
+        // Simulate a chunk deletion that bypassed the FTS trigger
+        store.conn.execute_batch(
+            "DROP TRIGGER chunks_ad; DELETE FROM chunks WHERE name = 'hello';",
+        ).unwrap();
+
+        assert_eq!(store.fts_row_mismatches().unwrap(), (1, 0));
+        assert!(store.fts_integrity_check().unwrap().unwrap().starts_with("chunks_fts"));
+
+        store.rebuild_fts().unwrap();
+        assert_eq!(store.fts_row_mismatches().unwrap(), (0, 0));
+        assert!(store.fts_integrity_check().unwrap().is_none());
+    }
+
+    #[test]
+    fn mismatched_embeddings_detected() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/lib.rs", "rust");
+        let ids: Vec<i64> = store.conn
+            .prepare("SELECT id FROM chunks ORDER BY id").unwrap()
+            .query_map([], |r| r.get(0)).unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        store.upsert_embedding(ids[0], "m", &[0.1, 0.2, 0.3]).unwrap();
+        store.upsert_embedding(ids[1], "m", &[0.1, 0.2, 0.3]).unwrap();
+        store.upsert_embedding(ids[2], "m", &[0.1, 0.2]).unwrap();
+
+        assert_eq!(store.mismatched_embeddings().unwrap(), vec![ids[2]]);
+        assert_eq!(store.delete_embeddings(&[ids[2]]).unwrap(), 1);
+        assert!(store.mismatched_embeddings().unwrap().is_empty());
+    }
+
+    #[test]
+    fn orphan_embeddings_detected_and_deleted() {
+        let (_dir, store) = test_store();
+        insert_test_file(&store, "src/lib.rs", "rust");
+        store.conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+        store.upsert_embedding(9999, "m", &[0.1, 0.2]).unwrap();
+
+        assert_eq!(store.orphan_embedding_count().unwrap(), 1);
+        assert_eq!(store.delete_orphan_embeddings().unwrap(), 1);
+        assert_eq!(store.orphan_embedding_count().unwrap(), 0);
+    }
+
     // ── FTS Sanitization ──
 
     #[test]
     fn sanitize_plain_query() {
-        assert_eq!(sanitize_fts_query("hello world"), "hello world");
+        assert_eq!(
+            sanitize_fts_query("hello world"),
+            "(hello OR terms:hello*) AND (world OR terms:world*)",
+        );
+        assert_eq!(sanitize_fts_query("fn io"), "fn io");
     }
 
     #[test]
@@ -1217,7 +2908,30 @@ mod tests {
 
     #[test]
     fn sanitize_mixed_query() {
-        assert_eq!(sanitize_fts_query("hello tree-sitter world"), "hello \"tree-sitter\" world");
+        assert_eq!(
+            sanitize_fts_query("hello tree-sitter world"),
+            "(hello OR terms:hello*) AND \"tree-sitter\" AND (world OR terms:world*)",
+        );
+    }
+
+    #[test]
+    fn sanitize_splits_identifiers() {
+        assert_eq!(
+            sanitize_fts_query("upsertEmbeddingsBatch"),
+            "(upsertEmbeddingsBatch OR \"upsert embeddings batch\")",
+        );
+        assert_eq!(
+            sanitize_fts_query("index_directory"),
+            "(index_directory OR \"index directory\")",
+        );
+    }
+
+    #[test]
+    fn sanitize_keeps_operators() {
+        assert_eq!(
+            sanitize_fts_query("parse OR lex NOT test"),
+            "(parse OR terms:parse*) OR (lex OR terms:lex*) NOT (test OR terms:test*)",
+        );
     }
 
     #[test]
@@ -1250,55 +2964,3 @@ mod tests {
         assert!(store.get_file("a.rs").unwrap().is_none());
     }
 }
-
-/// Sanitize user input for FTS5 MATCH queries.
-/// FTS5 treats `-`, `AND`, `OR`, `NOT`, `NEAR` as operators.
-/// We quote bare terms that contain special characters, and preserve
-/// user-supplied phrases (already in double quotes).
-fn sanitize_fts_query(input: &str) -> String {
-    let mut result = String::new();
-    let mut chars = input.chars().peekable();
-
-    while let Some(&ch) = chars.peek() {
-        if ch == '"' {
-            // Pass through quoted phrases verbatim
-            result.push(ch);
-            chars.next();
-            while let Some(&c) = chars.peek() {
-                result.push(c);
-                chars.next();
-                if c == '"' {
-                    break;
-                }
-            }
-        } else if ch.is_whitespace() {
-            result.push(ch);
-            chars.next();
-        } else {
-            // Collect a bare token
-            let mut token = String::new();
-            while let Some(&c) = chars.peek() {
-                if c.is_whitespace() || c == '"' {
-                    break;
-                }
-                token.push(c);
-                chars.next();
-            }
-            let needs_quoting = token.contains('-')
-                || token.contains('.')
-                || token.contains('/')
-                || token.contains(':')
-                || token.contains('*')
-                || token.contains('^');
-            if needs_quoting {
-                result.push('"');
-                result.push_str(&token);
-                result.push('"');
-            } else {
-                result.push_str(&token);
-            }
-        }
-    }
-
-    result
-}
@@ -0,0 +1,139 @@
+//! Identifier-aware tokenization for the FTS index.
+//!
+//! `chunks_fts` indexes whole identifiers (`_` is a token character), so
+//! `index_directory` matches exactly. Sub-tokens of compound identifiers
+//! (camelCase, PascalCase, snake_case, kebab-case, letter/digit runs) go into
+//! a separate `terms` column, so `embedding` finds `upsertEmbeddingsBatch`
+//! and `index dir` finds `index_directory`. Queries are split the same way.
+
+use std::collections::HashSet;
+
+/// Split an identifier into lowercase sub-tokens.
+///
+/// `upsertEmbeddingsBatch` → `upsert embeddings batch`,
+/// `HTTPServer2` → `http server 2`, `tree-sitter` → `tree sitter`.
+pub fn split_identifier(ident: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for segment in ident.split(['_', '-']) {
+        let chars: Vec<char> = segment.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (prev, cur) = (chars[i - 1], chars[i]);
+            let next_is_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            let boundary = (prev.is_lowercase() && cur.is_uppercase())
+                // Acronym followed by a word: the last capital starts the word
+                || (prev.is_uppercase() && cur.is_uppercase() && next_is_lower)
+                || (prev.is_alphabetic() && cur.is_ascii_digit())
+                || (prev.is_ascii_digit() && cur.is_alphabetic());
+            if boundary {
+                parts.push(chars[start..i].iter().collect::<String>().to_lowercase());
+                start = i;
+            }
+        }
+        if start < chars.len() {
+            parts.push(chars[start..].iter().collect::<String>().to_lowercase());
+        }
+    }
+    parts
+}
+
+/// Compute the `terms` column for a chunk: the sub-tokens of every distinct
+/// compound identifier in `text`, one identifier per line. Simple words are left out
+/// since the name/content columns already index them.
+pub fn identifier_terms(text: &str) -> String {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut out = String::new();
+    for ident in text
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .map(|w| w.trim_matches(['_', '-']))
+        .filter(|w| !w.is_empty())
+    {
+        if !seen.insert(ident) {
+            continue;
+        }
+        let parts = split_identifier(ident);
+        if parts.len() < 2 {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&parts.join(" "));
+    }
+    out
+}
+
+/// The `terms` column value for a chunk.
+pub fn chunk_terms(name: Option<&str>, content: &str) -> String {
+    match name {
+        Some(n) if !content.contains(n) => identifier_terms(&format!("{n}\n{content}")),
+        _ => identifier_terms(content),
+    }
+}
+
+/// Words that say nothing about what a query or goal is after.
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "all", "an", "and", "are", "as", "at", "be", "by", "for", "from", "how", "in",
+    "into", "is", "it", "its", "make", "more", "new", "of", "on", "or", "our", "so", "that", "the",
+    "their", "this", "to", "up", "use", "using", "we", "when", "with",
+];
+
+/// The terms of a query or goal, as highlighting, ranking stages, synonym
+/// lookup and intents all match them: each word lowercased and followed by
+/// its identifier sub-tokens, deduplicated in order of appearance. FTS5
+/// operators, stop words and single characters are left out.
+///
+/// `fix parseConfig AND the loader` → `fix parseconfig parse config loader`.
+pub fn query_terms(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if word.is_empty() || matches!(word, "AND" | "OR" | "NOT" | "NEAR") {
+            continue;
+        }
+        for term in std::iter::once(word.to_lowercase()).chain(split_identifier(word)) {
+            if term.chars().nth(1).is_some() && !STOP_WORDS.contains(&term.as_str()) && !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+    }
+    terms
+}
+
+/// True for tokens made only of identifier characters.
+pub fn is_identifier(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_camel_and_pascal_case() {
+        assert_eq!(split_identifier("upsertEmbeddingsBatch"), ["upsert", "embeddings", "batch"]);
+        assert_eq!(split_identifier("SearchResult"), ["search", "result"]);
+        assert_eq!(split_identifier("HTTPServer"), ["http", "server"]);
+    }
+
+    #[test]
+    fn splits_snake_kebab_and_digits() {
+        assert_eq!(split_identifier("index_directory"), ["index", "directory"]);
+        assert_eq!(split_identifier("tree-sitter"), ["tree", "sitter"]);
+        assert_eq!(split_identifier("utf8Decode"), ["utf", "8", "decode"]);
+        assert_eq!(split_identifier("__init__"), ["init"]);
+    }
+
+    #[test]
+    fn query_terms_add_parts_and_skip_operators_and_stop_words() {
+        assert_eq!(query_terms("fix parseConfig AND the loader"), ["fix", "parseconfig", "parse", "config", "loader"]);
+        assert_eq!(query_terms("a NEAR b_c of x"), ["b_c"]);
+        assert_eq!(query_terms("Index index_dir"), ["index", "index_dir", "dir"]);
+    }
+
+    #[test]
+    fn terms_skip_simple_words_and_duplicates() {
+        let terms = identifier_terms("fn index_directory(root) { index_directory(root); helper() }");
+        assert_eq!(terms, "index directory");
+        assert_eq!(identifier_terms("plain words only"), "");
+    }
+}